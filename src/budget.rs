@@ -0,0 +1,80 @@
+use std::cell::Cell;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use regex::{Regex, RegexBuilder};
+
+// Limits on the compiled size of user patterns, so one like `(\w{1,100}){1,100}` is rejected
+// right away instead of taking seconds and hundreds of megabytes to build
+const SIZE_LIMIT: usize = 2 * 1024 * 1024;
+const DFA_SIZE_LIMIT: usize = 1024 * 1024;
+
+// How long matching may take in one file, unset when --regex-timeout is 0
+static TIMEOUT: OnceLock<Duration> = OnceLock::new();
+
+thread_local! {
+    // When the time for the file being searched runs out
+    static DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+    // Whether the file being searched ran out of time, so it's only warned about once
+    static TIMED_OUT: Cell<bool> = const { Cell::new(false) };
+}
+
+pub fn set_timeout(timeout: Duration) {
+    if !timeout.is_zero() {
+        let _ = TIMEOUT.set(timeout);
+    }
+}
+
+// Parses --regex-timeout: a number of seconds, or one ending in `ms`, `s` or `m`
+pub fn parse_timeout(value: &str) -> Result<Duration, String> {
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit() && c != '.') {
+        Some(i) => value.split_at(i),
+        None => (value, "s"),
+    };
+    let number: f64 = number.parse().map_err(|_| format!("'{}' is not a duration like 500ms, 2s or 1m", value))?;
+    match unit {
+        "ms" => Ok(Duration::from_secs_f64(number / 1000.0)),
+        "s" => Ok(Duration::from_secs_f64(number)),
+        "m" => Ok(Duration::from_secs_f64(number * 60.0)),
+        _ => Err(format!("'{}' is not a duration like 500ms, 2s or 1m", value)),
+    }
+}
+
+// A builder for a user pattern with the size limits applied
+pub fn builder(pattern: &str) -> RegexBuilder {
+    let mut builder = RegexBuilder::new(pattern);
+    builder.size_limit(SIZE_LIMIT).dfa_size_limit(DFA_SIZE_LIMIT);
+    builder
+}
+
+// Compiles a user pattern with the size limits applied
+pub fn regex(pattern: &str) -> Result<Regex, regex::Error> {
+    builder(pattern).build()
+}
+
+// Starts the clock for the next file searched
+pub fn start_file() {
+    DEADLINE.set(TIMEOUT.get().map(|timeout| Instant::now() + *timeout));
+    TIMED_OUT.set(false);
+}
+
+// Whether matching in a file has taken longer than --regex-timeout, warning the first time, so
+// search loops can skip the rest of the file rather than hold up the whole search
+pub fn exceeded(path: &Path) -> bool {
+    if TIMED_OUT.get() {
+        return true;
+    }
+    let Some(deadline) = DEADLINE.get() else { return false };
+    if Instant::now() < deadline {
+        return false;
+    }
+    TIMED_OUT.set(true);
+    eprintln!("Warning: Matching in '{}' took longer than --regex-timeout {:?}, skipping the rest of the file", path.display(), TIMEOUT.get().copied().unwrap_or_default());
+    true
+}
+
+// Whether the file being searched ran out of time, so its partial results aren't cached
+pub fn timed_out() -> bool {
+    TIMED_OUT.get()
+}
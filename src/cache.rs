@@ -0,0 +1,107 @@
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::Result;
+
+// Per-file search results stored under the user's cache directory for --cache. An entry is
+// keyed on the query and options, the file path and the file content, so editing a file or
+// changing the search simply misses and unchanged files replay their output without parsing
+struct ResultCache {
+    dir: PathBuf,
+    options_hash: u64,
+}
+
+static CACHE: OnceLock<ResultCache> = OnceLock::new();
+
+// Set by --line-buffered: print every line as soon as it's found instead of once per file
+static LINE_BUFFERED: OnceLock<bool> = OnceLock::new();
+
+thread_local! {
+    // Output of the file being searched, collected so it can be stored and printed in one piece
+    // once the file is done
+    static CAPTURE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+// Turns the cache on for this run. `options` describes everything besides the file that
+// affects the output: mode, query and flags
+pub fn enable(options: &str) -> Result<()> {
+    let Some(cache_dir) = dirs::cache_dir() else {
+        return Err(anyhow::anyhow!("No cache directory found for this user"));
+    };
+    let dir = cache_dir.join("phrep").join("results");
+    std::fs::create_dir_all(&dir)?;
+    let _ = CACHE.set(ResultCache { dir, options_hash: hash(&(env!("CARGO_PKG_VERSION"), options)) });
+    Ok(())
+}
+
+pub fn set_line_buffered() {
+    let _ = LINE_BUFFERED.set(true);
+}
+
+// Prints a line of search output, or keeps it for the output of the current file
+pub fn emit(line: String) {
+    let captured = CAPTURE.with_borrow_mut(|capture| match capture {
+        Some(output) => {
+            output.push_str(&line);
+            output.push('\n');
+            true
+        }
+        None => false,
+    });
+    if !captured {
+        let mut stdout = std::io::stdout().lock();
+        let _ = writeln!(stdout, "{}", line).and_then(|_| stdout.flush());
+    }
+}
+
+// Runs `search` over one file unless its output is already cached, printing the output either
+// way. The matches of a file are written in one go so they stay together when several files
+// are searched at once, unless --line-buffered asks for them as they come
+pub fn cached(path: &Path, content: &str, search: impl FnOnce()) {
+    let search = || crate::guard::file(path, search);
+    let Some(cache) = CACHE.get() else {
+        if LINE_BUFFERED.get().is_some() {
+            search();
+        } else {
+            print_file_output(&capture(search));
+        }
+        return;
+    };
+
+    let entry = cache.dir.join(format!("{:016x}", hash(&(cache.options_hash, path, content))));
+    if let Ok(output) = std::fs::read_to_string(&entry) {
+        print_file_output(&output);
+        return;
+    }
+
+    let output = capture(search);
+    // A file cut short by --regex-timeout or an internal error is searched again next time
+    if !crate::budget::timed_out() && !crate::guard::failed()
+        && let Err(e) = std::fs::write(&entry, &output) {
+        eprintln!("Warning: Could not write cache entry '{}': {}", entry.display(), e);
+    }
+    print_file_output(&output);
+}
+
+// Runs `search` and returns what it emitted instead of printing it
+pub fn capture(search: impl FnOnce()) -> String {
+    CAPTURE.set(Some(String::new()));
+    search();
+    CAPTURE.take().unwrap_or_default()
+}
+
+fn print_file_output(output: &str) {
+    if !output.is_empty() {
+        let _ = std::io::stdout().lock().write_all(output.as_bytes());
+    }
+}
+
+fn hash(value: &impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
@@ -0,0 +1,115 @@
+use std::collections::{HashMap, HashSet};
+
+use tree_sitter::Node;
+
+use crate::php::{self, FileNames};
+
+// How a call site refers to what it calls
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CallKind {
+    // foo()
+    Function,
+    // $this->foo()
+    This,
+    // $object->foo()
+    Instance,
+    // self::foo() and static::foo()
+    SelfStatic,
+    // parent::foo()
+    Parent,
+    // Foo::foo()
+    Static,
+}
+
+// A statically named function or method call
+#[derive(Debug)]
+pub struct CallSite<'a> {
+    pub name_node: Node<'a>,
+    pub kind: CallKind,
+    pub name: &'a str,
+    // Fully qualified class the method is called on, when it can be told from the call itself
+    pub class: Option<String>,
+}
+
+impl CallSite<'_> {
+    // Key of the called method or function in a call graph, see `method_key` and `function_key`
+    pub fn target_key(&self) -> Option<String> {
+        match self.kind {
+            CallKind::Function => Some(function_key(self.name)),
+            CallKind::Instance => None,
+            _ => self.class.as_deref().map(|class| method_key(class, self.name)),
+        }
+    }
+}
+
+// Call graph key for a method, PHP method and class names are case-insensitive
+pub fn method_key(class: &str, method: &str) -> String {
+    format!("{}::{}", class.to_lowercase(), method.to_lowercase())
+}
+
+// Call graph key for a function, namespaces are ignored since unqualified calls fall back to
+// the global function anyway
+pub fn function_key(function: &str) -> String {
+    php::short_name(function).to_lowercase()
+}
+
+// Finds the key of the method or function a call key refers to among `callables`, looking up
+// the extends chain (lowercased class to lowercased parent) for inherited methods
+pub fn resolve<T>(key: &str, callables: &HashMap<String, T>, extends: &HashMap<String, String>) -> Option<String> {
+    let mut key = key.to_string();
+    let mut seen = HashSet::new();
+    while !callables.contains_key(&key) {
+        let (class, method) = key.split_once("::")?;
+        let parent = extends.get(class)?;
+        if !seen.insert(parent.clone()) {
+            return None;
+        }
+        key = method_key(parent, method);
+    }
+    Some(key)
+}
+
+// Recognizes function, method and static calls whose callee is a plain name. Dynamic calls
+// like `$object->$method()` or `$callback()` can't be resolved and are skipped
+pub fn call_site<'a>(node: Node<'a>, content: &'a str, names: &FileNames) -> Option<CallSite<'a>> {
+    let is_name = |node: &Node| node.kind() == "name" || node.kind() == "qualified_name";
+    match node.kind() {
+        "function_call_expression" => {
+            let function = node.child_by_field_name("function").filter(is_name)?;
+            Some(CallSite {
+                name_node: function,
+                kind: CallKind::Function,
+                name: php::short_name(php::node_text(function, content)),
+                class: None,
+            })
+        }
+        "member_call_expression" | "nullsafe_member_call_expression" => {
+            let name = node.child_by_field_name("name").filter(is_name)?;
+            let object = node.child_by_field_name("object")?;
+            let on_this = object.kind() == "variable_name" && php::node_text(object, content) == "$this";
+            Some(CallSite {
+                name_node: name,
+                kind: if on_this { CallKind::This } else { CallKind::Instance },
+                name: php::node_text(name, content),
+                class: if on_this { names.class_fqcn(php::enclosing_class(node)?, content) } else { None },
+            })
+        }
+        "scoped_call_expression" => {
+            let name = node.child_by_field_name("name").filter(is_name)?;
+            let scope = node.child_by_field_name("scope")?;
+            let kind = match php::node_text(scope, content).to_lowercase().as_str() {
+                "self" | "static" => CallKind::SelfStatic,
+                "parent" => CallKind::Parent,
+                _ if is_name(&scope) || scope.kind() == "relative_scope" => CallKind::Static,
+                _ => return None,
+            };
+            Some(CallSite {
+                name_node: name,
+                kind,
+                name: php::node_text(name, content),
+                class: names.resolve_node(scope, content),
+            })
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,94 @@
+use anyhow::Result;
+
+use crate::php::{self, FileNames};
+
+// Finds the methods touching `$this-><property>` and classifies each one: getters only read it,
+// setters only write it, mixed methods do both. Constructors assigning it and constructor
+// parameters promoting it are reported as such
+pub fn accessors(property: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let property = property.trim_start_matches('$');
+    let prefilter = |content: &str| content.contains(property);
+
+    let mut found = 0;
+    crate::for_each_php_tree(dir, file, exclude_dirs, prefilter, |path, content, tree| {
+        let names = FileNames::new(tree.root_node(), content);
+        php::walk(tree.root_node(), &mut |node| {
+            if node.kind() != "method_declaration" {
+                return;
+            }
+            let Some(name) = node.child_by_field_name("name").map(|name| php::node_text(name, content)) else { return };
+            let class = php::enclosing_class(node)
+                .and_then(|class| names.class_fqcn(class, content))
+                .map(|fqcn| php::short_name(&fqcn).to_string())
+                .unwrap_or_default();
+            let symbol = format!("{}::{}()", class, name);
+            let is_constructor = name.eq_ignore_ascii_case("__construct");
+
+            if is_constructor && let Some(parameters) = node.child_by_field_name("parameters") {
+                let mut cursor = parameters.walk();
+                for parameter in parameters.named_children(&mut cursor) {
+                    let promotes = parameter.kind() == "property_promotion_parameter"
+                        && parameter.child_by_field_name("name").is_some_and(|name| php::node_text(name, content).trim_start_matches('$') == property);
+                    if promotes {
+                        crate::print_symbol_match(path, parameter.start_position().row + 1, Some(&symbol), "promoted in constructor");
+                        found += 1;
+                    }
+                }
+            }
+
+            let Some(body) = node.child_by_field_name("body") else { return };
+            let (mut reads, mut writes) = (false, false);
+            php::walk_body(body, &mut |inner| {
+                if !is_this_property(inner, property, content) {
+                    return;
+                }
+                if is_written(inner) {
+                    writes = true;
+                } else {
+                    reads = true;
+                }
+            });
+            let kind = match (reads, writes) {
+                (_, true) if is_constructor => "assigned in constructor",
+                (true, true) => "mixed",
+                (true, false) => "getter",
+                (false, true) => "setter",
+                (false, false) => return,
+            };
+            crate::print_symbol_match(path, node.start_position().row + 1, Some(&symbol), kind);
+            found += 1;
+        });
+    })?;
+
+    if found == 0 {
+        crate::report::summary(format!("No methods access $this->{}.", property));
+    }
+    Ok(())
+}
+
+// Checks for `$this->property` (or `$this?->property`)
+fn is_this_property(node: tree_sitter::Node, property: &str, content: &str) -> bool {
+    if node.kind() != "member_access_expression" && node.kind() != "nullsafe_member_access_expression" {
+        return false;
+    }
+    let object = node.child_by_field_name("object").map(|object| php::node_text(object, content));
+    let name = node.child_by_field_name("name").map(|name| php::node_text(name, content));
+    object == Some("$this") && name == Some(property)
+}
+
+// Checks whether a property access is assigned to, incremented or unset, including through an
+// array element like `$this->items[] = $item`
+fn is_written(node: tree_sitter::Node) -> bool {
+    let mut current = node;
+    while let Some(parent) = current.parent() {
+        match parent.kind() {
+            "subscript_expression" if parent.named_child(0) == Some(current) => current = parent,
+            "assignment_expression" | "augmented_assignment_expression" | "reference_assignment_expression" => {
+                return parent.child_by_field_name("left") == Some(current);
+            }
+            "update_expression" | "unset_statement" => return true,
+            _ => return false,
+        }
+    }
+    false
+}
@@ -0,0 +1,202 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use colored::*;
+use regex::Regex;
+
+use crate::php;
+
+// The searches a batch query can run, named like the flags selecting them
+const MODES: [&str; 3] = ["basic", "grep", "method-search"];
+
+// One named query of a batch file
+struct Query {
+    name: String,
+    mode: &'static str,
+    // The pattern as written, which --method-search looks for in names as is
+    source: String,
+    pattern: Regex,
+    // Paths below the search directory to search, all of it when empty
+    scope: Vec<PathBuf>,
+    output: PathBuf,
+    // What was written so far: lines and files with matches
+    lines: usize,
+    files: usize,
+}
+
+impl Query {
+    fn covers(&self, relative_path: &Path) -> bool {
+        self.scope.is_empty() || self.scope.iter().any(|scope| relative_path.starts_with(scope))
+    }
+
+    // Whether a file is worth searching, before it's parsed
+    fn may_match(&self, content: &str) -> bool {
+        if self.mode == "method-search" { content.contains(&self.source) } else { self.pattern.is_match(content) }
+    }
+}
+
+// Runs every query of a batch file over the search directory in one pass: each file is read and
+// parsed once for all the queries, and each query's matches go to its own output file as plain
+// text, in the same form a search with the query's mode would print them
+pub fn batch(queries_file: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let Some(content) = crate::read_file(Path::new(queries_file)) else {
+        return Err(anyhow::anyhow!("Could not read file '{}'", queries_file));
+    };
+    let mut queries = match parse(&content) {
+        Ok(queries) => queries,
+        Err(e) => {
+            eprintln!("Error: Invalid batch file '{}': {}", queries_file, e);
+            return Err(anyhow::anyhow!("Invalid batch file '{}': {}", queries_file, e));
+        }
+    };
+    let mut outputs = Vec::new();
+    for query in &queries {
+        match std::fs::File::create(&query.output) {
+            Ok(output) => outputs.push(std::io::BufWriter::new(output)),
+            Err(e) => {
+                eprintln!("Error: Could not create '{}' for query '{}': {}", query.output.display(), query.name, e);
+                return Err(anyhow::anyhow!("Could not create '{}': {}", query.output.display(), e));
+            }
+        }
+    }
+
+    // The result files are read as text, not on a terminal
+    colored::control::set_override(false);
+    let mut parser = php::new_parser()?;
+    for path in crate::php_files(dir, file, exclude_dirs) {
+        let relative_path = path.strip_prefix(dir).unwrap_or(&path).to_path_buf();
+        if !queries.iter().any(|query| query.covers(&relative_path)) {
+            continue;
+        }
+        let Some(content) = crate::read_file(&path) else { continue };
+        let matching: Vec<usize> = (0..queries.len())
+            .filter(|&i| queries[i].covers(&relative_path) && queries[i].may_match(&content))
+            .collect();
+        if matching.is_empty() {
+            continue;
+        }
+        let tree = if matching.iter().any(|&i| queries[i].mode != "grep") { crate::parse_content(&mut parser, &content, &path) } else { None };
+
+        for i in matching {
+            let query = &mut queries[i];
+            let output = crate::cache::capture(|| crate::guard::file(&path, || {
+                let searched = match (query.mode, &tree) {
+                    ("grep", _) => {
+                        crate::grep_lines(&content, &query.pattern, &path);
+                        Ok(())
+                    }
+                    ("basic", Some(tree)) => crate::search_function_bodies(&content, &query.pattern, tree, &path, &false),
+                    (_, Some(tree)) => crate::search_parsed_method_names(&content, &query.source, tree, &path),
+                    (_, None) => Ok(()),
+                };
+                if let Err(e) = searched {
+                    eprintln!("Warning: Error processing file '{}' for query '{}': {}", path.display(), query.name, e);
+                }
+            }));
+            if output.is_empty() {
+                continue;
+            }
+            if let Err(e) = outputs[i].write_all(output.as_bytes()) {
+                eprintln!("Error: Could not write '{}': {}", query.output.display(), e);
+                return Err(anyhow::anyhow!("Could not write '{}': {}", query.output.display(), e));
+            }
+            query.lines += output.lines().count();
+            query.files += 1;
+        }
+    }
+    for (query, output) in queries.iter().zip(outputs.iter_mut()) {
+        if let Err(e) = output.flush() {
+            eprintln!("Error: Could not write '{}': {}", query.output.display(), e);
+            return Err(anyhow::anyhow!("Could not write '{}': {}", query.output.display(), e));
+        }
+    }
+    colored::control::unset_override();
+
+    for query in &queries {
+        crate::report::summary(format!("{}: {} lines from {} files written to {}", query.name.bold(), query.lines, query.files, query.output.display()));
+    }
+    Ok(())
+}
+
+// The name, line and key-value pairs of a table of a batch file
+type Table = (String, usize, Vec<(String, String)>);
+
+// Reads the `[name]` tables of a batch file, each with a `pattern` and optionally a `mode` (basic
+// by default), a `scope` below the search directory and an `output` file (`<name>.txt` by default)
+fn parse(content: &str) -> Result<Vec<Query>> {
+    let mut tables: Vec<Table> = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|rest| rest.split_once(']')).map(|(name, _)| name.trim()) {
+            if name.is_empty() || tables.iter().any(|(other, _, _)| other == name) {
+                return Err(anyhow::anyhow!("line {}: expected a new query name in [brackets]", i + 1));
+            }
+            tables.push((name.to_string(), i + 1, Vec::new()));
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(anyhow::anyhow!("line {}: expected `key = value`", i + 1));
+        };
+        let Some((_, _, entries)) = tables.last_mut() else {
+            return Err(anyhow::anyhow!("line {}: `{}` comes before the first [query]", i + 1, key.trim()));
+        };
+        let value = parse_string(value.trim()).ok_or_else(|| anyhow::anyhow!("line {}: expected a quoted string", i + 1))?;
+        entries.push((key.trim().to_string(), value));
+    }
+
+    let mut queries = Vec::new();
+    for (name, line, entries) in tables {
+        let value = |key: &str| entries.iter().rev().find(|(entry_key, _)| entry_key == key).map(|(_, value)| value.clone());
+        if let Some((key, _)) = entries.iter().find(|(key, _)| !matches!(key.as_str(), "pattern" | "mode" | "scope" | "output")) {
+            return Err(anyhow::anyhow!("query '{}' on line {}: unknown key '{}'", name, line, key));
+        }
+        let Some(source) = value("pattern") else {
+            return Err(anyhow::anyhow!("query '{}' on line {} has no pattern", name, line));
+        };
+        let mode = value("mode").unwrap_or_else(|| "basic".to_string());
+        let Some(mode) = MODES.iter().copied().find(|known| *known == mode) else {
+            return Err(anyhow::anyhow!("query '{}' on line {}: mode '{}' isn't one of {}", name, line, mode, MODES.join(", ")));
+        };
+        let pattern = crate::budget::regex(&source).map_err(|e| anyhow::anyhow!("query '{}' on line {}: invalid regex pattern: {}", name, line, e))?;
+        let scope = value("scope").map(|scope| scope.split(',').map(|part| PathBuf::from(part.trim().trim_matches('/'))).filter(|part| !part.as_os_str().is_empty()).collect()).unwrap_or_default();
+        let output = PathBuf::from(value("output").unwrap_or_else(|| format!("{}.txt", name)));
+        queries.push(Query { name, mode, source, pattern, scope, output, lines: 0, files: 0 });
+    }
+    if queries.is_empty() {
+        return Err(anyhow::anyhow!("no [query] tables"));
+    }
+    Ok(queries)
+}
+
+// Parses a `"basic string"` with backslash escapes or a `'literal string'`, which suits
+// regexes, allowing a trailing comment
+fn parse_string(value: &str) -> Option<String> {
+    let quote = value.chars().next().filter(|quote| *quote == '"' || *quote == '\'')?;
+    let mut parsed = String::new();
+    let mut chars = value[1..].char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c == quote {
+            let rest = value[1 + i + 1..].trim();
+            return (rest.is_empty() || rest.starts_with('#')).then_some(parsed);
+        }
+        if c == '\\' && quote == '"' {
+            // Other escapes are kept as written, so `"\d+"` is still the regex it looks like
+            match chars.next()?.1 {
+                'n' => parsed.push('\n'),
+                't' => parsed.push('\t'),
+                escaped @ ('"' | '\\') => parsed.push(escaped),
+                escaped => {
+                    parsed.push('\\');
+                    parsed.push(escaped);
+                }
+            }
+        } else {
+            parsed.push(c);
+        }
+    }
+    None
+}
@@ -0,0 +1,109 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use colored::*;
+
+// The searches timed: a name, and the phrep flags besides the query and directory
+const MODES: [(&str, &[&str]); 3] = [
+    ("grep", &["--grep"]),
+    ("basic", &[]),
+    ("cached", &["--cache"]),
+];
+
+// Times grep, basic and cached (--cache with a warm cache) searches over a corpus, either the
+// one given or a synthetic one generated for the run, and prints the throughput of each. Every
+// search runs as a child process, the way users run phrep, with its output thrown away
+pub fn bench(corpus: Option<&str>, files: usize, runs: usize, query: &str) -> Result<()> {
+    let dir = match corpus {
+        Some(corpus) => PathBuf::from(corpus),
+        None => generate(files)?,
+    };
+    let result = measure(&dir, runs.max(1), query);
+    if corpus.is_none() {
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    result
+}
+
+fn measure(dir: &Path, runs: usize, query: &str) -> Result<()> {
+    let Some(dir_str) = dir.to_str() else {
+        return Err(anyhow::anyhow!("'{}' is not a valid UTF-8 path", dir.display()));
+    };
+    let paths: Vec<PathBuf> = crate::php_files(dir_str, ".php", "").collect();
+    let bytes: u64 = paths.iter().filter_map(|path| std::fs::metadata(path).ok()).map(|metadata| metadata.len()).sum();
+    if paths.is_empty() {
+        eprintln!("Error: No PHP files found in '{}'.", dir.display());
+        return Err(anyhow::anyhow!("No PHP files found in '{}'", dir.display()));
+    }
+    println!("Corpus: {} ({} files, {:.1} MB), query '{}', median of {} run{}", crate::format_filename(dir), paths.len(), bytes as f64 / 1e6, query, runs, if runs == 1 { "" } else { "s" });
+    println!("{:<8} {:>9} {:>10} {:>8}", "Mode".bold(), "Time".bold(), "Files/s".bold(), "MB/s".bold());
+
+    let phrep = std::env::current_exe()?;
+    for (name, flags) in MODES {
+        let run = || -> Result<Duration> {
+            let start = Instant::now();
+            let status = Command::new(&phrep)
+                .arg(query)
+                .args(flags)
+                .args(["--dir", dir_str, "--no-default-excludes"])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()?;
+            if !status.success() {
+                return Err(anyhow::anyhow!("The {} search failed with {}", name, status));
+            }
+            Ok(start.elapsed())
+        };
+        // The first cached run fills the cache the timed runs read from
+        if flags.contains(&"--cache") {
+            run()?;
+        }
+        let mut times = (0..runs).map(|_| run()).collect::<Result<Vec<_>>>()?;
+        times.sort();
+        let seconds = times[times.len() / 2].as_secs_f64().max(f64::EPSILON);
+        println!("{:<8} {:>8.3}s {:>10.0} {:>8.1}", name, seconds, paths.len() as f64 / seconds, bytes as f64 / 1e6 / seconds);
+    }
+    Ok(())
+}
+
+// Writes `files` PHP files of classes with methods of typical legacy code, the same every time,
+// into a new temporary directory
+fn generate(files: usize) -> Result<PathBuf> {
+    let dir = std::env::temp_dir().join(format!("phrep-bench-{}", std::process::id()));
+    let mut seed: u64 = 0x5eed;
+    // A small linear congruential generator keeps the corpus reproducible without a dependency
+    let mut next = |below: u64| {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (seed >> 33) % below
+    };
+
+    for i in 0..files {
+        let module = format!("Module{}", i % 20);
+        let class = format!("Service{}", i);
+        let mut code = format!("<?php\n\nnamespace App\\{};\n\nuse App\\Support\\Logger;\nuse App\\Support\\Db;\n\n/**\n * Generated for phrep bench\n */\nclass {}\n{{\n    private Db $db;\n    private Logger $logger;\n\n", module, class);
+        for method in 0..(5 + next(10)) {
+            code.push_str(&format!("    /**\n     * @param int $id\n     * @return array\n     */\n    public function method{}(int $id, array $options = []): array\n    {{\n", method));
+            for statement in 0..(4 + next(12)) {
+                code.push_str(&match next(6) {
+                    0 => format!("        $rows = $this->db->query('SELECT * FROM table{} WHERE id = ?', [$id]);\n", statement),
+                    1 => format!("        $this->logger->info(\"Loaded {{$id}} in {}\");\n", class),
+                    2 => "        foreach ($options as $key => $value) {\n            $result[$key] = strtoupper((string) $value);\n        }\n".to_string(),
+                    3 => format!("        if ($id > {}) {{\n            throw new \\InvalidArgumentException('Too large');\n        }}\n", next(1000)),
+                    4 => "        $total = array_sum(array_map(fn($row) => $row['amount'] ?? 0, $rows ?? []));\n".to_string(),
+                    _ => format!("        // Step {} of the legacy flow\n        $value = $options['key{}'] ?? null;\n", statement, next(50)),
+                });
+            }
+            code.push_str("        return $result ?? [];\n    }\n\n");
+        }
+        code.push_str("}\n");
+
+        let path = dir.join(&module).join(format!("{}.php", class));
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, code)?;
+    }
+    Ok(dir)
+}
@@ -0,0 +1,167 @@
+use std::path::Path;
+
+use anyhow::Result;
+use colored::*;
+
+use crate::commands::deps::{self, Dependency};
+
+// A layer of the architecture: the classes in any of its namespaces
+struct Layer {
+    name: String,
+    namespaces: Vec<String>,
+}
+
+// The layers and, for the layers that have a rule, the other layers they may depend on. Layers
+// without a rule may depend on anything, classes outside any layer can be used from everywhere
+struct Rules {
+    layers: Vec<Layer>,
+    allowed: Vec<(String, Vec<String>)>,
+}
+
+impl Rules {
+    // The layer of the deepest namespace containing `name`, a namespace or a fully qualified class
+    fn layer_of(&self, name: &str) -> Option<&str> {
+        self.layers.iter()
+            .flat_map(|layer| layer.namespaces.iter().map(move |namespace| (layer, namespace)))
+            .filter(|(_, namespace)| in_namespace(name, namespace))
+            .max_by_key(|(_, namespace)| namespace.len())
+            .map(|(layer, _)| layer.name.as_str())
+    }
+
+    // The layers a dependency goes from and to, if the rules don't allow it
+    fn violation(&self, dependency: &Dependency) -> Option<(&str, &str)> {
+        let from = self.layer_of(&dependency.namespace)?;
+        let to = self.layer_of(&dependency.target)?;
+        let (_, allowed) = self.allowed.iter().find(|(layer, _)| layer == from)?;
+        if from == to || allowed.iter().any(|layer| layer == to) {
+            return None;
+        }
+        Some((from, to))
+    }
+}
+
+// Checks the dependencies between namespace layers against a rules file and reports every use
+// statement, `new` and static call that crosses a boundary it isn't allowed to. The run fails when
+// there are any, so it can gate CI
+pub fn boundaries(rules_path: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let rules = match std::fs::read_to_string(rules_path).map_err(anyhow::Error::from).and_then(|content| parse(Path::new(rules_path), &content)) {
+        Ok(rules) => rules,
+        Err(e) => {
+            eprintln!("Error: Could not read rules '{}': {}", rules_path, e);
+            return Err(anyhow::anyhow!("Could not read rules '{}': {}", rules_path, e));
+        }
+    };
+
+    let mut violations = 0;
+    for dependency in deps::dependencies(dir, file, exclude_dirs)? {
+        let Some((from, to)) = rules.violation(&dependency) else { continue };
+        violations += 1;
+        let line = format!("{} {}", dependency.line_styled.trim(), format!("[uses {}]", dependency.target).dimmed());
+        crate::print_symbol_match(&dependency.path, dependency.line, Some(&format!("{} → {}", from, to)), &line);
+    }
+
+    if violations > 0 {
+        crate::report::summary(format!("{} boundary violation{}", violations, if violations == 1 { "" } else { "s" }));
+        eprintln!("Error: Found {} dependencies crossing layer boundaries.", violations);
+        return Err(anyhow::anyhow!("Found {} dependencies crossing layer boundaries", violations));
+    }
+    crate::report::summary("No boundary violations");
+    Ok(())
+}
+
+// Whether a namespace or class name is `namespace` or below it. Namespaces are case-insensitive
+fn in_namespace(name: &str, namespace: &str) -> bool {
+    let namespace = namespace.trim_matches('\\');
+    let name = name.trim_start_matches('\\');
+    name.len() >= namespace.len()
+        && name[..namespace.len()].eq_ignore_ascii_case(namespace)
+        && (name.len() == namespace.len() || name[namespace.len()..].starts_with('\\'))
+}
+
+// Reads the small YAML subset the rules need:
+//
+//   layers:
+//     Domain: App\Domain
+//     Infrastructure: [App\Infrastructure, App\Persistence]
+//   rules:
+//     Domain: []
+//     Infrastructure: [Domain]
+//
+// Lists can also be written as `- item` lines below their key
+fn parse(path: &Path, content: &str) -> Result<Rules> {
+    let mut rules = Rules { layers: Vec::new(), allowed: Vec::new() };
+    let mut section = "";
+    // The entry a `- item` line is added to
+    let mut last_key: Option<String> = None;
+    for (i, line) in content.lines().enumerate() {
+        let line = line.split(" #").next().unwrap_or("").trim_end();
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        let indented = line.starts_with(' ') || line.starts_with('\t');
+        let line = line.trim();
+
+        if !indented {
+            match line.strip_suffix(':') {
+                Some(key @ ("layers" | "rules")) => section = key,
+                Some(key) => {
+                    eprintln!("Warning: Unknown section '{}' on line {} of '{}'", key, i + 1, path.display());
+                    section = "";
+                }
+                None => return Err(anyhow::anyhow!("line {}: expected `layers:` or `rules:`", i + 1)),
+            }
+            last_key = None;
+            continue;
+        }
+
+        let (key, values) = if let Some(item) = line.strip_prefix("- ") {
+            let Some(key) = &last_key else {
+                return Err(anyhow::anyhow!("line {}: list item without a key", i + 1));
+            };
+            (key.clone(), vec![unquote(item)])
+        } else {
+            let Some((key, value)) = line.split_once(':') else {
+                return Err(anyhow::anyhow!("line {}: expected `name: value`", i + 1));
+            };
+            let key = unquote(key);
+            last_key = Some(key.clone());
+            (key, parse_list(value.trim()))
+        };
+
+        match section {
+            "layers" => match rules.layers.iter_mut().find(|layer| layer.name == key) {
+                Some(layer) => layer.namespaces.extend(values),
+                None => rules.layers.push(Layer { name: key, namespaces: values }),
+            },
+            "rules" => match rules.allowed.iter_mut().find(|(layer, _)| *layer == key) {
+                Some((_, allowed)) => allowed.extend(values),
+                None => rules.allowed.push((key, values)),
+            },
+            _ => {}
+        }
+    }
+
+    for (layer, allowed) in &rules.allowed {
+        for name in std::iter::once(layer).chain(allowed) {
+            if !rules.layers.iter().any(|known| &known.name == name) {
+                return Err(anyhow::anyhow!("the rules name the layer '{}', which isn't declared under layers", name));
+            }
+        }
+    }
+    Ok(rules)
+}
+
+// `[a, b]`, `a` or nothing (a list that continues with `- item` lines)
+fn parse_list(value: &str) -> Vec<String> {
+    let items = value.strip_prefix('[').and_then(|list| list.strip_suffix(']')).unwrap_or(value);
+    items.split(',').map(unquote).filter(|item| !item.is_empty()).collect()
+}
+
+// Strips YAML quotes. Backslashes are escaped inside double quotes but not in plain or single-quoted values
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    if let Some(quoted) = value.strip_prefix('"').and_then(|value| value.strip_suffix('"')) {
+        return quoted.replace("\\\\", "\\");
+    }
+    value.strip_prefix('\'').and_then(|value| value.strip_suffix('\'')).unwrap_or(value).to_string()
+}
@@ -0,0 +1,117 @@
+use std::path::Path;
+
+use anyhow::Result;
+use colored::*;
+use tree_sitter::Node;
+
+use crate::declarations;
+use crate::php::{self, FileNames};
+
+// The member kinds class-dump can be restricted to
+pub const MEMBER_KINDS: [&str; 3] = ["methods", "props", "consts"];
+
+// Prints the members of one class in full, in the order they're declared: methods with their
+// bodies, properties (promoted ones as their constructor parameter) and constants or enum
+// cases. `members` restricts the kinds and `pattern` keeps only members whose text matches it
+pub fn class_dump(class_name: &str, members: &[String], pattern: Option<&str>, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let pattern = match pattern.map(crate::budget::regex).transpose() {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            eprintln!("Invalid regex pattern: {}", e);
+            return Err(anyhow::anyhow!("Invalid regex pattern"));
+        }
+    };
+    let files = declarations::scan(dir, file, exclude_dirs)?;
+    let (path, class) = declarations::find_class(&files, class_name)?;
+
+    let Some(content) = crate::read_file(path) else {
+        return Err(anyhow::anyhow!("Could not read file '{}'", path.display()));
+    };
+    let mut parser = php::new_parser()?;
+    let Some(tree) = crate::parse_content(&mut parser, &content, path) else {
+        return Err(anyhow::anyhow!("Could not parse file '{}' as PHP", path.display()));
+    };
+    let names = FileNames::new(tree.root_node(), &content);
+    let mut class_node = None;
+    php::walk(tree.root_node(), &mut |node| {
+        if class_node.is_none() && node.start_position().row + 1 == class.line
+            && names.class_fqcn(node, &content).is_some_and(|fqcn| fqcn.eq_ignore_ascii_case(&class.fqcn)) {
+            class_node = Some(node);
+        }
+    });
+    let Some(body) = class_node.and_then(|node| node.child_by_field_name("body")) else {
+        return Err(anyhow::anyhow!("Could not find the body of {}", class.fqcn));
+    };
+
+    crate::report::summary(format!("{} {} ({}:{})", class.kind, class.fqcn.bold().yellow(), crate::format_filename(path), class.line));
+    let wanted = |kind: &str| members.is_empty() || members.iter().any(|member| member == kind);
+    let mut printed = 0;
+    let mut cursor = body.walk();
+    for member in body.named_children(&mut cursor) {
+        let (kind, labels): (&str, Vec<String>) = match member.kind() {
+            "method_declaration" => ("methods", named(member, "name", &content).map(|name| format!("{}()", name)).into_iter().collect()),
+            "property_declaration" => ("props", elements(member, "property_element", &content).into_iter().map(|name| format!("${}", name)).collect()),
+            "const_declaration" => ("consts", elements(member, "const_element", &content).into_iter().map(|name| name.to_string()).collect()),
+            "enum_case" => ("consts", named(member, "name", &content).map(str::to_string).into_iter().collect()),
+            _ => continue,
+        };
+        if wanted("props") && kind == "methods" {
+            printed += print_promoted(path, &class.name, member, pattern.as_ref(), &content);
+        }
+        if !wanted(kind) {
+            continue;
+        }
+        if print_member(path, &format!("{}::{}", class.name, labels.join(", ")), member, pattern.as_ref(), &content) {
+            printed += 1;
+        }
+    }
+
+    crate::report::summary(format!("{} members printed", printed));
+    Ok(())
+}
+
+// Prints a member's text with the pattern highlighted, if the pattern matches it
+fn print_member(path: &Path, symbol: &str, member: Node, pattern: Option<&regex::Regex>, content: &str) -> bool {
+    let text = php::node_text(member, content);
+    let styled = match pattern {
+        Some(pattern) if !pattern.is_match(text) => return false,
+        Some(pattern) => text.lines().map(|line| crate::highlight(line, pattern)).collect::<Vec<_>>().join("\n"),
+        None => text.to_string(),
+    };
+    crate::print_symbol_match(path, member.start_position().row + 1, Some(symbol), &styled);
+    true
+}
+
+// Prints the promoted parameters of a constructor as the properties they declare
+fn print_promoted(path: &Path, class_name: &str, method: Node, pattern: Option<&regex::Regex>, content: &str) -> usize {
+    if !named(method, "name", content).is_some_and(|name| name.eq_ignore_ascii_case("__construct")) {
+        return 0;
+    }
+    let Some(params) = method.child_by_field_name("parameters") else { return 0 };
+    let mut cursor = params.walk();
+    params.named_children(&mut cursor)
+        .filter(|param| param.kind() == "property_promotion_parameter")
+        .filter(|param| {
+            let name = named(*param, "name", content).unwrap_or_default().trim_start_matches('$');
+            print_member(path, &format!("{}::${}", class_name, name), *param, pattern, content)
+        })
+        .count()
+}
+
+// The text of a node's field
+fn named<'a>(node: Node, field: &str, content: &'a str) -> Option<&'a str> {
+    node.child_by_field_name(field).map(|name| php::node_text(name, content))
+}
+
+// The names declared by the elements of a property or constant declaration, `$a, $b = 1`
+fn elements<'a>(declaration: Node, kind: &str, content: &'a str) -> Vec<&'a str> {
+    let mut cursor = declaration.walk();
+    declaration.named_children(&mut cursor)
+        .filter(|element| element.kind() == kind)
+        .filter_map(|element| {
+            let mut element_cursor = element.walk();
+            let name = element.child_by_field_name("name").or_else(|| element.named_children(&mut element_cursor).find(|child| child.kind() == "name"));
+            name.map(|name| php::node_text(name, content).trim_start_matches('$'))
+        })
+        .collect()
+}
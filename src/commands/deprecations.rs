@@ -0,0 +1,248 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use colored::*;
+use tree_sitter::Node;
+
+use crate::calls::{self, CallKind};
+use crate::declarations::{self, FileDecls};
+use crate::php::{self, FileNames};
+
+// What kind of symbol is deprecated, which decides what counts as a use of it
+enum Target {
+    Class { fqcn: String },
+    Method { class: String, name: String },
+    Function { key: String },
+}
+
+// A class, method or function marked @deprecated in its docblock or with #[Deprecated]
+struct Deprecated {
+    // `App\Mailer`, `App\Mailer::send()` or `format_date()`
+    symbol: String,
+    target: Target,
+    path: PathBuf,
+    // The lines of the declaration, uses inside it don't count
+    lines: (usize, usize),
+}
+
+// A use of a deprecated symbol
+struct Use {
+    symbol: String,
+    path: PathBuf,
+    line: usize,
+    caller: Option<String>,
+    line_styled: String,
+}
+
+// Lists the deprecated classes, methods and functions with the places still using them
+pub fn deprecations(dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let (deprecated, uses) = find(dir, file, exclude_dirs)?;
+    for symbol in &deprecated {
+        let symbol_uses: Vec<&Use> = uses.iter().filter(|site| site.symbol == symbol.symbol).collect();
+        crate::report::summary(format!("{} {} ({}:{}), {} use{}", symbol.symbol.bold().yellow(), "deprecated".dimmed(), crate::format_filename(&symbol.path), symbol.lines.0, symbol_uses.len(), if symbol_uses.len() == 1 { "" } else { "s" }));
+        for site in symbol_uses {
+            crate::print_match(&site.path, site.line, site.caller.as_deref(), &site.line_styled);
+        }
+    }
+    crate::report::summary(format!("{} deprecated symbol{}, {} use{}", deprecated.len(), if deprecated.len() == 1 { "" } else { "s" }, uses.len(), if uses.len() == 1 { "" } else { "s" }));
+    Ok(())
+}
+
+// Fails when deprecated symbols are used in places the allowlist doesn't have, or more often in
+// a place than it allows, so CI stops new code from picking up deprecated APIs while existing
+// uses are worked off. Uses are keyed on the symbol, the file below the search directory and
+// the calling method rather than line numbers, so unrelated edits don't trip the gate. With
+// `update` the allowlist is rewritten from the uses found
+pub fn gate(allowlist: &str, update: bool, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let (_, uses) = find(dir, file, exclude_dirs)?;
+    let mut found: BTreeMap<(String, String, String), Vec<&Use>> = BTreeMap::new();
+    for site in &uses {
+        let relative = site.path.strip_prefix(dir).unwrap_or(&site.path).display().to_string();
+        found.entry((site.symbol.clone(), relative, site.caller.clone().unwrap_or_default())).or_default().push(site);
+    }
+
+    if update {
+        let mut content = String::from("# Uses of deprecated symbols allowed by `phrep deprecations gate`: symbol, file, caller and count\n");
+        for ((symbol, path, caller), sites) in &found {
+            content.push_str(&format!("{}\t{}\t{}\t{}\n", symbol, path, caller, sites.len()));
+        }
+        if let Err(e) = std::fs::write(allowlist, content) {
+            eprintln!("Error: Could not write the allowlist '{}': {}", allowlist, e);
+            return Err(anyhow::anyhow!("Could not write the allowlist '{}': {}", allowlist, e));
+        }
+        crate::report::summary(format!("Wrote {} use{} of deprecated symbols to {}", uses.len(), if uses.len() == 1 { "" } else { "s" }, allowlist));
+        return Ok(());
+    }
+
+    let allowed = match std::fs::read_to_string(allowlist).map_err(anyhow::Error::from).and_then(|content| parse(&content)) {
+        Ok(allowed) => allowed,
+        Err(e) => {
+            eprintln!("Error: Could not read the allowlist '{}': {}, run with --update to create it.", allowlist, e);
+            return Err(anyhow::anyhow!("Could not read the allowlist '{}': {}", allowlist, e));
+        }
+    };
+
+    let mut new_uses = 0;
+    for (key, sites) in &found {
+        let allowed_count = allowed.get(key).copied().unwrap_or(0);
+        if sites.len() <= allowed_count {
+            continue;
+        }
+        new_uses += sites.len() - allowed_count;
+        let note = match allowed_count {
+            0 => "[new]".to_string(),
+            count => format!("[{} allowed here]", count),
+        };
+        for site in sites {
+            let line = format!("{} {}", site.line_styled.trim(), note.dimmed());
+            crate::print_symbol_match(&site.path, site.line, Some(&site.symbol), &line);
+        }
+    }
+
+    let gone: usize = allowed.iter().map(|(key, count)| count.saturating_sub(found.get(key).map_or(0, Vec::len))).sum();
+    if gone > 0 {
+        crate::report::summary(format!("{} allowed use{} of deprecated symbols {} gone, run with --update to tighten the allowlist", gone, if gone == 1 { "" } else { "s" }, if gone == 1 { "is" } else { "are" }));
+    }
+    if new_uses > 0 {
+        eprintln!("Error: Found {} new use{} of deprecated symbols.", new_uses, if new_uses == 1 { "" } else { "s" });
+        return Err(anyhow::anyhow!("Found {} new use{} of deprecated symbols", new_uses, if new_uses == 1 { "" } else { "s" }));
+    }
+    crate::report::summary("No new uses of deprecated symbols");
+    Ok(())
+}
+
+// Reads the tab-separated lines `--update` writes, skipping comments
+fn parse(content: &str) -> Result<HashMap<(String, String, String), usize>> {
+    let mut allowed = HashMap::new();
+    for (i, line) in content.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [symbol, path, caller, count] = fields.as_slice() else {
+            return Err(anyhow::anyhow!("line {}: expected symbol, file, caller and count separated by tabs", i + 1));
+        };
+        let Ok(count) = count.trim().parse::<usize>() else {
+            return Err(anyhow::anyhow!("line {}: '{}' is not a count", i + 1, count));
+        };
+        *allowed.entry((symbol.to_string(), path.to_string(), caller.to_string())).or_insert(0) += count;
+    }
+    Ok(allowed)
+}
+
+// Finds the deprecated symbols, then every use of them: class references, calls of deprecated
+// functions, and calls of deprecated methods made on their class, a subclass or an object of
+// unknown type
+fn find(dir: &str, file: &str, exclude_dirs: &str) -> Result<(Vec<Deprecated>, Vec<Use>)> {
+    let mut deprecated = Vec::new();
+    let mut files = Vec::new();
+    crate::for_each_php_tree(dir, file, exclude_dirs, |_| true, |path, content, tree| {
+        deprecated.extend(deprecated_symbols(path, tree.root_node(), content));
+        files.push((path.to_path_buf(), FileDecls::new(tree.root_node(), content)));
+    })?;
+    deprecated.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+    if deprecated.is_empty() {
+        return Ok((deprecated, Vec::new()));
+    }
+    let parents = declarations::hierarchy(files.iter().flat_map(|(_, decls)| decls.classes.iter()));
+    let is_class = |class: &str, target: &str| class.eq_ignore_ascii_case(target) || declarations::inherits_from(class, target, &parents);
+
+    let mut uses = Vec::new();
+    crate::for_each_php_tree(dir, file, exclude_dirs, |_| true, |path, content, tree| {
+        let names = FileNames::new(tree.root_node(), content);
+        let lines: Vec<&str> = content.lines().collect();
+        let mut found: Vec<(&Deprecated, Node, bool)> = Vec::new();
+        php::walk(tree.root_node(), &mut |node| {
+            let Some(call) = calls::call_site(node, content, &names) else { return };
+            for symbol in &deprecated {
+                let unknown_receiver = match &symbol.target {
+                    Target::Function { key } if call.kind == CallKind::Function && calls::function_key(call.name) == *key => false,
+                    Target::Method { class, name } if call.kind != CallKind::Function && call.name.eq_ignore_ascii_case(name) => match &call.class {
+                        Some(called_class) if is_class(called_class, class) => false,
+                        None if call.kind == CallKind::Instance => true,
+                        _ => continue,
+                    },
+                    _ => continue,
+                };
+                found.push((symbol, call.name_node, unknown_receiver));
+            }
+        });
+        for node in php::class_references(tree.root_node(), content) {
+            let Some(fqcn) = names.resolve_node(node, content) else { continue };
+            found.extend(deprecated.iter()
+                .filter(|symbol| matches!(&symbol.target, Target::Class { fqcn: class } if class.eq_ignore_ascii_case(&fqcn)))
+                .map(|symbol| (symbol, node, false)));
+        }
+        found.sort_by_key(|(_, node, _)| node.start_byte());
+
+        for (symbol, node, unknown_receiver) in found {
+            let line = node.start_position().row + 1;
+            if symbol.path == path && (symbol.lines.0..=symbol.lines.1).contains(&line) {
+                continue;
+            }
+            let class = php::enclosing_class(node).and_then(|class| names.class_fqcn(class, content));
+            let caller = match (&class, php::enclosing_function(node, content)) {
+                (Some(fqcn), Some(function)) => Some(format!("{}::{}", php::short_name(fqcn), function)),
+                (None, Some(function)) => Some(function.to_string()),
+                (Some(fqcn), None) => Some(php::short_name(fqcn).to_string()),
+                (None, None) => None,
+            };
+            let line_styled = crate::highlight_node(&lines, node);
+            uses.push(Use {
+                symbol: symbol.symbol.clone(),
+                path: path.to_path_buf(),
+                line,
+                caller,
+                line_styled: if unknown_receiver { format!("{} {}", line_styled.trim(), "(receiver type unknown)".dimmed()) } else { line_styled },
+            });
+        }
+    })?;
+    Ok((deprecated, uses))
+}
+
+// The classes, methods and functions declared in a file that are marked deprecated
+fn deprecated_symbols(path: &Path, root: Node, content: &str) -> Vec<Deprecated> {
+    let names = FileNames::new(root, content);
+    let mut found = Vec::new();
+    php::walk(root, &mut |node| {
+        if !matches!(node.kind(), "class_declaration" | "interface_declaration" | "trait_declaration" | "enum_declaration" | "method_declaration" | "function_definition")
+            || !is_deprecated(node, content) {
+            return;
+        }
+        let Some(name) = node.child_by_field_name("name").map(|name| php::node_text(name, content)) else { return };
+        let (symbol, target) = match node.kind() {
+            "method_declaration" => {
+                let Some(class) = php::enclosing_class(node).and_then(|class| names.class_fqcn(class, content)) else { return };
+                (format!("{}::{}()", class, name), Target::Method { class, name: name.to_string() })
+            }
+            "function_definition" => (format!("{}()", name), Target::Function { key: calls::function_key(name) }),
+            _ => {
+                let Some(fqcn) = names.class_fqcn(node, content) else { return };
+                (fqcn.clone(), Target::Class { fqcn })
+            }
+        };
+        found.push(Deprecated {
+            symbol,
+            target,
+            path: path.to_path_buf(),
+            lines: (node.start_position().row + 1, node.end_position().row + 1),
+        });
+    });
+    found
+}
+
+// Whether a declaration's docblock has an @deprecated tag or it has a #[Deprecated] attribute
+fn is_deprecated(node: Node, content: &str) -> bool {
+    let mut has_attribute = false;
+    if let Some(attributes) = node.child_by_field_name("attributes") {
+        php::walk(attributes, &mut |attribute| {
+            if attribute.kind() == "attribute" && attribute.named_child(0).is_some_and(|name| php::short_name(php::node_text(name, content)).eq_ignore_ascii_case("Deprecated")) {
+                has_attribute = true;
+            }
+        });
+    }
+    has_attribute || node.prev_named_sibling()
+        .filter(|comment| comment.kind() == "comment" && php::node_text(*comment, content).starts_with("/**"))
+        .is_some_and(|comment| php::node_text(comment, content).split_whitespace().any(|word| word == "@deprecated"))
+}
@@ -0,0 +1,229 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::path::PathBuf;
+
+use anyhow::Result;
+use colored::*;
+use tree_sitter::Node;
+
+use crate::json::Value;
+use crate::php::{self, FileNames};
+
+pub const DEPS_LEVELS: [&str; 2] = ["namespace", "class"];
+
+// A reference to a class from a use statement, `new` or a static call
+pub struct Dependency {
+    pub namespace: String,
+    // The class the reference is in, or for a use statement the classes declared after it in
+    // its namespace. Empty for code outside of classes
+    pub classes: Vec<String>,
+    // Fully qualified class referred to
+    pub target: String,
+    pub path: PathBuf,
+    pub line: usize,
+    pub line_styled: String,
+}
+
+// Collects the class references of every file, resolving names through the file's
+// namespace and imports
+pub fn dependencies(dir: &str, file: &str, exclude_dirs: &str) -> Result<Vec<Dependency>> {
+    let mut dependencies = Vec::new();
+    crate::for_each_php_tree(dir, file, exclude_dirs, |_| true, |path, content, tree| {
+        let names = FileNames::new(tree.root_node(), content);
+        let lines: Vec<&str> = content.lines().collect();
+        let mut classes: Vec<(String, usize)> = Vec::new();
+        php::walk(tree.root_node(), &mut |node| {
+            if matches!(node.kind(), "class_declaration" | "interface_declaration" | "trait_declaration" | "enum_declaration")
+                && let Some(fqcn) = names.class_fqcn(node, content) {
+                classes.push((fqcn, node.start_byte()));
+            }
+        });
+
+        php::walk(tree.root_node(), &mut |node| {
+            let namespace = names.namespace_at(node.start_byte()).to_string();
+            let is_name = |node: &Node| node.kind() == "name" || node.kind() == "qualified_name";
+            let (at, targets): (Option<Node>, Vec<String>) = match node.kind() {
+                "namespace_use_declaration" => (Some(node), php::use_declaration_aliases(node, content).into_iter().map(|(_, fqcn)| fqcn).collect()),
+                "object_creation_expression" => {
+                    let class = node.named_child(0).filter(is_name);
+                    (class, class.and_then(|class| names.resolve_node(class, content)).into_iter().collect())
+                }
+                "scoped_call_expression" => {
+                    let scope = node.child_by_field_name("scope").filter(is_name);
+                    (scope, scope.and_then(|scope| names.resolve_node(scope, content)).into_iter().collect())
+                }
+                _ => return,
+            };
+            let Some(at) = at else { return };
+
+            let dependent: Vec<String> = if node.kind() == "namespace_use_declaration" {
+                classes.iter()
+                    .filter(|(_, start)| *start > node.start_byte() && names.namespace_at(*start) == namespace)
+                    .map(|(fqcn, _)| fqcn.clone())
+                    .collect()
+            } else {
+                php::enclosing_class(node).and_then(|class| names.class_fqcn(class, content)).into_iter().collect()
+            };
+            for target in targets {
+                dependencies.push(Dependency {
+                    namespace: namespace.clone(),
+                    classes: dependent.clone(),
+                    target,
+                    path: path.to_path_buf(),
+                    line: at.start_position().row + 1,
+                    line_styled: crate::highlight_node(&lines, at),
+                });
+            }
+        });
+    })?;
+    Ok(dependencies)
+}
+
+// The namespace part of a fully qualified class name
+pub fn namespace_of(fqcn: &str) -> &str {
+    fqcn.rsplit_once('\\').map_or("", |(namespace, _)| namespace)
+}
+
+// Prints the dependencies between namespaces or classes as a list, DOT graph or JSON, with
+// the dependency cycles among them
+pub fn deps(level: &str, dot: bool, json: bool, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let mut edges: BTreeMap<(String, String), usize> = BTreeMap::new();
+    for dependency in dependencies(dir, file, exclude_dirs)? {
+        let pairs: Vec<(String, String)> = if level == "class" {
+            dependency.classes.iter().map(|class| (class.clone(), dependency.target.clone())).collect()
+        } else {
+            vec![(dependency.namespace.clone(), namespace_of(&dependency.target).to_string())]
+        };
+        for (from, to) in pairs.into_iter().filter(|(from, to)| !from.eq_ignore_ascii_case(to)) {
+            *edges.entry((from, to)).or_insert(0) += 1;
+        }
+    }
+
+    let cycles = cycles(&edges);
+    let in_cycle: BTreeSet<(&str, &str)> = cycles.iter()
+        .flat_map(|cycle| cycle.windows(2).map(|pair| (pair[0].as_str(), pair[1].as_str())))
+        .collect();
+    let label = |name: &str| if name.is_empty() { "\\".to_string() } else { name.to_string() };
+
+    if dot {
+        println!("digraph dependencies {{");
+        for ((from, to), count) in &edges {
+            let color = if in_cycle.contains(&(from.as_str(), to.as_str())) { ", color=red" } else { "" };
+            println!("    {} -> {} [label=\"{}\"{}];", dot_id(&label(from)), dot_id(&label(to)), count, color);
+        }
+        println!("}}");
+        return Ok(());
+    }
+
+    if json {
+        let nodes: BTreeSet<&String> = edges.keys().flat_map(|(from, to)| [from, to]).collect();
+        let document = Value::Object(vec![
+            crate::report::schema_version(),
+            ("nodes".to_string(), Value::Array(nodes.into_iter().map(|node| Value::String(label(node))).collect())),
+            ("edges".to_string(), Value::Array(edges.iter().map(|((from, to), count)| Value::Object(vec![
+                ("from".to_string(), Value::String(label(from))),
+                ("to".to_string(), Value::String(label(to))),
+                ("count".to_string(), Value::Number(*count as f64)),
+            ])).collect())),
+            ("cycles".to_string(), Value::Array(cycles.iter().map(|cycle| Value::Array(cycle.iter().map(|node| Value::String(label(node))).collect())).collect())),
+        ]);
+        println!("{}", document);
+        return Ok(());
+    }
+
+    for ((from, to), count) in &edges {
+        println!("{} → {} ({})", label(from).bold(), label(to), count);
+    }
+    let labels: Vec<String> = cycles.iter().map(|cycle| cycle.iter().map(|node| label(node)).collect::<Vec<_>>().join(" → ")).collect();
+    for cycle in &labels {
+        println!("{} {}", "Cycle:".bold().red(), cycle);
+    }
+    println!("{} dependencies, {} cycle{}", edges.len(), cycles.len(), if cycles.len() == 1 { "" } else { "s" });
+    Ok(())
+}
+
+// Finds the strongly connected components of the graph (Tarjan's algorithm) and returns a
+// shortest cycle through the first node of each, written with the first node repeated at the end
+fn cycles(edges: &BTreeMap<(String, String), usize>) -> Vec<Vec<String>> {
+    let mut successors: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (from, to) in edges.keys() {
+        successors.entry(from).or_default().push(to);
+        successors.entry(to).or_default();
+    }
+
+    struct Tarjan<'a> {
+        successors: &'a BTreeMap<&'a str, Vec<&'a str>>,
+        index: HashMap<&'a str, usize>,
+        low: HashMap<&'a str, usize>,
+        stack: Vec<&'a str>,
+        components: Vec<Vec<&'a str>>,
+    }
+    impl<'a> Tarjan<'a> {
+        fn visit(&mut self, node: &'a str) {
+            let index = self.index.len();
+            self.index.insert(node, index);
+            self.low.insert(node, index);
+            self.stack.push(node);
+            for &next in &self.successors[node] {
+                if !self.index.contains_key(next) {
+                    self.visit(next);
+                    self.low.insert(node, self.low[node].min(self.low[next]));
+                } else if self.stack.contains(&next) {
+                    self.low.insert(node, self.low[node].min(self.index[next]));
+                }
+            }
+            if self.low[node] == self.index[node] {
+                let mut component = Vec::new();
+                while let Some(member) = self.stack.pop() {
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                self.components.push(component);
+            }
+        }
+    }
+
+    let mut tarjan = Tarjan { successors: &successors, index: HashMap::new(), low: HashMap::new(), stack: Vec::new(), components: Vec::new() };
+    for &node in successors.keys() {
+        if !tarjan.index.contains_key(node) {
+            tarjan.visit(node);
+        }
+    }
+
+    let mut cycles = Vec::new();
+    for component in tarjan.components.iter().filter(|component| component.len() > 1) {
+        let Some(&start) = component.iter().min() else { continue };
+        // Breadth-first from the start node back to itself, staying inside the component
+        let mut came_from: HashMap<&str, &str> = HashMap::new();
+        let mut queue = VecDeque::from([start]);
+        'search: while let Some(node) = queue.pop_front() {
+            for &next in &successors[node] {
+                if !component.contains(&next) {
+                    continue;
+                }
+                if next == start {
+                    let mut path = Vec::new();
+                    let mut current = node;
+                    while current != start {
+                        path.push(current.to_string());
+                        current = came_from[current];
+                    }
+                    path.reverse();
+                    cycles.push([vec![start.to_string()], path, vec![start.to_string()]].concat());
+                    break 'search;
+                }
+                if !came_from.contains_key(next) {
+                    came_from.insert(next, node);
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+    cycles
+}
+
+// Quotes a node name for DOT
+fn dot_id(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Result;
+use colored::*;
+
+use crate::json::{self, Value};
+
+// A match read back from a saved `--format json` run
+struct SavedMatch {
+    path: String,
+    line: usize,
+    symbol: Option<String>,
+    text: String,
+    // What identifies the match across runs: the file, the declaration around it and its text
+    // with whitespace collapsed, but not the line, which moves whenever code above it changes
+    key: String,
+}
+
+// Compares two saved `--format json` runs and prints the matches only the new one has and those
+// only the old one has. Matches pair up by file, enclosing declaration (or symbol) and text, so
+// reformatting and lines shifting around them don't count as changes; a key seen more often in
+// one run than the other reports the difference
+pub fn diff_results(old_file: &str, new_file: &str) -> Result<()> {
+    let old = read_run(old_file)?;
+    let new = read_run(new_file)?;
+
+    let mut remaining: HashMap<&str, usize> = HashMap::new();
+    for saved in &old {
+        *remaining.entry(&saved.key).or_default() += 1;
+    }
+    let mut added = Vec::new();
+    for saved in &new {
+        match remaining.get_mut(saved.key.as_str()) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => added.push(saved),
+        }
+    }
+    // The old matches left over once every new one has taken its pair, the last ones of a key first
+    let mut removed = Vec::new();
+    for saved in old.iter().rev() {
+        if let Some(count) = remaining.get_mut(saved.key.as_str())
+            && *count > 0 {
+            *count -= 1;
+            removed.push(saved);
+        }
+    }
+    removed.reverse();
+
+    for (marker, found) in [("+".green().bold(), &added), ("-".red().bold(), &removed)] {
+        for saved in found {
+            println!("{} {}", marker, crate::text_match(Path::new(&saved.path), saved.line, saved.symbol.as_deref(), &saved.text));
+        }
+    }
+    let unchanged = new.len() - added.len();
+    crate::report::summary(format!("{} added, {} removed, {} unchanged", added.len().to_string().green(), removed.len().to_string().red(), unchanged));
+    Ok(())
+}
+
+// The matches of a file written by `--format json`, one JSON object per line
+fn read_run(run_file: &str) -> Result<Vec<SavedMatch>> {
+    let Some(content) = crate::read_file(Path::new(run_file)) else {
+        return Err(anyhow::anyhow!("Could not read file '{}'", run_file));
+    };
+    let mut run = Vec::new();
+    for (i, line) in content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        match json::parse(line).map_err(|e| e.to_string()).and_then(|value| saved_match(&value).ok_or_else(|| "expected a match with a path and text".to_string())) {
+            Ok(saved) => run.push(saved),
+            Err(e) => {
+                eprintln!("Error: Line {} of '{}' isn't a match written by --format json: {}", i + 1, run_file, e);
+                return Err(anyhow::anyhow!("Line {} of '{}' isn't a match written by --format json: {}", i + 1, run_file, e));
+            }
+        }
+    }
+    Ok(run)
+}
+
+fn saved_match(value: &Value) -> Option<SavedMatch> {
+    let path = value.get("path")?.as_str()?.to_string();
+    let text = value.get("text")?.as_str()?.to_string();
+    let line = match value.get("line") {
+        Some(Value::Number(line)) => *line as usize,
+        _ => 0,
+    };
+    let symbol = value.get("symbol").and_then(Value::as_str).map(str::to_string);
+    let owner = value.get("enclosing").and_then(|enclosing| enclosing.get("name")).and_then(Value::as_str).or(symbol.as_deref()).unwrap_or_default();
+    let normalized = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let key = format!("{}\0{}\0{}", path, owner, normalized);
+    Some(SavedMatch { path, line, symbol, text, key })
+}
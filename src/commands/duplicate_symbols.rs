@@ -0,0 +1,45 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::Result;
+use colored::*;
+
+use crate::declarations;
+
+// Reports classes, interfaces, traits, enums and global functions declared under the same fully
+// qualified name in more than one file. Only one of them wins at runtime, depending on autoload order
+pub fn duplicate_symbols(dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let files = declarations::scan(dir, file, exclude_dirs)?;
+
+    // Keyed by lowercase name since PHP class and function names are case-insensitive
+    let mut symbols: BTreeMap<String, Vec<(&Path, usize, String)>> = BTreeMap::new();
+    for (path, decls) in &files {
+        for class in &decls.classes {
+            symbols.entry(format!("class {}", class.fqcn.to_lowercase()))
+                .or_default()
+                .push((path.as_path(), class.line, format!("{} {}", class.kind, class.fqcn)));
+        }
+        for function in &decls.functions {
+            symbols.entry(format!("function {}", function.fqn.to_lowercase()))
+                .or_default()
+                .push((path.as_path(), function.line, format!("function {}", function.fqn)));
+        }
+    }
+
+    let mut duplicates = 0;
+    for locations in symbols.values() {
+        let mut files: Vec<&Path> = locations.iter().map(|(path, _, _)| *path).collect();
+        files.dedup();
+        if files.len() < 2 {
+            continue;
+        }
+        duplicates += 1;
+        let message = format!("declared in {} files", files.len());
+        for (path, line, symbol) in locations {
+            crate::print_symbol_match(path, *line, Some(symbol), &message.red().to_string());
+        }
+    }
+
+    crate::report::summary(format!("{} duplicate symbols found", duplicates));
+    Ok(())
+}
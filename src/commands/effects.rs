@@ -0,0 +1,282 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+
+use anyhow::Result;
+use colored::*;
+use tree_sitter::Node;
+
+use crate::calls::{self, CallKind};
+use crate::declarations::{self, FileDecls};
+use crate::php::{self, FileNames};
+
+// The kinds of side effect a statement can have, in the order they're reported
+#[derive(Clone, Copy, PartialEq)]
+enum Effect {
+    Db,
+    Http,
+    Filesystem,
+    GlobalState,
+}
+
+// Each with its heading and its name in the summary
+const EFFECTS: [(Effect, &str, &str); 4] = [
+    (Effect::Db, "DB calls", "DB"),
+    (Effect::Http, "HTTP calls", "HTTP"),
+    (Effect::Filesystem, "Filesystem", "filesystem"),
+    (Effect::GlobalState, "Global state", "global state"),
+];
+
+// Functions whose name tells what they touch, by prefix or in full
+const DB_FUNCTIONS: [&str; 7] = ["mysql_", "mysqli_", "pg_", "sqlite_", "oci_", "sqlsrv_", "odbc_"];
+const HTTP_FUNCTIONS: [&str; 8] = ["curl_", "fsockopen", "stream_socket_client", "header", "setcookie", "http_response_code", "get_headers", "mail"];
+const FILESYSTEM_FUNCTIONS: [&str; 29] = [
+    "fopen", "fwrite", "fputs", "fread", "fgets", "fgetcsv", "fputcsv", "fclose", "file_put_contents", "file_get_contents", "file", "readfile",
+    "unlink", "rename", "copy", "mkdir", "rmdir", "touch", "chmod", "is_file", "is_dir", "file_exists", "scandir", "glob", "tempnam",
+    "tmpfile", "move_uploaded_file", "opendir", "filesize",
+];
+const GLOBAL_STATE_FUNCTIONS: [&str; 7] = ["define", "putenv", "ini_set", "session_", "set_error_handler", "set_exception_handler", "date_default_timezone_set"];
+
+// Methods that read or write a database when called on an object typed as one of DB_TYPES, and
+// the words in the receiver that make any call one. Names like query() or delete() are common on
+// requests and caches too, so the name alone isn't enough
+const DB_METHODS: [&str; 18] = [
+    "query", "exec", "execute", "prepare", "fetch", "fetchall", "fetchone", "fetchcolumn", "fetchassoc", "insert", "update", "delete",
+    "select", "begintransaction", "commit", "rollback", "persist", "flush",
+];
+const DB_RECEIVERS: [&str; 9] = ["db", "pdo", "connection", "conn", "entitymanager", "repository", "database", "stmt", "statement"];
+const DB_TYPES: [&str; 9] = ["pdo", "pdostatement", "mysqli", "mysqli_stmt", "sqlite3", "connection", "entitymanager", "entitymanagerinterface", "querybuilder"];
+const SUPERGLOBALS: [&str; 9] = ["$_SESSION", "$_COOKIE", "$GLOBALS", "$_GET", "$_POST", "$_REQUEST", "$_SERVER", "$_FILES", "$_ENV"];
+const HTTP_RECEIVERS: [&str; 4] = ["http", "client", "guzzle", "curl"];
+const FILESYSTEM_RECEIVERS: [&str; 4] = ["filesystem", "storage", "disk", "fs"];
+
+// A statement with side effects
+struct Site {
+    effect: Effect,
+    line: usize,
+    line_styled: String,
+}
+
+// A method or function with the side effects of its body, how many of its statements have
+// none, and its statically resolvable calls
+struct Callable {
+    class: Option<String>,
+    name: String,
+    symbol: String,
+    path: PathBuf,
+    sites: Vec<Site>,
+    statements: usize,
+    pure: usize,
+    calls: Vec<String>,
+}
+
+// Sorts the statements of a method, and with `depth` of the methods it calls, into DB calls,
+// HTTP calls, filesystem access, global state and pure ones, so the side effects of a critical
+// path can be reviewed at a glance. What a call touches is told from the function or method
+// name and the name of the object it's called on, e.g. `$this->db->query()`
+pub fn effects(method: &str, depth: usize, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let mut callables: HashMap<String, Callable> = HashMap::new();
+    let mut all_decls: Vec<FileDecls> = Vec::new();
+    crate::for_each_php_tree(dir, file, exclude_dirs, |_| true, |path, content, tree| {
+        let names = FileNames::new(tree.root_node(), content);
+        let lines: Vec<&str> = content.lines().collect();
+        all_decls.push(FileDecls::new(tree.root_node(), content));
+        php::walk(tree.root_node(), &mut |node| {
+            if node.kind() != "method_declaration" && node.kind() != "function_definition" {
+                return;
+            }
+            let (Some(name), Some(body)) = (node.child_by_field_name("name"), node.child_by_field_name("body")) else { return };
+            let name = php::node_text(name, content);
+            let (key, class, symbol) = match php::enclosing_class(node).filter(|_| node.kind() == "method_declaration") {
+                Some(class) => {
+                    let Some(fqcn) = names.class_fqcn(class, content) else { return };
+                    (calls::method_key(&fqcn, name), Some(fqcn.clone()), format!("{}::{}", php::short_name(&fqcn), name))
+                }
+                None => (calls::function_key(name), None, name.to_string()),
+            };
+
+            let mut callable = Callable { class, name: name.to_string(), symbol, path: path.to_path_buf(), sites: Vec::new(), statements: 0, pure: 0, calls: Vec::new() };
+            let mut effect_bytes = Vec::new();
+            php::walk_body(body, &mut |inner| {
+                if let Some(effect) = effect_of(inner, content) {
+                    effect_bytes.push(inner.start_byte());
+                    let line = inner.start_position().row + 1;
+                    if !callable.sites.iter().any(|site| site.line == line && site.effect == effect) {
+                        callable.sites.push(Site { effect, line, line_styled: crate::highlight_node(&lines, inner) });
+                    }
+                }
+                if let Some(call) = calls::call_site(inner, content, &names)
+                    && call.kind != CallKind::Instance
+                    && let Some(target) = call.target_key() {
+                    callable.calls.push(target);
+                }
+            });
+            php::walk_body(body, &mut |inner| {
+                if matches!(inner.kind(), "expression_statement" | "return_statement" | "echo_statement" | "unset_statement" | "global_declaration" | "function_static_declaration") {
+                    callable.statements += 1;
+                    if !effect_bytes.iter().any(|byte| (inner.start_byte()..inner.end_byte()).contains(byte)) {
+                        callable.pure += 1;
+                    }
+                }
+            });
+            callables.insert(key, callable);
+        });
+    })?;
+
+    let extends: HashMap<String, String> = all_decls.iter()
+        .flat_map(|decls| decls.classes.iter())
+        .filter_map(|class: &declarations::ClassDecl| Some((class.fqcn.to_lowercase(), class.extends.first()?.to_lowercase())))
+        .collect();
+    let start: Vec<&String> = match method.split_once("::") {
+        Some((class, name)) => callables.iter()
+            .filter(|(_, callable)| callable.class.as_deref().is_some_and(|fqcn| php::class_matches(fqcn, class)) && callable.name.eq_ignore_ascii_case(name))
+            .map(|(key, _)| key)
+            .collect(),
+        None => callables.iter()
+            .filter(|(_, callable)| callable.class.is_none() && callable.name.eq_ignore_ascii_case(php::short_name(method)))
+            .map(|(key, _)| key)
+            .collect(),
+    };
+    if start.is_empty() {
+        eprintln!("Error: Method or function '{}' not found.", method);
+        return Err(anyhow::anyhow!("Method or function '{}' not found", method));
+    }
+
+    // The callables reached, each with the call chain leading to it
+    let mut reached: Vec<(&Callable, Vec<String>)> = Vec::new();
+    let mut seen = HashSet::new();
+    let mut queue: VecDeque<(String, usize, Vec<String>)> = start.into_iter().map(|key| (key.clone(), 0, Vec::new())).collect();
+    while let Some((key, level, chain)) = queue.pop_front() {
+        let Some(key) = calls::resolve(&key, &callables, &extends) else { continue };
+        if !seen.insert(key.clone()) {
+            continue;
+        }
+        let callable = &callables[&key];
+        if level < depth {
+            for target in &callable.calls {
+                let mut chain = chain.clone();
+                chain.push(callable.symbol.clone());
+                queue.push_back((target.clone(), level + 1, chain));
+            }
+        }
+        reached.push((callable, chain));
+    }
+
+    let mut counts = Vec::new();
+    for (effect, title, name) in EFFECTS {
+        let sites: Vec<(&Callable, &Vec<String>, &Site)> = reached.iter()
+            .flat_map(|(callable, chain)| callable.sites.iter().filter(|site| site.effect == effect).map(move |site| (*callable, chain, site)))
+            .collect();
+        counts.push(format!("{} {}", name, sites.len()));
+        if sites.is_empty() {
+            continue;
+        }
+        crate::report::summary(format!("{}: {}", title.bold(), sites.len()));
+        for (callable, chain, site) in sites {
+            let via = if chain.is_empty() { String::new() } else { format!(" {}", format!("(via {})", chain.join(" → ")).dimmed()) };
+            crate::print_match(&callable.path, site.line, Some(&callable.symbol), &format!("{}{}", site.line_styled.trim(), via));
+        }
+    }
+    let statements: usize = reached.iter().map(|(callable, _)| callable.statements).sum();
+    let pure: usize = reached.iter().map(|(callable, _)| callable.pure).sum();
+    crate::report::summary(format!("{}; {} of {} statement{} pure", counts.join(", "), pure, statements, if statements == 1 { "" } else { "s" }));
+    Ok(())
+}
+
+// What a node inside a function body touches, if it's a call, `new`, superglobal, `global` or
+// static property that has side effects
+fn effect_of(node: Node, content: &str) -> Option<Effect> {
+    let name_in = |name: &str, list: &[&str]| {
+        let name = name.trim_start_matches('\\').to_lowercase();
+        list.iter().any(|entry| if entry.ends_with('_') { name.starts_with(entry) } else { name == *entry })
+    };
+    // The last word of the receiver, e.g. `db` for `$this->db` and `Storage` for `Storage::`
+    let receiver_in = |receiver: Node, list: &[&str]| {
+        let text = php::node_text(receiver, content).to_lowercase();
+        let last = text.rsplit(['>', ':', '$', '\\']).next().unwrap_or("").trim_start_matches('_');
+        list.iter().any(|word| last == *word || last.ends_with(word))
+    };
+
+    match node.kind() {
+        "function_call_expression" => {
+            let function = php::node_text(node.child_by_field_name("function")?, content);
+            if name_in(function, &DB_FUNCTIONS) {
+                Some(Effect::Db)
+            } else if name_in(function, &["file_get_contents", "fopen", "file"]) && first_argument_is_url(node, content) || name_in(function, &HTTP_FUNCTIONS) {
+                Some(Effect::Http)
+            } else if name_in(function, &FILESYSTEM_FUNCTIONS) {
+                Some(Effect::Filesystem)
+            } else if name_in(function, &GLOBAL_STATE_FUNCTIONS) {
+                Some(Effect::GlobalState)
+            } else {
+                None
+            }
+        }
+        "member_call_expression" | "nullsafe_member_call_expression" | "scoped_call_expression" => {
+            let receiver = node.child_by_field_name("object").or_else(|| node.child_by_field_name("scope"))?;
+            let method = php::node_text(node.child_by_field_name("name")?, content).to_lowercase();
+            if receiver_in(receiver, &HTTP_RECEIVERS) {
+                Some(Effect::Http)
+            } else if receiver_in(receiver, &FILESYSTEM_RECEIVERS) {
+                Some(Effect::Filesystem)
+            } else if receiver_in(receiver, &DB_RECEIVERS) || DB_METHODS.contains(&method.as_str()) && declared_type(receiver, content).is_some_and(|type_text| name_in(type_text, &DB_TYPES)) {
+                Some(Effect::Db)
+            } else {
+                None
+            }
+        }
+        "object_creation_expression" => {
+            let class = php::short_name(php::node_text(node.named_child(0)?, content)).to_lowercase();
+            match class.as_str() {
+                "pdo" | "mysqli" => Some(Effect::Db),
+                "splfileobject" | "directoryiterator" | "recursivedirectoryiterator" | "filesystemiterator" => Some(Effect::Filesystem),
+                _ => None,
+            }
+        }
+        "variable_name" => SUPERGLOBALS.contains(&php::node_text(node, content)).then_some(Effect::GlobalState),
+        "global_declaration" | "function_static_declaration" => Some(Effect::GlobalState),
+        // Writing a static property, reading one is harmless
+        "assignment_expression" | "augmented_assignment_expression" | "update_expression" => node.child_by_field_name("left")
+            .or_else(|| node.child_by_field_name("argument"))
+            .filter(|target| target.kind() == "scoped_property_access_expression")
+            .map(|_| Effect::GlobalState),
+        _ => None,
+    }
+}
+
+// The short class name a receiver is declared with: the type of a `$param` of the enclosing
+// function, or of a `$this->property` declared or promoted in the enclosing class
+fn declared_type<'a>(receiver: Node, content: &'a str) -> Option<&'a str> {
+    let (scope, name) = match receiver.kind() {
+        "variable_name" => (php::enclosing_function_node(receiver)?.child_by_field_name("parameters")?, php::node_text(receiver, content).to_string()),
+        "member_access_expression" if receiver.child_by_field_name("object").is_some_and(|object| php::node_text(object, content) == "$this") => {
+            (php::enclosing_class(receiver)?.child_by_field_name("body")?, format!("${}", php::node_text(receiver.child_by_field_name("name")?, content)))
+        }
+        _ => return None,
+    };
+    let mut declared = None;
+    php::walk(scope, &mut |node| {
+        let typed = match node.kind() {
+            "simple_parameter" | "property_promotion_parameter" => node.child_by_field_name("name").filter(|param| php::node_text(*param, content) == name).and(node.child_by_field_name("type")),
+            "property_declaration" => {
+                let mut cursor = node.walk();
+                let declares = node.named_children(&mut cursor).any(|element| element.kind() == "property_element"
+                    && element.child_by_field_name("name").is_some_and(|element_name| php::node_text(element_name, content) == name));
+                node.child_by_field_name("type").filter(|_| declares)
+            }
+            _ => None,
+        };
+        if let Some(type_node) = typed.filter(|_| declared.is_none()) {
+            declared = Some(php::short_name(php::node_text(type_node, content).trim_start_matches('?')));
+        }
+    });
+    declared
+}
+
+// Whether the first argument of a call is a string starting with a URL scheme
+fn first_argument_is_url(call: Node, content: &str) -> bool {
+    call.child_by_field_name("arguments")
+        .and_then(|arguments| arguments.named_child(0))
+        .and_then(|argument| argument.named_child(0))
+        .and_then(|value| php::string_literal(value, content))
+        .is_some_and(|value| value.starts_with("http://") || value.starts_with("https://"))
+}
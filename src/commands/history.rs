@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Result;
+use colored::*;
+use regex::Regex;
+
+use crate::git;
+use crate::php::{self, FileNames};
+
+// A line matching the pattern inside a method or function body
+struct BodyMatch {
+    symbol: String,
+    line: usize,
+    text: String,
+}
+
+// Walks the commits touching PHP files, newest first like `git log -S`, and reports the
+// commits where lines matching the pattern appeared in or disappeared from a method body.
+// Matches are compared per method, so moving a method around in its file isn't a change
+pub fn history(query: &str, since: Option<&str>, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let pattern = match crate::budget::regex(query) {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            eprintln!("Invalid regex pattern: {}", e);
+            return Err(anyhow::anyhow!("Invalid regex pattern"));
+        }
+    };
+
+    let mut log_args = vec!["log", "--no-merges", "--date=short", "--format=%h%x1f%ad%x1f%an%x1f%s"];
+    let since_arg = since.map(|since| format!("--since={}", since));
+    log_args.extend(since_arg.as_deref());
+    log_args.extend(["--", "*.php"]);
+
+    let exclude_dirs = crate::exclude_list(exclude_dirs);
+    let mut parser = php::new_parser()?;
+    for commit in git::git(dir, &log_args)?.lines() {
+        let mut fields = commit.splitn(4, '\x1f');
+        let (Some(hash), Some(date), Some(author), Some(subject)) = (fields.next(), fields.next(), fields.next(), fields.next()) else { continue };
+
+        let changed = git::git(dir, &["diff-tree", "--root", "-r", "--no-commit-id", "--name-only", "--relative", "-z", hash, "--", "*.php"])?;
+        let mut header_printed = false;
+        for relative_path in changed.split('\0').filter(|path| !path.is_empty()) {
+            let path = Path::new(dir).join(relative_path);
+            if crate::is_excluded(&path, dir, &exclude_dirs) || !crate::file_selected(&path, dir, file) {
+                continue;
+            }
+
+            // Added and deleted files have no content on one side
+            let old_content = git::show_file(dir, &format!("{}^", hash), relative_path).unwrap_or_default();
+            let new_content = git::show_file(dir, hash, relative_path).unwrap_or_default();
+            let old_matches = body_matches(&mut parser, &old_content, &pattern);
+            let new_matches = body_matches(&mut parser, &new_content, &pattern);
+            let (added, removed) = (changed_matches(&new_matches, &old_matches), changed_matches(&old_matches, &new_matches));
+            if added.is_empty() && removed.is_empty() {
+                continue;
+            }
+
+            if !header_printed {
+                println!("{} {} {} {}", hash.bold().magenta(), date, author.bold(), subject);
+                header_printed = true;
+            }
+            for (marker, found) in [("+".green().bold(), added), ("-".red().bold(), removed)] {
+                for body_match in found {
+                    print!("  {} ", marker);
+                    crate::print_match(&path, body_match.line, Some(&body_match.symbol), &body_match.text);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// Lines matching the pattern in each method or function body of a file. A line inside a
+// closure or nested function belongs to the innermost named function around it
+fn body_matches(parser: &mut tree_sitter::Parser, content: &str, pattern: &Regex) -> Vec<BodyMatch> {
+    if !pattern.is_match(content) {
+        return Vec::new();
+    }
+    let Some(tree) = parser.parse(content, None) else { return Vec::new() };
+    let names = FileNames::new(tree.root_node(), content);
+    let lines: Vec<&str> = content.lines().collect();
+
+    let mut owners: Vec<Option<String>> = vec![None; lines.len()];
+    php::walk(tree.root_node(), &mut |node| {
+        if node.kind() != "method_declaration" && node.kind() != "function_definition" {
+            return;
+        }
+        let (Some(name), Some(body)) = (node.child_by_field_name("name"), node.child_by_field_name("body")) else { return };
+        let name = php::node_text(name, content);
+        let symbol = match php::enclosing_class(node).filter(|_| node.kind() == "method_declaration") {
+            Some(class) => format!("{}::{}", names.class_fqcn(class, content).as_deref().map(php::short_name).unwrap_or(""), name),
+            None => name.to_string(),
+        };
+        // Nested functions come later in document order and take over their lines
+        let end = body.end_position().row.min(lines.len().saturating_sub(1));
+        for owner in owners.iter_mut().take(end + 1).skip(body.start_position().row) {
+            *owner = Some(symbol.clone());
+        }
+    });
+
+    lines.iter()
+        .enumerate()
+        .filter(|(_, line)| pattern.is_match(line))
+        .filter_map(|(row, line)| {
+            let symbol = owners[row].clone()?;
+            Some(BodyMatch { symbol, line: row + 1, text: crate::highlight(line, pattern) })
+        })
+        .collect()
+}
+
+// Matches in `current` that `previous` doesn't have, comparing the trimmed line text per
+// method so each extra copy of a line counts once
+fn changed_matches<'a>(current: &'a [BodyMatch], previous: &[BodyMatch]) -> Vec<&'a BodyMatch> {
+    let mut previous_counts: HashMap<(&str, &str), usize> = HashMap::new();
+    for body_match in previous {
+        *previous_counts.entry((body_match.symbol.as_str(), body_match.text.trim())).or_default() += 1;
+    }
+    current.iter()
+        .filter(|body_match| {
+            match previous_counts.get_mut(&(body_match.symbol.as_str(), body_match.text.trim())) {
+                Some(count) if *count > 0 => {
+                    *count -= 1;
+                    false
+                }
+                _ => true,
+            }
+        })
+        .collect()
+}
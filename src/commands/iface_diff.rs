@@ -0,0 +1,118 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use anyhow::Result;
+use colored::*;
+
+use crate::declarations::{self, ClassDecl, FunctionDecl, TypeDecl};
+use crate::php;
+
+// Compares the method signatures of every implementation of an interface against the interface
+// itself, reporting renamed, retyped, added or removed parameters and return type changes
+pub fn iface_diff(interface: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let files = declarations::scan(dir, file, exclude_dirs)?;
+    let classes: Vec<(&Path, &ClassDecl)> = files.iter()
+        .flat_map(|(path, decls)| decls.classes.iter().map(move |class| (path.as_path(), class)))
+        .collect();
+
+    let targets: Vec<&(&Path, &ClassDecl)> = classes.iter()
+        .filter(|(_, class)| class.kind == "interface" && php::class_matches(&class.fqcn, interface))
+        .collect();
+    if targets.is_empty() {
+        eprintln!("Error: Interface '{}' not found.", interface);
+        return Err(anyhow::anyhow!("Interface '{}' not found", interface));
+    }
+
+    let parents = declarations::hierarchy(classes.iter().map(|(_, class)| *class));
+
+    for (iface_path, iface) in targets {
+        crate::report::summary(format!("{}:{}: {} {}", crate::format_filename(iface_path).bold().blue(), iface.line, iface.kind, iface.fqcn.bold().yellow()));
+
+        let mut checked = 0;
+        let mut differences = 0;
+        for (path, class) in &classes {
+            if class.kind == "interface" || !declarations::inherits_from(&class.fqcn, &iface.fqcn, &parents) {
+                continue;
+            }
+            checked += 1;
+            for iface_method in &iface.methods {
+                let Some(method) = class.methods.iter().find(|m| m.name.eq_ignore_ascii_case(&iface_method.name)) else { continue };
+                for difference in signature_differences(iface_method, method) {
+                    differences += 1;
+                    let symbol = format!("{}::{}", class.name, method.name);
+                    crate::print_match(path, method.line, Some(&symbol), &difference);
+                }
+            }
+        }
+
+        crate::report::summary(format!("{} implementations checked, {} signature differences", checked, differences));
+    }
+
+    Ok(())
+}
+
+fn signature_differences(expected: &FunctionDecl, actual: &FunctionDecl) -> Vec<String> {
+    let mut differences = Vec::new();
+
+    for (i, expected_param) in expected.params.iter().enumerate() {
+        let Some(param) = actual.params.get(i) else {
+            differences.push(format!("parameter ${} is missing", expected_param.name));
+            continue;
+        };
+        if param.name != expected_param.name {
+            differences.push(format!("parameter ${} renamed to {}", expected_param.name, format!("${}", param.name).red()));
+        }
+        match (&expected_param.type_hint, &param.type_hint) {
+            (Some(expected_type), Some(actual_type)) if expected_type.normalized != actual_type.normalized => {
+                differences.push(format!("parameter ${} type {} changed to {}{}", param.name, expected_type.text, actual_type.text.red(), type_change(expected_type, actual_type)));
+            }
+            (Some(expected_type), None) => differences.push(format!("parameter ${} type {} removed", param.name, expected_type.text)),
+            (None, Some(actual_type)) => differences.push(format!("parameter ${} type {} added", param.name, actual_type.text.red())),
+            _ => {}
+        }
+        match (&expected_param.default_value, &param.default_value) {
+            (None, Some(default_value)) => differences.push(format!("parameter ${} made optional (= {})", param.name, default_value.red())),
+            (Some(_), None) => differences.push(format!("parameter ${} made required", param.name)),
+            _ => {}
+        }
+        if param.by_ref != expected_param.by_ref {
+            differences.push(format!("parameter ${} {} by reference", param.name, if param.by_ref { "now passed" } else { "no longer passed" }));
+        }
+        if param.variadic != expected_param.variadic {
+            differences.push(format!("parameter ${} {} variadic", param.name, if param.variadic { "made" } else { "no longer" }));
+        }
+    }
+
+    for param in actual.params.iter().skip(expected.params.len()) {
+        let kind = if param.default_value.is_some() || param.variadic { "optional" } else { "required" };
+        differences.push(format!("extra {} parameter {}", kind, param.to_string().red()));
+    }
+
+    match (&expected.return_type, &actual.return_type) {
+        (Some(expected_type), Some(actual_type)) if expected_type.normalized != actual_type.normalized => {
+            differences.push(format!("return type {} changed to {}{}", expected_type.text, actual_type.text.red(), type_change(expected_type, actual_type)));
+        }
+        (Some(expected_type), None) => differences.push(format!("return type {} removed", expected_type.text)),
+        (None, Some(actual_type)) => differences.push(format!("return type {} added", actual_type.text.red())),
+        _ => {}
+    }
+
+    differences
+}
+
+// Describes how a type changed when one side is a strict superset of the other
+fn type_change(expected: &TypeDecl, actual: &TypeDecl) -> &'static str {
+    let expected_parts: HashSet<&str> = expected.normalized.split('|').collect();
+    let actual_parts: HashSet<&str> = actual.normalized.split('|').collect();
+    if actual.is_nullable() && !expected.is_nullable() && actual_parts.len() == expected_parts.len() + 1 && actual_parts.is_superset(&expected_parts) {
+        " (nullable added)"
+    } else if expected.is_nullable() && !actual.is_nullable() && expected_parts.len() == actual_parts.len() + 1 && expected_parts.is_superset(&actual_parts) {
+        " (nullable removed)"
+    } else if actual_parts.is_superset(&expected_parts) {
+        " (widened)"
+    } else if expected_parts.is_superset(&actual_parts) {
+        " (narrowed)"
+    } else {
+        ""
+    }
+}
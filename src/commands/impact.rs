@@ -0,0 +1,229 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use colored::*;
+
+use crate::calls::{self, CallKind};
+use crate::declarations;
+use crate::php::{self, FileNames};
+
+// Where a call site is, relative to the class declaring the method
+#[derive(Clone, Copy, PartialEq)]
+enum Scope {
+    DeclaringClass,
+    Subclass,
+    External,
+}
+
+// How a call site reaches the method, which decides what breaks when the method is made static
+// or stops being static
+#[derive(Clone, Copy, PartialEq)]
+enum Style {
+    // Foo::method(), self::method() and static::method()
+    Static,
+    // $this->method() and $object->method()
+    Instance,
+    // parent::method(), which works either way
+    Parent,
+}
+
+impl Style {
+    fn of(kind: CallKind) -> Style {
+        match kind {
+            CallKind::Parent => Style::Parent,
+            CallKind::This | CallKind::Instance => Style::Instance,
+            _ => Style::Static,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Style::Static => "static",
+            Style::Instance => "instance",
+            Style::Parent => "parent::",
+        }
+    }
+}
+
+struct Call {
+    scope: Scope,
+    style: Style,
+    path: PathBuf,
+    line: usize,
+    caller: Option<String>,
+    line_styled: String,
+    // `$object->method()` calls can't be told apart from calls to other classes' methods of
+    // the same name
+    unknown_receiver: bool,
+}
+
+// Lists the call sites of a method grouped by whether they are in its own class, in a subclass
+// or elsewhere, to tell whether the method could be made private or protected. Each call is
+// marked static, instance or parent::, and `only_static` lists only the static (true) or only
+// the instance (false) ones; the verdict still counts every call
+pub fn impact(method: &str, only_static: Option<bool>, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let Some((class_name, method_name)) = method.split_once("::") else {
+        eprintln!("Error: Expected the method as Class::method, got '{}'.", method);
+        return Err(anyhow::anyhow!("Expected the method as Class::method, got '{}'", method));
+    };
+
+    let files = declarations::scan(dir, file, exclude_dirs)?;
+    let (class_path, class) = declarations::find_class(&files, class_name)?;
+    let declaration = class.methods.iter().find(|m| m.name.eq_ignore_ascii_case(method_name));
+    // An undeclared method may still be called through __call or __callStatic
+    let (call_handler, call_static_handler) = (magic_handler(&files, class, "__call"), magic_handler(&files, class, "__callStatic"));
+    if declaration.is_none() && call_handler.is_none() && call_static_handler.is_none() {
+        eprintln!("Error: Method '{}' not found in {}.", method_name, class.fqcn);
+        return Err(anyhow::anyhow!("Method '{}' not found in {}", method_name, class.fqcn));
+    }
+    let parents = declarations::hierarchy(files.iter().flat_map(|(_, decls)| decls.classes.iter()));
+    let scope_of = |fqcn: &str| {
+        if fqcn.eq_ignore_ascii_case(&class.fqcn) {
+            Scope::DeclaringClass
+        } else if declarations::inherits_from(fqcn, &class.fqcn, &parents) {
+            Scope::Subclass
+        } else {
+            Scope::External
+        }
+    };
+
+    let method_lower = method_name.to_lowercase();
+    let mut found = Vec::new();
+    crate::for_each_php_tree(dir, file, exclude_dirs, |content| content.to_lowercase().contains(&method_lower), |path, content, tree| {
+        let names = FileNames::new(tree.root_node(), content);
+        let lines: Vec<&str> = content.lines().collect();
+        php::walk(tree.root_node(), &mut |node| {
+            let Some(call) = calls::call_site(node, content, &names) else { return };
+            if call.kind == CallKind::Function || !call.name.eq_ignore_ascii_case(method_name) {
+                return;
+            }
+            // Resolved calls have to reach the method through its class or a subclass
+            if let Some(target) = &call.class
+                && scope_of(target) == Scope::External {
+                return;
+            }
+
+            let caller_class = php::enclosing_class(node).and_then(|class| names.class_fqcn(class, content));
+            let caller = match (&caller_class, php::enclosing_function(node, content)) {
+                (Some(fqcn), Some(function)) => Some(format!("{}::{}", php::short_name(fqcn), function)),
+                (None, Some(function)) => Some(function.to_string()),
+                (_, None) => None,
+            };
+            // Static calls are handled by __callStatic, others (and self:: calls from an
+            // object) by __call
+            if declaration.is_none() && match call.kind {
+                CallKind::Static => call_static_handler.is_none(),
+                CallKind::SelfStatic => call_static_handler.is_none() && call_handler.is_none(),
+                _ => call_handler.is_none(),
+            } {
+                return;
+            }
+            found.push(Call {
+                scope: caller_class.as_deref().map_or(Scope::External, scope_of),
+                style: Style::of(call.kind),
+                path: path.to_path_buf(),
+                line: call.name_node.start_position().row + 1,
+                caller,
+                line_styled: crate::highlight_node(&lines, call.name_node),
+                unknown_receiver: call.kind == CallKind::Instance,
+            });
+        });
+    })?;
+
+    // parent:: calls keep working whether or not the method is static, so either filter leaves them out
+    let shown = |call: &Call| only_static.is_none_or(|only_static| call.style == if only_static { Style::Static } else { Style::Instance });
+
+    let Some(declaration) = declaration else {
+        let handlers: Vec<String> = [call_handler, call_static_handler].into_iter().flatten()
+            .map(|(path, handler_class, handler)| format!("{}::{} ({}:{})", handler_class.name, handler.name, crate::format_filename(path), handler.line))
+            .collect();
+        crate::report::summary(format!("{}::{} isn't declared, calls to it may be handled by {}", class.fqcn.bold().yellow(), method_name, handlers.join(" and ")));
+        let calls: Vec<&Call> = found.iter().filter(|call| shown(call)).collect();
+        crate::report::summary(format!("{}: {}", "Possibly handled by __call".bold(), calls.len()));
+        for call in calls {
+            crate::print_match(&call.path, call.line, call.caller.as_deref(), &annotated(call));
+        }
+        return Ok(());
+    };
+
+    crate::report::summary(format!("{}::{} is {} ({}:{})", class.fqcn.bold().yellow(), declaration.name, declaration.visibility, crate::format_filename(class_path), declaration.line));
+    for (scope, title) in [(Scope::DeclaringClass, "In the declaring class"), (Scope::Subclass, "In subclasses"), (Scope::External, "Outside the class hierarchy")] {
+        let calls: Vec<&Call> = found.iter().filter(|call| call.scope == scope && shown(call)).collect();
+        crate::report::summary(format!("{}: {}", title.bold(), calls.len()));
+        for call in calls {
+            crate::print_match(&call.path, call.line, call.caller.as_deref(), &annotated(call));
+        }
+    }
+    let counts: Vec<String> = [Style::Static, Style::Instance, Style::Parent].into_iter()
+        .map(|style| format!("{} {}", found.iter().filter(|call| call.style == style).count(), style.label()))
+        .collect();
+    crate::report::summary(format!("{}: {}", "By call style".bold(), counts.join(", ")));
+
+    let external: Vec<&Call> = found.iter().filter(|call| call.scope == Scope::External).collect();
+    let (verdict, narrowest) = if external.iter().any(|call| !call.unknown_receiver) {
+        ("has to stay public".to_string(), "public")
+    } else if !external.is_empty() {
+        ("may have to stay public, it's called on objects of unknown type outside the class hierarchy".to_string(), "public")
+    } else if found.iter().any(|call| call.scope == Scope::Subclass) {
+        ("can be made protected".to_string(), "protected")
+    } else {
+        ("can be made private".to_string(), "private")
+    };
+    // PHP doesn't allow an override to be less visible than the method of a parent class or
+    // interface it overrides; private methods of a parent aren't inherited, so they don't count
+    let overridden = files.iter()
+        .flat_map(|(_, decls)| decls.classes.iter())
+        .filter(|ancestor| declarations::inherits_from(&class.fqcn, &ancestor.fqcn, &parents))
+        .find_map(|ancestor| ancestor.methods.iter()
+            .find(|method| method.name.eq_ignore_ascii_case(method_name) && method.visibility != "private")
+            .map(|method| (ancestor, method)));
+    let verdict = match overridden {
+        Some((ancestor, method)) if visibility_rank(narrowest) < visibility_rank(method.visibility) => {
+            let relation = if ancestor.kind == "interface" { "implements" } else { "overrides" };
+            format!("has to keep the {} visibility of {}::{}, which it {}", method.visibility, ancestor.fqcn, method.name, relation)
+        }
+        _ => verdict,
+    };
+    crate::report::summary(format!("{}::{} {}", class.name, declaration.name, verdict));
+    Ok(())
+}
+
+// Orders visibilities from private to public
+fn visibility_rank(visibility: &str) -> u8 {
+    match visibility {
+        "private" => 0,
+        "protected" => 1,
+        _ => 2,
+    }
+}
+
+// The call's line followed by how it calls the method, and whether the object's class is unknown
+fn annotated(call: &Call) -> String {
+    let note = if call.unknown_receiver {
+        format!("({}, receiver type unknown)", call.style.label())
+    } else {
+        format!("({})", call.style.label())
+    };
+    format!("{} {}", call.line_styled.trim(), note.dimmed())
+}
+
+// The `__call` or `__callStatic` method a class declares or inherits from a parent class, with
+// the file and class declaring it
+fn magic_handler<'a>(files: &'a [(PathBuf, declarations::FileDecls)], class: &'a declarations::ClassDecl, name: &str) -> Option<(&'a PathBuf, &'a declarations::ClassDecl, &'a declarations::FunctionDecl)> {
+    let find = |fqcn: &str| files.iter()
+        .flat_map(|(path, decls)| decls.classes.iter().map(move |class| (path, class)))
+        .find(|(_, class)| class.fqcn.eq_ignore_ascii_case(fqcn));
+    let mut seen = Vec::new();
+    let mut current = find(&class.fqcn);
+    while let Some((path, current_class)) = current {
+        if seen.contains(&&current_class.fqcn) {
+            return None;
+        }
+        seen.push(&current_class.fqcn);
+        if let Some(handler) = current_class.methods.iter().find(|method| method.name.eq_ignore_ascii_case(name)) {
+            return Some((path, current_class, handler));
+        }
+        current = current_class.extends.first().and_then(|parent| find(parent));
+    }
+    None
+}
@@ -0,0 +1,66 @@
+use anyhow::Result;
+use colored::*;
+
+use crate::diff;
+use crate::git;
+use crate::php::{self, FileNames};
+
+// Extracts one method's source at two git revisions with `git show` (no checkout) and prints a
+// diff between them. `method` is written `Class::method`, the class short or fully qualified
+pub fn method_diff(method: &str, from: &str, to: &str, dir: &str) -> Result<()> {
+    let Some((class, method_name)) = method.split_once("::") else {
+        eprintln!("Error: Method must be written as Class::method.");
+        return Err(anyhow::anyhow!("Method must be written as Class::method"));
+    };
+
+    let old = find_method(class, method_name, from, dir)?;
+    let new = find_method(class, method_name, to, dir)?;
+    if old.is_none() && new.is_none() {
+        eprintln!("Error: {} not found at {} or {}.", method, from, to);
+        return Err(anyhow::anyhow!("{} not found at {} or {}", method, from, to));
+    }
+
+    for (marker, rev, found) in [("---", from, &old), ("+++", to, &new)] {
+        match found {
+            Some((path, line, _)) => println!("{} {} @ {} ({}:{})", marker, method.bold().yellow(), rev, path.bold().blue(), line),
+            None => println!("{} {} @ {} (not present)", marker, method.bold().yellow(), rev),
+        }
+    }
+
+    let old_text = old.as_ref().map(|(_, _, text)| text.as_str()).unwrap_or("");
+    let new_text = new.as_ref().map(|(_, _, text)| text.as_str()).unwrap_or("");
+    diff::print_diff(&diff::diff_lines(old_text, new_text));
+    Ok(())
+}
+
+// Looks for the method in every PHP file at `rev` mentioning its name and returns the file,
+// line and source of the first declaration found
+fn find_method(class: &str, method_name: &str, rev: &str, dir: &str) -> Result<Option<(String, usize, String)>> {
+    let mut parser = php::new_parser()?;
+    for path in git::php_files_containing(dir, rev, method_name)? {
+        let content = git::show_file(dir, rev, &path)?;
+        let Some(tree) = parser.parse(&content, None) else { continue };
+        let names = FileNames::new(tree.root_node(), &content);
+
+        let mut found = None;
+        php::walk(tree.root_node(), &mut |node| {
+            if found.is_some() || node.kind() != "method_declaration" {
+                return;
+            }
+            let Some(name) = node.child_by_field_name("name") else { return };
+            if !php::node_text(name, &content).eq_ignore_ascii_case(method_name) {
+                return;
+            }
+            let Some(fqcn) = php::enclosing_class(node).and_then(|class_node| names.class_fqcn(class_node, &content)) else { return };
+            if php::class_matches(&fqcn, class) {
+                // Start from the beginning of the line so the first line keeps its indentation
+                let line_start = content[..node.start_byte()].rfind('\n').map(|i| i + 1).unwrap_or(0);
+                found = Some((path.clone(), node.start_position().row + 1, content[line_start..node.end_byte()].to_string()));
+            }
+        });
+        if found.is_some() {
+            return Ok(found);
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,30 @@
+pub mod iface_diff;
+pub mod recursion;
+pub mod untyped;
+pub mod strict_types;
+pub mod psr4_check;
+pub mod duplicate_symbols;
+pub mod outline;
+pub mod symbols;
+pub mod xref;
+pub mod method_diff;
+pub mod history;
+pub mod accessors;
+pub mod properties;
+pub mod impact;
+pub mod throws;
+pub mod similar;
+pub mod trend;
+pub mod deps;
+pub mod boundaries;
+pub mod rename_method;
+pub mod rename_class;
+pub mod bench;
+pub mod deprecations;
+pub mod effects;
+pub mod undo;
+pub mod unused_params;
+pub mod uninitialized_props;
+pub mod class_dump;
+pub mod batch;
+pub mod diff_results;
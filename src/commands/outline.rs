@@ -0,0 +1,87 @@
+use std::path::Path;
+
+use anyhow::Result;
+use colored::*;
+
+use crate::declarations::{ClassDecl, FileDecls, FunctionDecl};
+
+// Prints the structure of a single file: namespaces, class-likes with their constants,
+// properties and methods, and global functions, each with its line number
+pub fn outline(file_path: &str) -> Result<()> {
+    let path = Path::new(file_path);
+    let Some(content) = crate::read_file(path) else {
+        return Err(anyhow::anyhow!("Could not read file '{}'", file_path));
+    };
+    let mut parser = crate::php::new_parser()?;
+    let Some(tree) = crate::parse_content(&mut parser, &content, path) else {
+        return Err(anyhow::anyhow!("Could not parse file '{}' as PHP", file_path));
+    };
+    let decls = FileDecls::new(tree.root_node(), &content);
+
+    println!("{}", crate::format_filename(path).bold().blue());
+
+    // Declarations are grouped by namespace in the order the namespaces first appear
+    let mut namespaces: Vec<&str> = Vec::new();
+    for namespace in decls.classes.iter().map(|class| class.namespace.as_str()).chain(decls.functions.iter().map(|function| function.namespace())) {
+        if !namespaces.contains(&namespace) {
+            namespaces.push(namespace);
+        }
+    }
+
+    for namespace in namespaces {
+        let indent = if namespace.is_empty() { "" } else { "  " };
+        if !namespace.is_empty() {
+            println!("namespace {}", namespace.bold());
+        }
+        for class in decls.classes.iter().filter(|class| class.namespace == namespace) {
+            print_class(class, indent);
+        }
+        for function in decls.functions.iter().filter(|function| function.namespace() == namespace) {
+            println!("{}{:>5}  function {}", indent, function.line, function.signature().yellow());
+        }
+    }
+
+    Ok(())
+}
+
+fn print_class(class: &ClassDecl, indent: &str) {
+    let mut header = format!("{}{} {}", if class.is_abstract { "abstract " } else { "" }, class.kind, class.name.bold().yellow());
+    if !class.extends.is_empty() {
+        header.push_str(&format!(" extends {}", class.extends.join(", ")));
+    }
+    if !class.implements.is_empty() {
+        header.push_str(&format!(" implements {}", class.implements.join(", ")));
+    }
+    println!("{}{:>5}  {}", indent, class.line, header);
+
+    for constant in &class.constants {
+        println!("{}{:>5}    {} {}", indent, constant.line, constant.kind, constant.name.magenta());
+    }
+    for property in &class.properties {
+        let mut modifiers = vec![property.visibility];
+        if property.is_static {
+            modifiers.push("static");
+        }
+        if property.is_readonly {
+            modifiers.push("readonly");
+        }
+        let type_hint = property.type_hint.as_ref().map(|type_hint| format!("{} ", type_hint.text)).unwrap_or_default();
+        println!("{}{:>5}    {} {}{}", indent, property.line, modifiers.join(" "), type_hint, format!("${}", property.name).green());
+    }
+    for method in &class.methods {
+        println!("{}{:>5}    {} {}", indent, method.line, method_modifiers(method), method.signature().yellow());
+    }
+}
+
+fn method_modifiers(method: &FunctionDecl) -> String {
+    let mut modifiers = Vec::new();
+    if method.is_abstract {
+        modifiers.push("abstract");
+    }
+    modifiers.push(method.visibility);
+    if method.is_static {
+        modifiers.push("static");
+    }
+    modifiers.push("function");
+    modifiers.join(" ")
+}
@@ -0,0 +1,115 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use colored::*;
+
+use crate::declarations::{self, TypeDecl};
+
+// Which properties to list, from the --readonly/--mutable and --typed-properties/--untyped-properties flags
+pub struct PropertyFilter {
+    pub readonly: Option<bool>,
+    pub typed: Option<bool>,
+}
+
+// A declared or constructor promoted property, flattened for filtering
+struct Property<'a> {
+    symbol: String,
+    line: usize,
+    visibility: &'a str,
+    is_static: bool,
+    is_readonly: bool,
+    type_hint: Option<&'a TypeDecl>,
+    promoted: bool,
+}
+
+// Listed, readonly, typed and total counts for one namespace
+#[derive(Default)]
+struct Counts {
+    listed: usize,
+    readonly: usize,
+    typed: usize,
+    total: usize,
+}
+
+// Lists class properties, including constructor promoted ones, matching the filter and sums up
+// per namespace how many are readonly and typed. Properties of readonly classes are readonly
+pub fn properties(filter: &PropertyFilter, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let files = declarations::scan(dir, file, exclude_dirs)?;
+    let mut counts: BTreeMap<&str, Counts> = BTreeMap::new();
+
+    for (path, decls) in &files {
+        for class in &decls.classes {
+            let mut properties: Vec<Property> = class.properties.iter()
+                .map(|property| Property {
+                    symbol: format!("{}::${}", class.name, property.name),
+                    line: property.line,
+                    visibility: property.visibility,
+                    is_static: property.is_static,
+                    is_readonly: property.is_readonly || class.is_readonly,
+                    type_hint: property.type_hint.as_ref(),
+                    promoted: false,
+                })
+                .collect();
+            let constructor = class.methods.iter().find(|method| method.name.eq_ignore_ascii_case("__construct"));
+            for (param, modifiers) in constructor.iter().flat_map(|constructor| &constructor.params).filter_map(|param| Some((param, param.promotion.as_deref()?))) {
+                let modifiers = modifiers.to_lowercase();
+                properties.push(Property {
+                    symbol: format!("{}::${}", class.name, param.name),
+                    line: constructor.map(|constructor| constructor.line).unwrap_or(class.line),
+                    visibility: ["private", "protected"].into_iter().find(|visibility| modifiers.contains(visibility)).unwrap_or("public"),
+                    is_static: false,
+                    is_readonly: modifiers.contains("readonly") || class.is_readonly,
+                    type_hint: param.type_hint.as_ref(),
+                    promoted: true,
+                });
+            }
+
+            let namespace = counts.entry(&class.namespace).or_default();
+            for property in &properties {
+                namespace.total += 1;
+                namespace.readonly += property.is_readonly as usize;
+                namespace.typed += property.type_hint.is_some() as usize;
+                if filter.readonly.is_some_and(|readonly| readonly != property.is_readonly)
+                    || filter.typed.is_some_and(|typed| typed != property.type_hint.is_some()) {
+                    continue;
+                }
+                namespace.listed += 1;
+                crate::print_symbol_match(path, property.line, Some(&property.symbol), &describe(property));
+            }
+        }
+    }
+
+    crate::report::summary(format!("{:<40} {:>8} {:>18} {:>18}", "Namespace".bold(), "Listed".bold(), "Readonly".bold(), "Typed".bold()));
+    for (namespace, counts) in counts.iter().filter(|(_, counts)| counts.total > 0) {
+        let namespace = if namespace.is_empty() { "(global)" } else { namespace };
+        crate::report::summary(format!("{:<40} {:>8} {:>18} {:>18}", namespace, counts.listed, ratio(counts.readonly, counts.total), ratio(counts.typed, counts.total)));
+    }
+
+    Ok(())
+}
+
+// Writes a property back roughly as declared, e.g. `private readonly string` or `public (untyped)`
+fn describe(property: &Property) -> String {
+    let mut description = property.visibility.to_string();
+    if property.is_static {
+        description.push_str(" static");
+    }
+    if property.is_readonly {
+        description.push_str(" readonly");
+    }
+    match property.type_hint {
+        Some(type_hint) => description.push_str(&format!(" {}", type_hint.text)),
+        None => description.push_str(&format!(" {}", "(untyped)".red())),
+    }
+    if property.promoted {
+        description.push_str(" (promoted)");
+    }
+    description
+}
+
+fn ratio(count: usize, total: usize) -> String {
+    if total == 0 {
+        return "-".to_string();
+    }
+    format!("{}/{} {:.1}%", count, total, count as f64 * 100.0 / total as f64)
+}
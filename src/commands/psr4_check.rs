@@ -0,0 +1,119 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use colored::*;
+
+use crate::declarations;
+use crate::json;
+
+// A PSR-4 autoload rule: classes under `prefix` live in `dir`
+struct Psr4Rule {
+    prefix: String,
+    dir: PathBuf,
+    // The directory as written in composer.json, used when printing expected paths
+    dir_display: String,
+}
+
+// Reports classes, interfaces, traits and enums whose fully qualified name doesn't match the
+// file they're declared in according to composer.json's psr-4 rules (autoload and autoload-dev).
+// Files outside every psr-4 directory (classmaps, scripts, ...) aren't checked
+pub fn psr4_check(composer: Option<&str>, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let composer_path = match composer {
+        Some(composer) => PathBuf::from(composer),
+        None => match find_composer_json(Path::new(dir)) {
+            Some(path) => path,
+            None => {
+                eprintln!("Error: No composer.json found in '{}' or its parent directories.", dir);
+                return Err(anyhow::anyhow!("No composer.json found"));
+            }
+        },
+    };
+    let rules = psr4_rules(&composer_path)?;
+    if rules.is_empty() {
+        eprintln!("Error: No psr-4 autoload rules in '{}'.", composer_path.display());
+        return Err(anyhow::anyhow!("No psr-4 autoload rules"));
+    }
+
+    let mut checked = 0;
+    let mut mismatches = 0;
+    for (path, decls) in declarations::scan(dir, file, exclude_dirs)? {
+        let Ok(canonical_path) = path.canonicalize() else { continue };
+        if !rules.iter().any(|rule| canonical_path.starts_with(&rule.dir)) {
+            continue;
+        }
+
+        for class in &decls.classes {
+            checked += 1;
+            let candidates: Vec<&Psr4Rule> = rules.iter()
+                .filter(|rule| rule.prefix.is_empty() || class.fqcn.starts_with(&rule.prefix))
+                .collect();
+            let expected: Vec<(PathBuf, String)> = candidates.iter()
+                .map(|rule| {
+                    let relative = format!("{}.php", class.fqcn[rule.prefix.len()..].replace('\\', "/"));
+                    (rule.dir.join(&relative), format!("{}/{}", rule.dir_display.trim_end_matches('/'), relative))
+                })
+                .collect();
+            if expected.iter().any(|(expected_path, _)| *expected_path == canonical_path) {
+                continue;
+            }
+
+            mismatches += 1;
+            let message = if expected.is_empty() {
+                format!("namespace {} matches no psr-4 prefix", class.namespace.red())
+            } else {
+                let paths: Vec<&str> = expected.iter().map(|(_, display)| display.as_str()).collect();
+                format!("expected at {}", paths.join(" or ").red())
+            };
+            crate::print_symbol_match(&path, class.line, Some(&format!("{} {}", class.kind, class.fqcn)), &message);
+        }
+    }
+
+    crate::report::summary(format!("{} classes checked, {} psr-4 mismatches", checked, mismatches));
+    Ok(())
+}
+
+// The file PSR-4 autoloading expects a class in, going by the longest matching prefix in the
+// composer.json above `dir`
+pub fn expected_path(fqcn: &str, dir: &str) -> Result<PathBuf> {
+    let Some(composer_path) = find_composer_json(Path::new(dir)) else {
+        return Err(anyhow::anyhow!("No composer.json found in '{}' or its parent directories", dir));
+    };
+    psr4_rules(&composer_path)?
+        .iter()
+        .find(|rule| rule.prefix.is_empty() || fqcn.starts_with(&rule.prefix))
+        .map(|rule| rule.dir.join(format!("{}.php", fqcn[rule.prefix.len()..].replace('\\', "/"))))
+        .ok_or_else(|| anyhow::anyhow!("{} matches no psr-4 prefix in '{}'", fqcn, composer_path.display()))
+}
+
+fn find_composer_json(dir: &Path) -> Option<PathBuf> {
+    let dir = dir.canonicalize().ok()?;
+    dir.ancestors().map(|ancestor| ancestor.join("composer.json")).find(|candidate| candidate.is_file())
+}
+
+// Reads the psr-4 rules from composer.json, longest prefixes first
+fn psr4_rules(composer_path: &Path) -> Result<Vec<Psr4Rule>> {
+    let content = std::fs::read_to_string(composer_path)
+        .map_err(|e| anyhow::anyhow!("Could not read '{}': {}", composer_path.display(), e))?;
+    let composer = json::parse(&content)
+        .map_err(|e| anyhow::anyhow!("Invalid JSON in '{}': {}", composer_path.display(), e))?;
+    let base_dir = composer_path.parent().unwrap_or(Path::new("."));
+
+    let mut rules = Vec::new();
+    for section in ["autoload", "autoload-dev"] {
+        let Some(psr4) = composer.get(section).and_then(|autoload| autoload.get("psr-4")).and_then(|psr4| psr4.as_object()) else { continue };
+        for (prefix, dirs) in psr4 {
+            let dirs: Vec<&str> = match dirs.as_str() {
+                Some(dir) => vec![dir],
+                None => dirs.as_array().unwrap_or_default().iter().filter_map(|dir| dir.as_str()).collect(),
+            };
+            for dir_display in dirs {
+                // Directories that don't exist can't contain any of the scanned files
+                let Ok(dir) = base_dir.join(dir_display).canonicalize() else { continue };
+                rules.push(Psr4Rule { prefix: prefix.clone(), dir, dir_display: dir_display.to_string() });
+            }
+        }
+    }
+
+    rules.sort_by_key(|rule| std::cmp::Reverse(rule.prefix.len()));
+    Ok(rules)
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use colored::*;
+
+use crate::calls::{self, CallKind};
+use crate::php::{self, FileNames};
+
+// A method or function along with the calls its body makes
+struct Callable {
+    key: String,
+    symbol: String,
+    path: PathBuf,
+    line: usize,
+    calls: Vec<(String, usize, String)>,
+}
+
+// Flags methods and functions that call themselves. With a depth above one, indirect cycles
+// (a calls b calls a) of up to that many calls are reported too, using statically resolvable
+// calls only: functions, $this->, self::, static::, parent:: and Class:: calls
+pub fn recursion(max_depth: usize, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let mut callables = Vec::new();
+    crate::for_each_php_tree(dir, file, exclude_dirs, |_| true, |path, content, tree| {
+        let names = FileNames::new(tree.root_node(), content);
+        let lines: Vec<&str> = content.lines().collect();
+        php::walk(tree.root_node(), &mut |node| {
+            if node.kind() != "method_declaration" && node.kind() != "function_definition" {
+                return;
+            }
+            let (Some(name), Some(body)) = (node.child_by_field_name("name"), node.child_by_field_name("body")) else { return };
+            let name = php::node_text(name, content);
+            let (key, symbol) = match php::enclosing_class(node).filter(|_| node.kind() == "method_declaration") {
+                Some(class) => {
+                    let Some(fqcn) = names.class_fqcn(class, content) else { return };
+                    (calls::method_key(&fqcn, name), format!("{}::{}", php::short_name(&fqcn), name))
+                }
+                None => (calls::function_key(name), name.to_string()),
+            };
+
+            let mut callable = Callable { key, symbol, path: path.to_path_buf(), line: node.start_position().row + 1, calls: Vec::new() };
+            php::walk_body(body, &mut |inner| {
+                let Some(call) = calls::call_site(inner, content, &names) else { return };
+                if call.kind == CallKind::Instance {
+                    return;
+                }
+                let Some(target) = call.target_key() else { return };
+                let row = call.name_node.start_position().row;
+                callable.calls.push((target, row + 1, crate::highlight_node(&lines, call.name_node)));
+            });
+            callables.push(callable);
+        });
+    })?;
+
+    let mut found = 0;
+    for callable in &callables {
+        let mut reported = false;
+        for (target, line, line_styled) in &callable.calls {
+            if *target == callable.key {
+                crate::print_match(&callable.path, *line, Some(&callable.symbol), line_styled);
+                reported = true;
+            }
+        }
+        found += reported as usize;
+    }
+
+    if max_depth > 1 {
+        let by_key: HashMap<&str, &Callable> = callables.iter().map(|callable| (callable.key.as_str(), callable)).collect();
+        for callable in &callables {
+            let mut path = vec![callable.key.as_str()];
+            let mut cycles = Vec::new();
+            find_cycles(&by_key, &mut path, max_depth, &mut cycles);
+            for cycle in cycles {
+                let symbols: Vec<&str> = cycle.iter()
+                    .chain(std::iter::once(&cycle[0]))
+                    .map(|key| by_key[key].symbol.as_str())
+                    .collect();
+                let message = format!("recursion cycle {}", symbols.join(" → ").red());
+                crate::print_match(&callable.path, callable.line, Some(&callable.symbol), &message);
+                found += 1;
+            }
+        }
+    }
+
+    crate::report::summary(format!("{} recursive methods found", found));
+    Ok(())
+}
+
+// Depth first search for call chains leading back to the first key in `path`. Each cycle is
+// only reported from its alphabetically first member so it shows up once
+fn find_cycles<'a>(by_key: &HashMap<&'a str, &'a Callable>, path: &mut Vec<&'a str>, max_depth: usize, cycles: &mut Vec<Vec<&'a str>>) {
+    let start = path[0];
+    let Some(current) = by_key.get(path[path.len() - 1]) else { return };
+    let mut targets: Vec<&str> = current.calls.iter().map(|(target, _, _)| target.as_str()).collect();
+    targets.sort();
+    targets.dedup();
+
+    for target in targets {
+        if target == start && path.len() > 1 {
+            cycles.push(path.clone());
+        } else if path.len() < max_depth && target > start && !path.contains(&target)
+            && let Some(callable) = by_key.get(target) {
+            path.push(callable.key.as_str());
+            find_cycles(by_key, path, max_depth, cycles);
+            path.pop();
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use anyhow::Result;
+use tree_sitter::Node;
+
+use crate::commands::{deps, psr4_check};
+use crate::declarations;
+use crate::edit::{self, FileEdits, Unchanged};
+use crate::php::{self, FileNames};
+
+// Renames a class: its declaration and, when the namespace changes, the namespace statement of
+// its file, then every use statement and class reference resolving to it. References are
+// rewritten to the short name where an import or the namespace makes that enough and to the
+// fully qualified name elsewhere. With `move_file` the file moves to where PSR-4 expects the new
+// name. Strings mentioning the class are listed instead. Prints the changes as a diff and only
+// writes them as `mode` says
+pub fn rename_class(old: &str, new: &str, move_file: bool, mode: edit::Mode, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let new = new.trim_start_matches('\\');
+    if !new.split('\\').all(php::is_identifier) {
+        eprintln!("Error: '{}' is not a valid class name.", new);
+        return Err(anyhow::anyhow!("'{}' is not a valid class name", new));
+    }
+
+    let files = declarations::scan(dir, file, exclude_dirs)?;
+    let (class_path, class) = declarations::find_class(&files, old.trim_start_matches('\\'))?;
+    let old = class.fqcn.as_str();
+    if let Some(existing) = files.iter().flat_map(|(_, decls)| decls.classes.iter()).find(|other| other.fqcn.eq_ignore_ascii_case(new) && !other.fqcn.eq_ignore_ascii_case(old)) {
+        eprintln!("Error: {} {} already exists.", existing.kind, existing.fqcn);
+        return Err(anyhow::anyhow!("{} {} already exists", existing.kind, existing.fqcn));
+    }
+
+    let (old_namespace, new_namespace) = (deps::namespace_of(old), deps::namespace_of(new));
+    let new_short = php::short_name(new);
+    let namespace_changes = !old_namespace.eq_ignore_ascii_case(new_namespace);
+    let declares_others = files.iter()
+        .filter(|(path, _)| path == class_path)
+        .any(|(_, decls)| decls.classes.len() > 1);
+    if namespace_changes && declares_others {
+        eprintln!("Error: '{}' declares other classes too, move {} to its own file first.", class_path.display(), old);
+        return Err(anyhow::anyhow!("'{}' declares other classes too", class_path.display()));
+    }
+    let move_to = if move_file {
+        match psr4_check::expected_path(new, dir) {
+            Ok(target) => Some(target).filter(|target| class_path.canonicalize().ok().as_ref() != Some(target)),
+            Err(e) => {
+                eprintln!("Error: Could not find where {} belongs: {}", new, e);
+                return Err(anyhow::anyhow!("Could not find where {} belongs: {}", new, e));
+            }
+        }
+    } else {
+        None
+    };
+
+    let old_short = php::short_name(old).to_lowercase();
+    let old_lower = old.to_lowercase();
+    let mut edits = Vec::new();
+    let mut unresolved = Vec::new();
+    let (mut imports_count, mut references_count) = (0, 0);
+    crate::for_each_php_tree(dir, file, exclude_dirs, |content| content.to_lowercase().contains(&old_short), |path, content, tree| {
+        let root = tree.root_node();
+        let names = FileNames::new(root, content);
+        let lines: Vec<&str> = content.lines().collect();
+        let mut file_edits = FileEdits::new(path, content);
+        let is_declaring_file = path == class_path.as_path();
+        // The namespace code at a byte will be in once the rename is done
+        let namespace_after = |byte: usize| if is_declaring_file { new_namespace } else { names.namespace_at(byte) };
+
+        php::walk(root, &mut |node| {
+            match node.kind() {
+                "class_declaration" | "interface_declaration" | "trait_declaration" | "enum_declaration"
+                    if names.class_fqcn(node, content).is_some_and(|fqcn| fqcn.eq_ignore_ascii_case(old)) => {
+                    if let Some(name) = node.child_by_field_name("name") {
+                        file_edits.replace(name, new_short);
+                    }
+                }
+                "namespace_definition" if is_declaring_file && namespace_changes => match node.child_by_field_name("name") {
+                    // `namespace {` is the braced form of the global namespace
+                    Some(name) if !new_namespace.is_empty() || node.child_by_field_name("body").is_some() => file_edits.replace(name, new_namespace),
+                    _ => file_edits.replace(node, &namespace_statement(new_namespace)),
+                },
+                "php_tag" if is_declaring_file && namespace_changes && old_namespace.is_empty() => {
+                    file_edits.insert(node.end_byte(), &format!("\n\n{}", namespace_statement(new_namespace)));
+                }
+                "namespace_use_clause" => {
+                    let Some((name, prefix)) = use_clause_name(node, content) else { return };
+                    let full = match prefix {
+                        Some(prefix) => format!("{}\\{}", prefix.trim_matches('\\'), php::node_text(name, content)),
+                        None => php::node_text(name, content).trim_start_matches('\\').to_string(),
+                    };
+                    if !full.eq_ignore_ascii_case(old) {
+                        return;
+                    }
+                    match prefix {
+                        None => file_edits.replace(name, new),
+                        Some(prefix) if prefix.trim_matches('\\').eq_ignore_ascii_case(new_namespace) => file_edits.replace(name, new_short),
+                        // A group use of another namespace would need splitting up
+                        Some(_) => {
+                            unresolved.push(Unchanged::at(path, node, content, &lines));
+                            return;
+                        }
+                    }
+                    imports_count += 1;
+                }
+                "string" | "encapsed_string" => {
+                    let text = php::node_text(node, content).replace("\\\\", "\\").to_lowercase();
+                    if text.contains(&old_lower) {
+                        unresolved.push(Unchanged::at(path, node, content, &lines));
+                    }
+                }
+                _ => {}
+            }
+        });
+
+        for reference in php::class_references(root, content) {
+            let text = php::node_text(reference, content);
+            let byte = reference.start_byte();
+            let resolved = names.resolve(text, byte);
+            let imported = names.is_imported(text, byte);
+            if !resolved.eq_ignore_ascii_case(old) {
+                // Unqualified names in the declaring file that found their class through the old namespace
+                if is_declaring_file && namespace_changes && !text.starts_with('\\') && !imported && !text.to_lowercase().starts_with("namespace\\") {
+                    file_edits.replace(reference, &format!("\\{}", resolved));
+                }
+                continue;
+            }
+
+            let replacement = if imported && !text.contains('\\') {
+                // An alias chosen in the use statement stays, an import without one now brings in the new short name
+                if !text.eq_ignore_ascii_case(php::short_name(old)) {
+                    continue;
+                }
+                new_short.to_string()
+            } else if !text.starts_with('\\') && !imported && namespace_after(byte).eq_ignore_ascii_case(new_namespace) {
+                new_short.to_string()
+            } else {
+                format!("\\{}", new)
+            };
+            if replacement != text {
+                file_edits.replace(reference, &replacement);
+                references_count += 1;
+            }
+        }
+
+        if is_declaring_file {
+            file_edits.move_to = move_to.clone();
+        }
+        edits.push(file_edits);
+    })?;
+
+    let applied = edit::apply(&edits, mode, dir)?;
+    edit::print_unchanged("Not renamed, these mention the class in a string or a group use of another namespace:", &unresolved);
+    let action = mode.action("Renamed", "Would rename");
+    let mut summary = format!("{} {} to {}, updating {} import{} and {} reference{} in {} file{}", action, old, new,
+        imports_count, if imports_count == 1 { "" } else { "s" },
+        references_count, if references_count == 1 { "" } else { "s" },
+        applied.files, if applied.files == 1 { "" } else { "s" });
+    if let Some(target) = &move_to {
+        summary.push_str(&format!(", moving it to {}", crate::format_filename(target)));
+    }
+    if applied.skipped > 0 {
+        summary.push_str(&format!(", skipped {} change{}", applied.skipped, if applied.skipped == 1 { "" } else { "s" }));
+    }
+    if mode == edit::Mode::Preview {
+        summary.push_str(", run with --write or --confirm to apply");
+    }
+    crate::report::summary(summary);
+    Ok(())
+}
+
+// The imported name of a class `use` clause, and the prefix of the group use it is part of
+fn use_clause_name<'a>(clause: Node<'a>, content: &'a str) -> Option<(Node<'a>, Option<&'a str>)> {
+    let in_group = clause.parent().filter(|parent| parent.kind() == "namespace_use_group");
+    let declaration = in_group.and_then(|group| group.parent()).or(clause.parent())?;
+    if declaration.child_by_field_name("type").is_some() || clause.child_by_field_name("type").is_some() {
+        return None;
+    }
+    let mut cursor = clause.walk();
+    let name = clause.named_children(&mut cursor).find(|child| child.kind() == "name" || child.kind() == "qualified_name")?;
+    let prefix = in_group.and_then(|_| {
+        let mut cursor = declaration.walk();
+        let prefix = declaration.named_children(&mut cursor).find(|child| child.kind() == "namespace_name");
+        prefix.map(|prefix| php::node_text(prefix, content))
+    });
+    Some((name, prefix))
+}
+
+fn namespace_statement(namespace: &str) -> String {
+    if namespace.is_empty() {
+        String::new()
+    } else {
+        format!("namespace {};", namespace)
+    }
+}
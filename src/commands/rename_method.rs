@@ -0,0 +1,101 @@
+use anyhow::Result;
+
+use crate::calls::{self, CallKind};
+use crate::declarations;
+use crate::edit::{self, FileEdits, Unchanged};
+use crate::php::{self, FileNames};
+
+// Renames a method of a class, along with its overrides in subclasses and the calls that
+// statically reach it: `$this->`, `self::`, `static::`, `parent::` and `Class::` calls. Calls on
+// objects of unknown type and callable strings are listed instead since they may call other
+// classes' methods of the same name. Prints the changes as a diff and writes them as `mode` says
+pub fn rename_method(old: &str, new: &str, class_name: &str, mode: edit::Mode, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    if !php::is_identifier(new) {
+        eprintln!("Error: '{}' is not a valid method name.", new);
+        return Err(anyhow::anyhow!("'{}' is not a valid method name", new));
+    }
+
+    let files = declarations::scan(dir, file, exclude_dirs)?;
+    let (_, class) = declarations::find_class(&files, class_name)?;
+    if !class.methods.iter().any(|method| method.name.eq_ignore_ascii_case(old)) {
+        eprintln!("Error: Method '{}' not found in {}.", old, class.fqcn);
+        return Err(anyhow::anyhow!("Method '{}' not found in {}", old, class.fqcn));
+    }
+
+    let classes: Vec<&declarations::ClassDecl> = files.iter().flat_map(|(_, decls)| decls.classes.iter()).collect();
+    let parents = declarations::hierarchy(classes.iter().copied());
+    let declares = |class: &declarations::ClassDecl, name: &str| class.methods.iter().any(|method| method.name.eq_ignore_ascii_case(name));
+    // Renaming an implementation of an inherited method would break the contract with the parent
+    if let Some(ancestor) = classes.iter().find(|ancestor| declarations::inherits_from(&class.fqcn, &ancestor.fqcn, &parents) && declares(ancestor, old)) {
+        eprintln!("Error: {}::{} overrides {}::{}, rename it there instead.", class.fqcn, old, ancestor.fqcn, old);
+        return Err(anyhow::anyhow!("{}::{} overrides {}::{}", class.fqcn, old, ancestor.fqcn, old));
+    }
+    let renamed: Vec<String> = classes.iter()
+        .filter(|other| other.fqcn.eq_ignore_ascii_case(&class.fqcn) || declarations::inherits_from(&other.fqcn, &class.fqcn, &parents))
+        .map(|other| other.fqcn.to_lowercase())
+        .collect();
+    if let Some(clash) = classes.iter().find(|other| renamed.contains(&other.fqcn.to_lowercase()) && declares(other, new)) {
+        eprintln!("Error: {} already has a method named '{}'.", clash.fqcn, new);
+        return Err(anyhow::anyhow!("{} already has a method named '{}'", clash.fqcn, new));
+    }
+    let is_renamed = |fqcn: &str| renamed.contains(&fqcn.to_lowercase());
+
+    let old_lower = old.to_lowercase();
+    let mut edits = Vec::new();
+    let mut unresolved = Vec::new();
+    let (mut declarations_count, mut calls_count) = (0, 0);
+    crate::for_each_php_tree(dir, file, exclude_dirs, |content| content.to_lowercase().contains(&old_lower), |path, content, tree| {
+        let names = FileNames::new(tree.root_node(), content);
+        let lines: Vec<&str> = content.lines().collect();
+        let mut file_edits = FileEdits::new(path, content);
+        php::walk(tree.root_node(), &mut |node| {
+            if node.kind() == "method_declaration"
+                && let Some(name) = node.child_by_field_name("name")
+                && php::node_text(name, content).eq_ignore_ascii_case(old)
+                && php::enclosing_class(node).and_then(|class| names.class_fqcn(class, content)).is_some_and(|fqcn| is_renamed(&fqcn)) {
+                file_edits.replace(name, new);
+                declarations_count += 1;
+                return;
+            }
+
+            // `[$object, 'name']`, 'Class::name' and the like
+            if matches!(node.kind(), "string" | "encapsed_string") {
+                let text = php::node_text(node, content).trim_matches(|c| c == '\'' || c == '"');
+                if text.eq_ignore_ascii_case(old) || text.to_lowercase().ends_with(&format!("::{}", old_lower)) {
+                    unresolved.push(Unchanged::at(path, node, content, &lines));
+                }
+                return;
+            }
+
+            let Some(call) = calls::call_site(node, content, &names) else { return };
+            if call.kind == CallKind::Function || !call.name.eq_ignore_ascii_case(old) {
+                return;
+            }
+            match (&call.class, call.kind) {
+                (_, CallKind::Instance) => unresolved.push(Unchanged::at(path, call.name_node, content, &lines)),
+                (Some(target), _) if is_renamed(target) => {
+                    file_edits.replace(call.name_node, new);
+                    calls_count += 1;
+                }
+                _ => {}
+            }
+        });
+        edits.push(file_edits);
+    })?;
+
+    let applied = edit::apply(&edits, mode, dir)?;
+    edit::print_unchanged("Not renamed, these may call another class's method of the same name:", &unresolved);
+    let action = mode.action("Renamed", "Would rename");
+    let mut summary = format!("{} {} declaration{} and {} call{} in {} file{}", action,
+        declarations_count, if declarations_count == 1 { "" } else { "s" },
+        calls_count, if calls_count == 1 { "" } else { "s" },
+        applied.files, if applied.files == 1 { "" } else { "s" });
+    if applied.skipped > 0 {
+        summary.push_str(&format!(", skipped {} change{}", applied.skipped, if applied.skipped == 1 { "" } else { "s" }));
+    }
+    if mode == edit::Mode::Preview {
+        summary.push_str(", run with --write or --confirm to apply");
+    }
+    crate::report::summary(summary);
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use tree_sitter::Node;
+
+use crate::php;
+
+// Code is compared in runs of this many tokens, so similar code has to agree on their order too
+const SHINGLE: usize = 4;
+
+struct Candidate {
+    path: PathBuf,
+    line: usize,
+    symbol: String,
+    tokens: usize,
+    score: f64,
+}
+
+// Ranks the methods and functions of the search directory by how much their bodies resemble the
+// code in a snippet file, ignoring layout, comments, variable names and literal values
+pub fn similar(snippet: &str, top: usize, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let source = match std::fs::read_to_string(snippet) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Error: Could not read snippet '{}': {}", snippet, e);
+            return Err(anyhow::anyhow!("Could not read snippet '{}': {}", snippet, e));
+        }
+    };
+    // Snippets may leave out the opening tag
+    let source = if source.trim_start().starts_with("<?php") { source } else { format!("<?php\n{}", source) };
+    let mut parser = php::new_parser()?;
+    let Some(tree) = parser.parse(&source, None) else {
+        eprintln!("Error: Could not parse snippet '{}' as PHP.", snippet);
+        return Err(anyhow::anyhow!("Could not parse snippet '{}' as PHP", snippet));
+    };
+
+    // A snippet declaring a method or function is compared by its body
+    let mut code = tree.root_node();
+    php::walk(tree.root_node(), &mut |node| {
+        if code == tree.root_node()
+            && matches!(node.kind(), "method_declaration" | "function_definition")
+            && let Some(body) = node.child_by_field_name("body") {
+            code = body;
+        }
+    });
+    let wanted_tokens = normalize(code, &source);
+    let wanted = shingles(&wanted_tokens);
+    if wanted.is_empty() {
+        eprintln!("Error: Snippet '{}' contains no PHP code.", snippet);
+        return Err(anyhow::anyhow!("Snippet '{}' contains no PHP code", snippet));
+    }
+    let wanted_count: usize = wanted.values().sum();
+    let snippet_path = std::fs::canonicalize(snippet).ok();
+
+    let mut candidates = Vec::new();
+    crate::for_each_php_tree(dir, file, exclude_dirs, |_| true, |path, content, tree| {
+        if snippet_path.is_some() && std::fs::canonicalize(path).ok() == snippet_path {
+            return;
+        }
+        php::walk(tree.root_node(), &mut |node| {
+            if !matches!(node.kind(), "method_declaration" | "function_definition") {
+                return;
+            }
+            let (Some(name), Some(body)) = (node.child_by_field_name("name"), node.child_by_field_name("body")) else { return };
+            let tokens = normalize(body, content);
+            let found = shingles(&tokens);
+            let shared: usize = found.iter().map(|(shingle, count)| (*count).min(wanted.get(shingle).copied().unwrap_or(0))).sum();
+            if shared == 0 {
+                return;
+            }
+            let name = php::node_text(name, content);
+            let symbol = match php::enclosing_class(node).and_then(|class| class.child_by_field_name("name")).filter(|_| node.kind() == "method_declaration") {
+                Some(class) => format!("{}::{}", php::node_text(class, content), name),
+                None => name.to_string(),
+            };
+            candidates.push(Candidate {
+                path: path.to_path_buf(),
+                line: node.start_position().row + 1,
+                symbol,
+                tokens: tokens.len(),
+                // Dice coefficient of the two multisets of shingles
+                score: 2.0 * shared as f64 / (wanted_count + found.values().sum::<usize>()) as f64,
+            });
+        });
+    })?;
+
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+    candidates.truncate(top);
+    for candidate in &candidates {
+        crate::print_match(&candidate.path, candidate.line, Some(&candidate.symbol), &format!("{:.0}% similar ({} tokens)", candidate.score * 100.0, candidate.tokens));
+    }
+    if candidates.is_empty() {
+        crate::report::summary("No similar methods found");
+    }
+    Ok(())
+}
+
+// The tokens of `node` with variable names, strings and numbers replaced by placeholders, so
+// code copied and then renamed still compares equal
+fn normalize(node: Node, content: &str) -> Vec<String> {
+    php::tokens(node, content).into_iter()
+        .map(|token| match token.kind() {
+            "name" if token.parent().is_some_and(|parent| parent.kind() == "variable_name") => "v".to_string(),
+            "string_content" | "string_value" => "s".to_string(),
+            "integer" | "float" => "0".to_string(),
+            "heredoc_start" | "heredoc_end" | "nowdoc_start" => "eot".to_string(),
+            // Keywords and function names are case-insensitive in PHP
+            _ => php::node_text(token, content).to_lowercase(),
+        })
+        .collect()
+}
+
+fn shingles(tokens: &[String]) -> HashMap<&[String], usize> {
+    let mut shingles = HashMap::new();
+    if tokens.is_empty() {
+        return shingles;
+    }
+    for shingle in tokens.windows(SHINGLE.min(tokens.len())) {
+        *shingles.entry(shingle).or_insert(0) += 1;
+    }
+    shingles
+}
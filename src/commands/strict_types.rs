@@ -0,0 +1,46 @@
+use anyhow::Result;
+use colored::*;
+
+use crate::php;
+
+// Lists PHP files without a `declare(strict_types=1);` statement. Paths containing any of the
+// comma-separated `ignore_paths` (views and templates by default) are left out, and with
+// `forbid` the run fails when a file is missing the declaration so it can gate CI
+pub fn strict_types(ignore_paths: &str, forbid: bool, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let ignore_paths: Vec<&str> = ignore_paths.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()).collect();
+    let mut checked = 0;
+    let mut missing = 0;
+
+    crate::for_each_php_tree(dir, file, exclude_dirs, |_| true, |path, content, tree| {
+        let path_str = path.to_string_lossy();
+        if ignore_paths.iter().any(|ignored| path_str.contains(ignored)) {
+            return;
+        }
+        checked += 1;
+
+        let root = tree.root_node();
+        let mut cursor = root.walk();
+        let declared = root.named_children(&mut cursor).any(|statement| {
+            statement.kind() == "declare_statement" && {
+                let directive: String = php::node_text(statement, content).chars().filter(|c| !c.is_whitespace()).collect();
+                directive.to_lowercase().contains("strict_types=1")
+            }
+        });
+
+        if !declared {
+            missing += 1;
+            crate::print_match(path, 1, None, &format!("missing {}", "declare(strict_types=1);".red()));
+        }
+    })?;
+
+    let declared = checked - missing;
+    let percentage = if checked == 0 { 100.0 } else { declared as f64 * 100.0 / checked as f64 };
+    crate::report::summary(format!("{} of {} files declare strict_types ({:.1}%), {} missing", declared, checked, percentage, missing));
+
+    if forbid && missing > 0 {
+        eprintln!("Error: {} files are missing declare(strict_types=1).", missing);
+        return Err(anyhow::anyhow!("{} files are missing declare(strict_types=1)", missing));
+    }
+
+    Ok(())
+}
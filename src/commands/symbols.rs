@@ -0,0 +1,89 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::declarations;
+use crate::json::Value;
+
+// Symbol kinds accepted by --kind. `class` covers interfaces, traits and enums as well
+pub const SYMBOL_KINDS: [&str; 5] = ["class", "method", "function", "const", "property"];
+
+// A declared symbol as listed by the symbols command
+struct Symbol<'a> {
+    kind: &'static str,
+    name: String,
+    fqn: String,
+    id: String,
+    path: &'a Path,
+    line: usize,
+    detail: String,
+}
+
+// Dumps every declared symbol of the requested kinds whose fully qualified name matches the
+// optional pattern, either as `file:line: name → detail` lines or as one JSON object per line
+pub fn symbols(kinds: &[String], pattern: Option<&str>, json: bool, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let pattern = match pattern.map(crate::budget::regex).transpose() {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            eprintln!("Invalid regex pattern: {}", e);
+            return Err(anyhow::anyhow!("Invalid regex pattern"));
+        }
+    };
+    let wanted = |kind: &str| kinds.is_empty() || kinds.iter().any(|k| k == kind);
+
+    let files = declarations::scan(dir, file, exclude_dirs)?;
+    let mut symbols = Vec::new();
+    for (path, decls) in &files {
+        for class in &decls.classes {
+            if wanted("class") {
+                symbols.push(Symbol { kind: class.kind, name: class.name.clone(), fqn: class.fqcn.clone(), id: declarations::symbol_id(&class.fqcn, class.kind), path, line: class.line, detail: class.kind.to_string() });
+            }
+            if wanted("const") {
+                for constant in &class.constants {
+                    let fqn = format!("{}::{}", class.fqcn, constant.name);
+                    symbols.push(Symbol { kind: constant.kind, name: constant.name.clone(), id: declarations::symbol_id(&fqn, constant.kind), fqn, path, line: constant.line, detail: constant.kind.to_string() });
+                }
+            }
+            if wanted("property") {
+                for property in &class.properties {
+                    let detail = match &property.type_hint {
+                        Some(type_hint) => format!("{} {} ${}", property.visibility, type_hint.text, property.name),
+                        None => format!("{} ${}", property.visibility, property.name),
+                    };
+                    let fqn = format!("{}::${}", class.fqcn, property.name);
+                    symbols.push(Symbol { kind: "property", name: property.name.clone(), id: declarations::symbol_id(&fqn, "property"), fqn, path, line: property.line, detail });
+                }
+            }
+            if wanted("method") {
+                for method in &class.methods {
+                    symbols.push(Symbol { kind: "method", name: method.name.clone(), fqn: method.fqn.clone(), id: method.id(), path, line: method.line, detail: format!("{}{} function {}", method.visibility, if method.is_static { " static" } else { "" }, method.signature()) });
+                }
+            }
+        }
+        if wanted("function") {
+            for function in &decls.functions {
+                symbols.push(Symbol { kind: "function", name: function.name.clone(), fqn: function.fqn.clone(), id: function.id(), path, line: function.line, detail: format!("function {}", function.signature()) });
+            }
+        }
+    }
+
+    for symbol in symbols.iter().filter(|symbol| pattern.as_ref().is_none_or(|pattern| pattern.is_match(&symbol.fqn))) {
+        if json {
+            let record = Value::Object(vec![
+                crate::report::schema_version(),
+                ("kind".to_string(), Value::String(symbol.kind.to_string())),
+                ("name".to_string(), Value::String(symbol.name.clone())),
+                ("fqn".to_string(), Value::String(symbol.fqn.clone())),
+                ("id".to_string(), Value::String(symbol.id.clone())),
+                ("file".to_string(), Value::String(symbol.path.display().to_string())),
+                ("line".to_string(), Value::Number(symbol.line as f64)),
+                ("detail".to_string(), Value::String(symbol.detail.clone())),
+            ]);
+            println!("{}", record);
+        } else {
+            crate::print_symbol_match(symbol.path, symbol.line, Some(&symbol.fqn), &symbol.detail);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,192 @@
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+
+use anyhow::Result;
+use colored::*;
+use tree_sitter::Node;
+
+use crate::calls::{self, CallKind};
+use crate::declarations::{self, FileDecls};
+use crate::php::{self, FileNames};
+
+// A throw statement along with the exceptions it raises and the ones caught around it
+struct Throw {
+    line: usize,
+    line_styled: String,
+    // Empty when a variable is thrown that isn't the variable of an enclosing catch
+    exceptions: Vec<String>,
+    caught: Vec<String>,
+}
+
+// A method or function with the throws and statically resolvable calls of its body
+struct Callable {
+    // Fully qualified class of a method
+    class: Option<String>,
+    name: String,
+    symbol: String,
+    path: PathBuf,
+    throws: Vec<Throw>,
+    // Called key along with the exceptions caught around the call
+    calls: Vec<(String, Vec<String>)>,
+}
+
+// Lists the throw statements of a method and, up to `depth` calls deep, of the methods it
+// calls, leaving out exceptions caught on the way, then sums up the exceptions that can escape
+pub fn throws(method: &str, depth: usize, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let mut callables: HashMap<String, Callable> = HashMap::new();
+    let mut all_decls: Vec<FileDecls> = Vec::new();
+    crate::for_each_php_tree(dir, file, exclude_dirs, |_| true, |path, content, tree| {
+        let names = FileNames::new(tree.root_node(), content);
+        let lines: Vec<&str> = content.lines().collect();
+        all_decls.push(FileDecls::new(tree.root_node(), content));
+        php::walk(tree.root_node(), &mut |node| {
+            if node.kind() != "method_declaration" && node.kind() != "function_definition" {
+                return;
+            }
+            let (Some(name), Some(body)) = (node.child_by_field_name("name"), node.child_by_field_name("body")) else { return };
+            let name = php::node_text(name, content);
+            let (key, class, symbol) = match php::enclosing_class(node).filter(|_| node.kind() == "method_declaration") {
+                Some(class) => {
+                    let Some(fqcn) = names.class_fqcn(class, content) else { return };
+                    (calls::method_key(&fqcn, name), Some(fqcn.clone()), format!("{}::{}", php::short_name(&fqcn), name))
+                }
+                None => (calls::function_key(name), None, name.to_string()),
+            };
+
+            let mut callable = Callable { class, name: name.to_string(), symbol, path: path.to_path_buf(), throws: Vec::new(), calls: Vec::new() };
+            php::walk_body(body, &mut |inner| {
+                if inner.kind() == "throw_expression" {
+                    callable.throws.push(Throw {
+                        line: inner.start_position().row + 1,
+                        line_styled: crate::highlight_node(&lines, inner),
+                        exceptions: thrown_exceptions(inner, content, &names),
+                        caught: caught_around(inner, content, &names),
+                    });
+                } else if let Some(call) = calls::call_site(inner, content, &names)
+                    && call.kind != CallKind::Instance
+                    && let Some(target) = call.target_key() {
+                    callable.calls.push((target, caught_around(inner, content, &names)));
+                }
+            });
+            callables.insert(key, callable);
+        });
+    })?;
+
+    let classes: Vec<&declarations::ClassDecl> = all_decls.iter().flat_map(|decls| decls.classes.iter()).collect();
+    let parents = declarations::hierarchy(classes.iter().copied());
+    let extends: HashMap<String, String> = classes.iter()
+        .filter_map(|class| Some((class.fqcn.to_lowercase(), class.extends.first()?.to_lowercase())))
+        .collect();
+
+    let start: Vec<&String> = match method.split_once("::") {
+        Some((class, name)) => callables.iter()
+            .filter(|(_, callable)| callable.class.as_deref().is_some_and(|fqcn| php::class_matches(fqcn, class)) && callable.name.eq_ignore_ascii_case(name))
+            .map(|(key, _)| key)
+            .collect(),
+        None => callables.iter()
+            .filter(|(_, callable)| callable.class.is_none() && callable.name.eq_ignore_ascii_case(php::short_name(method)))
+            .map(|(key, _)| key)
+            .collect(),
+    };
+    if start.is_empty() {
+        eprintln!("Error: Method or function '{}' not found.", method);
+        return Err(anyhow::anyhow!("Method or function '{}' not found", method));
+    }
+
+    let mut escaping = BTreeSet::new();
+    let mut unknown = 0;
+    let mut seen = HashSet::new();
+    // (key, depth, exceptions caught by the callers on the way, call chain)
+    let mut queue: VecDeque<(String, usize, Vec<String>, Vec<String>)> = start.into_iter().map(|key| (key.clone(), 0, Vec::new(), Vec::new())).collect();
+    while let Some((key, level, caught_by_callers, chain)) = queue.pop_front() {
+        let Some(key) = calls::resolve(&key, &callables, &extends) else { continue };
+        if !seen.insert(key.clone()) {
+            continue;
+        }
+        let callable = &callables[&key];
+        let is_caught = |exception: &str, caught: &[String]| caught.iter().chain(caught_by_callers.iter())
+            .any(|catch| catch.eq_ignore_ascii_case(exception) || php::short_name(catch).eq_ignore_ascii_case("Throwable") || declarations::inherits_from(exception, catch, &parents));
+
+        for throw in &callable.throws {
+            let escapes: Vec<&String> = throw.exceptions.iter().filter(|exception| !is_caught(exception, &throw.caught)).collect();
+            if !throw.exceptions.is_empty() && escapes.is_empty() {
+                continue;
+            }
+            let via = if chain.is_empty() { String::new() } else { format!(" {}", format!("(via {})", chain.join(" → ")).dimmed()) };
+            crate::print_match(&callable.path, throw.line, Some(&callable.symbol), &format!("{}{}", throw.line_styled.trim(), via));
+            if throw.exceptions.is_empty() {
+                unknown += 1;
+            }
+            escaping.extend(escapes.into_iter().cloned());
+        }
+
+        if level < depth {
+            for (target, caught) in &callable.calls {
+                let mut caught = caught.clone();
+                caught.extend(caught_by_callers.iter().cloned());
+                let mut chain = chain.clone();
+                chain.push(callable.symbol.clone());
+                queue.push_back((target.clone(), level + 1, caught, chain));
+            }
+        }
+    }
+
+    let mut summary = if escaping.is_empty() {
+        "No exceptions escape".to_string()
+    } else {
+        format!("Exceptions that can escape: {}", escaping.into_iter().collect::<Vec<_>>().join(", ").bold().red())
+    };
+    if unknown > 0 {
+        summary.push_str(&format!(" (plus {} throw{} of a value whose class isn't known)", unknown, if unknown == 1 { "" } else { "s" }));
+    }
+    crate::report::summary(summary);
+    Ok(())
+}
+
+// The exception classes a throw raises. Rethrowing the variable of an enclosing catch raises
+// the classes that catch handles
+fn thrown_exceptions(throw: Node, content: &str, names: &FileNames) -> Vec<String> {
+    let resolve = |nodes: Vec<Node>| -> Vec<String> {
+        nodes.into_iter()
+            .filter(|node| node.kind() == "name" || node.kind() == "qualified_name")
+            .filter_map(|node| names.resolve_node(node, content))
+            .collect()
+    };
+    let thrown = resolve(crate::thrown_class(throw));
+    if !thrown.is_empty() {
+        return thrown;
+    }
+
+    let Some(variable) = throw.named_child(0).filter(|value| value.kind() == "variable_name") else { return Vec::new() };
+    let mut current = throw.parent();
+    while let Some(node) = current {
+        if node.kind() == "catch_clause"
+            && node.child_by_field_name("name").is_some_and(|name| php::node_text(name, content) == php::node_text(variable, content)) {
+            return resolve(crate::caught_classes(node));
+        }
+        if matches!(node.kind(), "method_declaration" | "function_definition") {
+            break;
+        }
+        current = node.parent();
+    }
+    Vec::new()
+}
+
+// The exception classes caught by the try blocks `node` is in, within its function
+fn caught_around(node: Node, content: &str, names: &FileNames) -> Vec<String> {
+    let mut caught = Vec::new();
+    let mut current = node;
+    while let Some(parent) = current.parent() {
+        if matches!(parent.kind(), "method_declaration" | "function_definition" | "anonymous_function" | "arrow_function") {
+            break;
+        }
+        if parent.kind() == "try_statement" && parent.child_by_field_name("body") == Some(current) {
+            let mut cursor = parent.walk();
+            for catch in parent.named_children(&mut cursor).filter(|child| child.kind() == "catch_clause") {
+                caught.extend(crate::caught_classes(catch).into_iter().filter_map(|class| names.resolve_node(class, content)));
+            }
+        }
+        current = parent;
+    }
+    caught
+}
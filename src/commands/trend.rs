@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Result;
+use colored::*;
+use regex::Regex;
+
+use crate::git;
+
+pub const TREND_STEPS: [&str; 3] = ["day", "week", "month"];
+
+// Counts the matches of a pattern in the PHP files at points `step` apart from `since` up to
+// now, taking the last commit on HEAD before each point, to see whether the use of an API
+// is actually going down
+pub fn trend(query: &str, since: &str, step: &str, csv: bool, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let pattern = match crate::budget::regex(query) {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            eprintln!("Invalid regex pattern: {}", e);
+            return Err(anyhow::anyhow!("Invalid regex pattern"));
+        }
+    };
+    let start = match git::timestamp(dir, since) {
+        Ok(start) => start,
+        Err(e) => {
+            eprintln!("Error: Could not read --since '{}': {}", since, e);
+            return Err(anyhow::anyhow!("Could not read --since '{}': {}", since, e));
+        }
+    };
+
+    // Points in time from now backwards, `N <step>s ago` so months follow the calendar
+    let mut points = Vec::new();
+    for steps_back in 0.. {
+        let point = git::timestamp(dir, &format!("{} {}s ago", steps_back, step))?;
+        if point < start {
+            break;
+        }
+        points.push(point);
+    }
+    points.reverse();
+
+    let exclude_dirs = crate::exclude_list(exclude_dirs);
+    let mut counted: HashMap<String, (usize, usize)> = HashMap::new();
+    let mut rows = Vec::new();
+    for point in points {
+        let Some(commit) = git::commit_at(dir, point)? else { continue };
+        let (matches, files) = match counted.get(&commit) {
+            Some(counts) => *counts,
+            None => {
+                let counts = count_matches(&pattern, &commit, dir, file, &exclude_dirs)?;
+                counted.insert(commit.clone(), counts);
+                counts
+            }
+        };
+        rows.push((date(point), commit, matches, files));
+    }
+
+    if csv {
+        println!("date,commit,matches,files");
+        for (date, commit, matches, files) in &rows {
+            println!("{},{},{},{}", date, commit, matches, files);
+        }
+        return Ok(());
+    }
+
+    println!("{:<10}  {:<10} {:>8} {:>6} {:>7}", "Date".bold(), "Commit".bold(), "Matches".bold(), "Files".bold(), "Change".bold());
+    let mut previous = None;
+    for (date, commit, matches, files) in &rows {
+        let change = match previous {
+            Some(previous) if *matches != previous => {
+                let change = *matches as i64 - previous as i64;
+                let text = format!("{:+}", change);
+                if change < 0 { text.green() } else { text.red() }
+            }
+            _ => "".normal(),
+        };
+        println!("{:<10}  {:<10} {:>8} {:>6} {:>7}", date, &commit[..commit.len().min(10)], matches, files, change);
+        previous = Some(*matches);
+    }
+    Ok(())
+}
+
+// Matches of the pattern in the selected PHP files at a commit, and the number of files with any
+fn count_matches(pattern: &Regex, commit: &str, dir: &str, file: &str, exclude_dirs: &[String]) -> Result<(usize, usize)> {
+    let paths: Vec<String> = git::files_at(dir, commit)?
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|extension| extension == "php"))
+        .filter(|path| !crate::is_excluded(path, dir, exclude_dirs) && crate::file_selected(path, dir, file))
+        .filter_map(|path| path.strip_prefix(dir).ok().and_then(Path::to_str).map(str::to_string))
+        .collect();
+
+    let mut matches = 0;
+    let mut files = 0;
+    for (_, content) in git::show_files(dir, commit, &paths)? {
+        let count = pattern.find_iter(&content).count();
+        matches += count;
+        if count > 0 {
+            files += 1;
+        }
+    }
+    Ok((matches, files))
+}
+
+// Formats a unix time as a YYYY-MM-DD date (UTC)
+fn date(timestamp: i64) -> String {
+    // Days to civil date, after Howard Hinnant's algorithm
+    let days = timestamp.div_euclid(86400) + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
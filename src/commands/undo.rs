@@ -0,0 +1,61 @@
+use anyhow::Result;
+use colored::*;
+
+use crate::journal;
+
+// Restores the files changed by the last operation that wrote any, moving moved files back, and
+// drops its journal so the next undo goes one operation further back. Files edited since phrep
+// wrote them are only overwritten with `force`
+pub fn undo(force: bool, dir: &str) -> Result<()> {
+    let Some((journal_dir, entries)) = journal::last(dir)? else {
+        eprintln!("Error: Nothing to undo, there is no journal in '{}'.", journal::undo_dir(dir).display());
+        return Err(anyhow::anyhow!("Nothing to undo, there is no journal in '{}'", journal::undo_dir(dir).display()));
+    };
+
+    if !force {
+        let changed: Vec<_> = entries.iter()
+            .filter(|entry| std::fs::read_to_string(&entry.path).ok().as_deref() != Some(entry.written.as_str()))
+            .collect();
+        for entry in &changed {
+            eprintln!("Warning: '{}' changed since phrep wrote it", entry.path.display());
+        }
+        if !changed.is_empty() {
+            eprintln!("Error: {} file{} changed since, run with --force to restore them anyway.", changed.len(), if changed.len() == 1 { "" } else { "s" });
+            return Err(anyhow::anyhow!("{} file{} changed since, run with --force to restore them anyway", changed.len(), if changed.len() == 1 { "" } else { "s" }));
+        }
+    }
+
+    // Backwards, so a file written twice ends up with the content it had first
+    for entry in entries.iter().rev() {
+        let moved = entry.path != entry.original_path;
+        let restored = (|| -> std::io::Result<()> {
+            if let Some(parent) = entry.original_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&entry.original_path, &entry.original)?;
+            if moved && entry.path.exists() {
+                std::fs::remove_file(&entry.path)?;
+                // Only succeeds for the directory the move created, once it's empty again
+                if let Some(parent) = entry.path.parent() {
+                    let _ = std::fs::remove_dir(parent);
+                }
+            }
+            Ok(())
+        })();
+        if let Err(e) = restored {
+            eprintln!("Error: Could not restore '{}': {}", entry.original_path.display(), e);
+            return Err(anyhow::anyhow!("Could not restore '{}': {}", entry.original_path.display(), e));
+        }
+        if moved {
+            println!("Restored {} {}", crate::format_filename(&entry.original_path), format!("(moved back from {})", crate::format_filename(&entry.path)).dimmed());
+        } else {
+            println!("Restored {}", crate::format_filename(&entry.original_path));
+        }
+    }
+
+    if let Err(e) = std::fs::remove_dir_all(&journal_dir) {
+        eprintln!("Warning: Could not remove the undo journal '{}': {}", journal_dir.display(), e);
+    }
+    crate::report::summary(format!("Undid the last operation, restoring {} file{}", entries.len(), if entries.len() == 1 { "" } else { "s" }));
+    Ok(())
+}
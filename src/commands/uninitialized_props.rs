@@ -0,0 +1,130 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use colored::*;
+use tree_sitter::Node;
+
+use crate::calls::{self, CallKind};
+use crate::php::{self, FileNames};
+
+// Reports typed properties that start out uninitialized: declared without a default and not
+// assigned by the constructor, so reading one throws "must not be accessed before
+// initialization". `$this->prop = ...` counts in the constructor, in the `init_methods` (such as
+// PHPUnit's setUp()) and in the methods of the class they call through `$this->`, `self::` or
+// `static::`. Nullable and mixed properties are left out unless `include_nullable` is set, as
+// are static properties and virtual ones with hooks
+pub fn uninitialized_props(include_nullable: bool, init_methods: &[String], dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let mut found = 0;
+    crate::for_each_php_tree(dir, file, exclude_dirs, |content| content.contains('$'), |path, content, tree| {
+        let names = FileNames::new(tree.root_node(), content);
+        php::walk(tree.root_node(), &mut |class| {
+            if class.kind() != "class_declaration" {
+                return;
+            }
+            let Some(body) = class.child_by_field_name("body") else { return };
+            let class_name = class.child_by_field_name("name").map_or("", |name| php::node_text(name, content));
+
+            let mut properties = Vec::new();
+            let mut methods = HashMap::new();
+            let mut cursor = body.walk();
+            for member in body.named_children(&mut cursor) {
+                match member.kind() {
+                    "property_declaration" => properties.extend(uninitialized(member, content, &names, include_nullable)),
+                    "method_declaration" => {
+                        if let (Some(name), Some(method_body)) = (member.child_by_field_name("name"), member.child_by_field_name("body")) {
+                            methods.insert(php::node_text(name, content).to_lowercase(), method_body);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if properties.is_empty() {
+                return;
+            }
+
+            let starts = std::iter::once("__construct").chain(init_methods.iter().map(String::as_str)).map(str::to_lowercase);
+            let assigned = assigned_properties(starts.collect(), &methods, content, &names);
+            for (element, name, type_text) in properties {
+                if assigned.contains(name) {
+                    continue;
+                }
+                let symbol = format!("{}::${}", class_name, name);
+                let message = format!("{} has no default and isn't assigned in the constructor", format!("{} ${}", type_text, name).red());
+                crate::print_symbol_match(path, element.start_position().row + 1, Some(&symbol), &message);
+                found += 1;
+            }
+        });
+    })?;
+
+    crate::report::summary(format!("{} uninitialized properties found", found));
+    Ok(())
+}
+
+// The elements of a property declaration that are typed but have no default, with their name
+// and type, unless the declaration is static or has hooks or the type allows null when null
+// doesn't count
+fn uninitialized<'a>(declaration: Node<'a>, content: &'a str, names: &FileNames, include_nullable: bool) -> Vec<(Node<'a>, &'a str, &'a str)> {
+    let Some(type_node) = declaration.child_by_field_name("type") else { return Vec::new() };
+    let mut cursor = declaration.walk();
+    let children: Vec<Node> = declaration.named_children(&mut cursor).collect();
+    if children.iter().any(|child| matches!(child.kind(), "static_modifier" | "property_hook_list")) {
+        return Vec::new();
+    }
+    if !include_nullable && crate::declarations::type_decl(type_node, content, names).is_nullable() {
+        return Vec::new();
+    }
+    children.into_iter()
+        .filter(|child| child.kind() == "property_element" && child.child_by_field_name("default_value").is_none())
+        .filter_map(|element| element.child_by_field_name("name").map(|name| (element, php::node_text(name, content).trim_start_matches('$'), php::node_text(type_node, content))))
+        .collect()
+}
+
+// The properties assigned through `$this->` by the methods in `starts` and the methods of the
+// same class they call, followed as far as the calls go
+fn assigned_properties(starts: Vec<String>, methods: &HashMap<String, Node>, content: &str, names: &FileNames) -> HashSet<String> {
+    let mut assigned = HashSet::new();
+    let mut queue: Vec<String> = starts;
+    let mut seen = HashSet::new();
+    while let Some(method) = queue.pop() {
+        if !seen.insert(method.clone()) {
+            continue;
+        }
+        let Some(&body) = methods.get(&method) else { continue };
+        php::walk_body(body, &mut |node| {
+            if let Some(call) = calls::call_site(node, content, names)
+                && matches!(call.kind, CallKind::This | CallKind::SelfStatic) {
+                queue.push(call.name.to_lowercase());
+            }
+            let is_assignment = match node.kind() {
+                "assignment_expression" | "reference_assignment_expression" => true,
+                "augmented_assignment_expression" => node.child_by_field_name("operator").is_some_and(|operator| php::node_text(operator, content) == "??="),
+                _ => false,
+            };
+            if let Some(left) = node.child_by_field_name("left").filter(|_| is_assignment) {
+                assigned_targets(left, content, &mut assigned);
+            }
+        });
+    }
+    assigned
+}
+
+// Adds the `$this->name` targets of an assignment's left side, including those of
+// `[$this->a, $this->b] = ...`, but not `$this->items[] = ...`, which reads the property first
+fn assigned_targets(left: Node, content: &str, assigned: &mut HashSet<String>) {
+    match left.kind() {
+        "member_access_expression" => {
+            let is_this = left.child_by_field_name("object").is_some_and(|object| php::node_text(object, content) == "$this");
+            if let Some(name) = left.child_by_field_name("name").filter(|name| is_this && name.kind() == "name") {
+                assigned.insert(php::node_text(name, content).to_string());
+            }
+        }
+        "list_literal" | "array_creation_expression" | "array_element_initializer" | "by_ref" => {
+            let mut cursor = left.walk();
+            let children: Vec<Node> = left.named_children(&mut cursor).collect();
+            for child in children {
+                assigned_targets(child, content, assigned);
+            }
+        }
+        _ => {}
+    }
+}
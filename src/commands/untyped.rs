@@ -0,0 +1,82 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use colored::*;
+
+use crate::declarations::{self, FunctionDecl};
+
+// Typed vs total counts for one namespace
+#[derive(Default)]
+struct Coverage {
+    params: (usize, usize),
+    returns: (usize, usize),
+    properties: (usize, usize),
+}
+
+// Methods that can't declare a return type
+const NO_RETURN_TYPE: [&str; 3] = ["__construct", "__destruct", "__clone"];
+
+// Lists every untyped parameter, missing return type and untyped property and sums up the
+// type coverage of each namespace
+pub fn untyped(summary_only: bool, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let files = declarations::scan(dir, file, exclude_dirs)?;
+    let mut coverage: BTreeMap<String, Coverage> = BTreeMap::new();
+
+    for (path, decls) in &files {
+        for class in &decls.classes {
+            let namespace = coverage.entry(class.namespace.clone()).or_default();
+            for method in &class.methods {
+                let symbol = format!("{}::{}", class.name, method.name);
+                check_function(path, &symbol, method, namespace, summary_only);
+            }
+            for property in &class.properties {
+                count(&mut namespace.properties, property.type_hint.is_some());
+                if property.type_hint.is_none() && !summary_only {
+                    let symbol = format!("{}::${}", class.name, property.name);
+                    crate::print_symbol_match(path, property.line, Some(&symbol), &format!("property {} has no type", format!("${}", property.name).red()));
+                }
+            }
+        }
+        for function in &decls.functions {
+            let namespace = coverage.entry(function.namespace().to_string()).or_default();
+            check_function(path, &function.name, function, namespace, summary_only);
+        }
+    }
+
+    crate::report::summary(format!("{:<40} {:>18} {:>18} {:>18}", "Namespace".bold(), "Parameters".bold(), "Return types".bold(), "Properties".bold()));
+    for (namespace, coverage) in &coverage {
+        let namespace = if namespace.is_empty() { "(global)" } else { namespace };
+        crate::report::summary(format!("{:<40} {:>18} {:>18} {:>18}", namespace, percentage(coverage.params), percentage(coverage.returns), percentage(coverage.properties)));
+    }
+
+    Ok(())
+}
+
+fn check_function(path: &std::path::Path, symbol: &str, function: &FunctionDecl, coverage: &mut Coverage, summary_only: bool) {
+    for param in &function.params {
+        count(&mut coverage.params, param.type_hint.is_some());
+        if param.type_hint.is_none() && !summary_only {
+            crate::print_match(path, function.line, Some(symbol), &format!("parameter {} has no type", format!("${}", param.name).red()));
+        }
+    }
+
+    if NO_RETURN_TYPE.iter().any(|name| function.name.eq_ignore_ascii_case(name)) {
+        return;
+    }
+    count(&mut coverage.returns, function.return_type.is_some());
+    if function.return_type.is_none() && !summary_only {
+        crate::print_match(path, function.line, Some(symbol), &format!("{}", "return type missing".red()));
+    }
+}
+
+fn count(counter: &mut (usize, usize), typed: bool) {
+    counter.0 += typed as usize;
+    counter.1 += 1;
+}
+
+fn percentage((typed, total): (usize, usize)) -> String {
+    if total == 0 {
+        return "-".to_string();
+    }
+    format!("{}/{} {:.1}%", typed, total, typed as f64 * 100.0 / total as f64)
+}
@@ -0,0 +1,169 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use colored::*;
+use tree_sitter::Node;
+
+use crate::declarations::{self, FileDecls};
+use crate::php::{self, FileNames};
+
+// Calls that read every parameter without naming them
+const READ_ALL: [&str; 3] = ["func_get_args", "func_get_arg", "get_defined_vars"];
+
+// Reports the parameters of methods and functions that their body never mentions. `compact()`
+// counts the names it's given and func_get_args() or `$$name` count as using every parameter.
+// Promoted constructor parameters are properties and abstract or empty methods have no body to
+// use them in, so those are left out, except for empty constructors. A method whose signature comes from a parent class or
+// interface declared in the search directory is marked, or skipped with `skip_overrides`
+pub fn unused_params(skip_overrides: bool, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let files = declarations::scan(dir, file, exclude_dirs)?;
+    let parents = declarations::hierarchy(files.iter().flat_map(|(_, decls)| decls.classes.iter()));
+
+    let (mut unused, mut functions) = (0, 0);
+    crate::for_each_php_tree(dir, file, exclude_dirs, |_| true, |path, content, tree| {
+        let names = FileNames::new(tree.root_node(), content);
+        php::walk(tree.root_node(), &mut |node| {
+            if node.kind() != "method_declaration" && node.kind() != "function_definition" {
+                return;
+            }
+            let (Some(name), Some(params), Some(body)) = (node.child_by_field_name("name"), node.child_by_field_name("parameters"), node.child_by_field_name("body")) else { return };
+            let name = php::node_text(name, content);
+            // Empty methods are hooks meant to be overridden, but an empty constructor gets
+            // nothing from a parameter that isn't promoted
+            let mut cursor = body.walk();
+            if body.named_children(&mut cursor).all(|child| child.kind() == "comment") && !name.eq_ignore_ascii_case("__construct") {
+                return;
+            }
+            let class_fqcn = php::enclosing_class(node).filter(|_| node.kind() == "method_declaration").and_then(|class| names.class_fqcn(class, content));
+            let overridden = class_fqcn.as_deref().and_then(|fqcn| overridden_method(&files, &parents, fqcn, name));
+            if overridden.is_some() && skip_overrides {
+                return;
+            }
+
+            let mut cursor = params.walk();
+            let declared: Vec<(Node, &str)> = params.named_children(&mut cursor)
+                .filter(|param| param.kind() != "property_promotion_parameter")
+                .filter_map(|param| param.child_by_field_name("name").map(|name| (param, php::node_text(name, content).trim_start_matches('$'))))
+                .collect();
+            if declared.is_empty() {
+                return;
+            }
+            let Some(used) = used_variables(body, content) else { return };
+            let symbol = match &class_fqcn {
+                Some(fqcn) => format!("{}::{}", php::short_name(fqcn), name),
+                None => name.to_string(),
+            };
+            let mut reported = false;
+            for (param, param_name) in declared {
+                if used.contains(param_name) {
+                    continue;
+                }
+                let mut message = format!("parameter {} is never used", format!("${}", param_name).red());
+                if let Some(overridden) = &overridden {
+                    message.push_str(&format!(" {}", format!("(signature from {})", overridden).dimmed()));
+                }
+                crate::print_match(path, param.start_position().row + 1, Some(&symbol), &message);
+                unused += 1;
+                reported = true;
+            }
+            functions += reported as usize;
+        });
+    })?;
+
+    crate::report::summary(format!("{} unused parameters found in {} methods and functions", unused, functions));
+    Ok(())
+}
+
+// The names of the variables a body mentions, or None when it can read any variable through
+// func_get_args(), get_defined_vars(), `$$name` or compact() with a computed name. Closures
+// only see what their `use` clause imports, arrow functions see everything but their own
+// parameters
+fn used_variables(body: Node, content: &str) -> Option<HashSet<String>> {
+    let mut used = HashSet::new();
+    collect_used(body, content, &mut used).then_some(used)
+}
+
+// Adds the variables below `node` to `used`, returning false if any variable may be read
+fn collect_used(node: Node, content: &str, used: &mut HashSet<String>) -> bool {
+    match node.kind() {
+        "variable_name" => {
+            used.insert(php::node_text(node, content).trim_start_matches('$').to_string());
+            return true;
+        }
+        "dynamic_variable_name" => return false,
+        "function_call_expression" => {
+            let function = node.child_by_field_name("function").map(|function| php::node_text(function, content).trim_start_matches('\\').to_lowercase()).unwrap_or_default();
+            if READ_ALL.contains(&function.as_str()) {
+                return false;
+            }
+            if function == "compact"
+                && let Some(arguments) = node.child_by_field_name("arguments")
+                && !compact_names(arguments, content, used) {
+                return false;
+            }
+        }
+        "anonymous_function" => {
+            let mut cursor = node.walk();
+            return node.children(&mut cursor)
+                .filter(|child| child.kind() == "anonymous_function_use_clause")
+                .all(|clause| collect_used(clause, content, used));
+        }
+        "arrow_function" => {
+            let Some(body) = node.child_by_field_name("body") else { return true };
+            let mut inner = HashSet::new();
+            if !collect_used(body, content, &mut inner) {
+                return false;
+            }
+            if let Some(params) = node.child_by_field_name("parameters") {
+                let mut cursor = params.walk();
+                for param in params.named_children(&mut cursor) {
+                    if let Some(name) = param.child_by_field_name("name") {
+                        inner.remove(php::node_text(name, content).trim_start_matches('$'));
+                    }
+                }
+            }
+            used.extend(inner);
+            return true;
+        }
+        "function_definition" | "class_declaration" | "interface_declaration" | "trait_declaration" | "enum_declaration" => return true,
+        // Only the constructor arguments of `new class(...) {}` are in the outer scope
+        "anonymous_class" => {
+            let mut cursor = node.walk();
+            return node.children(&mut cursor).filter(|child| child.kind() == "arguments").all(|arguments| collect_used(arguments, content, used));
+        }
+        _ => {}
+    }
+    let mut cursor = node.walk();
+    let children: Vec<Node> = node.children(&mut cursor).collect();
+    children.into_iter().all(|child| collect_used(child, content, used))
+}
+
+// Adds the variable names given to compact() as strings or arrays of strings, returning false
+// when one of them is computed
+fn compact_names(arguments: Node, content: &str, used: &mut HashSet<String>) -> bool {
+    let mut literal = true;
+    let mut cursor = arguments.walk();
+    for argument in arguments.named_children(&mut cursor) {
+        php::walk(argument, &mut |node| match node.kind() {
+            "string" | "encapsed_string" => match php::string_literal(node, content) {
+                Some(name) => {
+                    used.insert(name);
+                }
+                None => literal = false,
+            },
+            "variable_name" | "name" => literal = false,
+            _ => {}
+        });
+    }
+    literal
+}
+
+// The `Parent::method` a method overrides or implements, when the parent class or interface is
+// one of the scanned files and declares it
+fn overridden_method(files: &[(std::path::PathBuf, FileDecls)], parents: &std::collections::HashMap<String, Vec<String>>, fqcn: &str, method: &str) -> Option<String> {
+    files.iter()
+        .flat_map(|(_, decls)| decls.classes.iter())
+        .filter(|class| !class.fqcn.eq_ignore_ascii_case(fqcn) && declarations::inherits_from(fqcn, &class.fqcn, parents))
+        .find(|class| class.methods.iter().any(|declared| declared.name.eq_ignore_ascii_case(method)))
+        .map(|class| format!("{}::{}", class.name, method))
+}
@@ -0,0 +1,82 @@
+use std::path::Path;
+
+use anyhow::Result;
+use colored::*;
+
+use crate::calls::{self, CallKind};
+use crate::declarations;
+use crate::php::{self, FileNames};
+
+// For every method of every class in a file, lists the other methods of the same class it calls
+// (through $this->, self::, static:: or the class name) and the ones calling it
+pub fn xref(file_path: &str) -> Result<()> {
+    let path = Path::new(file_path);
+    let Some(content) = crate::read_file(path) else {
+        return Err(anyhow::anyhow!("Could not read file '{}'", file_path));
+    };
+    let mut parser = php::new_parser()?;
+    let Some(tree) = crate::parse_content(&mut parser, &content, path) else {
+        return Err(anyhow::anyhow!("Could not parse file '{}' as PHP", file_path));
+    };
+    let names = FileNames::new(tree.root_node(), &content);
+
+    println!("{}", crate::format_filename(path).bold().blue());
+    php::walk(tree.root_node(), &mut |node| {
+        if !matches!(node.kind(), "class_declaration" | "trait_declaration" | "enum_declaration") {
+            return;
+        }
+        let (Some(fqcn), Some(body)) = (names.class_fqcn(node, &content), node.child_by_field_name("body")) else { return };
+
+        let mut cursor = body.walk();
+        let methods: Vec<(&str, usize, tree_sitter::Node)> = body.named_children(&mut cursor)
+            .filter(|member| member.kind() == "method_declaration")
+            .filter_map(|method| {
+                let name = php::node_text(method.child_by_field_name("name")?, &content);
+                Some((name, method.start_position().row + 1, method))
+            })
+            .collect();
+
+        // calls[i] holds the indexes of the methods method i calls, in order of first call
+        let mut method_calls: Vec<Vec<usize>> = vec![Vec::new(); methods.len()];
+        for (i, (_, _, method)) in methods.iter().enumerate() {
+            let Some(method_body) = method.child_by_field_name("body") else { continue };
+            php::walk(method_body, &mut |inner| {
+                let Some(call) = calls::call_site(inner, &content, &names) else { return };
+                let same_class = match call.kind {
+                    CallKind::This | CallKind::SelfStatic => true,
+                    CallKind::Static => call.class.as_deref().is_some_and(|class| class.eq_ignore_ascii_case(&fqcn)),
+                    _ => false,
+                };
+                if !same_class {
+                    return;
+                }
+                if let Some(target) = methods.iter().position(|(name, _, _)| name.eq_ignore_ascii_case(call.name))
+                    && target != i && !method_calls[i].contains(&target) {
+                    method_calls[i].push(target);
+                }
+            });
+        }
+
+        println!("{} {}", declarations::class_kind(node), fqcn.bold().yellow());
+        for (i, (name, line, _)) in methods.iter().enumerate() {
+            let callees: Vec<&str> = method_calls[i].iter().map(|&target| methods[target].0).collect();
+            let callers: Vec<&str> = method_calls.iter().enumerate()
+                .filter(|(_, targets)| targets.contains(&i))
+                .map(|(caller, _)| methods[caller].0)
+                .collect();
+            println!("{:>5}  {}()", line, name.bold());
+            println!("         calls: {}", list(&callees).green());
+            println!("         called by: {}", list(&callers).magenta());
+        }
+    });
+
+    Ok(())
+}
+
+fn list(names: &[&str]) -> String {
+    if names.is_empty() {
+        "-".to_string()
+    } else {
+        names.join(", ")
+    }
+}
@@ -0,0 +1,145 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use regex::Regex;
+
+pub const CONFIG_FILE: &str = "phrep.toml";
+
+// Settings from one phrep.toml, applying to the directory it's in and everything below
+#[derive(Debug, Default)]
+struct Config {
+    // Directories, relative to the config file, not to search
+    exclude_dirs: Vec<String>,
+    // File extensions to search instead of the ones inherited from above (php by default)
+    extensions: Option<Vec<String>>,
+    // Globs, relative to the config file, of files refactorings only change with --force
+    protected_paths: Vec<String>,
+}
+
+// The phrep.toml files found while listing the search directory. Settings are merged from the
+// outermost config inwards: excludes add up and extensions are replaced, so a subtree such as
+// legacy/ can carry its own conventions
+pub struct Configs {
+    configs: Vec<(PathBuf, Config)>,
+}
+
+impl Configs {
+    pub fn load(paths: &[PathBuf]) -> Self {
+        let mut configs = Vec::new();
+        for path in paths.iter().filter(|path| path.file_name().is_some_and(|name| name == CONFIG_FILE)) {
+            let Some(content) = crate::read_file(path) else { continue };
+            match parse(path, &content) {
+                Ok(config) => configs.push((path.parent().map(Path::to_path_buf).unwrap_or_default(), config)),
+                Err(e) => eprintln!("Warning: Ignoring '{}': {}", path.display(), e),
+            }
+        }
+        // Outer directories first so inner configs are applied over them
+        configs.sort_by_key(|(dir, _)| dir.components().count());
+        Configs { configs }
+    }
+
+    // Checks a file against the extensions and excludes of the configs above it
+    pub fn selects(&self, path: &Path) -> bool {
+        let mut extensions = None;
+        for (dir, config) in &self.configs {
+            let Ok(relative_path) = path.strip_prefix(dir) else { continue };
+            if config.exclude_dirs.iter().any(|excluded| relative_path.starts_with(excluded.trim_matches('/'))) {
+                return false;
+            }
+            if let Some(config_extensions) = &config.extensions {
+                extensions = Some(config_extensions);
+            }
+        }
+        let extension = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+        match extensions {
+            Some(extensions) => extensions.iter().any(|allowed| allowed.trim_start_matches('.').eq_ignore_ascii_case(extension)),
+            None => extension == "php",
+        }
+    }
+}
+
+// The `protected_paths` of the phrep.toml files between the search directory and the files a
+// refactoring edits, such as `vendor/**` or `database/migrations/**`
+pub struct Protected {
+    patterns: Vec<(PathBuf, Vec<(String, Regex)>)>,
+}
+
+impl Protected {
+    pub fn load(root: &Path, paths: &[&Path]) -> Self {
+        let mut dirs: Vec<&Path> = paths.iter()
+            .flat_map(|path| path.ancestors().skip(1).take_while(|dir| dir.starts_with(root)))
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+        let mut patterns = Vec::new();
+        for dir in dirs {
+            let path = dir.join(CONFIG_FILE);
+            let Some(content) = path.is_file().then(|| crate::read_file(&path)).flatten() else { continue };
+            let config = match parse(&path, &content) {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("Warning: Ignoring '{}': {}", path.display(), e);
+                    continue;
+                }
+            };
+            let globs = config.protected_paths.into_iter()
+                .filter_map(|glob| Regex::new(&format!("^{}(?:/.*)?$", crate::tool_excludes::glob(glob.trim_matches('/'), false))).ok().map(|pattern| (glob, pattern)))
+                .collect();
+            patterns.push((dir.to_path_buf(), globs));
+        }
+        Protected { patterns }
+    }
+
+    // The protected_paths entry covering a file, if one does
+    pub fn pattern(&self, path: &Path) -> Option<&str> {
+        self.patterns.iter().find_map(|(dir, globs)| {
+            let relative_path = path.strip_prefix(dir).ok()?.to_string_lossy().replace('\\', "/");
+            globs.iter().find(|(_, pattern)| pattern.is_match(&relative_path)).map(|(glob, _)| glob.as_str())
+        })
+    }
+}
+
+// Reads the `key = "value"` and `key = ["a", "b"]` lines of the small TOML subset phrep needs
+fn parse(path: &Path, content: &str) -> Result<Config> {
+    let mut config = Config::default();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(anyhow::anyhow!("line {}: expected `key = value`", i + 1));
+        };
+        let values = parse_strings(value.trim()).ok_or_else(|| anyhow::anyhow!("line {}: expected a string or a list of strings", i + 1))?;
+        match key.trim() {
+            "exclude_dirs" => config.exclude_dirs = values,
+            "extensions" => config.extensions = Some(values),
+            "protected_paths" => config.protected_paths = values,
+            key => eprintln!("Warning: Unknown setting '{}' on line {} of '{}'", key, i + 1, path.display()),
+        }
+    }
+    Ok(config)
+}
+
+// Parses `"a"` or `["a", "b"]`, allowing a trailing comment
+fn parse_strings(value: &str) -> Option<Vec<String>> {
+    let (items, rest) = match value.strip_prefix('[') {
+        Some(list) => {
+            let (items, rest) = list.split_once(']')?;
+            (items, rest)
+        }
+        None => {
+            let end = value.get(1..)?.find('"')? + 2;
+            (&value[..end], &value[end..])
+        }
+    };
+    let rest = rest.trim();
+    if !rest.is_empty() && !rest.starts_with('#') {
+        return None;
+    }
+    items.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| item.strip_prefix('"')?.strip_suffix('"').map(str::to_string))
+        .collect()
+}
@@ -0,0 +1,268 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use colored::*;
+use tree_sitter::{Node, Point, Tree};
+
+use crate::php;
+
+// Set by --context-statements: how many statements to print before and after the statement of
+// every match
+static STATEMENTS: OnceLock<usize> = OnceLock::new();
+
+// Set by --relative-lines
+static RELATIVE_LINES: OnceLock<bool> = OnceLock::new();
+
+// Set by --with-signature and --with-doc: what to print above the first match in a method
+static HEADER: OnceLock<(bool, bool)> = OnceLock::new();
+
+// A parsed file the context is taken from
+struct ParsedFile {
+    path: PathBuf,
+    content: String,
+    tree: Tree,
+}
+
+thread_local! {
+    // The file matches were last reported in, parsed once for all of its matches
+    static FILE: RefCell<Option<ParsedFile>> = const { RefCell::new(None) };
+    // Where the statement last printed with its context starts, so several matches in one
+    // statement only print it once
+    static LAST_STATEMENT: RefCell<Option<(PathBuf, usize)>> = const { RefCell::new(None) };
+    // The header of the function being searched, printed with its first match
+    static PENDING_HEADER: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+pub fn set_statements(statements: usize) {
+    let _ = STATEMENTS.set(statements);
+}
+
+pub fn set_relative_lines() {
+    let _ = RELATIVE_LINES.set(true);
+}
+
+// The ` (+17 in processOrder)` --relative-lines adds to the location of a match: how many lines
+// below the start of its method or function the match is
+pub fn relative_line(path: &Path, line_number: usize) -> Option<String> {
+    RELATIVE_LINES.get()?;
+    with_file(path, |file| {
+        let (offset, name) = function_offset(file.tree.root_node(), &file.content, line_number)?;
+        Some(format!(" (+{} in {})", offset, name))
+    })
+}
+
+// How many lines below the start of the innermost named method or function around it a line
+// is, counting from its signature, with that function's name. Closures and arrow functions count as part of the function
+// they're in; lines outside of any function have no offset
+fn function_offset<'a>(root: Node, content: &'a str, line_number: usize) -> Option<(usize, &'a str)> {
+    let row = line_number.checked_sub(1)?;
+    let line = content.lines().nth(row)?;
+    let column = line.len() - line.trim_start().len();
+    let node = root.descendant_for_point_range(Point::new(row, column), Point::new(row, column))?;
+    let function = std::iter::once(node).chain(std::iter::successors(node.parent(), |node| node.parent()))
+        .find(|node| node.kind() == "method_declaration" || node.kind() == "function_definition")?;
+    let name = function.child_by_field_name("name").map_or("", |name| php::node_text(name, content));
+    // Attributes above the signature aren't part of where the function starts
+    let mut cursor = function.walk();
+    let start = function.children(&mut cursor).find(|child| child.kind() != "attribute_list").unwrap_or(function).start_position().row;
+    Some((row.checked_sub(start)?, name))
+}
+
+pub fn set_header(signature: bool, doc: bool) {
+    let _ = HEADER.set((signature, doc));
+}
+
+// Starts searching a method or function, so its first match is printed below its docblock
+// summary and signature line
+pub fn begin_function(function: Node, content: &str) {
+    let Some(&(signature, doc)) = HEADER.get() else { return };
+    let mut header = Vec::new();
+    if doc && let Some(summary) = doc_summary(function, content) {
+        header.push(format!("  {}", format!("/** {} */", summary).dimmed()));
+    }
+    if signature {
+        header.push(format!("  {}", php::signature(function, content).cyan()));
+    }
+    PENDING_HEADER.set(Some(header.join("\n")).filter(|header| !header.is_empty()));
+}
+
+pub fn end_function() {
+    PENDING_HEADER.set(None);
+}
+
+// Prints the header of the function being searched if it's the function's first match
+pub fn print_header() {
+    if let Some(header) = PENDING_HEADER.take() {
+        crate::cache::emit(header);
+    }
+}
+
+// The first line of the docblock right above a function that isn't a tag
+fn doc_summary(function: Node, content: &str) -> Option<String> {
+    let comment = function.prev_named_sibling().filter(|comment| comment.kind() == "comment")?;
+    let text = php::node_text(comment, content);
+    if !text.starts_with("/**") {
+        return None;
+    }
+    text.lines()
+        .map(|line| line.trim().trim_start_matches("/**").trim_end_matches("*/").trim_start_matches('*').trim())
+        .find(|line| !line.is_empty())
+        .filter(|line| !line.starts_with('@'))
+        .map(str::to_string)
+}
+
+// Prints the statement a match on `line_number` is in along with the sibling statements around
+// it, each with its line number and the matching statement marked. Statements are taken whole
+// from the file's syntax tree, so a call spread over several lines is never cut in half
+pub fn print(path: &Path, line_number: usize) {
+    let Some(&count) = STATEMENTS.get() else { return };
+    with_file(path, |file| {
+        let statement = statement_at(&file.tree, &file.content, line_number)?;
+
+        let key = (path.to_path_buf(), statement.start_byte());
+        if LAST_STATEMENT.with_borrow(|last| last.as_ref() == Some(&key)) {
+            return None;
+        }
+        LAST_STATEMENT.set(Some(key));
+
+        // The furthest of `count` statements before or after the matching one
+        let furthest = |before: bool| {
+            let mut found = Vec::new();
+            let mut current = statement;
+            while found.len() < count {
+                let next = if before { current.prev_named_sibling() } else { current.next_named_sibling() };
+                let Some(sibling) = next else { break };
+                current = sibling;
+                if !matches!(sibling.kind(), "comment" | "php_tag") {
+                    found.push(sibling);
+                }
+            }
+            found.last().copied().unwrap_or(statement)
+        };
+        let (first, last) = (furthest(true), furthest(false));
+
+        let lines: Vec<&str> = file.content.lines().collect();
+        let width = (last.end_position().row + 1).to_string().len();
+        for row in first.start_position().row..=last.end_position().row {
+            let text = lines.get(row).copied().unwrap_or("");
+            let number = format!("{:>width$}", row + 1, width = width);
+            if (statement.start_position().row..=statement.end_position().row).contains(&row) {
+                crate::cache::emit(format!("  {} {} {}", number.bold(), ">".bold().red(), text));
+            } else {
+                crate::cache::emit(format!("  {}   {}", number.dimmed(), text));
+            }
+        }
+        crate::cache::emit("  --".dimmed().to_string());
+        Some(())
+    });
+}
+
+// Runs `visit` on the parsed file, parsing it unless it's the one matches were last reported in
+fn with_file<T>(path: &Path, visit: impl FnOnce(&ParsedFile) -> Option<T>) -> Option<T> {
+    FILE.with_borrow_mut(|file| {
+        if file.as_ref().is_none_or(|file| file.path != path) {
+            *file = load(path);
+        }
+        visit(file.as_ref()?)
+    })
+}
+
+fn load(path: &Path) -> Option<ParsedFile> {
+    let content = crate::read_file(path)?;
+    let mut parser = php::new_parser().ok()?;
+    let tree = crate::parse_content(&mut parser, &content, path)?;
+    Some(ParsedFile { path: path.to_path_buf(), content, tree })
+}
+
+// The innermost statement of a statement list (a block, a file or a case) that starts on or
+// spans a line, found from the first code on the line
+fn statement_at<'a>(tree: &'a Tree, content: &str, line_number: usize) -> Option<Node<'a>> {
+    let line = content.lines().nth(line_number.checked_sub(1)?)?;
+    let column = line.len() - line.trim_start().len();
+    let point = Point::new(line_number - 1, column);
+    let mut node = tree.root_node().descendant_for_point_range(point, point)?;
+    loop {
+        let parent = node.parent()?;
+        let in_list = matches!(parent.kind(), "compound_statement" | "program" | "case_statement" | "default_statement" | "colon_block" | "declaration_list");
+        if in_list && node.is_named() && !matches!(node.kind(), "comment" | "php_tag") && parent.child_by_field_name("value") != Some(node) {
+            return Some(node);
+        }
+        node = parent;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::function_offset;
+
+    fn offset(content: &str, line_number: usize) -> Option<(usize, String)> {
+        let mut parser = crate::php::new_parser().unwrap();
+        let tree = parser.parse(content, None).unwrap();
+        function_offset(tree.root_node(), content, line_number).map(|(offset, name)| (offset, name.to_string()))
+    }
+
+    const CLASS: &str = "<?php\nclass Order {\n    public function process($order)\n    {\n        $total = 0;\n\n        return $total;\n    }\n}\n";
+
+    #[test]
+    fn counts_from_the_declaration_line() {
+        assert_eq!(offset(CLASS, 3), Some((0, "process".to_string())));
+        assert_eq!(offset(CLASS, 5), Some((2, "process".to_string())));
+        assert_eq!(offset(CLASS, 7), Some((4, "process".to_string())));
+        assert_eq!(offset(CLASS, 8), Some((5, "process".to_string())));
+    }
+
+    #[test]
+    fn blank_lines_inside_a_body_still_count() {
+        assert_eq!(offset(CLASS, 6), Some((3, "process".to_string())));
+    }
+
+    #[test]
+    fn lines_outside_functions_have_no_offset() {
+        assert_eq!(offset(CLASS, 1), None);
+        assert_eq!(offset(CLASS, 2), None);
+        assert_eq!(offset(CLASS, 9), None);
+    }
+
+    #[test]
+    fn line_numbers_out_of_range_have_no_offset() {
+        assert_eq!(offset(CLASS, 0), None);
+        assert_eq!(offset(CLASS, 100), None);
+    }
+
+    #[test]
+    fn closures_belong_to_the_function_around_them() {
+        let content = "<?php\nfunction totals($items) {\n    return array_map(function ($item) {\n        return $item * 2;\n    }, $items);\n}\n";
+        assert_eq!(offset(content, 4), Some((2, "totals".to_string())));
+        let arrow = "<?php\nfunction totals($items) {\n    return array_map(\n        fn ($item) => $item * 2,\n        $items\n    );\n}\n";
+        assert_eq!(offset(arrow, 4), Some((2, "totals".to_string())));
+    }
+
+    #[test]
+    fn nested_functions_count_from_the_innermost() {
+        let content = "<?php\nfunction outer() {\n    function inner() {\n        echo 1;\n    }\n}\n";
+        assert_eq!(offset(content, 4), Some((1, "inner".to_string())));
+    }
+
+    #[test]
+    fn attributes_and_multiline_signatures() {
+        let content = "<?php\nclass A {\n    #[Route('/x')]\n    public function show(\n        int $id,\n    ): void {\n        echo $id;\n    }\n}\n";
+        assert_eq!(offset(content, 7), Some((3, "show".to_string())));
+        assert_eq!(offset(content, 5), Some((1, "show".to_string())));
+        // A match in the attribute itself is above the signature
+        assert_eq!(offset(content, 3), None);
+    }
+
+    #[test]
+    fn indentation_with_tabs_and_crlf_line_endings() {
+        let content = "<?php\r\nclass A {\r\n\tfunction run() {\r\n\t\t$a = 1;\r\n\t}\r\n}\r\n";
+        assert_eq!(offset(content, 4), Some((1, "run".to_string())));
+    }
+
+    #[test]
+    fn matches_after_code_on_the_same_line_use_the_line_start() {
+        let content = "<?php\nfunction a() { return 1; } function b() {\n    return 2;\n}\n";
+        assert_eq!(offset(content, 2), Some((0, "a".to_string())));
+        assert_eq!(offset(content, 3), Some((1, "b".to_string())));
+    }
+}
@@ -0,0 +1,60 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use regex::Regex;
+use tree_sitter::Tree;
+
+use crate::php;
+
+// How many nodes from the innermost one outwards are named
+const MAX_KINDS: usize = 3;
+
+// Set by --debug-matches, with the query when it's a regex
+static QUERY: OnceLock<Option<Regex>> = OnceLock::new();
+
+// The file matches were last annotated in, parsed once for all of its matches
+struct ParsedFile {
+    path: PathBuf,
+    content: String,
+    tree: Option<Tree>,
+}
+
+thread_local! {
+    static FILE: RefCell<Option<ParsedFile>> = const { RefCell::new(None) };
+}
+
+pub fn enable(query: Option<&str>) {
+    let _ = QUERY.set(query.and_then(|query| crate::budget::regex(query).ok()));
+}
+
+// The kind of the innermost node around a match and of the nodes around that, with
+// --debug-matches. The match is the first one of the query on the line, or the line's first
+// token in modes without a query
+pub fn annotation(path: &Path, line_number: usize) -> Option<String> {
+    let query = QUERY.get()?;
+    FILE.with_borrow_mut(|file| {
+        if file.as_ref().is_none_or(|file| file.path != path) {
+            let content = crate::read_file(path).unwrap_or_default();
+            let tree = php::new_parser().ok().and_then(|mut parser| parser.parse(&content, None));
+            *file = Some(ParsedFile { path: path.to_path_buf(), content, tree });
+        }
+        let file = file.as_ref()?;
+        let line_start: usize = file.content.split('\n').take(line_number.checked_sub(1)?).map(|line| line.len() + 1).sum();
+        let line = file.content.get(line_start..)?.split('\n').next()?.trim_end_matches('\r');
+        let (start, end) = query.as_ref().and_then(|query| query.find(line)).map(|found| (found.start(), found.end()))
+            .unwrap_or_else(|| {
+                let start = line.len() - line.trim_start().len();
+                (start, start)
+            });
+        let node = file.tree.as_ref()?.root_node().named_descendant_for_byte_range(line_start + start, line_start + end)?;
+        // A few of the nodes around it, up to the statement, tell `name in variable_name in
+        // encapsed_string` apart from a plain variable
+        let kinds: Vec<&str> = std::iter::successors(Some(node), |node| node.parent())
+            .take_while(|node| !node.kind().ends_with("_statement") && !node.kind().ends_with("_declaration") && node.parent().is_some())
+            .take(MAX_KINDS)
+            .map(|node| node.kind())
+            .collect();
+        Some(if kinds.is_empty() { node.kind().to_string() } else { kinds.join(" in ") })
+    })
+}
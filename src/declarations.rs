@@ -0,0 +1,493 @@
+use std::collections::{HashMap, HashSet};
+
+use tree_sitter::Node;
+
+use crate::php::{self, FileNames};
+
+// A class, interface, trait or enum declared in a file
+#[derive(Debug)]
+pub struct ClassDecl {
+    pub kind: &'static str,
+    pub name: String,
+    pub fqcn: String,
+    pub namespace: String,
+    pub line: usize,
+    pub end_line: usize,
+    pub is_abstract: bool,
+    pub is_readonly: bool,
+    pub extends: Vec<String>,
+    pub implements: Vec<String>,
+    pub methods: Vec<FunctionDecl>,
+    pub properties: Vec<PropertyDecl>,
+    pub constants: Vec<ConstDecl>,
+}
+
+// A method or global function signature
+#[derive(Debug)]
+pub struct FunctionDecl {
+    pub name: String,
+    // Class::method for methods, Namespace\function for functions
+    pub fqn: String,
+    pub line: usize,
+    pub visibility: &'static str,
+    pub is_static: bool,
+    pub is_abstract: bool,
+    pub params: Vec<ParamDecl>,
+    pub return_type: Option<TypeDecl>,
+}
+
+// A property declared in a class body. Constructor promoted properties are only recorded as
+// parameters of the constructor
+#[derive(Debug)]
+pub struct PropertyDecl {
+    pub name: String,
+    pub line: usize,
+    pub type_hint: Option<TypeDecl>,
+    pub visibility: &'static str,
+    pub is_static: bool,
+    pub is_readonly: bool,
+}
+
+// A class constant or enum case
+#[derive(Debug)]
+pub struct ConstDecl {
+    pub kind: &'static str,
+    pub name: String,
+    pub line: usize,
+}
+
+#[derive(Debug)]
+pub struct ParamDecl {
+    pub name: String,
+    pub type_hint: Option<TypeDecl>,
+    pub default_value: Option<String>,
+    pub variadic: bool,
+    pub by_ref: bool,
+    // Modifiers of a constructor promoted property, e.g. `private readonly`
+    pub promotion: Option<String>,
+}
+
+// A declared type, both as written and in a normalized form where class names are fully
+// qualified, `?T` is spelled `T|null` and union members are sorted so types can be compared
+#[derive(Debug, Clone)]
+pub struct TypeDecl {
+    pub text: String,
+    pub normalized: String,
+}
+
+// Everything declared in a single file
+#[derive(Debug, Default)]
+pub struct FileDecls {
+    pub classes: Vec<ClassDecl>,
+    pub functions: Vec<FunctionDecl>,
+}
+
+impl FileDecls {
+    pub fn new(root: Node, content: &str) -> Self {
+        let names = FileNames::new(root, content);
+        let mut decls = FileDecls::default();
+        php::walk(root, &mut |node| match node.kind() {
+            "class_declaration" | "interface_declaration" | "trait_declaration" | "enum_declaration" => {
+                if let Some(class) = class_decl(node, content, &names) {
+                    decls.classes.push(class);
+                }
+            }
+            "function_definition" => {
+                if let Some(function) = function_decl(node, content, &names, None) {
+                    decls.functions.push(function);
+                }
+            }
+            _ => {}
+        });
+        decls
+    }
+}
+
+impl std::fmt::Display for ParamDecl {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let Some(promotion) = &self.promotion {
+            write!(f, "{} ", promotion)?;
+        }
+        if let Some(type_hint) = &self.type_hint {
+            write!(f, "{} ", type_hint.text)?;
+        }
+        if self.by_ref {
+            write!(f, "&")?;
+        }
+        if self.variadic {
+            write!(f, "...")?;
+        }
+        write!(f, "${}", self.name)?;
+        if let Some(default_value) = &self.default_value {
+            write!(f, " = {}", default_value)?;
+        }
+        Ok(())
+    }
+}
+
+impl FunctionDecl {
+    // Signature as it would be written, e.g. `find(int $id, ?string $name = null): ?User`
+    pub fn signature(&self) -> String {
+        let params: Vec<String> = self.params.iter().map(|param| param.to_string()).collect();
+        match &self.return_type {
+            Some(return_type) => format!("{}({}): {}", self.name, params.join(", "), return_type.text),
+            None => format!("{}({})", self.name, params.join(", ")),
+        }
+    }
+
+    // The symbol ID of the method or function, which leaves out parameter names and defaults so
+    // only a change of the types it takes or returns gives it a new one
+    pub fn id(&self) -> String {
+        let params: Vec<String> = self.params.iter()
+            .map(|param| format!("{}{}{}", param.type_hint.as_ref().map_or("_", |type_hint| &type_hint.normalized), if param.by_ref { "&" } else { "" }, if param.variadic { "..." } else { "" }))
+            .collect();
+        let return_type = self.return_type.as_ref().map_or("", |return_type| &return_type.normalized);
+        symbol_id(&self.fqn, &format!("({}):{}", params.join(","), return_type))
+    }
+
+    // Namespace a global function is declared in
+    pub fn namespace(&self) -> &str {
+        match self.fqn.split_once("::") {
+            Some((class, _)) => class.rsplit_once('\\').map(|(namespace, _)| namespace).unwrap_or(""),
+            None => self.fqn.rsplit_once('\\').map(|(namespace, _)| namespace).unwrap_or(""),
+        }
+    }
+}
+
+impl TypeDecl {
+    // Parses a type written on the command line, e.g. `?string`, `A&B` or `(A&B)|null`
+    pub fn parse(text: &str) -> anyhow::Result<TypeDecl> {
+        let content = format!("<?php function phrep_type_query(): {} {{}}", text);
+        let mut parser = php::new_parser()?;
+        let tree = parser.parse(&content, None).ok_or_else(|| anyhow::anyhow!("Could not parse type '{}'", text))?;
+        let mut return_type = None;
+        php::walk(tree.root_node(), &mut |node| {
+            if node.kind() == "function_definition" {
+                return_type = node.child_by_field_name("return_type");
+            }
+        });
+        match return_type {
+            Some(return_type) if !return_type.has_error() && !tree.root_node().has_error() => {
+                Ok(type_decl(return_type, &content, &FileNames::new(tree.root_node(), &content)))
+            }
+            _ => Err(anyhow::anyhow!("'{}' is not a valid PHP type", text)),
+        }
+    }
+
+    // Compares against a type query member by member regardless of order, so `?string` matches
+    // `null|string`. Class names in the query may be short or fully qualified
+    pub fn matches(&self, query: &TypeDecl) -> bool {
+        let members: Vec<&str> = self.normalized.split('|').collect();
+        let query_members: Vec<&str> = query.normalized.split('|').collect();
+        members.len() == query_members.len()
+            && query_members.iter().all(|query_member| members.iter().any(|member| type_member_matches(member, query_member)))
+    }
+
+    pub fn is_nullable(&self) -> bool {
+        self.normalized == "mixed" || self.normalized.split('|').any(|part| part == "null")
+    }
+}
+
+// A stable ID for a symbol that external tools can track across runs however its lines move:
+// the fully qualified name and a hash of its signature, `App\Repo::find#3f2a9c1d`. The hash is
+// FNV-1a, which unlike the standard library's hasher stays the same between Rust releases
+pub fn symbol_id(fqn: &str, signature: &str) -> String {
+    let hash = signature.bytes().fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+    format!("{}#{:08x}", fqn, hash >> 32)
+}
+
+pub fn class_kind(node: Node) -> &'static str {
+    match node.kind() {
+        "interface_declaration" => "interface",
+        "trait_declaration" => "trait",
+        "enum_declaration" => "enum",
+        _ => "class",
+    }
+}
+
+fn class_decl(node: Node, content: &str, names: &FileNames) -> Option<ClassDecl> {
+    let name = php::node_text(node.child_by_field_name("name")?, content).to_string();
+    let namespace = names.namespace_at(node.start_byte()).to_string();
+    let fqcn = if namespace.is_empty() { name.clone() } else { format!("{}\\{}", namespace, name) };
+
+    let mut class = ClassDecl {
+        kind: class_kind(node),
+        name,
+        fqcn,
+        namespace,
+        line: node.start_position().row + 1,
+        end_line: node.end_position().row + 1,
+        is_abstract: false,
+        is_readonly: false,
+        extends: Vec::new(),
+        implements: Vec::new(),
+        methods: Vec::new(),
+        properties: Vec::new(),
+        constants: Vec::new(),
+    };
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        match child.kind() {
+            "abstract_modifier" => class.is_abstract = true,
+            "readonly_modifier" => class.is_readonly = true,
+            "base_clause" => class.extends = clause_names(child, content, names),
+            "class_interface_clause" => class.implements = clause_names(child, content, names),
+            _ => {}
+        }
+    }
+
+    if let Some(body) = node.child_by_field_name("body") {
+        let mut body_cursor = body.walk();
+        for member in body.named_children(&mut body_cursor) {
+            match member.kind() {
+                "method_declaration" => class.methods.extend(function_decl(member, content, names, Some(&class.fqcn))),
+                "property_declaration" => class.properties.extend(property_decls(member, content, names)),
+                "const_declaration" => {
+                    let mut const_cursor = member.walk();
+                    class.constants.extend(member.named_children(&mut const_cursor)
+                        .filter(|element| element.kind() == "const_element")
+                        .filter_map(|element| {
+                            let mut element_cursor = element.walk();
+                            let name = element.named_children(&mut element_cursor).find(|child| child.kind() == "name")?;
+                            Some(ConstDecl { kind: "const", name: php::node_text(name, content).to_string(), line: element.start_position().row + 1 })
+                        }));
+                }
+                "enum_case" => {
+                    if let Some(name) = member.child_by_field_name("name") {
+                        class.constants.push(ConstDecl { kind: "case", name: php::node_text(name, content).to_string(), line: member.start_position().row + 1 });
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Some(class)
+}
+
+fn clause_names(clause: Node, content: &str, names: &FileNames) -> Vec<String> {
+    let mut cursor = clause.walk();
+    clause.named_children(&mut cursor)
+        .filter(|name| name.kind() == "name" || name.kind() == "qualified_name")
+        .filter_map(|name| names.resolve_node(name, content))
+        .collect()
+}
+
+// `class_fqcn` is the declaring class for methods and None for global functions
+pub fn function_decl(node: Node, content: &str, names: &FileNames, class_fqcn: Option<&str>) -> Option<FunctionDecl> {
+    let name = php::node_text(node.child_by_field_name("name")?, content).to_string();
+    let fqn = match class_fqcn {
+        Some(class_fqcn) => format!("{}::{}", class_fqcn, name),
+        None => names.resolve(&name, node.start_byte()),
+    };
+
+    let mut function = FunctionDecl {
+        name,
+        fqn,
+        line: node.start_position().row + 1,
+        visibility: if class_fqcn.is_some() { "public" } else { "" },
+        is_static: false,
+        is_abstract: false,
+        params: Vec::new(),
+        return_type: node.child_by_field_name("return_type").map(|return_type| type_decl(return_type, content, names)),
+    };
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        match child.kind() {
+            "visibility_modifier" => function.visibility = visibility(child, content),
+            "static_modifier" => function.is_static = true,
+            "abstract_modifier" => function.is_abstract = true,
+            _ => {}
+        }
+    }
+
+    if let Some(params) = node.child_by_field_name("parameters") {
+        let mut params_cursor = params.walk();
+        function.params = params.named_children(&mut params_cursor)
+            .filter_map(|param| param_decl(param, content, names))
+            .collect();
+    }
+
+    Some(function)
+}
+
+fn property_decls(node: Node, content: &str, names: &FileNames) -> Vec<PropertyDecl> {
+    let type_hint = node.child_by_field_name("type").map(|type_node| type_decl(type_node, content, names));
+    let mut visibility_name = "public";
+    let mut is_static = false;
+    let mut is_readonly = false;
+    let mut elements = Vec::new();
+
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        match child.kind() {
+            "visibility_modifier" => visibility_name = visibility(child, content),
+            "static_modifier" => is_static = true,
+            "readonly_modifier" => is_readonly = true,
+            "property_element" => elements.push(child),
+            _ => {}
+        }
+    }
+
+    elements.into_iter()
+        .filter_map(|element| {
+            let name = php::node_text(element.child_by_field_name("name")?, content);
+            Some(PropertyDecl {
+                name: name.trim_start_matches('$').to_string(),
+                line: element.start_position().row + 1,
+                type_hint: type_hint.clone(),
+                visibility: visibility_name,
+                is_static,
+                is_readonly,
+            })
+        })
+        .collect()
+}
+
+fn visibility(node: Node, content: &str) -> &'static str {
+    match php::node_text(node, content).to_lowercase().as_str() {
+        "private" => "private",
+        "protected" => "protected",
+        _ => "public",
+    }
+}
+
+fn param_decl(node: Node, content: &str, names: &FileNames) -> Option<ParamDecl> {
+    if !matches!(node.kind(), "simple_parameter" | "variadic_parameter" | "property_promotion_parameter") {
+        return None;
+    }
+    let name = php::node_text(node.child_by_field_name("name")?, content);
+    Some(ParamDecl {
+        name: name.trim_start_matches('&').trim_start_matches('$').to_string(),
+        type_hint: node.child_by_field_name("type").map(|type_node| type_decl(type_node, content, names)),
+        default_value: node.child_by_field_name("default_value").map(|value| php::node_text(value, content).to_string()),
+        variadic: node.kind() == "variadic_parameter",
+        by_ref: node.child_by_field_name("reference_modifier").is_some(),
+        promotion: (node.kind() == "property_promotion_parameter").then(|| {
+            ["visibility", "readonly"].iter()
+                .filter_map(|field| node.child_by_field_name(field))
+                .map(|modifier| php::node_text(modifier, content))
+                .collect::<Vec<_>>()
+                .join(" ")
+        }),
+    })
+}
+
+pub fn type_decl(node: Node, content: &str, names: &FileNames) -> TypeDecl {
+    TypeDecl {
+        text: php::node_text(node, content).to_string(),
+        normalized: normalize_type(node, content, names),
+    }
+}
+
+fn normalize_type(node: Node, content: &str, names: &FileNames) -> String {
+    let mut cursor = node.walk();
+    let parts: Vec<String> = node.named_children(&mut cursor)
+        .map(|part| normalize_type(part, content, names))
+        .collect();
+
+    match node.kind() {
+        "optional_type" => sorted_union(parts.into_iter().chain(std::iter::once("null".to_string())).collect()),
+        "union_type" | "type_list" => sorted_union(parts),
+        "disjunctive_normal_form_type" => sorted_union(parts.into_iter()
+            .map(|part| if part.contains('&') { format!("({})", part) } else { part })
+            .collect()),
+        "intersection_type" => {
+            let mut parts = parts;
+            parts.sort();
+            parts.join("&")
+        }
+        "named_type" => match node.named_child(0) {
+            Some(name) => named_type(name, content, names),
+            None => php::node_text(node, content).to_lowercase(),
+        },
+        "name" | "qualified_name" => named_type(node, content, names),
+        _ => php::node_text(node, content).to_lowercase(),
+    }
+}
+
+fn named_type(node: Node, content: &str, names: &FileNames) -> String {
+    let text = php::node_text(node, content);
+    match text.to_lowercase().as_str() {
+        "self" | "static" | "parent" | "mixed" | "iterable" | "object" | "callable" | "void" | "never" | "null" | "false" | "true" => text.to_lowercase(),
+        _ => names.resolve(text, node.start_byte()),
+    }
+}
+
+// Compares one member of a union, which may itself be a parenthesized intersection
+fn type_member_matches(member: &str, query_member: &str) -> bool {
+    let parts: Vec<&str> = member.trim_matches(|c| c == '(' || c == ')').split('&').collect();
+    let query_parts: Vec<&str> = query_member.trim_matches(|c| c == '(' || c == ')').split('&').collect();
+    parts.len() == query_parts.len()
+        && query_parts.iter().all(|query_part| parts.iter().any(|part| {
+            part.eq_ignore_ascii_case(query_part) || php::class_matches(part, query_part)
+        }))
+}
+
+fn sorted_union(mut parts: Vec<String>) -> String {
+    parts.sort();
+    parts.dedup();
+    parts.join("|")
+}
+
+// Maps each class (lowercased) to the lowercased classes and interfaces it directly extends
+// or implements
+pub fn hierarchy<'a>(classes: impl Iterator<Item = &'a ClassDecl>) -> HashMap<String, Vec<String>> {
+    classes
+        .map(|class| {
+            let parents = class.extends.iter().chain(class.implements.iter()).map(|name| name.to_lowercase()).collect();
+            (class.fqcn.to_lowercase(), parents)
+        })
+        .collect()
+}
+
+// Whether `class` extends or implements `target`, directly or further up the hierarchy
+pub fn inherits_from(class: &str, target: &str, parents: &HashMap<String, Vec<String>>) -> bool {
+    ancestors_include(class, target, parents, &mut HashSet::new())
+}
+
+fn ancestors_include(class: &str, target: &str, parents: &HashMap<String, Vec<String>>, seen: &mut HashSet<String>) -> bool {
+    let class = class.to_lowercase();
+    if !seen.insert(class.clone()) {
+        return false;
+    }
+    parents.get(&class).is_some_and(|direct| {
+        direct.iter().any(|parent| parent.eq_ignore_ascii_case(target) || ancestors_include(parent, target, parents, seen))
+    })
+}
+
+// Finds the one class a short or fully qualified name refers to among the scanned files,
+// failing when there is none or the short name is ambiguous
+pub fn find_class<'a>(files: &'a [(std::path::PathBuf, FileDecls)], name: &str) -> anyhow::Result<(&'a std::path::PathBuf, &'a ClassDecl)> {
+    let candidates: Vec<_> = files.iter()
+        .flat_map(|(path, decls)| decls.classes.iter().map(move |class| (path, class)))
+        .filter(|(_, class)| php::class_matches(&class.fqcn, name))
+        .collect();
+    match candidates.as_slice() {
+        [] => {
+            eprintln!("Error: Class '{}' not found.", name);
+            Err(anyhow::anyhow!("Class '{}' not found", name))
+        }
+        [found] => Ok(*found),
+        _ => {
+            let names: Vec<&str> = candidates.iter().map(|(_, class)| class.fqcn.as_str()).collect();
+            eprintln!("Error: '{}' matches several classes ({}), use the fully qualified name.", name, names.join(", "));
+            Err(anyhow::anyhow!("'{}' matches several classes", name))
+        }
+    }
+}
+
+// Parses every PHP file under the search root and collects its declarations
+pub fn scan(dir: &str, file: &str, exclude_dirs: &str) -> anyhow::Result<Vec<(std::path::PathBuf, FileDecls)>> {
+    let mut parser = php::new_parser()?;
+    let mut files = Vec::new();
+    for path in crate::php_files(dir, file, exclude_dirs) {
+        let path = path.as_path();
+        let Some(content) = crate::read_file(path) else { continue };
+        let Some(tree) = crate::parse_content(&mut parser, &content, path) else { continue };
+        crate::guard::file(path, || files.push((path.to_path_buf(), FileDecls::new(tree.root_node(), &content))));
+    }
+    Ok(files)
+}
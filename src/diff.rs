@@ -0,0 +1,166 @@
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::Result;
+use colored::*;
+
+use crate::git;
+
+// One line of a line based diff
+#[derive(Debug, PartialEq)]
+pub enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+// Longest common subsequence diff of two texts by line. The common prefix and suffix are
+// trimmed first so the quadratic part only covers the changed region
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    // lengths[i][j] is the LCS length of old_middle[i..] and new_middle[j..]
+    let mut lengths = vec![vec![0u32; new_middle.len() + 1]; old_middle.len() + 1];
+    for i in (0..old_middle.len()).rev() {
+        for j in (0..new_middle.len()).rev() {
+            lengths[i][j] = if old_middle[i] == new_middle[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines: Vec<DiffLine> = old[..prefix].iter().map(|line| DiffLine::Same(line)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < old_middle.len() || j < new_middle.len() {
+        if i < old_middle.len() && j < new_middle.len() && old_middle[i] == new_middle[j] {
+            lines.push(DiffLine::Same(old_middle[i]));
+            i += 1;
+            j += 1;
+        } else if i < old_middle.len() && (j == new_middle.len() || lengths[i + 1][j] >= lengths[i][j + 1]) {
+            lines.push(DiffLine::Removed(old_middle[i]));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(new_middle[j]));
+            j += 1;
+        }
+    }
+    lines.extend(old[old.len() - suffix..].iter().map(|line| DiffLine::Same(line)));
+    lines
+}
+
+// Prints a diff with -/+ markers in red and green
+pub fn print_diff(lines: &[DiffLine]) {
+    for line in lines {
+        match line {
+            DiffLine::Same(text) => println!(" {}", text),
+            DiffLine::Removed(text) => println!("{}", format!("-{}", text).red()),
+            DiffLine::Added(text) => println!("{}", format!("+{}", text).green()),
+        }
+    }
+}
+
+// Prints only the changed parts of a diff with `context` unchanged lines around each, under
+// `@@ -old +new @@` headers giving the line each hunk starts on
+pub fn print_hunks(lines: &[DiffLine], context: usize) {
+    let changed: Vec<usize> = lines.iter().enumerate()
+        .filter(|(_, line)| !matches!(line, DiffLine::Same(_)))
+        .map(|(i, _)| i)
+        .collect();
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for i in changed {
+        let (start, end) = (i.saturating_sub(context), (i + context + 1).min(lines.len()));
+        match hunks.last_mut() {
+            Some(hunk) if start <= hunk.1 => hunk.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    for (start, end) in hunks {
+        let old_line = 1 + lines[..start].iter().filter(|line| !matches!(line, DiffLine::Added(_))).count();
+        let new_line = 1 + lines[..start].iter().filter(|line| !matches!(line, DiffLine::Removed(_))).count();
+        println!("{}", format!("@@ -{} +{} @@", old_line, new_line).cyan());
+        print_diff(&lines[start..end]);
+    }
+}
+
+// Line numbers each file gained in a patch, used by --diff-filter to only report new code
+pub struct AddedLines {
+    files: Vec<(PathBuf, HashSet<usize>)>,
+}
+
+impl AddedLines {
+    // Reads the added lines from a patch file, or from `git diff <range>` run in `dir`
+    pub fn load(source: &str, dir: &str) -> Result<Self> {
+        if Path::new(source).is_file() {
+            return Ok(Self::parse(&std::fs::read_to_string(source)?, Path::new("")));
+        }
+        let patch = git::git(dir, &["diff", "--unified=0", "--no-color", "--no-ext-diff", "--relative", source])?;
+        Ok(Self::parse(&patch, Path::new(dir)))
+    }
+
+    // Parses a unified diff, resolving the file names it mentions against `base`
+    fn parse(patch: &str, base: &Path) -> Self {
+        let mut files: Vec<(PathBuf, HashSet<usize>)> = Vec::new();
+        let mut current: Option<usize> = None;
+        let mut line = 0;
+        for text in patch.lines() {
+            if let Some(name) = text.strip_prefix("+++ ") {
+                // Timestamps follow a tab in plain `diff -u` output
+                let name = name.split('\t').next().unwrap_or(name).trim();
+                current = (name != "/dev/null").then(|| {
+                    let name = name.strip_prefix("b/").unwrap_or(name);
+                    files.push((normalize(&base.join(name)), HashSet::new()));
+                    files.len() - 1
+                });
+            } else if let Some(hunk) = text.strip_prefix("@@ ") {
+                // @@ -old_start,old_count +new_start,new_count @@
+                line = hunk.split_whitespace()
+                    .find_map(|range| range.strip_prefix('+'))
+                    .and_then(|range| range.split(',').next())
+                    .and_then(|start| start.parse().ok())
+                    .unwrap_or(0);
+            } else if let Some(index) = current {
+                match text.chars().next() {
+                    Some('+') => {
+                        files[index].1.insert(line);
+                        line += 1;
+                    }
+                    Some(' ') => line += 1,
+                    _ => {}
+                }
+            }
+        }
+        AddedLines { files }
+    }
+
+    // Checks whether a line was added, matching file names by their trailing path components
+    // since a patch file may be relative to another directory than the search root
+    pub fn contains(&self, path: &Path, line: usize) -> bool {
+        let path = normalize(path);
+        self.files.iter().any(|(file, lines)| lines.contains(&line) && (path.ends_with(file) || file.ends_with(&path)))
+    }
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    path.components().filter(|component| *component != Component::CurDir).collect()
+}
+
+// Added lines loaded for --diff-filter
+static ADDED_LINES: OnceLock<AddedLines> = OnceLock::new();
+
+pub fn set_added_lines(added_lines: AddedLines) {
+    let _ = ADDED_LINES.set(added_lines);
+}
+
+pub fn added_lines() -> Option<&'static AddedLines> {
+    ADDED_LINES.get()
+}
@@ -0,0 +1,277 @@
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::Result;
+use colored::*;
+use tree_sitter::Node;
+
+use crate::config::Protected;
+use crate::diff;
+use crate::journal::{Journal, JournalEntry};
+
+// A place a refactoring found but couldn't safely change, listed for the user to check
+pub struct Unchanged {
+    pub path: PathBuf,
+    pub line: usize,
+    pub caller: Option<String>,
+    pub line_styled: String,
+}
+
+impl Unchanged {
+    pub fn at(path: &Path, node: Node, content: &str, lines: &[&str]) -> Self {
+        Unchanged {
+            path: path.to_path_buf(),
+            line: node.start_position().row + 1,
+            caller: crate::php::enclosing_function(node, content).map(str::to_string),
+            line_styled: crate::highlight_node(lines, node),
+        }
+    }
+}
+
+// Lists the places left unchanged under a heading saying why
+pub fn print_unchanged(heading: &str, unchanged: &[Unchanged]) {
+    if unchanged.is_empty() {
+        return;
+    }
+    crate::report::summary(heading.bold());
+    for site in unchanged {
+        crate::print_match(&site.path, site.line, site.caller.as_deref(), &site.line_styled);
+    }
+}
+
+// Replaces the bytes start..end of a file
+pub struct Edit {
+    pub start: usize,
+    pub end: usize,
+    pub replacement: String,
+}
+
+// The edits a refactoring makes to one file, and where to move it if it moves
+pub struct FileEdits {
+    pub path: PathBuf,
+    pub original: String,
+    pub edits: Vec<Edit>,
+    pub move_to: Option<PathBuf>,
+}
+
+impl FileEdits {
+    pub fn new(path: &Path, original: &str) -> Self {
+        FileEdits { path: path.to_path_buf(), original: original.to_string(), edits: Vec::new(), move_to: None }
+    }
+
+    pub fn replace(&mut self, node: Node, replacement: &str) {
+        self.edits.push(Edit { start: node.start_byte(), end: node.end_byte(), replacement: replacement.to_string() });
+    }
+
+    pub fn insert(&mut self, byte: usize, text: &str) {
+        self.edits.push(Edit { start: byte, end: byte, replacement: text.to_string() });
+    }
+
+    // The file with the edits applied, a range edited twice only gets the first edit
+    pub fn edited(&self) -> String {
+        self.edited_with(&self.edits.iter().collect::<Vec<_>>())
+    }
+
+    // The file with only some of its edits applied
+    fn edited_with(&self, edits: &[&Edit]) -> String {
+        let mut edits = edits.to_vec();
+        edits.sort_by_key(|edit| edit.start);
+        let mut edited = String::with_capacity(self.original.len());
+        let mut copied = 0;
+        for edit in edits {
+            if edit.start < copied {
+                continue;
+            }
+            edited.push_str(&self.original[copied..edit.start]);
+            edited.push_str(&edit.replacement);
+            copied = edit.end;
+        }
+        edited.push_str(&self.original[copied..]);
+        edited
+    }
+
+    // The edits in the order of the file, grouped into the hunks their diff shows them in
+    fn hunks(&self) -> Vec<Vec<&Edit>> {
+        let line_of = |byte: usize| self.original[..byte].matches('\n').count();
+        let mut edits: Vec<&Edit> = self.edits.iter().collect();
+        edits.sort_by_key(|edit| edit.start);
+        let mut hunks: Vec<(usize, Vec<&Edit>)> = Vec::new();
+        for edit in edits {
+            let (first, last) = (line_of(edit.start), line_of(edit.end));
+            match hunks.last_mut() {
+                Some((end, hunk)) if first <= *end + 2 * CONTEXT => {
+                    *end = last.max(*end);
+                    hunk.push(edit);
+                }
+                _ => hunks.push((last, vec![edit])),
+            }
+        }
+        hunks.into_iter().map(|(_, hunk)| hunk).collect()
+    }
+}
+
+// Set by --force, so files covered by `protected_paths` are written too
+static FORCE: OnceLock<()> = OnceLock::new();
+
+pub fn set_force(force: bool) {
+    if force {
+        let _ = FORCE.set(());
+    }
+}
+
+// Unchanged lines shown around each change
+const CONTEXT: usize = 3;
+
+// How a refactoring applies its edits: only printing them, writing them all, or asking about each
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mode {
+    Preview,
+    Write,
+    Confirm,
+}
+
+impl Mode {
+    pub fn new(write: bool, confirm: bool) -> Self {
+        match (write, confirm) {
+            (_, true) => Mode::Confirm,
+            (true, false) => Mode::Write,
+            (false, false) => Mode::Preview,
+        }
+    }
+
+    // The verb for the summary, `done` when the changes are written and `planned` in a preview
+    pub fn action<'a>(self, done: &'a str, planned: &'a str) -> &'a str {
+        if self == Mode::Preview { planned } else { done }
+    }
+}
+
+// What applying the edits of a refactoring did
+pub struct Applied {
+    pub files: usize,
+    // Changes declined at the --confirm prompt
+    pub skipped: usize,
+}
+
+// The answer to a --confirm prompt that carries over to the changes after it
+#[derive(PartialEq)]
+enum Answer {
+    Ask,
+    All,
+    Quit,
+}
+
+// Prints the diff of every edited or moved file and, with `Mode::Write`, saves the edits and moves
+// the files. With `Mode::Confirm` each hunk and move is shown on its own and only applied if the
+// user accepts it, like `git add -p`. Written files are recorded in an undo journal below `root`.
+// Nothing is written when a file is covered by `protected_paths` in a phrep.toml, unless --force
+pub fn apply(files: &[FileEdits], mode: Mode, root: &str) -> Result<Applied> {
+    if mode == Mode::Confirm && !std::io::stdin().is_terminal() {
+        eprintln!("Error: --confirm needs an interactive terminal to ask on.");
+        return Err(anyhow::anyhow!("--confirm needs an interactive terminal to ask on"));
+    }
+
+    let changed: Vec<&Path> = files.iter()
+        .filter(|file| !file.edits.is_empty() || file.move_to.is_some())
+        .flat_map(|file| std::iter::once(file.path.as_path()).chain(file.move_to.as_deref()))
+        .collect();
+    let protected = Protected::load(Path::new(root), &changed);
+    let blocked: Vec<(&Path, &str)> = changed.iter().filter_map(|path| protected.pattern(path).map(|pattern| (*path, pattern))).collect();
+    for (path, pattern) in &blocked {
+        eprintln!("Warning: '{}' is protected by '{}' in {}", path.display(), pattern, crate::config::CONFIG_FILE);
+    }
+    if !blocked.is_empty() && mode != Mode::Preview && FORCE.get().is_none() {
+        eprintln!("Error: {} protected file{} would change, run with --force to change them anyway.", blocked.len(), if blocked.len() == 1 { "" } else { "s" });
+        return Err(anyhow::anyhow!("{} protected file{} would change, run with --force to change them anyway", blocked.len(), if blocked.len() == 1 { "" } else { "s" }));
+    }
+
+    let mut journal = if mode == Mode::Preview { None } else { Some(Journal::start(root)?) };
+    let mut applied = Applied { files: 0, skipped: 0 };
+    let mut answer = Answer::Ask;
+    for file in files.iter().filter(|file| !file.edits.is_empty() || file.move_to.is_some()) {
+        let target = file.move_to.as_deref().unwrap_or(&file.path);
+        println!("{}", format!("--- {}", crate::format_filename(&file.path)).bold());
+        println!("{}", format!("+++ {}", crate::format_filename(target)).bold());
+        if mode != Mode::Confirm {
+            let edited = file.edited();
+            diff::print_hunks(&diff::diff_lines(&file.original, &edited), CONTEXT);
+            if let Some(journal) = &mut journal {
+                save(file, &edited, file.move_to.as_deref(), journal)?;
+            }
+            applied.files += 1;
+            continue;
+        }
+
+        let mut accepted = Vec::new();
+        for hunk in file.hunks() {
+            diff::print_hunks(&diff::diff_lines(&file.original, &file.edited_with(&hunk)), CONTEXT);
+            if confirm("Apply this change", &mut answer)? {
+                accepted.extend(hunk);
+            } else {
+                applied.skipped += hunk.len();
+            }
+        }
+        let mut move_to = None;
+        if let Some(target) = &file.move_to {
+            println!("Move to {}", crate::format_filename(target));
+            if confirm("Move the file", &mut answer)? {
+                move_to = Some(target.as_path());
+            } else {
+                applied.skipped += 1;
+            }
+        }
+        if let Some(journal) = &mut journal
+            && (!accepted.is_empty() || move_to.is_some()) {
+            save(file, &file.edited_with(&accepted), move_to, journal)?;
+            applied.files += 1;
+        }
+    }
+    if let Some(journal) = journal {
+        journal.finish();
+    }
+    Ok(applied)
+}
+
+// Asks whether to apply a change unless an earlier a or q answered it already
+fn confirm(question: &str, answer: &mut Answer) -> Result<bool> {
+    loop {
+        match answer {
+            Answer::All => return Ok(true),
+            Answer::Quit => return Ok(false),
+            Answer::Ask => {}
+        }
+        print!("{} [y,n,a,q,?]? ", question.blue().bold());
+        std::io::stdout().flush()?;
+        let mut line = String::new();
+        if std::io::stdin().read_line(&mut line)? == 0 {
+            *answer = Answer::Quit;
+            continue;
+        }
+        match line.trim() {
+            "y" => return Ok(true),
+            "n" => return Ok(false),
+            "a" => *answer = Answer::All,
+            "q" => *answer = Answer::Quit,
+            _ => println!("{}", "y - apply this change\nn - skip this change\na - apply this and all later changes\nq - skip this and all later changes".red()),
+        }
+    }
+}
+
+fn save(file: &FileEdits, edited: &str, move_to: Option<&Path>, journal: &mut Journal) -> Result<()> {
+    let target = move_to.unwrap_or(&file.path);
+    journal.record(&JournalEntry { original_path: file.path.clone(), path: target.to_path_buf(), original: file.original.clone(), written: edited.to_string() })?;
+    let saved = std::fs::write(&file.path, edited).and_then(|_| match move_to {
+        Some(target) => {
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::rename(&file.path, target)
+        }
+        None => Ok(()),
+    });
+    if let Err(e) = saved {
+        eprintln!("Error: Could not write '{}': {}", target.display(), e);
+        return Err(anyhow::anyhow!("Could not write '{}': {}", target.display(), e));
+    }
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use std::collections::VecDeque;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::Result;
+
+// The placeholders an --exec command can use: `{}` is the file, like find -exec
+const PLACEHOLDERS: [&str; 5] = ["{}", "{file}", "{line}", "{symbol}", "{text}"];
+
+// The --exec command and the commands it started that may still be running
+struct Exec {
+    template: String,
+    jobs: usize,
+    running: VecDeque<(String, Child)>,
+    ran: usize,
+    failed: usize,
+}
+
+static EXEC: OnceLock<Mutex<Exec>> = OnceLock::new();
+
+// Sets the command to run through the shell for every match, with up to `jobs` running at once
+pub fn start(template: &str, jobs: usize) {
+    if !PLACEHOLDERS.iter().any(|placeholder| template.contains(placeholder)) {
+        eprintln!("Warning: --exec '{}' has none of the placeholders {}, so it runs the same command for every match", template, PLACEHOLDERS.join(" "));
+    }
+    let _ = EXEC.set(Mutex::new(Exec { template: template.to_string(), jobs, running: VecDeque::new(), ran: 0, failed: 0 }));
+}
+
+// Runs the --exec command for one match, once fewer than --exec-jobs commands are running.
+// Without --exec nothing happens
+pub fn run(path: &Path, line: usize, symbol: Option<&str>, text: &str) {
+    let Some(exec) = EXEC.get() else { return };
+    let Ok(mut exec) = exec.lock() else { return };
+
+    let text = crate::report::strip_colors(text.trim());
+    let command = substitute(&exec.template, &path.display().to_string(), line, symbol.unwrap_or_default(), text.lines().next().unwrap_or_default());
+    while exec.running.len() >= exec.jobs {
+        exec.wait_for_one();
+    }
+    exec.ran += 1;
+    match Command::new("sh").args(["-c", &command]).stdin(Stdio::null()).spawn() {
+        Ok(child) => exec.running.push_back((command, child)),
+        Err(e) => {
+            eprintln!("Warning: Could not run --exec command '{}': {}", command, e);
+            exec.failed += 1;
+        }
+    }
+}
+
+// Waits for the commands still running. Fails if any command couldn't run or exited non-zero
+pub fn finish() -> Result<()> {
+    let Some(exec) = EXEC.get() else { return Ok(()) };
+    let Ok(mut exec) = exec.lock() else { return Ok(()) };
+    while !exec.running.is_empty() {
+        exec.wait_for_one();
+    }
+    match exec.failed {
+        0 => Ok(()),
+        failed => Err(anyhow::anyhow!("{} of {} --exec commands failed", failed, exec.ran)),
+    }
+}
+
+impl Exec {
+    // Waits until one of the running commands exits, the first to have finished or else the oldest
+    fn wait_for_one(&mut self) {
+        let finished = self.running.iter_mut().position(|(_, child)| matches!(child.try_wait(), Ok(Some(_))));
+        let Some((command, mut child)) = self.running.remove(finished.unwrap_or(0)) else { return };
+        match child.wait() {
+            Ok(status) if status.success() => {}
+            Ok(status) => {
+                eprintln!("Warning: --exec command '{}' exited with {}", command, status);
+                self.failed += 1;
+            }
+            Err(e) => {
+                eprintln!("Warning: Could not wait for --exec command '{}': {}", command, e);
+                self.failed += 1;
+            }
+        }
+    }
+}
+
+// The command for one match, with each placeholder replaced by its value quoted for the shell.
+// Replaced values aren't searched for placeholders again
+fn substitute(template: &str, file: &str, line: usize, symbol: &str, text: &str) -> String {
+    let mut command = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        command.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(placeholder) = PLACEHOLDERS.iter().find(|placeholder| rest.starts_with(**placeholder)) else {
+            command.push('{');
+            rest = &rest[1..];
+            continue;
+        };
+        let value = match *placeholder {
+            "{line}" => line.to_string(),
+            "{symbol}" => quoted(symbol),
+            "{text}" => quoted(text),
+            _ => quoted(file),
+        };
+        command.push_str(&value);
+        rest = &rest[placeholder.len()..];
+    }
+    command.push_str(rest);
+    command
+}
+
+// A value in single quotes, which the shell takes literally
+fn quoted(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
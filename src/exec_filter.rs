@@ -0,0 +1,109 @@
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::Result;
+
+use crate::json::{self, Value};
+
+// The --exec-filter process. Every candidate match is written to its stdin as one JSON line,
+// {"path", "line", "symbol", "text"}, and it answers each with one line
+// {"accept": true|false, "annotation": "..."} ("annotation" is optional) before the match is
+// printed or dropped
+struct ExecFilter {
+    command: String,
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+}
+
+// What the filter decided about a match
+pub enum Verdict {
+    Accept(Option<String>),
+    Reject,
+}
+
+static FILTER: OnceLock<Mutex<Option<ExecFilter>>> = OnceLock::new();
+static FAILED: OnceLock<String> = OnceLock::new();
+
+// Starts the filter command through the shell
+pub fn start(command: &str) -> Result<()> {
+    let mut child = Command::new("sh")
+        .args(["-c", command])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let stdin = child.stdin.take();
+    let Some(stdout) = child.stdout.take() else {
+        return Err(anyhow::anyhow!("Could not read the output of '{}'", command));
+    };
+    let _ = FILTER.set(Mutex::new(Some(ExecFilter { command: command.to_string(), child, stdin, stdout: BufReader::new(stdout) })));
+    Ok(())
+}
+
+// Asks the filter about one match. Without --exec-filter, or once it has failed, every match
+// is accepted as is
+pub fn check(path: &Path, line: usize, symbol: Option<&str>, text: &str) -> Verdict {
+    let Some(filter) = FILTER.get() else {
+        return Verdict::Accept(None);
+    };
+    let Ok(mut filter) = filter.lock() else {
+        return Verdict::Accept(None);
+    };
+    let Some(running) = filter.as_mut() else {
+        return Verdict::Accept(None);
+    };
+
+    match running.ask(path, line, symbol, text) {
+        Ok(verdict) => verdict,
+        Err(e) => {
+            eprintln!("Warning: --exec-filter '{}' failed, reporting the remaining matches unfiltered: {}", running.command, e);
+            let _ = FAILED.set(e.to_string());
+            *filter = None;
+            Verdict::Accept(None)
+        }
+    }
+}
+
+// Closes the filter's input and waits for it to exit. Fails if the filter broke down during the run
+pub fn finish() -> Result<()> {
+    if let Some(filter) = FILTER.get()
+        && let Ok(mut filter) = filter.lock()
+        && let Some(mut running) = filter.take() {
+        running.stdin = None;
+        let _ = running.child.wait();
+    }
+    match FAILED.get() {
+        Some(e) => Err(anyhow::anyhow!("--exec-filter failed: {}", e)),
+        None => Ok(()),
+    }
+}
+
+impl ExecFilter {
+    fn ask(&mut self, path: &Path, line: usize, symbol: Option<&str>, text: &str) -> Result<Verdict> {
+        let candidate = Value::Object(vec![
+            ("path".to_string(), Value::String(path.display().to_string())),
+            ("line".to_string(), Value::Number(line as f64)),
+            ("symbol".to_string(), symbol.map_or(Value::Null, |symbol| Value::String(symbol.to_string()))),
+            // The filter gets plain text, without the highlighting meant for the terminal
+            ("text".to_string(), Value::String(crate::report::strip_colors(text.trim()))),
+        ]);
+        let Some(stdin) = self.stdin.as_mut() else {
+            return Err(anyhow::anyhow!("The filter's input is closed"));
+        };
+        writeln!(stdin, "{}", candidate)?;
+        stdin.flush()?;
+
+        let mut answer = String::new();
+        if self.stdout.read_line(&mut answer)? == 0 {
+            return Err(anyhow::anyhow!("The filter exited without answering"));
+        }
+        let answer = json::parse(&answer)?;
+        match answer.get("accept") {
+            Some(Value::Bool(true)) => Ok(Verdict::Accept(answer.get("annotation").and_then(Value::as_str).map(str::to_string))),
+            Some(Value::Bool(false)) => Ok(Verdict::Reject),
+            _ => Err(anyhow::anyhow!("Expected {{\"accept\": true|false}} but got {}", answer)),
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::Result;
+use tree_sitter::Node;
+
+use crate::php;
+
+// Set by --extract-to: where --method-search writes every matched method as a file of its own
+static EXTRACT_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+// File names written so far, so methods of the same name in different files don't overwrite
+// each other
+static WRITTEN: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+pub fn set_dir(dir: &str) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let _ = EXTRACT_DIR.set(PathBuf::from(dir));
+    Ok(())
+}
+
+// Writes a matched method or function to the --extract-to directory along with what's needed to
+// read it on its own: the namespace, the `use` statements it refers to and, for a method, a stub
+// of the class header around it
+pub fn method(path: &Path, content: &str, method: Node) {
+    let Some(dir) = EXTRACT_DIR.get() else { return };
+    let Some(name) = method.child_by_field_name("name").map(|name| php::node_text(name, content)) else { return };
+    let class = php::enclosing_class(method).filter(|_| method.kind() == "method_declaration");
+    let class_name = class.and_then(|class| class.child_by_field_name("name")).map(|name| php::node_text(name, content));
+
+    // The method from the start of its line, to keep its indentation, and with its doc comment
+    let start = method.prev_named_sibling()
+        .filter(|comment| comment.kind() == "comment" && comment.end_position().row + 1 == method.start_position().row)
+        .unwrap_or(method)
+        .start_byte();
+    let start = content[..start].rfind('\n').map_or(0, |newline| newline + 1);
+    let code = &content[start..method.end_byte()];
+    let header = class.and_then(|class| class.child_by_field_name("body").map(|body| content[class.start_byte()..body.start_byte()].trim()));
+
+    let mut packet = format!("<?php\n// Extracted by phrep from {}:{}\n", crate::format_filename(path), method.start_position().row + 1);
+    let mut root = method;
+    while let Some(parent) = root.parent() {
+        root = parent;
+    }
+    let (namespace, uses) = imports(content, root, method.start_byte());
+    if let Some(namespace) = namespace {
+        packet.push_str(&format!("\nnamespace {};\n", namespace));
+    }
+    let referenced: Vec<&str> = uses.iter()
+        .filter(|(aliases, _)| aliases.iter().any(|alias| mentions(header.unwrap_or(""), alias) || mentions(code, alias)))
+        .map(|(_, statement)| *statement)
+        .collect();
+    if !referenced.is_empty() {
+        packet.push('\n');
+        for statement in referenced {
+            packet.push_str(statement);
+            packet.push('\n');
+        }
+    }
+    match header {
+        Some(header) => packet.push_str(&format!("\n{}\n{{\n{}\n}}\n", header, code)),
+        None => packet.push_str(&format!("\n{}\n", code)),
+    }
+
+    let stem = match class_name {
+        Some(class_name) => format!("{}.{}", class_name, name),
+        None => name.to_string(),
+    };
+    let Ok(mut written) = WRITTEN.lock() else { return };
+    let mut target = dir.join(format!("{}.php", stem));
+    let mut n = 2;
+    while written.contains(&target) {
+        target = dir.join(format!("{}-{}.php", stem, n));
+        n += 1;
+    }
+    match std::fs::write(&target, packet) {
+        Ok(()) => written.push(target),
+        Err(e) => eprintln!("Warning: Could not write '{}': {}", target.display(), e),
+    }
+}
+
+// Says how many methods were written, if --extract-to is on
+pub fn finish() {
+    let Some(dir) = EXTRACT_DIR.get() else { return };
+    let count = WRITTEN.lock().map(|written| written.len()).unwrap_or(0);
+    crate::report::summary(format!("Extracted {} method{} to {}", count, if count == 1 { "" } else { "s" }, dir.display()));
+}
+
+// The namespace in effect at `byte` and the `use` statements declared in it before `byte`,
+// each with the names it imports
+fn imports<'a>(content: &'a str, root: Node, byte: usize) -> (Option<&'a str>, Vec<(Vec<&'a str>, &'a str)>) {
+    let mut namespace = None;
+    let mut scope_start = 0;
+    let mut uses = Vec::new();
+    php::walk(root, &mut |node| {
+        if node.start_byte() > byte {
+            return;
+        }
+        match node.kind() {
+            "namespace_definition" => {
+                namespace = node.child_by_field_name("name").map(|name| php::node_text(name, content));
+                scope_start = node.start_byte();
+            }
+            "namespace_use_declaration" => uses.push(node),
+            _ => {}
+        }
+    });
+
+    let uses = uses.into_iter()
+        .filter(|node| node.start_byte() >= scope_start)
+        .map(|node| {
+            let mut aliases = Vec::new();
+            php::walk(node, &mut |clause| {
+                if clause.kind() != "namespace_use_clause" {
+                    return;
+                }
+                let mut cursor = clause.walk();
+                let alias = clause.child_by_field_name("alias")
+                    .or_else(|| clause.named_children(&mut cursor).find(|child| child.kind() == "name" || child.kind() == "qualified_name"))
+                    .map(|name| php::short_name(php::node_text(name, content)));
+                aliases.extend(alias);
+            });
+            (aliases, php::node_text(node, content))
+        })
+        .collect();
+    (namespace, uses)
+}
+
+// Whether `code` uses `name` as a whole word, ignoring case like PHP does for class names
+fn mentions(code: &str, name: &str) -> bool {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let code = code.to_lowercase();
+    let name = name.to_lowercase();
+    code.match_indices(&name).any(|(i, _)| {
+        !code[..i].chars().next_back().is_some_and(is_word) && !code[i + name.len()..].chars().next().is_some_and(is_word)
+    })
+}
+
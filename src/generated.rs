@@ -0,0 +1,66 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+// The markers that flag generated code, unless --include-generated turned the check off
+static MARKERS: OnceLock<Vec<String>> = OnceLock::new();
+
+// The spans of generated lines in a file, from the first to the last line of each
+struct GeneratedSpans {
+    path: PathBuf,
+    spans: Vec<(usize, usize)>,
+}
+
+thread_local! {
+    // The file matches were last checked in
+    static FILE: RefCell<Option<GeneratedSpans>> = const { RefCell::new(None) };
+}
+
+pub fn set_markers(markers: Vec<String>) {
+    let _ = MARKERS.set(markers);
+}
+
+// Whether a line is generated code, so matches on it are left out
+pub fn is_generated(path: &Path, line_number: usize) -> bool {
+    let Some(markers) = MARKERS.get() else { return false };
+    FILE.with_borrow_mut(|file| {
+        if file.as_ref().is_none_or(|file| file.path != path) {
+            let spans = crate::read_file(path).map(|content| generated_spans(&content, markers)).unwrap_or_default();
+            *file = Some(GeneratedSpans { path: path.to_path_buf(), spans });
+        }
+        file.as_ref().is_some_and(|file| file.spans.iter().any(|(start, end)| (*start..=*end).contains(&line_number)))
+    })
+}
+
+// The spans of generated lines in a file. A marker in a comment above the first `{` marks the
+// whole file, as generated proxies and compiled containers have it in their header. Further down
+// a region runs from a comment with the marker followed by `start` or `begin` to one with the
+// marker followed by `end`, or to the end of the file
+fn generated_spans(content: &str, markers: &[String]) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut region_start = None;
+    let mut in_header = true;
+    for (i, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let is_comment = ["//", "/*", "*"].iter().any(|start| trimmed.starts_with(start)) || (trimmed.starts_with('#') && !trimmed.starts_with("#["));
+        if !is_comment {
+            in_header = in_header && !line.contains('{');
+            continue;
+        }
+        let Some(after) = markers.iter().find_map(|marker| line.find(marker.as_str()).map(|start| &line[start + marker.len()..])) else { continue };
+        let after = after.trim_start_matches(|c: char| c.is_whitespace() || c == ':' || c == '-').to_ascii_lowercase();
+        if after.starts_with("start") || after.starts_with("begin") {
+            region_start.get_or_insert(i + 1);
+        } else if after.starts_with("end") {
+            if let Some(start) = region_start.take() {
+                spans.push((start, i + 1));
+            }
+        } else if in_header {
+            return vec![(1, usize::MAX)];
+        }
+    }
+    if let Some(start) = region_start {
+        spans.push((start, usize::MAX));
+    }
+    spans
+}
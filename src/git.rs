@@ -0,0 +1,126 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use anyhow::Result;
+
+// Revision set with --rev that file listing and reading use instead of the working tree
+static REVISION: OnceLock<String> = OnceLock::new();
+
+pub fn set_revision(rev: &str) {
+    let _ = REVISION.set(rev.to_string());
+}
+
+pub fn revision() -> Option<&'static str> {
+    REVISION.get().map(String::as_str)
+}
+
+// Runs git inside `dir` and returns its stdout
+pub fn git(dir: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| anyhow::anyhow!("Could not run git: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow::anyhow!("git {} failed: {}", args.join(" "), stderr.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// Contents of a file at a revision, `path` is relative to `dir`
+pub fn show_file(dir: &str, rev: &str, path: &str) -> Result<String> {
+    git(dir, &["show", &format!("{}:./{}", rev, path)])
+}
+
+// Contents of a file at a revision, given the path it has in the working tree
+pub fn show_path(path: &Path, rev: &str) -> Result<String> {
+    let parent = path.parent().and_then(Path::to_str).filter(|parent| !parent.is_empty()).unwrap_or(".");
+    let file_name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    show_file(parent, rev, &file_name)
+}
+
+// Files below `dir` at a revision, joined onto `dir` so they read like working tree paths
+pub fn files_at(dir: &str, rev: &str) -> Result<Vec<PathBuf>> {
+    Ok(git(dir, &["ls-tree", "-r", "-z", "--name-only", rev])?
+        .split('\0')
+        .filter(|path| !path.is_empty())
+        .map(|path| Path::new(dir).join(path))
+        .collect())
+}
+
+// PHP files below `dir` at a revision containing `literal` (case-insensitively), relative to `dir`
+pub fn php_files_containing(dir: &str, rev: &str, literal: &str) -> Result<Vec<String>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["grep", "-l", "-i", "-F", "-e", literal, rev, "--", "*.php"])
+        .output()
+        .map_err(|e| anyhow::anyhow!("Could not run git: {}", e))?;
+    // git grep exits with 1 when nothing matched
+    if !output.status.success() && output.status.code() != Some(1) {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow::anyhow!("git grep failed: {}", stderr.trim()));
+    }
+    let prefix = format!("{}:", rev);
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.strip_prefix(&prefix).unwrap_or(line).to_string())
+        .collect())
+}
+
+// Unix time of a date in any form `git log --before` accepts, such as '3 months ago'
+pub fn timestamp(dir: &str, date: &str) -> Result<i64> {
+    let output = git(dir, &["rev-parse", &format!("--before={}", date)])?;
+    output.trim()
+        .strip_prefix("--min-age=")
+        .and_then(|seconds| seconds.parse().ok())
+        .ok_or_else(|| anyhow::anyhow!("'{}' is not a date git understands", date))
+}
+
+// The last commit on HEAD made at or before a unix time, if the history goes back that far
+pub fn commit_at(dir: &str, timestamp: i64) -> Result<Option<String>> {
+    let output = git(dir, &["rev-list", "-1", &format!("--before={}", timestamp), "HEAD"])?;
+    Ok(Some(output.trim().to_string()).filter(|commit| !commit.is_empty()))
+}
+
+// Contents of several files at a revision, read through one `git cat-file --batch` instead of
+// a git process per file. Paths are relative to `dir`, files that can't be read are left out
+pub fn show_files(dir: &str, rev: &str, paths: &[String]) -> Result<Vec<(String, String)>> {
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["cat-file", "--batch"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("Could not run git: {}", e))?;
+    let (Some(mut stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+        return Err(anyhow::anyhow!("Could not talk to git cat-file"));
+    };
+
+    // Written from another thread so neither side blocks on a full pipe
+    let objects: String = paths.iter().map(|path| format!("{}:./{}\n", rev, path)).collect();
+    let writer = std::thread::spawn(move || stdin.write_all(objects.as_bytes()));
+
+    let mut stdout = BufReader::new(stdout);
+    let mut files = Vec::new();
+    for path in paths {
+        let mut header = String::new();
+        if stdout.read_line(&mut header)? == 0 {
+            break;
+        }
+        // `<object> blob <size>`, or `<object> missing`
+        let Some(size) = header.split_whitespace().nth(2).and_then(|size| size.parse::<usize>().ok()) else { continue };
+        let mut content = vec![0; size + 1];
+        stdout.read_exact(&mut content)?;
+        content.pop();
+        files.push((path.clone(), String::from_utf8_lossy(&content).into_owned()));
+    }
+    let _ = writer.join();
+    let _ = child.wait();
+    Ok(files)
+}
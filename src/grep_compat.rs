@@ -0,0 +1,251 @@
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use anyhow::Result;
+use clap::CommandFactory;
+use colored::*;
+use regex::Regex;
+
+use crate::{budget, tool_excludes};
+
+// The grep flags --grep-compat takes, as read from the command line
+#[derive(Debug, Default)]
+struct Options {
+    line_numbers: bool,
+    // -H or -h, otherwise file names are printed unless a single file is searched
+    with_filename: Option<bool>,
+    files_with_matches: bool,
+    count: bool,
+    ignore_case: bool,
+    word: bool,
+    invert: bool,
+    // --include and --exclude-dir globs, for file and directory names
+    include: Vec<Regex>,
+    exclude_dirs: Vec<Regex>,
+}
+
+static OPTIONS: OnceLock<Options> = OnceLock::new();
+
+// The single letter grep flags, which can be bundled as in `-rnw`
+const SHORT_FLAGS: &str = "rRnHhlciwv";
+
+// Takes the grep flags out of the command line when --grep-compat is on it, leaving the rest for
+// clap. grep's PATTERN [PATH] operands become the query and --dir, so `grep -rnw foo src` works
+// with only the program name changed
+pub fn rewrite(args: impl IntoIterator<Item = OsString>) -> Result<Vec<OsString>> {
+    let args: Vec<OsString> = args.into_iter().collect();
+    let options_end = args.iter().position(|arg| arg == "--").unwrap_or(args.len());
+    if !args[..options_end].iter().any(|arg| arg == "--grep-compat") {
+        return Ok(args);
+    }
+
+    let (long_values, short_values) = value_options();
+    let mut options = Options::default();
+    let mut rewritten = Vec::new();
+    let mut operands = Vec::new();
+    let mut args = args.into_iter().enumerate();
+    if let Some((_, program)) = args.next() {
+        rewritten.push(program);
+    }
+    while let Some((i, arg)) = args.next() {
+        let Some(text) = arg.to_str().map(str::to_string) else {
+            rewritten.push(arg);
+            continue;
+        };
+        if i >= options_end {
+            if i > options_end {
+                operands.push(text);
+            }
+            continue;
+        }
+        if let Some(long) = text.strip_prefix("--") {
+            let (name, value) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (long, None),
+            };
+            match name {
+                "recursive" | "line-number" | "with-filename" | "no-filename" | "files-with-matches" | "count" | "ignore-case" | "word-regexp" | "invert-match" => {
+                    apply_long(&mut options, name);
+                }
+                "include" | "exclude-dir" => {
+                    let Some(glob) = value.or_else(|| args.next().and_then(|(_, value)| value.into_string().ok())) else {
+                        eprintln!("Error: --{} needs a glob.", name);
+                        return Err(anyhow::anyhow!("--{} needs a glob", name));
+                    };
+                    let pattern = Regex::new(&format!("^{}$", tool_excludes::glob(&glob, false)))?;
+                    if name == "include" { options.include.push(pattern) } else { options.exclude_dirs.push(pattern) }
+                }
+                _ => {
+                    rewritten.push(arg);
+                    if value.is_none() && long_values.iter().any(|option| option == name)
+                        && let Some((_, value)) = args.next() {
+                        rewritten.push(value);
+                    }
+                }
+            }
+        } else if let Some(letters) = text.strip_prefix('-').filter(|letters| !letters.is_empty()) {
+            // phrep's own short options are left alone, and so is everything from one that takes a value
+            let value_at = letters.find(|letter| short_values.contains(&letter));
+            let (flags, rest) = letters.split_at(value_at.unwrap_or(letters.len()));
+            let others: String = flags.chars().filter(|letter| !SHORT_FLAGS.contains(*letter)).collect();
+            for letter in flags.chars().filter(|letter| SHORT_FLAGS.contains(*letter)) {
+                apply_short(&mut options, letter);
+            }
+            if !others.is_empty() || !rest.is_empty() {
+                rewritten.push(OsString::from(format!("-{}{}", others, rest)));
+            }
+            // A value-taking option at the end of the bundle takes the next argument
+            if rest.len() == 1
+                && let Some((_, value)) = args.next() {
+                rewritten.push(value);
+            }
+        } else {
+            operands.push(text);
+        }
+    }
+
+    let mut operands = operands.into_iter();
+    let pattern = operands.next();
+    let paths: Vec<String> = operands.collect();
+    match paths.as_slice() {
+        [] => {}
+        [path] => rewritten.extend(["--dir".into(), path.into()]),
+        _ => {
+            eprintln!("Error: --grep-compat searches one directory or file, got {}.", paths.len());
+            return Err(anyhow::anyhow!("--grep-compat searches one directory or file, got {}", paths.len()));
+        }
+    }
+    // After `--` so a pattern starting with `-` isn't read as an option
+    if let Some(pattern) = pattern {
+        rewritten.extend(["--".into(), pattern.into()]);
+    }
+    let _ = OPTIONS.set(options);
+    Ok(rewritten)
+}
+
+fn apply_long(options: &mut Options, name: &str) {
+    match name {
+        "line-number" => options.line_numbers = true,
+        "with-filename" => options.with_filename = Some(true),
+        "no-filename" => options.with_filename = Some(false),
+        "files-with-matches" => options.files_with_matches = true,
+        "count" => options.count = true,
+        "ignore-case" => options.ignore_case = true,
+        "word-regexp" => options.word = true,
+        "invert-match" => options.invert = true,
+        // -r is implied, phrep always searches below the directory
+        _ => {}
+    }
+}
+
+fn apply_short(options: &mut Options, letter: char) {
+    let name = match letter {
+        'n' => "line-number",
+        'H' => "with-filename",
+        'h' => "no-filename",
+        'l' => "files-with-matches",
+        'c' => "count",
+        'i' => "ignore-case",
+        'w' => "word-regexp",
+        'v' => "invert-match",
+        _ => "recursive",
+    };
+    apply_long(options, name);
+}
+
+// The long and short names of phrep's options that take a value, so their values aren't taken
+// for grep operands
+fn value_options() -> (Vec<String>, Vec<char>) {
+    let command = crate::Cli::command();
+    let options: Vec<_> = command.get_arguments().filter(|arg| !arg.is_positional() && arg.get_action().takes_values()).collect();
+    (
+        options.iter().filter_map(|arg| arg.get_long().map(str::to_string)).collect(),
+        options.iter().filter_map(|arg| arg.get_short()).collect(),
+    )
+}
+
+// Searches like GNU grep -r: lines are printed as `file:line:text` as the flags ask for, -l and
+// -c print file names and counts instead, and nothing else is written to stdout. Returns whether
+// any line was selected, for grep's exit code
+pub fn search(query: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<bool> {
+    let options = OPTIONS.get_or_init(Options::default);
+    let source = if options.word { format!(r"\b(?:{})\b", query) } else { query.to_string() };
+    let pattern = match budget::builder(&source).case_insensitive(options.ignore_case).build() {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            eprintln!("Invalid regex pattern: {}", e);
+            return Err(anyhow::anyhow!("Invalid regex pattern"));
+        }
+    };
+    let with_filename = options.with_filename.unwrap_or(!Path::new(dir).is_file());
+
+    let mut stdout = std::io::stdout().lock();
+    let mut selected_any = false;
+    for path in crate::php_files(dir, file, exclude_dirs) {
+        if !selected(&path, dir, options) {
+            continue;
+        }
+        let Some(content) = crate::read_file(&path) else { continue };
+        let filename = crate::format_filename(&path);
+        let mut count = 0;
+        for (i, line) in content.lines().enumerate() {
+            if budget::exceeded(&path) {
+                break;
+            }
+            if pattern.is_match(line) == options.invert {
+                continue;
+            }
+            count += 1;
+            if options.files_with_matches {
+                break;
+            }
+            if options.count {
+                continue;
+            }
+            let mut prefix = String::new();
+            if with_filename {
+                prefix.push_str(&format!("{}{}", filename.magenta(), ":".cyan()));
+            }
+            if options.line_numbers {
+                prefix.push_str(&format!("{}{}", (i + 1).to_string().green(), ":".cyan()));
+            }
+            let line = if options.invert { line.to_string() } else { highlight(line, &pattern) };
+            if writeln!(stdout, "{}{}", prefix, line).is_err() {
+                return Ok(true);
+            }
+        }
+        selected_any |= count > 0;
+        let written = if options.files_with_matches {
+            if count > 0 { writeln!(stdout, "{}", filename.magenta()) } else { Ok(()) }
+        } else if options.count {
+            if with_filename { writeln!(stdout, "{}{}{}", filename.magenta(), ":".cyan(), count) } else { writeln!(stdout, "{}", count) }
+        } else {
+            Ok(())
+        };
+        if written.is_err() {
+            return Ok(selected_any);
+        }
+    }
+    let _ = stdout.flush();
+    Ok(selected_any)
+}
+
+// Applies --include to the file name and --exclude-dir to the directories below the search directory
+fn selected(path: &Path, dir: &str, options: &Options) -> bool {
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    if !options.include.is_empty() && !options.include.iter().any(|pattern| pattern.is_match(&name)) {
+        return false;
+    }
+    let relative_path = path.strip_prefix(dir).unwrap_or(path);
+    !relative_path.parent().is_some_and(|parent| parent.components().any(|component| {
+        let component = component.as_os_str().to_string_lossy();
+        options.exclude_dirs.iter().any(|pattern| pattern.is_match(&component))
+    }))
+}
+
+// Colors every match in a line the way grep --color does
+fn highlight(line: &str, pattern: &Regex) -> String {
+    pattern.replace_all(line, |captures: &regex::Captures| captures[0].bold().red().to_string()).into_owned()
+}
@@ -0,0 +1,64 @@
+use std::cell::{Cell, RefCell};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+thread_local! {
+    // Whether a file is being searched, so a panic is turned into a warning about it
+    static IN_FILE: Cell<bool> = const { Cell::new(false) };
+    // What the last panic while searching a file said, and where
+    static PANIC: RefCell<Option<String>> = const { RefCell::new(None) };
+    // Whether the file last searched was given up on
+    static FAILED: Cell<bool> = const { Cell::new(false) };
+}
+
+// Installs a panic hook that keeps quiet about panics while searching a file, `file` reports
+// those as a warning, and leaves other panics to the default hook
+pub fn install() {
+    let default = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if !IN_FILE.get() {
+            default(info);
+            return;
+        }
+        let message = info.payload().downcast_ref::<&str>().map(|message| message.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown error".to_string());
+        let location = info.location().map(|location| format!(" at {}:{}", location.file(), location.line())).unwrap_or_default();
+        PANIC.set(Some(format!("{}{}", message, location)));
+    }));
+}
+
+// Runs the search of one file, so a file that trips up phrep, like an error-recovered tree
+// with nodes missing where they're expected, is skipped with a warning instead of aborting
+// the whole run
+pub fn file(path: &Path, search: impl FnOnce()) {
+    IN_FILE.set(true);
+    let result = panic::catch_unwind(AssertUnwindSafe(search));
+    IN_FILE.set(false);
+    FAILED.set(result.is_err());
+    if result.is_err() {
+        let reason = PANIC.take().unwrap_or_else(|| "unknown error".to_string());
+        eprintln!("Warning: Skipped the rest of '{}' after an internal error: {}", path.display(), reason);
+    }
+}
+
+// Whether the file last searched was given up on, so its partial results aren't cached
+pub fn failed() -> bool {
+    FAILED.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    #[test]
+    fn a_panicking_file_is_given_up_on_and_the_next_one_searched() {
+        super::file(Path::new("broken.php"), || panic!("missing node"));
+        assert!(super::failed());
+
+        let mut searched = false;
+        super::file(Path::new("good.php"), || searched = true);
+        assert!(searched);
+        assert!(!super::failed());
+    }
+}
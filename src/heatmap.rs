@@ -0,0 +1,92 @@
+use std::path::{Path, PathBuf};
+
+use colored::*;
+
+use crate::declarations::FileDecls;
+use crate::json::Value;
+use crate::php;
+
+// Match counts of a file or class, ranked by matches per 100 lines
+struct Hotspot {
+    name: String,
+    path: PathBuf,
+    matches: usize,
+    lines: usize,
+}
+
+impl Hotspot {
+    // Matches per 100 lines, to one decimal
+    fn density(&self) -> f64 {
+        (self.matches as f64 * 1000.0 / self.lines.max(1) as f64).round() / 10.0
+    }
+
+    // Files are named by their path, classes get the path next to their name
+    fn to_json(&self, is_class: bool) -> Value {
+        let mut entries = Vec::new();
+        if is_class {
+            entries.push(("class".to_string(), Value::String(self.name.clone())));
+        }
+        entries.extend([
+            ("path".to_string(), Value::String(crate::format_filename(&self.path))),
+            ("matches".to_string(), Value::Number(self.matches as f64)),
+            ("lines".to_string(), Value::Number(self.lines as f64)),
+            ("density".to_string(), Value::Number(self.density())),
+        ]);
+        Value::Object(entries)
+    }
+}
+
+// Prints files and classes ranked by match density for --heatmap instead of the matches
+// themselves, as tables or, with --format json, as one JSON document
+pub fn print(matches: &[(PathBuf, usize)], json: bool) {
+    let mut files: Vec<(&Path, Vec<usize>)> = Vec::new();
+    for (path, line) in matches {
+        match files.iter_mut().find(|(file, _)| file == path) {
+            Some((_, lines)) => lines.push(*line),
+            None => files.push((path, vec![*line])),
+        }
+    }
+
+    let mut parser = php::new_parser().ok();
+    let mut file_spots = Vec::new();
+    let mut class_spots = Vec::new();
+    for (path, lines) in &files {
+        let Some(content) = crate::read_file(path) else { continue };
+        file_spots.push(Hotspot { name: crate::format_filename(path), path: path.to_path_buf(), matches: lines.len(), lines: content.lines().count() });
+
+        let Some(tree) = parser.as_mut().and_then(|parser| crate::parse_content(parser, &content, path)) else { continue };
+        let decls = FileDecls::new(tree.root_node(), &content);
+        for class in &decls.classes {
+            // Matches of nested (anonymous) classes count towards the outer class too
+            let count = lines.iter().filter(|line| (class.line..=class.end_line).contains(line)).count();
+            if count > 0 {
+                class_spots.push(Hotspot { name: class.fqcn.clone(), path: path.to_path_buf(), matches: count, lines: class.end_line - class.line + 1 });
+            }
+        }
+    }
+
+    let rank = |spots: &mut Vec<Hotspot>| spots.sort_by(|a, b| b.density().total_cmp(&a.density()).then(b.matches.cmp(&a.matches)));
+    rank(&mut file_spots);
+    rank(&mut class_spots);
+
+    if json {
+        let document = Value::Object(vec![
+            crate::report::schema_version(),
+            ("files".to_string(), Value::Array(file_spots.iter().map(|spot| spot.to_json(false)).collect())),
+            ("classes".to_string(), Value::Array(class_spots.iter().map(|spot| spot.to_json(true)).collect())),
+        ]);
+        println!("{}", document);
+        return;
+    }
+
+    print_table("File", &file_spots);
+    println!();
+    print_table("Class", &class_spots);
+}
+
+fn print_table(title: &str, spots: &[Hotspot]) {
+    println!("{:>10} {:>8} {:>8}  {}", "Per 100".bold(), "Matches".bold(), "Lines".bold(), title.bold());
+    for spot in spots {
+        println!("{:>10.1} {:>8} {:>8}  {}", spot.density(), spot.matches, spot.lines, spot.name);
+    }
+}
@@ -0,0 +1,108 @@
+use regex::Regex;
+use tree_sitter::{Language, Node};
+
+use crate::php;
+
+// The languages --inject parses strings as
+pub const LANGUAGES: [&str; 3] = ["sql", "js", "css"];
+
+unsafe extern "C" {
+    fn tree_sitter_sql() -> Language;
+    fn tree_sitter_javascript() -> Language;
+    fn tree_sitter_css() -> Language;
+}
+
+pub fn language(name: &str) -> Language {
+    match name {
+        "sql" => unsafe { tree_sitter_sql() },
+        "js" => unsafe { tree_sitter_javascript() },
+        _ => unsafe { tree_sitter_css() },
+    }
+}
+
+// The language a marker or heredoc tag names: `SQL`, `MySQL`, `JavaScript`, `CSS` and the like
+fn language_named(name: &str) -> Option<&'static str> {
+    match name.to_ascii_lowercase().as_str() {
+        "sql" | "mysql" | "postgresql" | "pgsql" | "sqlite" | "tsql" => Some("sql"),
+        "js" | "javascript" | "ecmascript" => Some("js"),
+        "css" => Some("css"),
+        _ => None,
+    }
+}
+
+// A string of the PHP file holding code in another language
+pub struct Injection<'a> {
+    // The PHP string, heredoc or nowdoc
+    pub node: Node<'a>,
+    // Where the code starts in the PHP file
+    pub start: usize,
+    // The code, with interpolated variables blanked to `_` so offsets line up with the file
+    pub code: String,
+}
+
+// Finds the strings of a file that hold `language`: those after a `/** @lang SQL */` or
+// `// language=SQL` comment, heredocs tagged `<<<SQL`, and for SQL strings starting with a
+// statement keyword such as SELECT or INSERT INTO
+pub fn injections<'a>(root: Node<'a>, content: &str, language: &str) -> Vec<Injection<'a>> {
+    let marker = Regex::new(r"(?i)(?:@lang\s+|language\s*=\s*)([\w-]+)").unwrap();
+    let sql = Regex::new(r"(?is)^\s*(?:select|insert\s+into|update\s+\S+\s+set|delete\s+from|with\s+\w+\s+as|create|alter|drop|replace\s+into)\b").unwrap();
+    let mut injections = Vec::new();
+    // The language of the last marker comment, until the string after it
+    let mut pending: Option<Option<&str>> = None;
+    php::walk(root, &mut |node| {
+        if node.kind() == "comment" {
+            if let Some(name) = marker.captures(php::node_text(node, content)) {
+                pending = Some(language_named(&name[1]));
+            }
+            return;
+        }
+        if !matches!(node.kind(), "string" | "encapsed_string" | "heredoc" | "nowdoc") {
+            return;
+        }
+        let Some((start, end)) = code_span(node, content) else { return };
+        let tag = node.child_by_field_name("identifier").and_then(|tag| language_named(php::node_text(tag, content).trim_matches(|c| c == '\'' || c == '"')));
+        let found = match pending.take() {
+            Some(marked) => marked,
+            None => tag.or_else(|| sql.is_match(&content[start..end]).then_some("sql")),
+        };
+        if found == Some(language) {
+            injections.push(Injection { node, start, code: blanked(node, content, start, end) });
+        }
+    });
+    injections
+}
+
+// The bytes between the quotes of a string, or the body of a heredoc or nowdoc
+fn code_span(node: Node, content: &str) -> Option<(usize, usize)> {
+    match node.kind() {
+        "heredoc" | "nowdoc" => {
+            let body = node.child_by_field_name("value")?;
+            Some((body.start_byte(), body.end_byte()))
+        }
+        _ => {
+            let text = php::node_text(node, content);
+            // Binary strings start with b
+            let quote = text.find(['\'', '"'])?;
+            (text.len() > quote + 1).then(|| (node.start_byte() + quote + 1, node.end_byte() - 1))
+        }
+    }
+}
+
+// The code with interpolations like `$id` or `{$this->table}` replaced by as many `_`, which
+// read as an identifier in every language and keep the offsets of the rest
+fn blanked(node: Node, content: &str, start: usize, end: usize) -> String {
+    let mut code = content[start..end].to_string();
+    let parts = node.child_by_field_name("value").unwrap_or(node);
+    let mut cursor = parts.walk();
+    for part in parts.children(&mut cursor) {
+        if matches!(part.kind(), "string_content" | "escape_sequence" | "nowdoc_string") {
+            continue;
+        }
+        // The quotes are outside the code
+        let (from, to) = (part.start_byte().max(start) - start, part.end_byte().min(end).saturating_sub(start));
+        if from < to && code.is_char_boundary(from) && code.is_char_boundary(to) {
+            code.replace_range(from..to, &"_".repeat(to - from));
+        }
+    }
+    code
+}
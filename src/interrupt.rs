@@ -0,0 +1,40 @@
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// The exit code of a run stopped with Ctrl-C, the one shells give a process killed by SIGINT
+const EXIT_CODE: i32 = 130;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// Catches Ctrl-C so the search stops after the file it's in and still prints what it found.
+// A second Ctrl-C exits right away
+#[cfg(unix)]
+pub fn install() {
+    extern "C" fn on_sigint(_: libc::c_int) {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            unsafe { libc::_exit(EXIT_CODE) };
+        }
+    }
+    unsafe { libc::signal(libc::SIGINT, on_sigint as *const () as libc::sighandler_t) };
+}
+
+#[cfg(not(unix))]
+pub fn install() {}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+// Ends an interrupted run once the partial results are printed, leaving the terminal with the
+// default colors and exiting with a code scripts can tell apart from a finished search
+pub fn finish() {
+    if !interrupted() {
+        return;
+    }
+    if colored::control::SHOULD_COLORIZE.should_colorize() {
+        print!("\x1b[0m");
+    }
+    crate::report::summary("Interrupted, the results are partial.");
+    let _ = std::io::stdout().flush();
+    std::process::exit(EXIT_CODE);
+}
@@ -0,0 +1,89 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+
+// Where the journals of the operations that wrote files are kept, below the search directory
+pub fn undo_dir(root: &str) -> PathBuf {
+    Path::new(root).join(".phrep").join("undo")
+}
+
+// A file an operation changed: where it was, where it is now, and its content before and after
+pub struct JournalEntry {
+    pub original_path: PathBuf,
+    pub path: PathBuf,
+    pub original: String,
+    pub written: String,
+}
+
+// The record of one operation that wrote files, kept in `.phrep/undo/<timestamp>` so `phrep undo`
+// can restore them. Every file is recorded before it's written, so an operation that fails
+// halfway can still be undone. The directory holds `manifest`, with a tab-separated line of the
+// original and the current path per file, and the contents `<n>.orig` and `<n>.new` of line n
+pub struct Journal {
+    dir: PathBuf,
+    entries: usize,
+}
+
+impl Journal {
+    pub fn start(root: &str) -> Result<Self> {
+        let millis = SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_millis()).unwrap_or(0);
+        let dir = undo_dir(root).join(millis.to_string());
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| anyhow::anyhow!("Could not create the undo journal '{}': {}", dir.display(), e))?;
+        Ok(Journal { dir, entries: 0 })
+    }
+
+    pub fn record(&mut self, entry: &JournalEntry) -> Result<()> {
+        let absolute = |path: &Path| std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        let recorded = std::fs::write(self.dir.join(format!("{}.orig", self.entries)), &entry.original)
+            .and_then(|_| std::fs::write(self.dir.join(format!("{}.new", self.entries)), &entry.written))
+            .and_then(|_| {
+                let mut manifest = std::fs::OpenOptions::new().create(true).append(true).open(self.dir.join("manifest"))?;
+                writeln!(manifest, "{}\t{}", absolute(&entry.original_path).display(), absolute(&entry.path).display())
+            });
+        if let Err(e) = recorded {
+            return Err(anyhow::anyhow!("Could not write the undo journal '{}': {}", self.dir.display(), e));
+        }
+        self.entries += 1;
+        Ok(())
+    }
+
+    // Drops the journal of an operation that didn't end up writing anything
+    pub fn finish(self) {
+        if self.entries == 0 {
+            let _ = std::fs::remove_dir(&self.dir);
+        }
+    }
+}
+
+// The directory of the most recent journal and the files it recorded
+pub fn last(root: &str) -> Result<Option<(PathBuf, Vec<JournalEntry>)>> {
+    let Ok(journals) = std::fs::read_dir(undo_dir(root)) else { return Ok(None) };
+    let Some(dir) = journals
+        .filter_map(|journal| journal.ok())
+        .filter(|journal| journal.path().join("manifest").is_file())
+        .filter_map(|journal| journal.file_name().to_str().and_then(|name| name.parse::<u128>().ok()).map(|millis| (millis, journal.path())))
+        .max_by_key(|(millis, _)| *millis)
+        .map(|(_, dir)| dir) else {
+        return Ok(None);
+    };
+
+    let read = |name: String| std::fs::read_to_string(dir.join(&name))
+        .map_err(|e| anyhow::anyhow!("Could not read '{}' of the undo journal '{}': {}", name, dir.display(), e));
+    let manifest = read("manifest".to_string())?;
+    let mut entries = Vec::new();
+    for (i, line) in manifest.lines().enumerate() {
+        let Some((original_path, path)) = line.split_once('\t') else {
+            return Err(anyhow::anyhow!("Line {} of '{}' is not a journal entry", i + 1, dir.join("manifest").display()));
+        };
+        entries.push(JournalEntry {
+            original_path: PathBuf::from(original_path),
+            path: PathBuf::from(path),
+            original: read(format!("{}.orig", i))?,
+            written: read(format!("{}.new", i))?,
+        });
+    }
+    Ok(Some((dir, entries)))
+}
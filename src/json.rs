@@ -0,0 +1,244 @@
+use anyhow::Result;
+
+// A parsed JSON document. Objects keep their keys in document order
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&[(String, Value)]> {
+        match self {
+            Value::Object(entries) => Some(entries),
+            _ => None,
+        }
+    }
+}
+
+// Writes compact JSON
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::String(s) => write!(f, "{}", escape(s)),
+            Value::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Value::Object(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}:{}", escape(key), value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+// Quotes and escapes a string for JSON output
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+pub fn parse(input: &str) -> Result<Value> {
+    let mut parser = JsonParser { chars: input.char_indices().peekable(), input };
+    let value = parser.value()?;
+    parser.whitespace();
+    if let Some((pos, _)) = parser.chars.peek() {
+        return Err(anyhow::anyhow!("Unexpected trailing characters at offset {}", pos));
+    }
+    Ok(value)
+}
+
+struct JsonParser<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+    input: &'a str,
+}
+
+impl JsonParser<'_> {
+    fn whitespace(&mut self) {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        match self.chars.next() {
+            Some((_, c)) if c == expected => Ok(()),
+            Some((pos, c)) => Err(anyhow::anyhow!("Expected '{}' but found '{}' at offset {}", expected, c, pos)),
+            None => Err(anyhow::anyhow!("Expected '{}' but reached the end of input", expected)),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        self.whitespace();
+        match self.chars.peek().copied() {
+            Some((_, '{')) => self.object(),
+            Some((_, '[')) => self.array(),
+            Some((_, '"')) => Ok(Value::String(self.string()?)),
+            Some((_, 't')) => self.keyword("true", Value::Bool(true)),
+            Some((_, 'f')) => self.keyword("false", Value::Bool(false)),
+            Some((_, 'n')) => self.keyword("null", Value::Null),
+            Some((_, c)) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some((pos, c)) => Err(anyhow::anyhow!("Unexpected '{}' at offset {}", c, pos)),
+            None => Err(anyhow::anyhow!("Unexpected end of input")),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Value) -> Result<Value> {
+        for expected in keyword.chars() {
+            self.expect(expected)?;
+        }
+        Ok(value)
+    }
+
+    fn number(&mut self) -> Result<Value> {
+        let start = self.chars.peek().map(|(pos, _)| *pos).unwrap_or(self.input.len());
+        let mut end = start;
+        while let Some((pos, c)) = self.chars.next_if(|(_, c)| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+            end = pos + c.len_utf8();
+        }
+        let text = &self.input[start..end];
+        text.parse::<f64>()
+            .map(Value::Number)
+            .map_err(|_| anyhow::anyhow!("Invalid number '{}' at offset {}", text, start))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(s),
+                Some((_, '\\')) => match self.chars.next() {
+                    Some((_, 'n')) => s.push('\n'),
+                    Some((_, 'r')) => s.push('\r'),
+                    Some((_, 't')) => s.push('\t'),
+                    Some((_, 'b')) => s.push('\u{8}'),
+                    Some((_, 'f')) => s.push('\u{c}'),
+                    Some((_, 'u')) => s.push(self.unicode_escape()?),
+                    Some((_, c)) => s.push(c),
+                    None => return Err(anyhow::anyhow!("Unterminated string")),
+                },
+                Some((_, c)) => s.push(c),
+                None => return Err(anyhow::anyhow!("Unterminated string")),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self.chars.next()
+                .and_then(|(_, c)| c.to_digit(16))
+                .ok_or_else(|| anyhow::anyhow!("Invalid unicode escape"))?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    fn unicode_escape(&mut self) -> Result<char> {
+        let code = self.hex4()?;
+        if (0xD800..0xDC00).contains(&code) {
+            self.expect('\\')?;
+            self.expect('u')?;
+            let low = self.hex4()?;
+            let combined = 0x10000 + ((code - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+            return char::from_u32(combined).ok_or_else(|| anyhow::anyhow!("Invalid surrogate pair"));
+        }
+        Ok(char::from_u32(code).unwrap_or('\u{FFFD}'))
+    }
+
+    fn array(&mut self) -> Result<Value> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+        self.whitespace();
+        if self.chars.next_if(|(_, c)| *c == ']').is_some() {
+            return Ok(Value::Array(values));
+        }
+        loop {
+            values.push(self.value()?);
+            self.whitespace();
+            match self.chars.next() {
+                Some((_, ',')) => continue,
+                Some((_, ']')) => return Ok(Value::Array(values)),
+                Some((pos, c)) => return Err(anyhow::anyhow!("Expected ',' or ']' but found '{}' at offset {}", c, pos)),
+                None => return Err(anyhow::anyhow!("Unterminated array")),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Value> {
+        self.expect('{')?;
+        let mut entries = Vec::new();
+        self.whitespace();
+        if self.chars.next_if(|(_, c)| *c == '}').is_some() {
+            return Ok(Value::Object(entries));
+        }
+        loop {
+            self.whitespace();
+            let key = self.string()?;
+            self.whitespace();
+            self.expect(':')?;
+            entries.push((key, self.value()?));
+            self.whitespace();
+            match self.chars.next() {
+                Some((_, ',')) => continue,
+                Some((_, '}')) => return Ok(Value::Object(entries)),
+                Some((pos, c)) => return Err(anyhow::anyhow!("Expected ',' or '}}' but found '{}' at offset {}", c, pos)),
+                None => return Err(anyhow::anyhow!("Unterminated object")),
+            }
+        }
+    }
+}
@@ -1,11 +1,43 @@
-use clap::Parser;
+use clap::{ArgGroup, Parser, Subcommand};
 use anyhow::Result;
 use regex::Regex;
 use tree_sitter::{Language, Parser as TreeSitterParser};
-use walkdir::WalkDir;
 use colored::*;
 use dirs::home_dir;
 extern crate tree_sitter_php;
+extern crate tree_sitter_css;
+extern crate tree_sitter_javascript;
+extern crate tree_sitter_sequel;
+
+mod budget;
+mod cache;
+mod calls;
+mod commands;
+mod config;
+mod context;
+mod debug_matches;
+mod declarations;
+mod diff;
+mod edit;
+mod exec;
+mod exec_filter;
+mod extract;
+mod generated;
+mod guard;
+mod git;
+mod grep_compat;
+mod heatmap;
+mod inject;
+mod interrupt;
+mod journal;
+mod json;
+mod minified;
+mod php;
+#[cfg(unix)]
+mod plugin;
+mod report;
+mod tool_excludes;
+mod walk;
 
 unsafe extern "C" { fn tree_sitter_php() -> Language; }
 
@@ -13,39 +45,671 @@ unsafe extern "C" { fn tree_sitter_php() -> Language; }
 #[derive(Parser, Debug)]
 #[command(name = "phrep")]
 #[command(about = "Grep style search inside PHP functions/methods. Basic search searches within methods and returns line and method information", version)]
+#[command(subcommand_negates_reqs = true)]
+// One search mode per run. --print-method counts as one as it prints whole methods instead of lines
+#[command(group(ArgGroup::new("search_mode").multiple(false).args(["print_method", "grep", "grep_compat", "method_search", "superglobals", "new_class", "throws", "catches", "returns_expr", "no_return", "match_arm", "type_query", "doc_tag", "plugin", "const_usage", "late_static_binding", "tokens", "heredoc", "default_value", "callback_of", "doc_search", "class_ref", "instanceof", "function", "assertions", "env", "i18n", "logs", "arg_of", "inject"])))]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Search query
-    query: String,
+    #[arg(required_unless_present_any = ["superglobals", "new_class", "throws", "catches", "returns_expr", "no_return", "match_arm", "type_query", "doc_tag", "plugin", "const_usage", "late_static_binding", "tokens", "heredoc", "default_value", "doc_search", "class_ref", "instanceof", "function", "assertions", "env", "i18n", "logs", "schema"])]
+    query: Option<String>,
     /// Directory to search recursively (default is current directory)
-    #[arg(long, short, value_name = "DIR", default_value = ".")]
+    #[arg(long, short, value_name = "DIR", default_value = ".", global = true)]
     dir:String,
 
     /// File to search (default is all .php files)
-    #[arg(long, short, value_name = "FILE", default_value = ".php")]
+    #[arg(long, short, value_name = "FILE", default_value = ".php", global = true)]
     file: String,
 
+    /// Only search files whose path below the search directory matches this regex, can be repeated
+    #[arg(long, value_name = "REGEX", global = true)]
+    file_regex: Vec<String>,
+
     /// Print full method body in basic search
-    #[arg(long, short, value_name = "PRINT_METHOD", default_value_t = false, conflicts_with_all = ["grep", "method_search"])]
+    #[arg(long, short, value_name = "PRINT_METHOD", default_value_t = false)]
     print_method: bool,
 
     /// Mimic grep search (default is false)
     #[arg(long, short, value_name = "GREP", default_value_t = false)]
     grep: bool,
 
+    /// Take GNU grep's flags (-r, -n, -H, -h, -l, -c, -i, -w, -v, --include, --exclude-dir) and print lines the way it does
+    #[arg(long, default_value_t = false, conflicts_with_all = ["cache", "format", "heatmap", "sample"])]
+    grep_compat: bool,
+
     /// Return the entire method if method name matches the query
-    #[arg(long, short, value_name = "METHOD_SEARCH", default_value_t = false, conflicts_with_all = ["grep", "print_method"])]
+    #[arg(long, short, value_name = "METHOD_SEARCH", default_value_t = false)]
     method_search: bool,
 
-    /// Exclude directories from search
-    #[arg(long, short, value_name = "EXCLUDE_DIRS", default_value = "vendor,cache,logs")]
+    /// Write every method found by --method-search to a file of its own in this directory, along with its namespace, imports and class header
+    #[arg(long, value_name = "DIR", requires = "method_search", conflicts_with = "cache")]
+    extract_to: Option<String>,
+
+    /// Exclude a directory from search on top of vendor, cache and logs, repeatable or comma-separated. Matched against whole path components below the search directory, e.g. 'tests' or 'src/Legacy'
+    #[arg(long, short, value_name = "DIR", value_delimiter = ',', alias = "exclude-dirs", global = true)]
+    exclude: Vec<String>,
+
+    /// Don't exclude vendor, cache and logs, only the --exclude directories
+    #[arg(long, default_value_t = false, global = true)]
+    no_default_excludes: bool,
+
+    /// The directories left out of the search, the defaults and --exclude joined with commas
+    #[arg(skip)]
     exclude_dirs: String,
+
+    /// Also leave out what the paths and excludes in these tools' configs leave out, comma-separated
+    #[arg(long, value_name = "TOOLS", value_delimiter = ',', value_parser = tool_excludes::TOOLS, global = true)]
+    import_excludes: Vec<String>,
+
+    /// Only search test files: paths containing one of --test-paths, or classes named *Test or extending TestCase
+    #[arg(long, default_value_t = false, conflicts_with = "no_tests", global = true)]
+    tests_only: bool,
+
+    /// Leave out test files, as classified for --tests-only
+    #[arg(long, default_value_t = false, global = true)]
+    no_tests: bool,
+
+    /// Comma-separated path fragments that mark test files for --tests-only and --no-tests
+    #[arg(long, value_name = "PATHS", default_value = "tests/,Tests/", global = true)]
+    test_paths: String,
+
+    /// Skip methods and functions whose name matches this regex, e.g. '^(get|set)[A-Z]'
+    #[arg(long, value_name = "REGEX")]
+    exclude_method: Option<String>,
+
+    /// Print only this many matches, picked at random out of all of them, to check how precise a broad pattern is
+    #[arg(long, value_name = "N", global = true)]
+    sample: Option<usize>,
+
+    /// Only search these kinds of declarations in the basic and --method-search searches, comma-separated
+    #[arg(long, value_name = "KINDS", value_delimiter = ',', value_parser = KINDS, conflicts_with = "print_method")]
+    kind: Vec<String>,
+
+    /// Text in a comment that marks generated code, replacing the defaults; can be repeated
+    #[arg(long, value_name = "TEXT", default_values = ["@generated", "DO NOT EDIT"], global = true)]
+    generated_marker: Vec<String>,
+
+    /// Also report matches in generated files and regions
+    #[arg(long, default_value_t = false, global = true)]
+    include_generated: bool,
+
+    /// Also search binary, minified and encoded files, which are skipped by default
+    #[arg(long, default_value_t = false, global = true)]
+    include_minified: bool,
+
+    /// How long matching may take in one file before the rest of it is skipped with a warning, e.g. 500ms or 2s; 0 for no limit
+    #[arg(long, value_name = "DURATION", default_value = "2s", value_parser = budget::parse_timeout, global = true)]
+    regex_timeout: std::time::Duration,
+
+    /// Search the files as they are at a git revision (branch, tag or commit) instead of the working tree
+    #[arg(long, value_name = "REV", global = true)]
+    rev: Option<String>,
+
+    /// Only report matches on lines added by a patch file or a git diff range (e.g. main...HEAD)
+    #[arg(long, value_name = "PATCH|RANGE", global = true)]
+    diff_filter: Option<String>,
+
+    /// Print N whole statements before and after the statement of every match
+    #[arg(long, value_name = "N", conflicts_with_all = ["print_method", "cache"])]
+    context_statements: Option<usize>,
+
+    /// Print the signature of the enclosing method above its first match
+    #[arg(long, default_value_t = false, conflicts_with_all = ["grep", "method_search"])]
+    with_signature: bool,
+
+    /// Print the docblock summary of the enclosing method above its first match
+    #[arg(long, default_value_t = false, conflicts_with_all = ["grep", "method_search"])]
+    with_doc: bool,
+
+    /// Follow each match with the kind of the innermost syntax node around it, such as string or comment
+    #[arg(long, default_value_t = false, conflicts_with_all = ["print_method", "heatmap", "cache", "grep_compat"])]
+    debug_matches: bool,
+
+    /// Also show how many lines below the start of its method each match is, e.g. file.php:842 (+17 in processOrder)
+    #[arg(long, default_value_t = false, global = true)]
+    relative_lines: bool,
+
+    /// Only report matches on a span of lines such as 100:250 (or 100: and :250), searching a single file
+    #[arg(long, value_name = "START:END", global = true)]
+    range: Option<String>,
+
+    /// Reuse per-file results of an identical earlier search for files that haven't changed
+    #[arg(long, default_value_t = false, conflicts_with_all = ["diff_filter", "range"])]
+    cache: bool,
+
+    /// Print each match as soon as it's found instead of keeping the matches of a file together
+    #[arg(long, default_value_t = false, conflicts_with = "cache")]
+    line_buffered: bool,
+
+    /// Pipe every match as a JSON line to this shell command, which accepts, rejects or annotates it
+    #[arg(long, value_name = "COMMAND", global = true)]
+    exec_filter: Option<String>,
+
+    /// Run this shell command for every match, with {file} (or {}), {line}, {symbol} and {text} replaced by the match's, quoted
+    #[arg(long, value_name = "COMMAND", global = true)]
+    exec: Option<String>,
+
+    /// How many --exec commands may run at once
+    #[arg(long, value_name = "N", default_value_t = 1, global = true, requires = "exec")]
+    exec_jobs: usize,
+
+    /// Leave the file name out of match lines, handy when searching a single file
+    #[arg(long, default_value_t = false, global = true)]
+    no_filename: bool,
+
+    /// Cut printed lines longer than this many characters, with an [... omitted] marker, and only highlight the part printed
+    #[arg(long, value_name = "N", global = true)]
+    max_columns: Option<usize>,
+
+    /// Print the file name with every match (the default), overrides an earlier --no-filename
+    #[arg(long, default_value_t = false, global = true, overrides_with = "no_filename")]
+    with_filename: bool,
+
+    /// Print absolute file paths
+    #[arg(long, default_value_t = false, global = true, conflicts_with = "relative_to")]
+    absolute_paths: bool,
+
+    /// Print file paths relative to this directory
+    #[arg(long, value_name = "DIR", global = true)]
+    relative_to: Option<String>,
+
+    /// Output format: text, aligned table, directory tree with match counts, JSON lines, or a phpstan JSON / checkstyle XML report for editors and CI
+    #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = report::FORMATS, global = true)]
+    format: String,
+
+    /// Rank files and classes by matches per 100 lines instead of listing the matches
+    #[arg(long, default_value_t = false, global = true)]
+    heatmap: bool,
+
+    /// Width to fit --format table into instead of the terminal width
+    #[arg(long, value_name = "COLUMNS", global = true)]
+    max_width: Option<usize>,
+
+    /// List superglobal accesses ($_GET, $_POST, $_SESSION, $GLOBALS, ...) and `global` statements
+    #[arg(long, default_value_t = false)]
+    superglobals: bool,
+
+    /// Find places where the given class is instantiated with `new` (resolves `use` aliases)
+    #[arg(long = "new", value_name = "CLASS")]
+    new_class: Option<String>,
+
+    /// Find throw expressions raising the given exception class (resolves `use` aliases)
+    #[arg(long, value_name = "CLASS")]
+    throws: Option<String>,
+
+    /// Find catch clauses handling the given exception class, including union catches
+    #[arg(long, value_name = "CLASS")]
+    catches: Option<String>,
+
+    /// Search only inside the expressions of return statements
+    #[arg(long, value_name = "PATTERN")]
+    returns_expr: Option<String>,
+
+    /// Find methods with a non-void return type that never return a value
+    #[arg(long, default_value_t = false)]
+    no_return: bool,
+
+    /// Find match arms and switch cases whose condition matches the pattern, e.g. 'OrderStatus::Refunded'
+    #[arg(long, value_name = "PATTERN")]
+    match_arm: Option<String>,
+
+    /// Find parameters, properties and return types declared with this type, e.g. '?string', 'array|false' or 'A&B'
+    #[arg(long, value_name = "TYPE")]
+    type_query: Option<String>,
+
+    /// Find docblock tags such as '@template', '@throws' or '@psalm-*' (trailing * matches a prefix) with the symbol they document
+    #[arg(long, value_name = "TAG")]
+    doc_tag: Option<String>,
+
+    /// Run the matchers of a plugin shared library over every file, can be repeated
+    #[arg(long, value_name = "LIBRARY", conflicts_with = "cache")]
+    plugin: Vec<String>,
+
+    /// Find uses of a class constant or enum case such as 'Status::ACTIVE', following imports and aliases
+    #[arg(long, value_name = "CLASS::NAME")]
+    const_usage: Option<String>,
+
+    /// Find self::, static:: and parent:: calls of a method ('name' or 'Class::name') and flag those where overrides in subclasses change what gets called
+    #[arg(long, value_name = "METHOD")]
+    late_static_binding: Option<String>,
+
+    /// Find a PHP snippet such as '"foo" . $bar' token by token, so line breaks, spacing and comments in between don't matter
+    #[arg(long, value_name = "SNIPPET")]
+    tokens: Option<String>,
+
+    /// Search only the contents of heredoc and nowdoc strings, where SQL and HTML templates tend to live
+    #[arg(long, value_name = "PATTERN")]
+    heredoc: Option<String>,
+
+    /// Find parameters whose default value matches a regex, e.g. 'null' or 'self::', flagging null defaults of non-nullable types
+    #[arg(long, value_name = "PATTERN")]
+    default_value: Option<String>,
+
+    /// Search the query only inside closures and arrow functions passed to functions or methods matching this regex, e.g. 'array_map|usort|collect'
+    #[arg(long, value_name = "FUNCTIONS", requires = "query")]
+    callback_of: Option<String>,
+
+    /// Find methods and functions whose docblock description matches a regex (case-insensitive), printing their signature and the matching line
+    #[arg(long, value_name = "PATTERN")]
+    doc_search: Option<String>,
+
+    /// Find every reference to a class: types, new, static calls, instanceof, catch clauses, attributes and use statements, following imports and aliases
+    #[arg(long, value_name = "FQCN")]
+    class_ref: Option<String>,
+
+    /// Find type checks against a class: instanceof, is_a() and is_subclass_of() calls and get_class() comparisons
+    #[arg(long, value_name = "CLASS")]
+    instanceof: Option<String>,
+
+    /// List the definitions of global functions whose name matches a regex together with their calls, flagging functions never defined in the search directory
+    #[arg(long, value_name = "NAME", conflicts_with = "cache")]
+    function: Option<String>,
+
+    /// Search a regex only inside the test methods of test files: methods named test*, marked #[Test] or tagged @test
+    #[arg(long, value_name = "PATTERN", conflicts_with = "no_tests")]
+    assertions: Option<String>,
+
+    /// Find reads of environment variables and config keys (getenv(), $_ENV, $_SERVER, env(), config()) and list the distinct names
+    #[arg(long, default_value_t = false, conflicts_with = "cache")]
+    env: bool,
+
+    /// Find the translation keys passed to the --i18n-functions, listing the distinct literal keys and reporting the keys that aren't literals separately
+    #[arg(long, default_value_t = false, conflicts_with = "cache")]
+    i18n: bool,
+
+    /// Comma-separated functions and methods taking a translation key as their first argument, for --i18n
+    #[arg(long, value_name = "NAMES", default_value = "__,trans,t,gettext")]
+    i18n_functions: String,
+
+    /// Find logger calls ($logger->info(), Log::error(), error_log()) with their level and message template, and sum them up by level
+    #[arg(long, default_value_t = false, conflicts_with = "cache")]
+    logs: bool,
+
+    /// Comma-separated log levels --logs reports, e.g. 'error,critical'; all levels by default
+    #[arg(long, value_name = "LEVELS", requires = "logs")]
+    log_level: Option<String>,
+
+    /// Search the query only inside the arguments of these functions and methods, comma-separated, e.g. 'preg_match,Route::get,->where'
+    #[arg(long, value_name = "CALLABLES", requires = "query")]
+    arg_of: Option<String>,
+
+    /// Only search the argument at this position (counting from 0) for --arg-of; all arguments by default
+    #[arg(long, value_name = "N", requires = "arg_of")]
+    arg_index: Option<usize>,
+
+    /// Run the query as a tree-sitter query on the SQL, JavaScript or CSS inside strings, e.g. '(where) @w'. Strings after a '/** @lang SQL */' comment, heredocs tagged <<<SQL and strings starting with a SQL statement are parsed
+    #[arg(long, value_name = "LANG", value_parser = inject::LANGUAGES, requires = "query")]
+    inject: Option<String>,
+
+    /// Print the JSON schema of phrep's JSON output and exit
+    #[arg(long, default_value_t = false, exclusive = true)]
+    schema: bool,
+}
+
+/// Analysis commands that work on declarations across the whole search directory
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compare the method signatures of an interface's implementations against the interface
+    IfaceDiff {
+        /// Interface name, short or fully qualified
+        interface: String,
+    },
+    /// Find methods and functions that call themselves
+    Recursion {
+        /// Longest indirect call cycle to look for, 1 only reports direct recursion
+        #[arg(long, value_name = "DEPTH", default_value_t = 1)]
+        max_depth: usize,
+    },
+    /// List untyped parameters, missing return types and untyped properties with per-namespace coverage
+    Untyped {
+        /// Only print the per-namespace summary
+        #[arg(long, default_value_t = false)]
+        summary: bool,
+    },
+    /// List method and function parameters that the body never uses
+    UnusedParams {
+        /// Leave out methods whose signature comes from a parent class or interface
+        #[arg(long, default_value_t = false)]
+        skip_overrides: bool,
+    },
+    /// List PHP files missing `declare(strict_types=1);`
+    StrictTypes {
+        /// Comma-separated path fragments to leave out, such as view or template directories
+        #[arg(long, value_name = "PATHS", default_value = "views,templates")]
+        ignore_paths: String,
+        /// Exit with an error when any file is missing the declaration
+        #[arg(long, default_value_t = false)]
+        forbid: bool,
+    },
+    /// Report classes whose namespace and name don't match their file under composer.json's psr-4 rules
+    Psr4Check {
+        /// Path to composer.json (default is the nearest one above the search directory)
+        #[arg(long, value_name = "FILE")]
+        composer: Option<String>,
+    },
+    /// Report classes, interfaces, traits, enums and functions declared with the same name in more than one file
+    DuplicatesSymbols,
+    /// Print the namespaces, classes, members and functions of a file with line numbers and signatures
+    Outline {
+        /// PHP file to outline
+        file: String,
+    },
+    /// Print the methods, properties and constants of one class in full, optionally only the kinds and members matching a pattern
+    ClassDump {
+        /// Class name, short or fully qualified
+        class: String,
+        /// Comma-separated member kinds to print; all kinds by default
+        #[arg(long, value_name = "KINDS", value_delimiter = ',', value_parser = commands::class_dump::MEMBER_KINDS)]
+        members: Vec<String>,
+        /// Only print members whose text matches this regex
+        #[arg(long, value_name = "REGEX")]
+        pattern: Option<String>,
+    },
+    /// List declared symbols across the search directory
+    Symbols {
+        /// Regex matched against the fully qualified symbol name
+        pattern: Option<String>,
+        /// Symbol kinds to list, comma-separated (class includes interfaces, traits and enums)
+        #[arg(long, value_name = "KIND", value_delimiter = ',', value_parser = commands::symbols::SYMBOL_KINDS)]
+        kind: Vec<String>,
+        /// Print one JSON object per symbol instead of text lines
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// For each method in a file, list the methods of the same class it calls and is called by
+    Xref {
+        /// PHP file to cross-reference
+        file: String,
+    },
+    /// Diff one method's implementation between two git revisions without checking them out
+    MethodDiff {
+        /// Method to compare, written Class::method
+        method: String,
+        /// Revision to compare from
+        #[arg(long, value_name = "REV")]
+        from: String,
+        /// Revision to compare to
+        #[arg(long, value_name = "REV", default_value = "HEAD")]
+        to: String,
+    },
+    /// Report the commits that added or removed lines matching a pattern inside method bodies
+    History {
+        /// Regex to look for
+        pattern: String,
+        /// Only look at commits after this date, in any form `git log --since` accepts
+        #[arg(long, value_name = "DATE")]
+        since: Option<String>,
+    },
+    /// Find the methods reading or writing `$this-><property>` and classify them as getters, setters or mixed
+    Accessors {
+        /// Property name, with or without the leading $
+        property: String,
+    },
+    /// List class properties, including promoted ones, with per-namespace readonly and type counts
+    Properties {
+        /// Only list readonly properties
+        #[arg(long, default_value_t = false, conflicts_with = "mutable")]
+        readonly: bool,
+        /// Only list properties that aren't readonly
+        #[arg(long, default_value_t = false)]
+        mutable: bool,
+        /// Only list properties with a declared type
+        #[arg(long, default_value_t = false, conflicts_with = "untyped_properties")]
+        typed_properties: bool,
+        /// Only list properties without a declared type
+        #[arg(long, default_value_t = false)]
+        untyped_properties: bool,
+    },
+    /// List typed properties without a default that the constructor doesn't assign, which throw when read before they're set
+    UninitializedProps {
+        /// Also list nullable and mixed properties, which are just as uninitialized without a default
+        #[arg(long, default_value_t = false)]
+        include_nullable: bool,
+        /// Comma-separated methods that count as constructors, such as a test's setUp()
+        #[arg(long, value_name = "NAMES", default_value = "setUp")]
+        init_methods: String,
+    },
+    /// List the call sites of a method grouped into its own class, subclasses and other code, to see if it could be private or protected
+    Impact {
+        /// The method, written Class::method
+        #[arg(long, value_name = "CLASS::METHOD")]
+        method: String,
+        /// Only list Foo::method(), self::method() and static::method() calls
+        #[arg(long, default_value_t = false, conflicts_with = "instance_only")]
+        static_only: bool,
+        /// Only list $this->method() and $object->method() calls
+        #[arg(long, default_value_t = false)]
+        instance_only: bool,
+    },
+    /// List the throw statements of a method, and of the methods it calls up to --depth calls deep, with the exceptions that can escape it
+    Throws {
+        /// The method (Class::method) or function
+        method: String,
+        /// How many calls deep to follow
+        #[arg(long, default_value_t = 0)]
+        depth: usize,
+    },
+    /// Rank methods and functions by how closely their bodies resemble a snippet, ignoring layout, names and literals
+    Similar {
+        /// PHP file holding the snippet, a method or just some statements
+        #[arg(long, value_name = "FILE")]
+        snippet: String,
+        /// How many of the most similar methods to list
+        #[arg(long, value_name = "N", default_value_t = 10)]
+        top: usize,
+    },
+    /// Count the matches of a regex at git revisions from --since up to now, one --step apart
+    Trend {
+        /// Regex to count
+        pattern: String,
+        /// How far back to start, in any form `git log --since` accepts
+        #[arg(long, value_name = "DATE", default_value = "6 months")]
+        since: String,
+        /// Time between two counts
+        #[arg(long, value_parser = commands::trend::TREND_STEPS, default_value = "month")]
+        step: String,
+        /// Print CSV instead of a table
+        #[arg(long, default_value_t = false)]
+        csv: bool,
+    },
+    /// List the dependencies between namespaces or classes from use statements, `new` and static calls, with the cycles among them
+    Deps {
+        /// Whether to draw the graph between namespaces or between classes
+        #[arg(long, value_parser = commands::deps::DEPS_LEVELS, default_value = "namespace")]
+        level: String,
+        /// Print the graph in Graphviz DOT
+        #[arg(long, default_value_t = false, conflicts_with = "json")]
+        dot: bool,
+        /// Print the graph as one JSON document
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Report use statements, `new` and static calls that cross layer boundaries a rules file doesn't allow
+    Boundaries {
+        /// Rules file declaring the layers and which layers each may depend on
+        #[arg(long, value_name = "FILE", default_value = "boundaries.yml")]
+        rules: String,
+    },
+    /// Rename a method, its overrides and the calls that statically reach it, printing the changes as a diff
+    RenameMethod {
+        /// Current method name
+        old: String,
+        /// New method name
+        new: String,
+        /// Class declaring the method, short or fully qualified
+        #[arg(long, value_name = "CLASS")]
+        class: String,
+        /// Write the changes instead of only printing them
+        #[arg(long, default_value_t = false)]
+        write: bool,
+        /// Ask before applying each change, like `git add -p`
+        #[arg(long, default_value_t = false, conflicts_with = "write")]
+        confirm: bool,
+        /// Also change files covered by `protected_paths` in a phrep.toml
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
+    /// Rename a class, updating its namespace, the use statements and references to it, printing the changes as a diff
+    RenameClass {
+        /// Current class name, short or fully qualified
+        old: String,
+        /// New fully qualified class name
+        new: String,
+        /// Also move the file to where PSR-4 autoloading expects the new name
+        #[arg(long = "move", default_value_t = false)]
+        move_file: bool,
+        /// Write the changes instead of only printing them
+        #[arg(long, default_value_t = false)]
+        write: bool,
+        /// Ask before applying each change, like `git add -p`
+        #[arg(long, default_value_t = false, conflicts_with = "write")]
+        confirm: bool,
+        /// Also change files covered by `protected_paths` in a phrep.toml
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
+    /// Run the named queries of a batch file in one pass over the files, writing each query's matches to its own file
+    Batch {
+        /// Batch file of `[name]` tables with a pattern and optionally a mode, scope and output file
+        queries: String,
+    },
+    /// Compare two runs saved with --format json, printing the matches added and removed between them
+    DiffResults {
+        /// Matches of the earlier run, one JSON line each
+        old: String,
+        /// Matches of the later run, one JSON line each
+        new: String,
+    },
+    /// Time grep, basic and cached searches over a synthetic or given corpus and print their throughput
+    Bench {
+        /// Directory of PHP files to search instead of a generated corpus
+        #[arg(long, value_name = "DIR")]
+        corpus: Option<String>,
+        /// Number of files to generate
+        #[arg(long, value_name = "N", default_value_t = 1000)]
+        files: usize,
+        /// Runs per mode, the median is reported
+        #[arg(long, value_name = "N", default_value_t = 3)]
+        runs: usize,
+        /// Pattern to search for
+        #[arg(long, value_name = "PATTERN", default_value = "\\$this->db")]
+        query: String,
+    },
+    /// List the classes, methods and functions marked @deprecated or #[Deprecated] with the places still using them
+    Deprecations {
+        #[command(subcommand)]
+        action: Option<DeprecationsAction>,
+    },
+    /// Sort the statements of a method, and of the methods it calls up to --depth calls deep, into DB calls, HTTP calls, filesystem access, global state and pure ones
+    Effects {
+        /// The method (Class::method) or function
+        method: String,
+        /// How many calls deep to follow
+        #[arg(long, default_value_t = 0)]
+        depth: usize,
+    },
+    /// Restore the files the last rename wrote, from the journal in .phrep/undo
+    Undo {
+        /// Restore files even if they changed since they were written
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
+}
+
+/// What to do with the uses of deprecated symbols instead of listing them
+#[derive(Subcommand, Debug)]
+enum DeprecationsAction {
+    /// Fail when deprecated symbols are used in places an allowlist doesn't have, to gate CI
+    Gate {
+        /// File of allowed uses, as written by --update
+        #[arg(long, value_name = "FILE", default_value = "deprecations-allowlist.txt")]
+        allowlist: String,
+        /// Rewrite the allowlist from the uses found instead of checking them
+        #[arg(long, default_value_t = false)]
+        update: bool,
+    },
+}
+
+// The directories left out of every search unless --no-default-excludes is given
+const DEFAULT_EXCLUDES: [&str; 3] = ["vendor", "cache", "logs"];
+
+// Compiled --file-regex patterns
+static FILE_PATTERNS: std::sync::OnceLock<Vec<Regex>> = std::sync::OnceLock::new();
+
+// How --tests-only and --no-tests tell test files apart from the rest
+struct TestFilter {
+    tests_only: bool,
+    paths: Vec<String>,
+    class_pattern: Regex,
+}
+
+static TEST_FILTER: std::sync::OnceLock<TestFilter> = std::sync::OnceLock::new();
+
+// How --absolute-paths and --relative-to print file names instead of the default `~/...` form
+enum PathStyle {
+    Absolute,
+    RelativeTo(std::path::PathBuf),
 }
 
+static PATH_STYLE: std::sync::OnceLock<PathStyle> = std::sync::OnceLock::new();
+
+// The first and last line --range reports matches on
+static LINE_RANGE: std::sync::OnceLock<(usize, usize)> = std::sync::OnceLock::new();
+
+// Set by --no-filename
+static NO_FILENAME: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+
+// Set by --max-columns
+static MAX_COLUMNS: std::sync::OnceLock<usize> = std::sync::OnceLock::new();
+
+// Marks where --max-columns cut a line
+const OMITTED: &str = "[... omitted]";
+
+// Compiled --exclude-method pattern
+static EXCLUDED_METHODS: std::sync::OnceLock<Regex> = std::sync::OnceLock::new();
+
+// The declaration kinds --kind accepts
+const KINDS: [&str; 6] = ["method", "function", "closure", "property", "const", "class"];
+
+// The declaration kinds --kind limits the basic and --method-search searches to
+static SEARCHED_KINDS: std::sync::OnceLock<Vec<String>> = std::sync::OnceLock::new();
+
+// Superglobals reported by --superglobals, as they appear in variable_name nodes
+const SUPERGLOBALS: [&str; 9] = ["$_GET", "$_POST", "$_REQUEST", "$_SESSION", "$_COOKIE", "$_FILES", "$_SERVER", "$_ENV", "$GLOBALS"];
+
 #[derive(Debug)]
 enum SearchMode {
     Basic,
     Grep,
     MethodSearch,
+    Superglobals,
+    Instantiations(String),
+    Throws(String),
+    Catches(String),
+    ReturnsExpr(String),
+    NoReturn,
+    MatchArm(String),
+    TypeQuery(String),
+    DocTag(String),
+    Plugins(Vec<String>),
+    ConstUsage(String),
+    LateStaticBinding(String),
+    Tokens(String),
+    Heredoc(String),
+    DefaultValue(String),
+    CallbackOf(String),
+    DocSearch(String),
+    ClassRef(String),
+    InstanceOf(String),
+    Function(String),
+    Assertions(String),
+    Env,
+    I18n(Vec<String>),
+    Logs(Vec<String>),
+    ArgOf(Vec<String>, Option<usize>),
+    Inject(String),
 }
 
 impl From<&Cli> for SearchMode {
@@ -54,6 +718,58 @@ impl From<&Cli> for SearchMode {
             SearchMode::Grep
         } else if args.method_search {
             SearchMode::MethodSearch
+        } else if args.superglobals {
+            SearchMode::Superglobals
+        } else if let Some(class) = &args.new_class {
+            SearchMode::Instantiations(class.clone())
+        } else if let Some(class) = &args.throws {
+            SearchMode::Throws(class.clone())
+        } else if let Some(class) = &args.catches {
+            SearchMode::Catches(class.clone())
+        } else if let Some(pattern) = &args.returns_expr {
+            SearchMode::ReturnsExpr(pattern.clone())
+        } else if args.no_return {
+            SearchMode::NoReturn
+        } else if let Some(pattern) = &args.match_arm {
+            SearchMode::MatchArm(pattern.clone())
+        } else if let Some(type_query) = &args.type_query {
+            SearchMode::TypeQuery(type_query.clone())
+        } else if let Some(tag) = &args.doc_tag {
+            SearchMode::DocTag(tag.clone())
+        } else if !args.plugin.is_empty() {
+            SearchMode::Plugins(args.plugin.clone())
+        } else if let Some(constant) = &args.const_usage {
+            SearchMode::ConstUsage(constant.clone())
+        } else if let Some(method) = &args.late_static_binding {
+            SearchMode::LateStaticBinding(method.clone())
+        } else if let Some(snippet) = &args.tokens {
+            SearchMode::Tokens(snippet.clone())
+        } else if let Some(pattern) = &args.heredoc {
+            SearchMode::Heredoc(pattern.clone())
+        } else if let Some(pattern) = &args.default_value {
+            SearchMode::DefaultValue(pattern.clone())
+        } else if let Some(functions) = &args.callback_of {
+            SearchMode::CallbackOf(functions.clone())
+        } else if let Some(pattern) = &args.doc_search {
+            SearchMode::DocSearch(pattern.clone())
+        } else if let Some(class) = &args.class_ref {
+            SearchMode::ClassRef(class.clone())
+        } else if let Some(class) = &args.instanceof {
+            SearchMode::InstanceOf(class.clone())
+        } else if let Some(function) = &args.function {
+            SearchMode::Function(function.clone())
+        } else if let Some(pattern) = &args.assertions {
+            SearchMode::Assertions(pattern.clone())
+        } else if args.env {
+            SearchMode::Env
+        } else if args.i18n {
+            SearchMode::I18n(exclude_list(&args.i18n_functions))
+        } else if args.logs {
+            SearchMode::Logs(args.log_level.as_deref().map(exclude_list).unwrap_or_default())
+        } else if let Some(callables) = &args.arg_of {
+            SearchMode::ArgOf(exclude_list(callables), args.arg_index)
+        } else if let Some(language) = &args.inject {
+            SearchMode::Inject(language.clone())
         } else {
             SearchMode::Basic
         }
@@ -61,35 +777,351 @@ impl From<&Cli> for SearchMode {
 }
 
 fn main() -> Result<()> {
-    let args: Cli = Cli::parse();
-    
+    // The error is printed already, and grep exits with 2 on errors
+    let Ok(command_line) = grep_compat::rewrite(std::env::args_os()) else { std::process::exit(2) };
+    let mut args: Cli = Cli::parse_from(command_line);
+    let defaults: &[&str] = if args.no_default_excludes { &[] } else { &DEFAULT_EXCLUDES };
+    args.exclude_dirs = defaults.iter().map(|dir| dir.to_string()).chain(args.exclude.iter().cloned()).collect::<Vec<_>>().join(",");
+    guard::install();
+    interrupt::install();
+    if args.schema {
+        print!("{}", report::SCHEMA);
+        return Ok(());
+    }
+
     validate_args(&args)?;
 
+    if let Some(rev) = &args.rev {
+        git::set_revision(rev);
+    }
+    budget::set_timeout(args.regex_timeout);
+    if args.include_minified {
+        minified::include();
+    }
+
+    let mut file_patterns = Vec::new();
+    for file_regex in &args.file_regex {
+        match budget::regex(file_regex) {
+            Ok(pattern) => file_patterns.push(pattern),
+            Err(e) => {
+                eprintln!("Invalid --file-regex pattern: {}", e);
+                return Err(anyhow::anyhow!("Invalid --file-regex pattern"));
+            }
+        }
+    }
+    let _ = FILE_PATTERNS.set(file_patterns);
+
+    let mut imported = Vec::new();
+    for tool in &args.import_excludes {
+        match tool_excludes::load(tool, &args.dir) {
+            Ok(paths) => imported.push(paths),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return Err(anyhow::anyhow!("Could not import the {} excludes: {}", tool, e));
+            }
+        }
+    }
+    tool_excludes::set(imported);
+
+    // --assertions only looks at test files
+    if args.tests_only || args.no_tests || args.assertions.is_some() {
+        let _ = TEST_FILTER.set(TestFilter {
+            tests_only: !args.no_tests,
+            paths: exclude_list(&args.test_paths),
+            class_pattern: Regex::new(r"\bclass\s+\w+Test\b|\bextends\s+[\w\\]*TestCase\b")?,
+        });
+    }
+
+    if !args.kind.is_empty() {
+        let _ = SEARCHED_KINDS.set(args.kind.clone());
+    }
+
+    if let Some(exclude_method) = &args.exclude_method {
+        match budget::regex(exclude_method) {
+            Ok(pattern) => {
+                let _ = EXCLUDED_METHODS.set(pattern);
+            }
+            Err(e) => {
+                eprintln!("Invalid --exclude-method pattern: {}", e);
+                return Err(anyhow::anyhow!("Invalid --exclude-method pattern"));
+            }
+        }
+    }
+
+    if !args.include_generated {
+        generated::set_markers(args.generated_marker.iter().filter(|marker| !marker.is_empty()).cloned().collect());
+    }
+
+    if let Some(source) = &args.diff_filter {
+        match diff::AddedLines::load(source, &args.dir) {
+            Ok(added_lines) => diff::set_added_lines(added_lines),
+            Err(e) => {
+                eprintln!("Error: Could not read the diff '{}': {}", source, e);
+                return Err(anyhow::anyhow!("Could not read the diff '{}': {}", source, e));
+            }
+        }
+    }
+
+    if let Some(range) = &args.range {
+        let Some(span) = parse_range(range) else {
+            eprintln!("Error: --range takes START:END line numbers, got '{}'.", range);
+            return Err(anyhow::anyhow!("--range takes START:END line numbers, got '{}'", range));
+        };
+        let files = php_files(&args.dir, &args.file, &args.exclude_dirs).count();
+        if files != 1 {
+            eprintln!("Error: --range needs the search to cover a single file, {} files match; narrow it down with --file.", files);
+            return Err(anyhow::anyhow!("--range needs the search to cover a single file, {} files match", files));
+        }
+        let _ = LINE_RANGE.set(span);
+    }
+
+    if args.debug_matches {
+        debug_matches::enable(args.query.as_deref());
+    }
+
+    if let Some(statements) = args.context_statements {
+        context::set_statements(statements);
+    }
+    if args.relative_lines {
+        context::set_relative_lines();
+    }
+    if args.with_signature || args.with_doc {
+        context::set_header(args.with_signature, args.with_doc);
+    }
+
+    report::set_format(&args.format);
+    if args.no_filename {
+        let _ = NO_FILENAME.set(true);
+    }
+    if let Some(max_columns) = args.max_columns {
+        let _ = MAX_COLUMNS.set(max_columns);
+    }
+    if args.absolute_paths {
+        let _ = PATH_STYLE.set(PathStyle::Absolute);
+    } else if let Some(root) = &args.relative_to {
+        let _ = PATH_STYLE.set(PathStyle::RelativeTo(std::path::PathBuf::from(root)));
+    }
+    if let Some(max_width) = args.max_width {
+        report::set_max_width(max_width);
+    }
+    if args.heatmap {
+        report::set_heatmap();
+    }
+    if let Some(count) = args.sample {
+        report::set_sample(count);
+    }
+
+    if args.line_buffered {
+        cache::set_line_buffered();
+    }
+
+    if let Some(dir) = &args.extract_to
+        && let Err(e) = extract::set_dir(dir) {
+        eprintln!("Error: Could not create '{}': {}", dir, e);
+        return Err(anyhow::anyhow!("Could not create '{}': {}", dir, e));
+    }
+
+    if let Some(command) = &args.exec_filter
+        && let Err(e) = exec_filter::start(command) {
+        eprintln!("Error: Could not start --exec-filter '{}': {}", command, e);
+        return Err(anyhow::anyhow!("Could not start --exec-filter '{}': {}", command, e));
+    }
+
+    if let Some(command) = &args.exec {
+        exec::start(command, args.exec_jobs);
+    }
+
+    if let Some(command) = &args.command {
+        // Findings are reported even when the command fails the run because of them
+        let result = run_command(command, &args);
+        report::finish();
+        exec_filter::finish()?;
+        exec::finish()?;
+        interrupt::finish();
+        return result;
+    }
+
+    let query = args.query.as_deref().unwrap_or_default();
+    if args.grep_compat {
+        let selected = grep_compat::search(query, &args.dir, &args.file, &args.exclude_dirs);
+        interrupt::finish();
+        // grep exits with 1 when no line was selected and 2 on errors
+        std::process::exit(match selected {
+            Ok(true) => 0,
+            Ok(false) => 1,
+            Err(_) => 2,
+        });
+    }
+
     let search_mode = SearchMode::from(&args);
 
-    search(&args.query, &args.dir, &args.file, search_mode, &args.print_method, &args.exclude_dirs)?;
+    if args.cache {
+        let colorize = colored::control::SHOULD_COLORIZE.should_colorize();
+        // Everything that changes what a file's search prints, as cached output would otherwise
+        // be replayed for a different search
+        let options = format!("{:?}|{}|{}|{}|{:?}|{:?}|{:?}|{}|{}|{:?}|{}|{:?}|{}|{}", search_mode, query, args.print_method, colorize, (!args.include_generated).then_some(&args.generated_marker), args.kind, args.exclude_method,
+            args.no_filename, args.absolute_paths, args.relative_to, args.relative_lines, args.max_columns, args.with_signature, args.with_doc);
+        if let Err(e) = cache::enable(&options) {
+            eprintln!("Warning: Searching without the cache: {}", e);
+        }
+    }
+    search(query, &args.dir, &args.file, search_mode, &args.print_method, &args.exclude_dirs)?;
 
-    println!("Search completed successfully.");
+    report::finish();
+    exec_filter::finish()?;
+    exec::finish()?;
+    let skipped = minified::skipped();
+    if skipped > 0 {
+        report::summary(format!("Skipped {} binary or minified file{}, --include-minified searches them", skipped, if skipped == 1 { "" } else { "s" }));
+    }
+    interrupt::finish();
+    report::summary("Search completed successfully.");
     Ok(())
 }
 
+fn run_command(command: &Command, args: &Cli) -> Result<()> {
+    match command {
+        Command::IfaceDiff { interface } => commands::iface_diff::iface_diff(interface, &args.dir, &args.file, &args.exclude_dirs),
+        Command::Recursion { max_depth } => commands::recursion::recursion(*max_depth, &args.dir, &args.file, &args.exclude_dirs),
+        Command::Untyped { summary } => commands::untyped::untyped(*summary, &args.dir, &args.file, &args.exclude_dirs),
+        Command::UnusedParams { skip_overrides } => commands::unused_params::unused_params(*skip_overrides, &args.dir, &args.file, &args.exclude_dirs),
+        Command::StrictTypes { ignore_paths, forbid } => commands::strict_types::strict_types(ignore_paths, *forbid, &args.dir, &args.file, &args.exclude_dirs),
+        Command::Psr4Check { composer } => commands::psr4_check::psr4_check(composer.as_deref(), &args.dir, &args.file, &args.exclude_dirs),
+        Command::DuplicatesSymbols => commands::duplicate_symbols::duplicate_symbols(&args.dir, &args.file, &args.exclude_dirs),
+        Command::Outline { file } => commands::outline::outline(file),
+        Command::ClassDump { class, members, pattern } => commands::class_dump::class_dump(class, members, pattern.as_deref(), &args.dir, &args.file, &args.exclude_dirs),
+        Command::Symbols { pattern, kind, json } => commands::symbols::symbols(kind, pattern.as_deref(), *json, &args.dir, &args.file, &args.exclude_dirs),
+        Command::Xref { file } => commands::xref::xref(file),
+        Command::MethodDiff { method, from, to } => commands::method_diff::method_diff(method, from, to, &args.dir),
+        Command::History { pattern, since } => commands::history::history(pattern, since.as_deref(), &args.dir, &args.file, &args.exclude_dirs),
+        Command::Accessors { property } => commands::accessors::accessors(property, &args.dir, &args.file, &args.exclude_dirs),
+        Command::Properties { readonly, mutable, typed_properties, untyped_properties } => {
+            let filter = commands::properties::PropertyFilter {
+                readonly: (*readonly || *mutable).then_some(*readonly),
+                typed: (*typed_properties || *untyped_properties).then_some(*typed_properties),
+            };
+            commands::properties::properties(&filter, &args.dir, &args.file, &args.exclude_dirs)
+        }
+        Command::UninitializedProps { include_nullable, init_methods } => {
+            commands::uninitialized_props::uninitialized_props(*include_nullable, &exclude_list(init_methods), &args.dir, &args.file, &args.exclude_dirs)
+        }
+        Command::Impact { method, static_only, instance_only } => {
+            commands::impact::impact(method, (*static_only || *instance_only).then_some(*static_only), &args.dir, &args.file, &args.exclude_dirs)
+        }
+        Command::Throws { method, depth } => commands::throws::throws(method, *depth, &args.dir, &args.file, &args.exclude_dirs),
+        Command::Similar { snippet, top } => commands::similar::similar(snippet, *top, &args.dir, &args.file, &args.exclude_dirs),
+        Command::Trend { pattern, since, step, csv } => commands::trend::trend(pattern, since, step, *csv, &args.dir, &args.file, &args.exclude_dirs),
+        Command::Deps { level, dot, json } => commands::deps::deps(level, *dot, *json, &args.dir, &args.file, &args.exclude_dirs),
+        Command::Boundaries { rules } => commands::boundaries::boundaries(rules, &args.dir, &args.file, &args.exclude_dirs),
+        Command::RenameMethod { old, new, class, write, confirm, force } => {
+            edit::set_force(*force);
+            commands::rename_method::rename_method(old, new, class, edit::Mode::new(*write, *confirm), &args.dir, &args.file, &args.exclude_dirs)
+        }
+        Command::RenameClass { old, new, move_file, write, confirm, force } => {
+            edit::set_force(*force);
+            commands::rename_class::rename_class(old, new, *move_file, edit::Mode::new(*write, *confirm), &args.dir, &args.file, &args.exclude_dirs)
+        }
+        Command::DiffResults { old, new } => commands::diff_results::diff_results(old, new),
+        Command::Batch { queries } => commands::batch::batch(queries, &args.dir, &args.file, &args.exclude_dirs),
+        Command::Bench { corpus, files, runs, query } => commands::bench::bench(corpus.as_deref(), *files, *runs, query),
+        Command::Deprecations { action: None } => commands::deprecations::deprecations(&args.dir, &args.file, &args.exclude_dirs),
+        Command::Deprecations { action: Some(DeprecationsAction::Gate { allowlist, update }) } => commands::deprecations::gate(allowlist, *update, &args.dir, &args.file, &args.exclude_dirs),
+        Command::Effects { method, depth } => commands::effects::effects(method, *depth, &args.dir, &args.file, &args.exclude_dirs),
+        Command::Undo { force } => commands::undo::undo(*force, &args.dir),
+    }
+}
+
 fn validate_args(args: &Cli) -> Result<()> {
-    if args.query.is_empty() {
+    if args.query.as_ref().is_some_and(|query| query.is_empty()) {
         eprintln!("Error: Query cannot be empty.");
         return Err(anyhow::anyhow!("Query cannot be empty"));
     }
 
+    if args.command.is_some() && args.query.is_some() {
+        eprintln!("Error: Cannot use a search query together with a command.");
+        return Err(anyhow::anyhow!("Cannot use a search query together with a command"));
+    }
+
+    if args.heatmap && (args.format == "phpstan" || args.format == "checkstyle") {
+        eprintln!("Error: --heatmap can only be printed as text, table or json.");
+        return Err(anyhow::anyhow!("--heatmap can only be printed as text, table or json"));
+    }
+
+    if args.exec_filter.is_some() && args.cache {
+        eprintln!("Error: --exec-filter can't be used with --cache, cached results would skip the filter.");
+        return Err(anyhow::anyhow!("--exec-filter can't be used with --cache"));
+    }
+
+    if args.exec.is_some() && (args.cache || args.sample.is_some()) {
+        eprintln!("Error: --exec can't be used with --cache or --sample.");
+        return Err(anyhow::anyhow!("--exec can't be used with --cache or --sample"));
+    }
+
+    if args.exec_jobs == 0 {
+        eprintln!("Error: --exec-jobs must be at least 1.");
+        return Err(anyhow::anyhow!("--exec-jobs must be at least 1"));
+    }
+
+    if args.sample.is_some() && (args.heatmap || args.print_method || args.method_search || args.cache || args.context_statements.is_some() || args.with_signature || args.with_doc) {
+        eprintln!("Error: --sample can't be used with --heatmap, --print-method, --method-search, --cache, --context-statements, --with-signature or --with-doc.");
+        return Err(anyhow::anyhow!("--sample can't be used with --heatmap, --print-method, --method-search, --cache, --context-statements, --with-signature or --with-doc"));
+    }
+
+    if (args.format != "text" || args.heatmap) && (args.print_method || args.method_search || args.cache) {
+        eprintln!("Error: --format {} and --heatmap can't be used with --print-method, --method-search or --cache.", args.format);
+        return Err(anyhow::anyhow!("--format {} and --heatmap can't be used with --print-method, --method-search or --cache", args.format));
+    }
+
+    if (args.format != "text" || args.heatmap)
+        && let Some(name) = match &args.command {
+            Some(Command::Outline { .. }) => Some("outline"),
+            Some(Command::Symbols { .. }) => Some("symbols"),
+            Some(Command::Xref { .. }) => Some("xref"),
+            Some(Command::MethodDiff { .. }) => Some("method-diff"),
+            Some(Command::History { .. }) => Some("history"),
+            Some(Command::Trend { .. }) => Some("trend"),
+            Some(Command::Deps { .. }) => Some("deps"),
+            Some(Command::RenameMethod { .. }) => Some("rename-method"),
+            Some(Command::RenameClass { .. }) => Some("rename-class"),
+            Some(Command::Batch { .. }) => Some("batch"),
+            Some(Command::DiffResults { .. }) => Some("diff-results"),
+            Some(Command::Bench { .. }) => Some("bench"),
+            Some(Command::Undo { .. }) => Some("undo"),
+            _ => None,
+        } {
+        eprintln!("Error: The {} command doesn't report findings, --format and --heatmap can't be used with it.", name);
+        return Err(anyhow::anyhow!("The {} command doesn't report findings, --format and --heatmap can't be used with it", name));
+    }
+
+    if (args.context_statements.is_some() || args.with_signature || args.with_doc) && (args.format != "text" || args.heatmap) {
+        eprintln!("Error: --context-statements, --with-signature and --with-doc can only be printed with --format text.");
+        return Err(anyhow::anyhow!("--context-statements, --with-signature and --with-doc can only be printed with --format text"));
+    }
+
+    if !args.kind.is_empty() && !matches!(SearchMode::from(args), SearchMode::Basic | SearchMode::MethodSearch) {
+        eprintln!("Error: --kind only applies to the basic and --method-search searches.");
+        return Err(anyhow::anyhow!("--kind only applies to the basic and --method-search searches"));
+    }
+
     if args.grep && args.method_search {
         eprintln!("Error: Cannot use both --grep and --method-search at the same time.");
         return Err(anyhow::anyhow!("Cannot use both --grep and --method-search at the same time"));
     }
 
-    if !args.exclude_dirs.is_empty() {
-        let dirs: Vec<&str> = args.exclude_dirs.split(',').collect();
-        if dirs.is_empty() || dirs.iter().any(|d| d.trim().is_empty()) {
-            eprintln!("Error: Invalid exclude_dirs format. Use a comma-separated list.");
-            return Err(anyhow::anyhow!("Invalid exclude_dirs format. Use a comma-separated list."));
-        }
+    if args.rev.is_some() && matches!(args.command, Some(Command::RenameMethod { .. } | Command::RenameClass { .. } | Command::Undo { .. })) {
+        eprintln!("Error: --rev can't be used with commands that edit files.");
+        return Err(anyhow::anyhow!("--rev can't be used with commands that edit files"));
+    }
+
+    if let Some(rev) = &args.rev
+        && git::git(&args.dir, &["rev-parse", "--verify", "--quiet", &format!("{}^{{commit}}", rev)]).is_err() {
+        eprintln!("Error: '{}' is not a revision of the git repository containing '{}'.", rev, args.dir);
+        return Err(anyhow::anyhow!("'{}' is not a revision of the git repository containing '{}'", rev, args.dir));
+    }
+
+    if args.exclude.iter().any(|dir| dir.trim().trim_matches('/').is_empty()) {
+        eprintln!("Error: Invalid --exclude format. Use a directory or a comma-separated list.");
+        return Err(anyhow::anyhow!("Invalid --exclude format. Use a directory or a comma-separated list."));
     }
 
     Ok(())
@@ -100,142 +1132,503 @@ fn search(query: &str, dir: &str, file: &str, mode: SearchMode, print_method: &b
         SearchMode::Basic => basic_search(query, dir, file, print_method, exclude_dirs),
         SearchMode::Grep => grep_search(query, dir, file, exclude_dirs),
         SearchMode::MethodSearch => method_search(query, dir, file, exclude_dirs),
+        SearchMode::Superglobals => superglobal_search(dir, file, exclude_dirs),
+        SearchMode::Instantiations(class) => instantiation_search(&class, dir, file, exclude_dirs),
+        SearchMode::Throws(class) => throws_search(&class, dir, file, exclude_dirs),
+        SearchMode::Catches(class) => catches_search(&class, dir, file, exclude_dirs),
+        SearchMode::ReturnsExpr(pattern) => returns_expr_search(&pattern, dir, file, exclude_dirs),
+        SearchMode::NoReturn => no_return_search(dir, file, exclude_dirs),
+        SearchMode::MatchArm(pattern) => match_arm_search(&pattern, dir, file, exclude_dirs),
+        SearchMode::TypeQuery(type_query) => type_query_search(&type_query, dir, file, exclude_dirs),
+        SearchMode::DocTag(tag) => doc_tag_search(&tag, dir, file, exclude_dirs),
+        SearchMode::Plugins(libraries) => plugin_search(&libraries, dir, file, exclude_dirs),
+        SearchMode::ConstUsage(constant) => const_usage_search(&constant, dir, file, exclude_dirs),
+        SearchMode::LateStaticBinding(method) => late_static_binding_search(&method, dir, file, exclude_dirs),
+        SearchMode::Tokens(snippet) => token_search(&snippet, dir, file, exclude_dirs),
+        SearchMode::Heredoc(pattern) => heredoc_search(&pattern, dir, file, exclude_dirs),
+        SearchMode::DefaultValue(pattern) => default_value_search(&pattern, dir, file, exclude_dirs),
+        SearchMode::CallbackOf(functions) => callback_search(query, &functions, dir, file, exclude_dirs),
+        SearchMode::DocSearch(pattern) => doc_search(&pattern, dir, file, exclude_dirs),
+        SearchMode::ClassRef(class) => class_ref_search(&class, dir, file, exclude_dirs),
+        SearchMode::InstanceOf(class) => instanceof_search(&class, dir, file, exclude_dirs),
+        SearchMode::Function(function) => function_search(&function, dir, file, exclude_dirs),
+        SearchMode::Assertions(pattern) => assertion_search(&pattern, dir, file, exclude_dirs),
+        SearchMode::Env => env_search(dir, file, exclude_dirs),
+        SearchMode::I18n(functions) => i18n_search(&functions, dir, file, exclude_dirs),
+        SearchMode::Logs(levels) => log_search(&levels, dir, file, exclude_dirs),
+        SearchMode::ArgOf(callables, index) => arg_search(query, &callables, index, dir, file, exclude_dirs),
+        SearchMode::Inject(language) => inject_search(query, &language, dir, file, exclude_dirs),
     }
 }
 
+// A path as printed and written into reports. Reports meant for tools keep the home directory,
+// as they can't expand `~`
 fn format_filename(path: &std::path::Path) -> String {
+    if let Some(filename) = styled_path(path) {
+        return filename;
+    }
     let mut filename = path.display().to_string();
-    if let Some(home_dir) = home_dir() {
-        if let Some(home_dir_str) = home_dir.to_str() {
-            if filename.starts_with(home_dir_str) {
-                filename = filename.replace(home_dir_str, "~");
-            }
-        }
+    if !report::is_structured()
+        && let Some(home_dir) = home_dir()
+        && let Some(home_dir_str) = home_dir.to_str()
+        && filename.starts_with(home_dir_str) {
+        filename = filename.replace(home_dir_str, "~");
     }
-    if filename.starts_with("./") {
-        filename = filename[2..].to_string();
+    if let Some(stripped) = filename.strip_prefix("./") {
+        filename = stripped.to_string();
     }
 
     filename
 }
 
-fn search_in_function_body(content: &str, pattern: &Regex, parser: &mut TreeSitterParser, path: &std::path::Path, print_method: &bool) -> Result<()> {
-    let tree = match parser.parse(&content, None) {
-        Some(tree) => tree,
-        None => {
-            return Err(anyhow::anyhow!("Could not parse content as PHP"));
+// The path as asked for with --absolute-paths or --relative-to, if either was given
+fn styled_path(path: &std::path::Path) -> Option<String> {
+    let absolute = |path: &std::path::Path| std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let path = absolute(path);
+    match PATH_STYLE.get()? {
+        PathStyle::Absolute => Some(path.display().to_string()),
+        PathStyle::RelativeTo(root) => {
+            let root = absolute(root);
+            let common = path.components().zip(root.components()).take_while(|(a, b)| a == b).count();
+            let mut relative = std::path::PathBuf::new();
+            for _ in root.components().skip(common) {
+                relative.push("..");
+            }
+            for component in path.components().skip(common) {
+                relative.push(component);
+            }
+            Some(relative.display().to_string())
         }
+    }
+}
+
+// Reads a file to search, warning and returning None if it can't be read. With --rev the
+// content comes from that revision rather than the working tree
+fn read_file(path: &std::path::Path) -> Option<String> {
+    let content = match git::revision() {
+        Some(rev) => git::show_path(path, rev),
+        None => std::fs::read_to_string(path).map_err(anyhow::Error::from),
     };
-    let root_node = tree.root_node();
-    
-    for node in root_node.children(&mut tree.walk()) {
-        if node.kind() == "class_declaration" {
-            let class_body = node.child_by_field_name("body");
-            let cursor = class_body.unwrap();
-            for method in class_body.unwrap().named_children(&mut cursor.walk()) {
-                if method.kind() == "method_declaration" || method.kind() == "function_declaration" {
-                    let name_node = method.child_by_field_name("name");
-                    let body_node = method.child_by_field_name("body");
-                    if let (Some(name_node), Some(body_node)) = (name_node, body_node) {
-                        let func_name = match name_node.utf8_text(content.as_bytes()) {
-                            Ok(name) => name,
-                            Err(_) => {
-                                eprintln!("Warning: Invalid UTF-8 in function name in file '{}'", path.display());
-                                continue;
-                            }
-                        };
+    match content {
+        Ok(content) if minified::skip(path, &content) => None,
+        Ok(content) => Some(content),
+        Err(e) if !minified::is_included() && e.downcast_ref::<std::io::Error>().is_some_and(|e| e.kind() == std::io::ErrorKind::InvalidData) => {
+            minified::skip_binary(path);
+            None
+        }
+        Err(e) => {
+            eprintln!("Warning: Could not read file '{}': {}", path.display(), e);
+            None
+        }
+    }
+}
 
-                        let body_text = match body_node.utf8_text(content.as_bytes()) {
-                            Ok(text) => text,
-                            Err(_) => {
-                                eprintln!("Warning: Invalid UTF-8 in function body in file '{}'", path.display());
-                                continue;
-                            }
-                        };
-                        let start_row = body_node.start_position().row;
-                        let start = body_node.start_position().row;
-                        let end = body_node.end_position().row;
-                        for (i, line) in content.lines().enumerate().skip(start + 1).take(end - start + 1) {
-                            if pattern.is_match(line) {
-                                let filename = format_filename(path);
-                                let file_name_styled = filename.bold().blue();
-                                let func_name_styled = func_name.bold().yellow();
+// Parses file content as PHP, warning and returning None if tree-sitter gives up
+fn parse_content(parser: &mut TreeSitterParser, content: &str, path: &std::path::Path) -> Option<tree_sitter::Tree> {
+    let tree = parser.parse(content, None);
+    if tree.is_none() {
+        eprintln!("Warning: Could not parse file '{}' as PHP", path.display());
+    }
+    tree
+}
 
-                                if *print_method {
-                                    if let Some(_pattern_str) = pattern.as_str().chars().next() {
-                                        let body_text_styled = body_text.replace(pattern.as_str(), &format!("{}", pattern.as_str().bold().red()));
-                                        println!("{}:{}: {}() → {}", file_name_styled, start_row + i + 1, func_name_styled, body_text_styled.trim());
-                                    } else {
-                                        println!("{}:{}: {}() → {}", file_name_styled, start_row + i + 1, func_name_styled, body_text.trim());
-                                    }
-                                } else {
-                                    if let Some(_pattern_str) = pattern.as_str().chars().next() {
-                                        let line_styled = line.replace(pattern.as_str(), &format!("{}", pattern.as_str().bold().red()));
-                                        println!("{}:{}: {}() → {}", file_name_styled, start_row + i + 1, func_name_styled, line_styled.trim());
-                                    } else {
-                                        println!("{}:{}: {}() → {}", file_name_styled, start_row + i + 1, func_name_styled, line.trim());
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+// Parses every PHP file under the search root whose content passes `prefilter` and hands
+// the path, content and syntax tree to `visit`, skipping files that can't be read or parsed
+fn for_each_php_tree(dir: &str, file: &str, exclude_dirs: &str, prefilter: impl Fn(&str) -> bool, mut visit: impl FnMut(&std::path::Path, &str, &tree_sitter::Tree)) -> Result<()> {
+    let mut parser = php::new_parser()?;
+    for path in php_files(dir, file, exclude_dirs) {
+        let path = path.as_path();
+        let Some(content) = read_file(path) else { continue };
+        if !prefilter(&content) {
+            continue;
         }
+        cache::cached(path, &content, || {
+            if let Some(tree) = parse_content(&mut parser, &content, path) {
+                visit(path, &content, &tree);
+            }
+        });
     }
-    
-    // Now recursively find all function_definition nodes (including nested ones)
-    search_in_all_functions(&root_node, content, pattern, path, print_method)?;
-    
     Ok(())
 }
 
-// Recursive function to search inside all function_definition nodes regardless of nesting
-fn search_in_all_functions(node: &tree_sitter::Node, content: &str, pattern: &Regex, path: &std::path::Path, print_method: &bool) -> Result<()> {
-    if node.kind() == "function_definition" {
-        if let Some(name_node) = node.child_by_field_name("name") {
-            let func_name = match name_node.utf8_text(content.as_bytes()) {
-                Ok(name) => name,
-                Err(_) => {
-                    eprintln!("Warning: Invalid UTF-8 in function name in file '{}'", path.display());
-                    return Ok(());
+// Highlights every match of `pattern` in a line
+fn highlight(line: &str, pattern: &Regex) -> String {
+    // Only the part of a long line that gets printed is worth highlighting
+    if let Some(clipped) = clip(line) {
+        return format!("{} {}", highlight(clipped, pattern), OMITTED.dimmed());
+    }
+    pattern.replace_all(line, |caps: &regex::Captures| format!("{}", caps[0].bold().red())).to_string()
+}
+
+// The first --max-columns characters of a line, if it's longer. Indentation isn't printed, so
+// it doesn't count
+fn clip(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    let (end, _) = line.char_indices().nth(*MAX_COLUMNS.get()?)?;
+    Some(&line[..end])
+}
+
+// Cuts a line that may already be highlighted down to --max-columns visible characters, so
+// one-line arrays and serialized data don't flood the output. Like clip, indentation doesn't count
+fn clip_styled(line: &str) -> std::borrow::Cow<'_, str> {
+    let Some(&max_columns) = MAX_COLUMNS.get() else { return line.into() };
+    let line = line.trim_start();
+    let plain = report::strip_colors(line);
+    if plain.chars().count() <= max_columns || plain.ends_with(OMITTED) {
+        return line.into();
+    }
+    let mut clipped = String::new();
+    let mut visible = 0;
+    let mut styled = false;
+    let mut chars = line.chars();
+    while visible < max_columns && let Some(c) = chars.next() {
+        clipped.push(c);
+        if c == '\x1b' {
+            styled = true;
+            for c in chars.by_ref() {
+                clipped.push(c);
+                if c.is_ascii_alphabetic() {
+                    break;
                 }
-            };
-            
-            if let Some(body_node) = node.child_by_field_name("body") {
-                let body_text = match body_node.utf8_text(content.as_bytes()) {
-                    Ok(text) => text,
-                    Err(_) => {
-                        eprintln!("Warning: Invalid UTF-8 in function body in file '{}'", path.display());
-                        return Ok(());
-                    }
-                };
-                let start_row = body_node.start_position().row;
-                
-                for (i, line) in body_text.lines().enumerate() {
-                    if pattern.is_match(line) {
-                        let filename = format_filename(path);
-                        let file_name_styled = filename.bold().blue();
-                        let func_name_styled = func_name.bold().yellow();
-
-                        if *print_method {
-                            if let Some(_pattern_str) = pattern.as_str().chars().next() {
-                                let body_text_styled = body_text.replace(pattern.as_str(), &format!("{}", pattern.as_str().bold().red()));
-                                println!("{}:{}: {}() → {}", file_name_styled, start_row + i + 1, func_name_styled, body_text_styled.trim());
-                            } else {
-                                println!("{}:{}: {}() → {}", file_name_styled, start_row + i + 1, func_name_styled, body_text.trim());
+            }
+        } else {
+            visible += 1;
+        }
+    }
+    if styled {
+        clipped.push_str("\x1b[0m");
+    }
+    format!("{} {}", clipped, OMITTED.dimmed()).into()
+}
+
+// Returns the line a node starts on with the part of the node on that line highlighted
+fn highlight_node(lines: &[&str], node: tree_sitter::Node) -> String {
+    let row = node.start_position().row;
+    let line = lines.get(row).copied().unwrap_or("");
+    let start = node.start_position().column.min(line.len());
+    let end = if node.end_position().row == row { node.end_position().column.clamp(start, line.len()) } else { line.len() };
+    if !line.is_char_boundary(start) || !line.is_char_boundary(end) {
+        return line.to_string();
+    }
+    format!("{}{}{}", &line[..start], line[start..end].bold().red(), &line[end..])
+}
+
+// Prints a single match, with the enclosing function when there is one
+fn print_match(path: &std::path::Path, line_number: usize, func_name: Option<&str>, line: &str) {
+    if func_name.is_some_and(is_excluded_method) {
+        return;
+    }
+    print_symbol_match(path, line_number, func_name.map(|func_name| format!("{}()", func_name)).as_deref(), line);
+}
+
+// Prints a match in a function body, naming the match or switch arm it's in when there is one
+fn print_body_match(path: &std::path::Path, line_number: usize, func_name: &str, arm: Option<String>, line: &str) {
+    if is_excluded_method(func_name) {
+        return;
+    }
+    let symbol = match arm {
+        Some(arm) => format!("{}() [{}]", func_name, arm),
+        None => format!("{}()", func_name),
+    };
+    print_symbol_match(path, line_number, Some(&symbol), line);
+}
+
+// Finds the arm around line `i` of a function body by looking at the first token on that line
+fn arm_at(body: tree_sitter::Node, i: usize, line: &str, content: &str) -> Option<String> {
+    let mut column = line.len() - line.trim_start().len();
+    if i == 0 {
+        column += body.start_position().column;
+    }
+    let point = tree_sitter::Point::new(body.start_position().row + i, column);
+    let node = body.descendant_for_point_range(point, point)?;
+    php::enclosing_arm(node).map(|arm| php::arm_label(arm, content))
+}
+
+// Prints a single match attributed to any kind of symbol (class, property, constant, ...)
+fn print_symbol_match(path: &std::path::Path, line_number: usize, symbol: Option<&str>, line: &str) {
+    if !is_reported(path, line_number) {
+        return;
+    }
+    let line = clip_styled(line);
+    let line = match exec_filter::check(path, line_number, symbol, &line) {
+        exec_filter::Verdict::Accept(Some(annotation)) => format!("{} {}", line.trim(), format!("[{}]", annotation).cyan()),
+        exec_filter::Verdict::Accept(None) => line.to_string(),
+        exec_filter::Verdict::Reject => return,
+    };
+    exec::run(path, line_number, symbol, &line);
+    let line = match debug_matches::annotation(path, line_number) {
+        Some(kind) => format!("{} {}", line.trim(), format!("[{}]", kind).dimmed()),
+        None => line,
+    };
+    if report::collects() {
+        report::add(path, line_number, symbol, &line);
+        return;
+    }
+    context::print_header();
+    cache::emit(text_match(path, line_number, symbol, &line));
+    context::print(path, line_number);
+}
+
+// A match as printed with --format text
+fn text_match(path: &std::path::Path, line_number: usize, symbol: Option<&str>, line: &str) -> String {
+    match symbol {
+        Some(symbol) => format!("{}: {} → {}", location(path, line_number), symbol.bold().yellow(), line.trim()),
+        None => format!("{} → {}", location(path, line_number), line.trim()),
+    }
+}
+
+// The `file:line` a match is printed with, or just the line with --no-filename
+fn location(path: &std::path::Path, line_number: usize) -> String {
+    let relative = context::relative_line(path, line_number).unwrap_or_default().dimmed();
+    if NO_FILENAME.get().is_some() {
+        return format!("{}{}", line_number, relative);
+    }
+    format!("{}:{}{}", format_filename(path).bold().blue(), line_number, relative)
+}
+
+// Checks a method or function name, bare or written Class::method, against --exclude-method
+fn is_excluded_method(name: &str) -> bool {
+    let name = name.rsplit("::").next().unwrap_or(name);
+    EXCLUDED_METHODS.get().is_some_and(|pattern| pattern.is_match(name))
+}
+
+// With --diff-filter only lines added by the diff are reported, and generated code never is
+// without --include-generated
+fn is_reported(path: &std::path::Path, line_number: usize) -> bool {
+    LINE_RANGE.get().is_none_or(|(start, end)| (*start..=*end).contains(&line_number))
+        && diff::added_lines().is_none_or(|added_lines| added_lines.contains(path, line_number))
+        && !generated::is_generated(path, line_number)
+}
+
+// Reads a --range span, either end can be left out
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let (start, end) = range.split_once(':')?;
+    let start = if start.is_empty() { 1 } else { start.trim().parse().ok()? };
+    let end = if end.is_empty() { usize::MAX } else { end.trim().parse().ok()? };
+    (start <= end).then_some((start, end))
+}
+
+// Walks the search directory (or lists it at --rev) and yields the PHP files matching the
+// file filter, skipping anything inside an excluded directory or left out by a phrep.toml or a
+// config from --import-excludes
+fn php_files<'a>(dir: &'a str, file: &'a str, exclude_dirs: &str) -> Box<dyn Iterator<Item = std::path::PathBuf> + 'a> {
+    let exclude_dirs = exclude_list(exclude_dirs);
+    let paths: Vec<std::path::PathBuf> = match git::revision() {
+        Some(rev) => match git::files_at(dir, rev) {
+            Ok(paths) => paths.into_iter().filter(|path| !is_excluded(path, dir, &exclude_dirs) && !tool_excludes::excluded(path, dir)).collect(),
+            Err(e) => {
+                eprintln!("Warning: Could not list files at '{}': {}", rev, e);
+                Vec::new()
+            }
+        },
+        None => walk::files(std::path::Path::new(dir), &|path| is_excluded(path, dir, &exclude_dirs) || tool_excludes::excluded(path, dir)),
+    };
+    let configs = config::Configs::load(&paths);
+
+    // The extension check already covers the default --file
+    let file = if file == ".php" { "" } else { file };
+    Box::new(paths.into_iter()
+        .filter(move |path| configs.selects(path) && tool_excludes::selects(path, dir))
+        .filter(move |path| file_selected(path, dir, file))
+        .filter(|path| TEST_FILTER.get().is_none_or(|filter| is_test_file(path, filter) == filter.tests_only))
+        .take_while(|_| !interrupt::interrupted())
+        .inspect(|_| budget::start_file()))
+}
+
+// Decides from the path where possible and only reads the file to look at its classes otherwise
+fn is_test_file(path: &std::path::Path, filter: &TestFilter) -> bool {
+    let path_str = path.to_string_lossy().replace('\\', "/");
+    if filter.paths.iter().any(|fragment| path_str.contains(fragment.as_str())) {
+        return true;
+    }
+    read_file(path).is_some_and(|content| filter.class_pattern.is_match(&content))
+}
+
+// Applies --file (a substring of the file name) and --file-regex (patterns of which at least
+// one has to match the path below the search directory)
+fn file_selected(path: &std::path::Path, dir: &str, file: &str) -> bool {
+    if !path.file_name().is_some_and(|name| name.to_string_lossy().contains(file)) {
+        return false;
+    }
+    let patterns = FILE_PATTERNS.get().map(Vec::as_slice).unwrap_or_default();
+    let relative_path = path.strip_prefix(dir).unwrap_or(path).to_string_lossy();
+    patterns.is_empty() || patterns.iter().any(|pattern| pattern.is_match(&relative_path))
+}
+
+// Splits a comma-separated list such as the excluded directories into its non-empty entries
+fn exclude_list(exclude_dirs: &str) -> Vec<String> {
+    exclude_dirs.split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+// Checks a path against the excluded directories. Each one matches whole components of the
+// path below the search directory, so `cache` leaves out `src/cache/` but not `CacheManager.php`
+// or a search directory that happens to be inside a `cache` directory, and `src/Legacy` leaves
+// out that directory only
+fn is_excluded(path: &std::path::Path, dir: &str, exclude_dirs: &[String]) -> bool {
+    let relative_path = path.strip_prefix(dir).unwrap_or(path);
+    let components: Vec<_> = relative_path.components()
+        .filter_map(|component| match component {
+            std::path::Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        })
+        .collect();
+    exclude_dirs.iter().any(|excluded_dir| {
+        let excluded: Vec<&str> = excluded_dir.trim_matches('/').split('/').filter(|part| !part.is_empty() && *part != ".").collect();
+        !excluded.is_empty() && components.windows(excluded.len()).any(|window| window.iter().zip(&excluded).all(|(component, part)| component == part))
+    })
+}
+
+fn search_in_function_body(content: &str, pattern: &Regex, parser: &mut TreeSitterParser, path: &std::path::Path, print_method: &bool) -> Result<()> {
+    let tree = match parser.parse(content, None) {
+        Some(tree) => tree,
+        None => {
+            return Err(anyhow::anyhow!("Could not parse content as PHP"));
+        }
+    };
+    search_function_bodies(content, pattern, &tree, path, print_method)
+}
+
+// Prints the lines of method and function bodies matching the pattern in an already parsed file
+fn search_function_bodies(content: &str, pattern: &Regex, tree: &tree_sitter::Tree, path: &std::path::Path, print_method: &bool) -> Result<()> {
+    let root_node = tree.root_node();
+    
+    for node in root_node.children(&mut tree.walk()) {
+        if node.kind() == "class_declaration" {
+            let Some(class_body) = node.child_by_field_name("body") else {
+                eprintln!("Warning: Class without a body at '{}:{}', skipping it", path.display(), node.start_position().row + 1);
+                continue;
+            };
+            for method in class_body.named_children(&mut class_body.walk()) {
+                if method.kind() == "method_declaration" || method.kind() == "function_declaration" {
+                    let name_node = method.child_by_field_name("name");
+                    let body_node = method.child_by_field_name("body");
+                    if let (Some(name_node), Some(body_node)) = (name_node, body_node) {
+                        let func_name = match name_node.utf8_text(content.as_bytes()) {
+                            Ok(name) => name,
+                            Err(_) => {
+                                eprintln!("Warning: Invalid UTF-8 in function name in file '{}'", path.display());
+                                continue;
                             }
-                        } else {
-                            if let Some(_pattern_str) = pattern.as_str().chars().next() {
-                                let line_styled = line.replace(pattern.as_str(), &format!("{}", pattern.as_str().bold().red()));
-                                println!("{}:{}: {}() → {}", file_name_styled, start_row + i + 1, func_name_styled, line_styled.trim());
-                            } else {
-                                println!("{}:{}: {}() → {}", file_name_styled, start_row + i + 1, func_name_styled, line.trim());
+                        };
+
+                        let body_text = match body_node.utf8_text(content.as_bytes()) {
+                            Ok(text) => text,
+                            Err(_) => {
+                                eprintln!("Warning: Invalid UTF-8 in function body in file '{}'", path.display());
+                                continue;
+                            }
+                        };
+                        let start_row = body_node.start_position().row;
+                        let nested = nested_function_bodies(body_node);
+                        context::begin_function(method, content);
+                        // The lines of the body itself, from the opening brace to the closing one
+                        for (i, line) in body_text.lines().enumerate() {
+                            if budget::exceeded(path) {
+                                break;
+                            }
+                            if has_own_match(pattern, line, body_text, body_node.start_byte(), &nested) {
+                                if *print_method {
+                                    if let Some(_pattern_str) = pattern.as_str().chars().next() {
+                                        let body_text_styled = body_text.replace(pattern.as_str(), &format!("{}", pattern.as_str().bold().red()));
+                                        print_body_match(path, start_row + i + 1, func_name, arm_at(body_node, i, line, content), &body_text_styled);
+                                    } else {
+                                        print_body_match(path, start_row + i + 1, func_name, arm_at(body_node, i, line, content), body_text);
+                                    }
+                                } else {
+                                    if let Some(_pattern_str) = pattern.as_str().chars().next() {
+                                        let line_styled = line.replace(pattern.as_str(), &format!("{}", pattern.as_str().bold().red()));
+                                        print_body_match(path, start_row + i + 1, func_name, arm_at(body_node, i, line, content), &line_styled);
+                                    } else {
+                                        print_body_match(path, start_row + i + 1, func_name, arm_at(body_node, i, line, content), line);
+                                    }
+                                }
                             }
                         }
+                        context::end_function();
+                    }
+                }
+            }
+        }
+    }
+    
+    // Now recursively find all function_definition nodes (including nested ones)
+    search_in_all_functions(&root_node, content, pattern, path, print_method)?;
+    
+    Ok(())
+}
+
+// The byte spans of the bodies of named functions declared inside a body
+fn nested_function_bodies(body: tree_sitter::Node) -> Vec<std::ops::Range<usize>> {
+    let mut nested = Vec::new();
+    php::walk(body, &mut |node| {
+        if node.kind() == "function_definition"
+            && let Some(body) = node.child_by_field_name("body") {
+            nested.push(body.byte_range());
+        }
+    });
+    nested
+}
+
+// Whether a line of a body has a match outside the named functions nested in it. Those are
+// searched on their own, so each match is reported once, with the innermost function around it
+fn has_own_match(pattern: &Regex, line: &str, body_text: &str, body_start: usize, nested: &[std::ops::Range<usize>]) -> bool {
+    if nested.is_empty() {
+        return pattern.is_match(line);
+    }
+    let line_start = body_start + (line.as_ptr() as usize - body_text.as_ptr() as usize);
+    pattern.find_iter(line).any(|found| !nested.iter().any(|span| span.contains(&(line_start + found.start()))))
+}
+
+// Recursive function to search inside all function_definition nodes regardless of nesting
+fn search_in_all_functions(node: &tree_sitter::Node, content: &str, pattern: &Regex, path: &std::path::Path, print_method: &bool) -> Result<()> {
+    if node.kind() == "function_definition"
+        && let Some(name_node) = node.child_by_field_name("name") {
+        let func_name = match name_node.utf8_text(content.as_bytes()) {
+            Ok(name) => name,
+            Err(_) => {
+                eprintln!("Warning: Invalid UTF-8 in function name in file '{}'", path.display());
+                return Ok(());
+            }
+        };
+        
+        if let Some(body_node) = node.child_by_field_name("body") {
+            let body_text = match body_node.utf8_text(content.as_bytes()) {
+                Ok(text) => text,
+                Err(_) => {
+                    eprintln!("Warning: Invalid UTF-8 in function body in file '{}'", path.display());
+                    return Ok(());
+                }
+            };
+            let start_row = body_node.start_position().row;
+            let nested = nested_function_bodies(body_node);
+            
+            context::begin_function(*node, content);
+            for (i, line) in body_text.lines().enumerate() {
+                if budget::exceeded(path) {
+                    break;
+                }
+                if has_own_match(pattern, line, body_text, body_node.start_byte(), &nested) {
+                    if *print_method {
+                        if let Some(_pattern_str) = pattern.as_str().chars().next() {
+                            let body_text_styled = body_text.replace(pattern.as_str(), &format!("{}", pattern.as_str().bold().red()));
+                            print_body_match(path, start_row + i + 1, func_name, arm_at(body_node, i, line, content), &body_text_styled);
+                        } else {
+                            print_body_match(path, start_row + i + 1, func_name, arm_at(body_node, i, line, content), body_text);
+                        }
+                    } else {
+                        if let Some(_pattern_str) = pattern.as_str().chars().next() {
+                            let line_styled = line.replace(pattern.as_str(), &format!("{}", pattern.as_str().bold().red()));
+                            print_body_match(path, start_row + i + 1, func_name, arm_at(body_node, i, line, content), &line_styled);
+                        } else {
+                            print_body_match(path, start_row + i + 1, func_name, arm_at(body_node, i, line, content), line);
+                        }
                     }
                 }
             }
+            context::end_function();
         }
     }
     
@@ -248,54 +1641,32 @@ fn search_in_all_functions(node: &tree_sitter::Node, content: &str, pattern: &Re
 }
 
 fn basic_search(query: &str, dir: &str, file: &str, print_method: &bool, exclude_dirs: &str) -> Result<()> {
-    let pattern = Regex::new(query);
+    let reg_pattern = &match budget::regex(query) {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            eprintln!("Invalid regex pattern: {}", e);
+            return Err(anyhow::anyhow!("Invalid regex pattern"));
+        }
+    };
+    if let Some(kinds) = SEARCHED_KINDS.get() {
+        return kind_search(reg_pattern, kinds, dir, file, exclude_dirs);
+    }
     let mut parser = TreeSitterParser::new();
     parser.set_language(unsafe { tree_sitter_php() })?;
-    if let Err(e) = pattern {
-        eprintln!("Invalid regex pattern: {}", e);
-        return Err(anyhow::anyhow!("Invalid regex pattern"));
-    }
-    let exclude_dirs: Vec<&str> = exclude_dirs.split(',').map(|s| s.trim()).collect();
-
-    for entry in WalkDir::new(dir)
-        .into_iter()
-        .filter_entry(|e| {
-            if let Some(path_str) = e.path().to_str() {
-                let relative_path = e.path().strip_prefix(dir).unwrap_or(e.path()).to_string_lossy();
-                !exclude_dirs.iter().any(|excluded_dir| {
-                    path_str.contains(excluded_dir) || 
-                    relative_path.starts_with(excluded_dir) ||
-                    path_str.ends_with(excluded_dir)
-                })
-            } else {
-                true
-            }
-        })
-        .filter_map(Result::ok)
-        .filter(|e| e.path().extension().and_then(|s| s.to_str()) == Some("php"))
-        .filter(|e| e.file_name().to_string_lossy().contains(file)) {
+    for path in php_files(dir, file, exclude_dirs) {
         
-        let path = entry.path();
-        if path.is_file() {
-            let content = match std::fs::read_to_string(path) {
-                Ok(content) => content,
-                Err(e) => {
-                    eprintln!("Warning: Could not read file '{}': {}", path.display(), e);
-                    continue;
-                }
-            };
-            
-            let reg_pattern = &pattern.clone().unwrap();
-            
-            if !content.lines().any(|line| reg_pattern.is_match(line)) {
-                continue;
-            }
+        let path = path.as_path();
+        let Some(content) = read_file(path) else { continue };
+        
+        if !content.lines().any(|line| !budget::exceeded(path) && reg_pattern.is_match(line)) {
+            continue;
+        }
 
-            if let Err(e) = search_in_function_body(&content, &reg_pattern, &mut parser, &path, print_method) {
+        cache::cached(path, &content, || {
+            if let Err(e) = search_in_function_body(&content, reg_pattern, &mut parser, path, print_method) {
                 eprintln!("Warning: Error processing file '{}': {}", path.display(), e);
-                continue;
             }
-        }
+        });
     }
 
     Ok(())
@@ -304,157 +1675,146 @@ fn basic_search(query: &str, dir: &str, file: &str, print_method: &bool, exclude
 // Searches method name match and prints the entire method body
 // This is useful for finding methods by name and seeing their implementation
 fn method_search(query: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
-    let pattern = Regex::new(query);
+    let pattern = budget::regex(query);
     let mut parser = TreeSitterParser::new();
     parser.set_language(unsafe { tree_sitter_php() })?;
     if let Err(e) = pattern {
         eprintln!("Invalid regex pattern: {}", e);
         return Err(anyhow::anyhow!("Invalid regex pattern"));
     }
+    if let Some(kinds) = SEARCHED_KINDS.get() {
+        return kind_name_search(query, kinds, dir, file, exclude_dirs);
+    }
 
-    let exclude_dirs: Vec<&str> = exclude_dirs.split(',').map(|s| s.trim()).collect();
+    for path in php_files(dir, file, exclude_dirs) {
 
-    for entry in WalkDir::new(dir)
-        .into_iter()
-        .filter_entry(|e| {
-            if let Some(path_str) = e.path().to_str() {
-                let relative_path = e.path().strip_prefix(dir).unwrap_or(e.path()).to_string_lossy();
-                !exclude_dirs.iter().any(|excluded_dir| {
-                    path_str.contains(excluded_dir) || 
-                    relative_path.starts_with(excluded_dir) ||
-                    path_str.ends_with(excluded_dir)
-                })
-            } else {
-                true
+        let path = path.as_path();
+        let Some(content) = read_file(path) else { continue };
+        
+        if !content.contains(query) {
+            continue;
+        }
+        
+        cache::cached(path, &content, || {
+            if let Err(e) = search_method_names(&content, query, &mut parser, path) {
+                eprintln!("Warning: Error processing functions in file '{}': {}", path.display(), e);
             }
-        })
-        .filter_map(Result::ok)
-        .filter(|e| e.path().extension().and_then(|s| s.to_str()) == Some("php"))
-        .filter(|e| e.file_name().to_string_lossy().contains(file)) {
-
-        let path = entry.path();
-        if path.is_file() {
-            let content = match std::fs::read_to_string(path) {
-                Ok(content) => content,
-                Err(e) => {
-                    eprintln!("Warning: Could not read file '{}': {}", path.display(), e);
-                    continue;
-                }
-            };
-            
-            if !content.contains(query) {
+        });
+    }
+
+    extract::finish();
+    Ok(())
+}
+
+// Prints the methods and functions of one file whose name contains the query
+fn search_method_names(content: &str, query: &str, parser: &mut TreeSitterParser, path: &std::path::Path) -> Result<()> {
+    let tree = match parser.parse(content, None) {
+        Some(tree) => tree,
+        None => {
+            eprintln!("Warning: Could not parse file '{}' as PHP", path.display());
+            return Ok(());
+        }
+    };
+    search_parsed_method_names(content, query, &tree, path)
+}
+
+// Prints the methods and functions whose name contains the query in an already parsed file
+fn search_parsed_method_names(content: &str, query: &str, tree: &tree_sitter::Tree, path: &std::path::Path) -> Result<()> {
+    let root_node = tree.root_node();
+    
+    for node in root_node.children(&mut tree.walk()) {
+        if node.kind() == "class_declaration" {
+            let Some(class_body) = node.child_by_field_name("body") else {
+                eprintln!("Warning: Class without a body at '{}:{}', skipping it", path.display(), node.start_position().row + 1);
                 continue;
-            }
-            
-            let tree = match parser.parse(&content, None) {
-                Some(tree) => tree,
-                None => {
-                    eprintln!("Warning: Could not parse file '{}' as PHP", path.display());
-                    continue;
-                }
             };
-            let root_node = tree.root_node();
-            
-            for node in root_node.children(&mut tree.walk()) {
-                if node.kind() == "class_declaration" {
-                    let class_body = node.child_by_field_name("body");
-                    let cursor = class_body.unwrap();
-                    for method in class_body.unwrap().named_children(&mut cursor.walk()) {
-                        if method.kind() == "method_declaration" || method.kind() == "function_declaration" {
-                            let name_node = method.child_by_field_name("name");
-                            let body_node = method.child_by_field_name("body");
-                            if let (Some(name_node), Some(body_node)) = (name_node, body_node) {
-                                let func_name = match name_node.utf8_text(content.as_bytes()) {
-                                    Ok(name) => name,
-                                    Err(_) => {
-                                        eprintln!("Warning: Invalid UTF-8 in method name in file '{}'", path.display());
-                                        continue;
-                                    }
-                                };
+            for method in class_body.named_children(&mut class_body.walk()) {
+                if method.kind() == "method_declaration" || method.kind() == "function_declaration" {
+                    let name_node = method.child_by_field_name("name");
+                    let body_node = method.child_by_field_name("body");
+                    if let (Some(name_node), Some(body_node)) = (name_node, body_node) {
+                        let func_name = match name_node.utf8_text(content.as_bytes()) {
+                            Ok(name) => name,
+                            Err(_) => {
+                                eprintln!("Warning: Invalid UTF-8 in method name in file '{}'", path.display());
+                                continue;
+                            }
+                        };
 
-                                let body_text = match body_node.utf8_text(content.as_bytes()) {
-                                    Ok(text) => text,
-                                    Err(_) => {
-                                        eprintln!("Warning: Invalid UTF-8 in method body in file '{}'", path.display());
-                                        continue;
-                                    }
-                                };
-                                let start_row = body_node.start_position().row;
-                                if func_name.contains(query) {
-                                    let filename = format_filename(path);
-                                    let file_name_styled = filename.bold().blue();
-                                    let func_name_styled = func_name.bold().yellow();
-                                   
-                                    let params_text = method.child_by_field_name("parameters")
-                                        .and_then(|p| p.utf8_text(content.as_bytes()).ok())
-                                        .unwrap_or("");
-                                    let params_styled = params_text.bold().green();
-
-                                    let return_type_text = method.child_by_field_name("return_type")
-                                        .and_then(|r| r.utf8_text(content.as_bytes()).ok())
-                                        .unwrap_or("");
-                                    let return_type_styled = return_type_text.bold().magenta();
-
-                                    println!("{}:{}: {}{}:{} → {}", file_name_styled, start_row + 1, func_name_styled, params_styled, return_type_styled, body_text.trim());
-                                }
+                        let body_text = match body_node.utf8_text(content.as_bytes()) {
+                            Ok(text) => text,
+                            Err(_) => {
+                                eprintln!("Warning: Invalid UTF-8 in method body in file '{}'", path.display());
+                                continue;
                             }
+                        };
+                        let start_row = body_node.start_position().row;
+                        if func_name.contains(query) && !is_excluded_method(func_name) && is_reported(path, start_row + 1) {
+                            let func_name_styled = func_name.bold().yellow();
+                           
+                            let params_text = method.child_by_field_name("parameters")
+                                .and_then(|p| p.utf8_text(content.as_bytes()).ok())
+                                .unwrap_or("");
+                            let params_styled = params_text.bold().green();
+
+                            let return_type_text = method.child_by_field_name("return_type")
+                                .and_then(|r| r.utf8_text(content.as_bytes()).ok())
+                                .unwrap_or("");
+                            let return_type_styled = return_type_text.bold().magenta();
+
+                            cache::emit(format!("{}: {}{}:{} → {}", location(path, start_row + 1), func_name_styled, params_styled, return_type_styled, body_text.trim()));
+                            exec::run(path, start_row + 1, Some(func_name), content.lines().nth(start_row).unwrap_or_default());
+                            extract::method(path, content, method);
                         }
                     }
                 }
             }
-            
-            if let Err(e) = find_all_functions(&root_node, &content, query, path) {
-                eprintln!("Warning: Error processing functions in file '{}': {}", path.display(), e);
-                continue;
-            }
         }
     }
-
-    Ok(())
+    
+    find_all_functions(&root_node, content, query, path)
 }
 
 // Recursive function to find all function_definition nodes regardless of nesting
 fn find_all_functions(node: &tree_sitter::Node, content: &str, query: &str, path: &std::path::Path) -> Result<()> {
     // Check if this node is a function_definition
-    if node.kind() == "function_definition" {
-        if let Some(name_node) = node.child_by_field_name("name") {
-            let func_name = match name_node.utf8_text(content.as_bytes()) {
-                Ok(name) => name,
-                Err(_) => {
-                    eprintln!("Warning: Invalid UTF-8 in function name in file '{}'", path.display());
-                    return Ok(());
-                }
-            };
-            
-            if func_name.contains(query) {
-                let filename = format_filename(path);
-                let file_name_styled = filename.bold().blue();
-                let func_name_styled = func_name.bold().yellow();
-                
-                let params_text = node.child_by_field_name("parameters")
-                    .and_then(|p| p.utf8_text(content.as_bytes()).ok())
-                    .unwrap_or("");
-                let params_styled = params_text.bold().green();
-
-                let return_type_text = node.child_by_field_name("return_type")
-                    .and_then(|r| r.utf8_text(content.as_bytes()).ok())
-                    .unwrap_or("");
-                let return_type_styled = return_type_text.bold().magenta();
-
-                let body_text = node.child_by_field_name("body")
-                    .and_then(|b| b.utf8_text(content.as_bytes()).ok())
-                    .unwrap_or("");
-                let start_row = node.start_position().row;
-
-                println!("{}:{}: {}{}:{} → {}", 
-                    file_name_styled, 
-                    start_row + 1, 
-                    func_name_styled, 
-                    params_styled, 
-                    return_type_styled, 
-                    body_text.trim()
-                );
+    if node.kind() == "function_definition"
+        && let Some(name_node) = node.child_by_field_name("name") {
+        let func_name = match name_node.utf8_text(content.as_bytes()) {
+            Ok(name) => name,
+            Err(_) => {
+                eprintln!("Warning: Invalid UTF-8 in function name in file '{}'", path.display());
+                return Ok(());
             }
+        };
+        
+        if func_name.contains(query) && !is_excluded_method(func_name) && is_reported(path, node.start_position().row + 1) {
+            let func_name_styled = func_name.bold().yellow();
+            
+            let params_text = node.child_by_field_name("parameters")
+                .and_then(|p| p.utf8_text(content.as_bytes()).ok())
+                .unwrap_or("");
+            let params_styled = params_text.bold().green();
+
+            let return_type_text = node.child_by_field_name("return_type")
+                .and_then(|r| r.utf8_text(content.as_bytes()).ok())
+                .unwrap_or("");
+            let return_type_styled = return_type_text.bold().magenta();
+
+            let body_text = node.child_by_field_name("body")
+                .and_then(|b| b.utf8_text(content.as_bytes()).ok())
+                .unwrap_or("");
+            let start_row = node.start_position().row;
+
+            cache::emit(format!("{}: {}{}:{} → {}", 
+                location(path, start_row + 1), 
+                func_name_styled, 
+                params_styled, 
+                return_type_styled, 
+                body_text.trim()
+            ));
+            exec::run(path, start_row + 1, Some(func_name), content.lines().nth(start_row).unwrap_or_default());
+            extract::method(path, content, *node);
         }
     }
     
@@ -467,54 +1827,1391 @@ fn find_all_functions(node: &tree_sitter::Node, content: &str, query: &str, path
     Ok(())
 }
 
-// Mimics grep search, searching for the query in all files
-fn grep_search(query: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
-    let pattern = Regex::new(query);
-    if let Err(e) = pattern {
-        eprintln!("Invalid regex pattern: {}", e);
-        return Err(anyhow::anyhow!("Invalid regex pattern"));
-    }
+// A declaration of a kind --kind asks for, with the bytes the basic search looks at
+struct KindSpan<'a> {
+    kind: &'static str,
+    node: tree_sitter::Node<'a>,
+    bytes: std::ops::Range<usize>,
+}
 
-    let exclude_dirs: Vec<&str> = exclude_dirs.split(',').map(|s| s.trim()).collect();
+// The declarations of the kinds asked for in a file. Methods and functions cover their body,
+// closures, properties and constants all of their text, and classes their header, as their
+// members are declarations of their own
+fn kind_spans<'a>(root: tree_sitter::Node<'a>, kinds: &[String]) -> Vec<KindSpan<'a>> {
+    let mut spans = Vec::new();
+    php::walk(root, &mut |node| {
+        let kind = match node.kind() {
+            "method_declaration" => "method",
+            "function_definition" => "function",
+            "anonymous_function" | "arrow_function" => "closure",
+            "property_declaration" => "property",
+            "const_declaration" => "const",
+            "class_declaration" | "interface_declaration" | "trait_declaration" | "enum_declaration" => "class",
+            _ => return,
+        };
+        if !kinds.iter().any(|wanted| wanted == kind) {
+            return;
+        }
+        let bytes = match (kind, node.child_by_field_name("body")) {
+            ("method" | "function", Some(body)) => body.byte_range(),
+            ("method" | "function", None) => return,
+            ("class", Some(body)) => node.start_byte()..body.start_byte() + 1,
+            _ => node.byte_range(),
+        };
+        spans.push(KindSpan { kind, node, bytes });
+    });
+    spans
+}
 
-    for entry in WalkDir::new(dir)
-        .into_iter()
-        .filter_entry(|e| {
-            if let Some(path_str) = e.path().to_str() {
-                let relative_path = e.path().strip_prefix(dir).unwrap_or(e.path()).to_string_lossy();
-                !exclude_dirs.iter().any(|excluded_dir| {
-                    path_str.contains(excluded_dir) || 
-                    relative_path.starts_with(excluded_dir) ||
-                    path_str.ends_with(excluded_dir)
-                })
-            } else {
-                true
+// The name a match in a declaration is reported under: the method or function around it, or
+// the class with the kind of declaration
+fn kind_symbol(span: &KindSpan, content: &str) -> String {
+    let class = php::enclosing_class(span.node).and_then(|class| class.child_by_field_name("name")).map(|name| php::node_text(name, content));
+    match span.kind {
+        "class" => format!("{} [class]", span.node.child_by_field_name("name").map_or("", |name| php::node_text(name, content))),
+        "closure" => format!("{}() [closure]", php::enclosing_function(span.node, content).unwrap_or("{closure}")),
+        kind => match class {
+            Some(class) => format!("{} [{}]", class, kind),
+            None => format!("[{}]", kind),
+        },
+    }
+}
+
+// The basic search limited to declarations of the kinds --kind asks for. Each match belongs to
+// the innermost declaration around it, so with `method,closure` matches inside closures are
+// reported as closure lines, and a line is reported once for each declaration it has matches in
+fn kind_search(pattern: &Regex, kinds: &[String], dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    for_each_php_tree(dir, file, exclude_dirs, |content| pattern.is_match(content), |path, content, tree| {
+        let spans = kind_spans(tree.root_node(), kinds);
+        for (row, line) in content.lines().enumerate() {
+            if budget::exceeded(path) {
+                break;
             }
-        })
-        .filter_map(Result::ok)
-        .filter(|e| e.path().extension().and_then(|s| s.to_str()) == Some("php"))
-        .filter(|e| e.file_name().to_string_lossy().contains(file)) {
-        
-        let path = entry.path();
-        if path.is_file() {
-            let content = match std::fs::read_to_string(path) {
-                Ok(content) => content,
-                Err(e) => {
-                    eprintln!("Warning: Could not read file '{}': {}", path.display(), e);
-                    continue;
+            let line_start = line.as_ptr() as usize - content.as_ptr() as usize;
+            let mut line_spans: Vec<&KindSpan> = Vec::new();
+            for found in pattern.find_iter(line) {
+                let byte = line_start + found.start();
+                let innermost = spans.iter().filter(|span| span.bytes.contains(&byte)).min_by_key(|span| span.bytes.len());
+                if let Some(span) = innermost
+                    && !line_spans.iter().any(|seen| std::ptr::eq(*seen, span)) {
+                    line_spans.push(span);
                 }
-            };
-            let filename = format_filename(path);
-            let file_name_styled = filename.bold().blue();
-            for (i, line) in content.lines().enumerate() {
-                if pattern.clone().unwrap().is_match(line) {
-                    let pattern_ref = pattern.clone().unwrap();
-                    let line_styled = line.replace(pattern_ref.as_str(), &format!("{}", pattern_ref.as_str().bold().red()));
-                    println!("{}:{} → {}", file_name_styled, i + 1, line_styled.trim());
+            }
+
+            let line_styled = highlight(line, pattern);
+            for span in line_spans {
+                match span.kind {
+                    "method" | "function" => {
+                        let (Some(name), Some(body)) = (span.node.child_by_field_name("name"), span.node.child_by_field_name("body")) else { continue };
+                        context::begin_function(span.node, content);
+                        print_body_match(path, row + 1, php::node_text(name, content), arm_at(body, row - body.start_position().row, line, content), &line_styled);
+                        context::end_function();
+                    }
+                    _ => print_symbol_match(path, row + 1, Some(&kind_symbol(span, content)), &line_styled),
+                }
+            }
+        }
+    })
+}
+
+// The name of a declaration --method-search matches against: the name of a method, function,
+// class or constant, the names of the properties declared, or the variable or property a
+// closure is assigned to
+fn declaration_names(node: tree_sitter::Node, content: &str) -> Vec<String> {
+    match node.kind() {
+        "property_declaration" | "const_declaration" => {
+            let mut names = Vec::new();
+            let mut cursor = node.walk();
+            for element in node.named_children(&mut cursor) {
+                if matches!(element.kind(), "property_element" | "const_element") {
+                    let name = element.child_by_field_name("name").or_else(|| element.named_child(0));
+                    names.extend(name.map(|name| php::node_text(name, content).trim_start_matches('$').to_string()));
                 }
             }
+            names
         }
+        "anonymous_function" | "arrow_function" => node.parent()
+            .filter(|parent| parent.kind() == "assignment_expression")
+            .and_then(|assignment| assignment.child_by_field_name("left"))
+            .map(|left| php::node_text(left, content).rsplit(['>', ':', '$']).next().unwrap_or("").to_string())
+            .into_iter()
+            .collect(),
+        _ => node.child_by_field_name("name").map(|name| php::node_text(name, content).to_string()).into_iter().collect(),
     }
+}
 
+// --method-search limited to declarations of the kinds --kind asks for. Methods and functions
+// print as usual with their body, other declarations with their first line
+fn kind_name_search(query: &str, kinds: &[String], dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    for_each_php_tree(dir, file, exclude_dirs, |content| content.contains(query), |path, content, tree| {
+        let lines: Vec<&str> = content.lines().collect();
+        for span in kind_spans(tree.root_node(), kinds) {
+            let names = declaration_names(span.node, content);
+            let Some(name) = names.iter().find(|name| name.contains(query)) else { continue };
+            let row = span.node.start_position().row;
+            if matches!(span.kind, "method" | "function") {
+                let start_row = span.node.child_by_field_name("body").map_or(row, |body| body.start_position().row);
+                if is_excluded_method(name) || !is_reported(path, start_row + 1) {
+                    continue;
+                }
+                let text = |field: &str| span.node.child_by_field_name(field).map_or("", |node| php::node_text(node, content));
+                cache::emit(format!("{}: {}{}:{} → {}", location(path, start_row + 1), name.bold().yellow(), text("parameters").bold().green(), text("return_type").bold().magenta(), text("body").trim()));
+                exec::run(path, start_row + 1, Some(name), content.lines().nth(start_row).unwrap_or_default());
+                extract::method(path, content, span.node);
+            } else {
+                print_symbol_match(path, row + 1, Some(&kind_symbol(&span, content)), &highlight_node(&lines, span.node));
+            }
+        }
+    })?;
+    extract::finish();
     Ok(())
-}
\ No newline at end of file
+}
+
+// Mimics grep search, searching for the query in all files
+fn grep_search(query: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let pattern = match budget::regex(query) {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            eprintln!("Invalid regex pattern: {}", e);
+            return Err(anyhow::anyhow!("Invalid regex pattern"));
+        }
+    };
+
+    for path in php_files(dir, file, exclude_dirs) {
+        
+        let path = path.as_path();
+        let Some(content) = read_file(path) else { continue };
+        cache::cached(path, &content, || grep_lines(&content, &pattern, path));
+    }
+
+    Ok(())
+}
+
+// Prints the lines of a file matching the pattern
+fn grep_lines(content: &str, pattern: &Regex, path: &std::path::Path) {
+    for (i, line) in content.lines().enumerate() {
+        if budget::exceeded(path) {
+            break;
+        }
+        if pattern.is_match(line) {
+            let line_styled = line.replace(pattern.as_str(), &format!("{}", pattern.as_str().bold().red()));
+            print_symbol_match(path, i + 1, None, &line_styled);
+        }
+    }
+}
+
+// Lists every superglobal access and `global` statement along with the function it appears in
+// This is a quick way to map out hidden inputs and shared state in an unfamiliar codebase
+fn superglobal_search(dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let prefilter = |content: &str| content.contains("global") || SUPERGLOBALS.iter().any(|global| content.contains(global));
+
+    for_each_php_tree(dir, file, exclude_dirs, prefilter, |path, content, tree| {
+        let lines: Vec<&str> = content.lines().collect();
+        let mut last_row = None;
+        php::walk(tree.root_node(), &mut |node| {
+            let token = match node.kind() {
+                "global_declaration" => "global",
+                "variable_name" if SUPERGLOBALS.contains(&php::node_text(node, content)) => php::node_text(node, content),
+                _ => return,
+            };
+
+            // Several accesses on one line are reported once
+            let row = node.start_position().row;
+            if last_row == Some(row) {
+                return;
+            }
+            last_row = Some(row);
+
+            let line = lines.get(row).copied().unwrap_or("");
+            let line_styled = line.replace(token, &format!("{}", token.bold().red()));
+            print_match(path, row + 1, php::enclosing_function(node, content), &line_styled);
+        });
+    })
+}
+
+// Finds the token sequence of a snippet in every file, so a call reformatted over several lines
+// or with comments in between still matches. Matches are reported on the line they start
+fn token_search(snippet: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let source = format!("<?php {}", snippet);
+    let mut parser = php::new_parser()?;
+    let Some(tree) = parser.parse(&source, None) else {
+        eprintln!("Error: Could not parse '{}' as PHP.", snippet);
+        return Err(anyhow::anyhow!("Could not parse '{}' as PHP", snippet));
+    };
+    let wanted: Vec<&str> = php::tokens(tree.root_node(), &source).into_iter().map(|token| php::node_text(token, &source)).collect();
+    if wanted.is_empty() {
+        eprintln!("Error: '{}' contains no PHP tokens.", snippet);
+        return Err(anyhow::anyhow!("'{}' contains no PHP tokens", snippet));
+    }
+
+    for_each_php_tree(dir, file, exclude_dirs, |content| wanted.iter().all(|token| content.contains(token)), |path, content, tree| {
+        let lines: Vec<&str> = content.lines().collect();
+        let tokens = php::tokens(tree.root_node(), content);
+        for found in tokens.windows(wanted.len()).filter(|window| window.iter().zip(&wanted).all(|(token, text)| php::node_text(*token, content) == *text)) {
+            let (first, last) = (found[0], found[found.len() - 1]);
+            let row = first.start_position().row;
+            let line = lines.get(row).copied().unwrap_or("");
+            let start = first.start_position().column.min(line.len());
+            let end = if last.end_position().row == row { last.end_position().column.clamp(start, line.len()) } else { line.len() };
+            let mut line_styled = match (line.is_char_boundary(start), line.is_char_boundary(end)) {
+                (true, true) => format!("{}{}{}", &line[..start], line[start..end].bold().red(), &line[end..]),
+                _ => line.to_string(),
+            };
+            let spanned = last.end_position().row - row;
+            if spanned > 0 {
+                line_styled = format!("{} {}", line_styled.trim_end(), format!("(+{} line{})", spanned, if spanned == 1 { "" } else { "s" }).dimmed());
+            }
+            print_match(path, row + 1, php::enclosing_function(first, content), &line_styled);
+        }
+    })
+}
+
+// Searches the bodies of heredoc and nowdoc strings line by line, numbering the lines from
+// where the body starts in the file, and labels each match with the string's tag
+fn heredoc_search(query: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let pattern = match budget::regex(query) {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            eprintln!("Invalid regex pattern: {}", e);
+            return Err(anyhow::anyhow!("Invalid regex pattern"));
+        }
+    };
+
+    for_each_php_tree(dir, file, exclude_dirs, |content| content.contains("<<<") && pattern.is_match(content), |path, content, tree| {
+        php::walk(tree.root_node(), &mut |node| {
+            if node.kind() != "heredoc" && node.kind() != "nowdoc" {
+                return;
+            }
+            let Some(body) = node.child_by_field_name("value") else { return };
+            let tag = node.child_by_field_name("identifier").map_or("", |tag| php::node_text(tag, content));
+            // The body starts right after the opening tag, on the line the heredoc starts on
+            for (i, line) in php::node_text(body, content).lines().enumerate() {
+                if pattern.is_match(line) {
+                    let line_styled = format!("{} {}", format!("<<<{}", tag).dimmed(), highlight(line.trim(), &pattern));
+                    print_match(path, body.start_position().row + i + 1, php::enclosing_function(node, content), &line_styled);
+                }
+            }
+        });
+    })
+}
+
+// Finds parameters, promoted ones included, whose default value expression matches the
+// pattern. A `null` default on a type that doesn't allow null makes the type implicitly
+// nullable, which PHP 8.4 deprecates, so those are flagged
+fn default_value_search(query: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let pattern = match budget::regex(query) {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            eprintln!("Invalid regex pattern: {}", e);
+            return Err(anyhow::anyhow!("Invalid regex pattern"));
+        }
+    };
+
+    // Anchored patterns like '^null$' only match the default on its own, not the whole file
+    for_each_php_tree(dir, file, exclude_dirs, |content| content.contains('='), |path, content, tree| {
+        php::walk(tree.root_node(), &mut |node| {
+            if node.kind() != "simple_parameter" && node.kind() != "property_promotion_parameter" {
+                return;
+            }
+            let Some(default) = node.child_by_field_name("default_value") else { return };
+            let default_text = php::node_text(default, content);
+            if !pattern.is_match(default_text) {
+                return;
+            }
+            let name = node.child_by_field_name("name").map_or("", |name| php::node_text(name, content));
+            let mut line_styled = match node.child_by_field_name("type") {
+                Some(param_type) => format!("{} {} = {}", php::node_text(param_type, content), name, highlight(default_text, &pattern)),
+                None => format!("{} = {}", name, highlight(default_text, &pattern)),
+            };
+            let implicitly_nullable = default_text.eq_ignore_ascii_case("null")
+                && node.child_by_field_name("type").is_some_and(|param_type| {
+                    let type_text = php::node_text(param_type, content).to_lowercase();
+                    !type_text.starts_with('?') && !type_text.split(['|', '(', ')']).any(|part| matches!(part.trim(), "null" | "mixed"))
+                });
+            if implicitly_nullable {
+                line_styled = format!("{} {}", line_styled, "[implicitly nullable]".yellow());
+            }
+            print_match(path, node.start_position().row + 1, php::enclosing_function(node, content), &line_styled);
+        });
+    })
+}
+
+// Searches the bodies of closures and arrow functions passed as arguments to the functions or
+// methods whose names match `functions`, attributing each line to the innermost such callback
+fn callback_search(query: &str, functions: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let (pattern, functions) = match (budget::regex(query), budget::regex(&format!("(?i)^(?:{})$", functions))) {
+        (Ok(pattern), Ok(functions)) => (pattern, functions),
+        (Err(e), _) => {
+            eprintln!("Invalid regex pattern: {}", e);
+            return Err(anyhow::anyhow!("Invalid regex pattern"));
+        }
+        (_, Err(e)) => {
+            eprintln!("Invalid --callback-of pattern: {}", e);
+            return Err(anyhow::anyhow!("Invalid --callback-of pattern"));
+        }
+    };
+
+    for_each_php_tree(dir, file, exclude_dirs, |content| pattern.is_match(content), |path, content, tree| {
+        let names = php::FileNames::new(tree.root_node(), content);
+        let mut callbacks: Vec<(&str, tree_sitter::Node)> = Vec::new();
+        php::walk(tree.root_node(), &mut |node| {
+            let Some(call) = calls::call_site(node, content, &names) else { return };
+            let Some(arguments) = node.child_by_field_name("arguments") else { return };
+            if !functions.is_match(call.name) {
+                return;
+            }
+            let mut cursor = arguments.walk();
+            for argument in arguments.named_children(&mut cursor) {
+                if let Some(callback) = argument.named_child(0).filter(|value| matches!(value.kind(), "anonymous_function" | "arrow_function"))
+                    && let Some(body) = callback.child_by_field_name("body") {
+                    callbacks.push((call.name, body));
+                }
+            }
+        });
+
+        let lines: Vec<&str> = content.lines().collect();
+        for &(function, body) in &callbacks {
+            let nested_rows = |row: usize| callbacks.iter().any(|&(_, other)| other != body
+                && other.start_byte() >= body.start_byte() && other.end_byte() <= body.end_byte()
+                && (other.start_position().row..=other.end_position().row).contains(&row));
+            let start_row = body.start_position().row;
+            for (i, segment) in php::node_text(body, content).lines().enumerate() {
+                let row = start_row + i;
+                if !pattern.is_match(segment) || nested_rows(row) {
+                    continue;
+                }
+                let line_styled = highlight(lines.get(row).copied().unwrap_or(segment), &pattern);
+                let label = format!("{} callback", function);
+                match php::enclosing_function(body, content) {
+                    Some(func_name) => print_body_match(path, row + 1, func_name, Some(label), &line_styled),
+                    None => print_symbol_match(path, row + 1, Some(&format!("[{}]", label)), &line_styled),
+                }
+            }
+        }
+    })
+}
+
+// Which of the --arg-of callables a call is to: `name` for a function, `Class::name` for a
+// static method (resolving `use` aliases) and `->name` for a method on any object
+fn arg_of<'a>(call: &calls::CallSite, callables: &'a [String]) -> Option<&'a str> {
+    callables.iter().map(String::as_str).find(|callable| {
+        if let Some(method) = callable.strip_prefix("->") {
+            matches!(call.kind, calls::CallKind::This | calls::CallKind::Instance) && call.name.eq_ignore_ascii_case(method)
+        } else if let Some((class, method)) = callable.rsplit_once("::") {
+            !matches!(call.kind, calls::CallKind::Function | calls::CallKind::This | calls::CallKind::Instance)
+                && call.name.eq_ignore_ascii_case(method)
+                && call.class.as_deref().is_some_and(|fqcn| php::class_matches(fqcn, class))
+        } else {
+            call.kind == calls::CallKind::Function && call.name.eq_ignore_ascii_case(php::short_name(callable))
+        }
+    })
+}
+
+// Searches the query only inside the arguments passed to the --arg-of callables, e.g. the
+// regexes given to preg_match or the URIs registered with Route::get. With --arg-index only the
+// positional argument at that index is searched
+fn arg_search(query: &str, callables: &[String], index: Option<usize>, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let pattern = match budget::regex(query) {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            eprintln!("Invalid regex pattern: {}", e);
+            return Err(anyhow::anyhow!("Invalid regex pattern"));
+        }
+    };
+
+    for_each_php_tree(dir, file, exclude_dirs, |content| pattern.is_match(content), |path, content, tree| {
+        let names = php::FileNames::new(tree.root_node(), content);
+        // The callable, the argument's position or name, and the argument
+        let mut arguments: Vec<(&str, String, tree_sitter::Node)> = Vec::new();
+        php::walk(tree.root_node(), &mut |node| {
+            let Some(call) = calls::call_site(node, content, &names) else { return };
+            let Some(list) = node.child_by_field_name("arguments") else { return };
+            let Some(callable) = arg_of(&call, callables) else { return };
+            let mut cursor = list.walk();
+            let positional = list.named_children(&mut cursor).filter(|argument| argument.child_by_field_name("name").is_none());
+            for (position, argument) in positional.enumerate() {
+                if index.is_none_or(|index| index == position) {
+                    arguments.push((callable, position.to_string(), argument));
+                }
+            }
+            if index.is_none() {
+                let mut cursor = list.walk();
+                for argument in list.named_children(&mut cursor) {
+                    if let Some(name) = argument.child_by_field_name("name") {
+                        arguments.push((callable, php::node_text(name, content).to_string(), argument));
+                    }
+                }
+            }
+        });
+
+        let lines: Vec<&str> = content.lines().collect();
+        for (callable, which, argument) in &arguments {
+            let argument = *argument;
+            // A listed call inside the argument reports the lines of its own arguments
+            let nested_rows = |row: usize| arguments.iter().any(|&(_, _, other)| other != argument
+                && other.start_byte() >= argument.start_byte() && other.end_byte() <= argument.end_byte()
+                && (other.start_position().row..=other.end_position().row).contains(&row));
+            let start_row = argument.start_position().row;
+            for (i, segment) in php::node_text(argument, content).lines().enumerate() {
+                let row = start_row + i;
+                if !pattern.is_match(segment) || nested_rows(row) {
+                    continue;
+                }
+                let label = format!("{}() arg {}", callable, which);
+                let line_styled = highlight(lines.get(row).copied().unwrap_or(segment), &pattern);
+                match php::enclosing_function(argument, content) {
+                    Some(func_name) => print_body_match(path, row + 1, func_name, Some(label), &line_styled),
+                    None => print_symbol_match(path, row + 1, Some(&format!("[{}]", label)), &line_styled),
+                }
+            }
+        }
+    })
+}
+
+// Runs a tree-sitter query on the code of another language inside the strings of each file, so
+// `--inject sql '(where) @w'` finds WHERE clauses however the SQL is spaced or quoted. The lines
+// are reported where the first capture of each match starts
+fn inject_search(query: &str, language: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let grammar = inject::language(language);
+    // A query without a capture captures the whole pattern. The newline keeps tree-sitter from
+    // slicing past the end of the query when the error is in a name at its end
+    let query = match tree_sitter::Query::new(grammar, &format!("{}\n", query)) {
+        Ok(parsed) if !parsed.capture_names().is_empty() => Ok(parsed),
+        Ok(_) => tree_sitter::Query::new(grammar, &format!("{} @match\n", query)),
+        Err(e) => Err(e),
+    };
+    let query = match query {
+        Ok(query) => query,
+        Err(e) => {
+            eprintln!("Error: Invalid {} query: {}", language, e);
+            return Err(anyhow::anyhow!("Invalid {} query", language));
+        }
+    };
+    let mut parser = TreeSitterParser::new();
+    parser.set_language(grammar)?;
+    let mut cursor = tree_sitter::QueryCursor::new();
+
+    for_each_php_tree(dir, file, exclude_dirs, |content| !content.is_empty(), |path, content, tree| {
+        let lines: Vec<&str> = content.lines().collect();
+        for injection in inject::injections(tree.root_node(), content, language) {
+            let Some(code_tree) = parser.parse(&injection.code, None) else { continue };
+            let mut rows = std::collections::BTreeMap::new();
+            for found in cursor.matches(&query, code_tree.root_node(), injection.code.as_bytes()) {
+                let Some(capture) = found.captures.first() else { continue };
+                let (start, end) = (injection.start + capture.node.start_byte(), injection.start + capture.node.end_byte());
+                let row = content[..start].matches('\n').count();
+                rows.entry(row).or_insert((start, end));
+            }
+            for (row, (start, end)) in rows {
+                let line = lines.get(row).copied().unwrap_or("");
+                let line_start = content[..start].rfind('\n').map_or(0, |newline| newline + 1);
+                let from = (start - line_start).min(line.len());
+                let to = (end - line_start).clamp(from, line.len());
+                let line_styled = if line.is_char_boundary(from) && line.is_char_boundary(to) {
+                    format!("{}{}{}", &line[..from], line[from..to].bold().red(), &line[to..])
+                } else {
+                    line.to_string()
+                };
+                match php::enclosing_function(injection.node, content) {
+                    Some(func_name) => print_body_match(path, row + 1, func_name, Some(language.to_string()), &line_styled),
+                    None => print_symbol_match(path, row + 1, Some(&format!("[{}]", language)), &line_styled),
+                }
+            }
+        }
+    })
+}
+
+// Finds `new` expressions creating the given class, resolving each file's namespace and imports
+// so aliased or partially qualified names still count while docblocks and static calls don't
+fn instantiation_search(class: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    class_reference_search(class, dir, file, exclude_dirs, instantiated_class)
+}
+
+fn instantiated_class(node: tree_sitter::Node) -> Vec<tree_sitter::Node> {
+    if node.kind() != "object_creation_expression" {
+        return Vec::new();
+    }
+    node.named_child(0).into_iter().collect()
+}
+
+// Finds throw expressions raising the given exception, either with `new` or a static factory
+fn throws_search(class: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    class_reference_search(class, dir, file, exclude_dirs, thrown_class)
+}
+
+fn thrown_class(node: tree_sitter::Node) -> Vec<tree_sitter::Node> {
+    if node.kind() != "throw_expression" {
+        return Vec::new();
+    }
+    match node.named_child(0) {
+        Some(thrown) if thrown.kind() == "object_creation_expression" => thrown.named_child(0).into_iter().collect(),
+        Some(thrown) if thrown.kind() == "scoped_call_expression" => thrown.child_by_field_name("scope").into_iter().collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Finds catch clauses handling the given exception, including each member of a union catch
+fn catches_search(class: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    class_reference_search(class, dir, file, exclude_dirs, caught_classes)
+}
+
+fn caught_classes(node: tree_sitter::Node) -> Vec<tree_sitter::Node> {
+    if node.kind() != "catch_clause" {
+        return Vec::new();
+    }
+    let Some(types) = node.child_by_field_name("type") else { return Vec::new() };
+    let mut cursor = types.walk();
+    types.named_children(&mut cursor)
+        .filter_map(|named_type| named_type.named_child(0))
+        .collect()
+}
+
+// Shared walk for the class based modes: `class_nodes` picks the class name nodes a syntax node
+// refers to, which are then resolved against the file's imports and compared with the query
+fn class_reference_search(class: &str, dir: &str, file: &str, exclude_dirs: &str, class_nodes: fn(tree_sitter::Node) -> Vec<tree_sitter::Node>) -> Result<()> {
+    let short_name = php::short_name(class.trim_start_matches('\\')).to_lowercase();
+    let prefilter = |content: &str| content.to_lowercase().contains(&short_name);
+
+    for_each_php_tree(dir, file, exclude_dirs, prefilter, |path, content, tree| {
+        let names = php::FileNames::new(tree.root_node(), content);
+        let lines: Vec<&str> = content.lines().collect();
+        php::walk(tree.root_node(), &mut |node| {
+            for class_node in class_nodes(node) {
+                if class_node.kind() != "name" && class_node.kind() != "qualified_name" {
+                    continue;
+                }
+                let Some(fqcn) = names.resolve_node(class_node, content) else { continue };
+                if !php::class_matches(&fqcn, class) {
+                    continue;
+                }
+
+                let row = class_node.start_position().row;
+                print_match(path, row + 1, php::enclosing_function(node, content), &highlight_node(&lines, class_node));
+            }
+        });
+    })
+}
+
+// Finds every place a class is named in code, the way an IDE's find usages does: the imports
+// in `use` statements and every reference php::class_references knows about, resolved through
+// each file's namespace and aliases so `use Foo\Bar as Baz; new Baz()` counts as Foo\Bar
+fn class_ref_search(class: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let short_name = php::short_name(class.trim_start_matches('\\')).to_lowercase();
+    let prefilter = |content: &str| content.to_lowercase().contains(&short_name);
+
+    for_each_php_tree(dir, file, exclude_dirs, prefilter, |path, content, tree| {
+        let names = php::FileNames::new(tree.root_node(), content);
+        let lines: Vec<&str> = content.lines().collect();
+        let mut references = Vec::new();
+        php::walk(tree.root_node(), &mut |node| {
+            if node.kind() == "namespace_use_declaration" {
+                references.extend(php::use_clauses(node, content).into_iter().filter(|(_, fqcn)| php::class_matches(fqcn, class)).map(|(clause, _)| clause));
+            }
+        });
+        references.extend(php::class_references(tree.root_node(), content).into_iter()
+            .filter(|node| names.resolve_node(*node, content).is_some_and(|fqcn| php::class_matches(&fqcn, class))));
+        references.sort_by_key(|node| node.start_byte());
+
+        for node in references {
+            print_match(path, node.start_position().row + 1, php::enclosing_function(node, content), &highlight_node(&lines, node));
+        }
+    })
+}
+
+// Finds the type checks against a class: `instanceof`, `is_a()` and `is_subclass_of()` calls,
+// and `get_class($x)` or `$x::class` compared with ==, ===, != or !==. The class can be written
+// as a name, as `Name::class` or as a string, and is resolved through the file's imports
+fn instanceof_search(class: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let short_name = php::short_name(class.trim_start_matches('\\')).to_lowercase();
+    let prefilter = |content: &str| content.to_lowercase().contains(&short_name);
+
+    for_each_php_tree(dir, file, exclude_dirs, prefilter, |path, content, tree| {
+        let names = php::FileNames::new(tree.root_node(), content);
+        let lines: Vec<&str> = content.lines().collect();
+        php::walk(tree.root_node(), &mut |node| {
+            let checked = match node.kind() {
+                "binary_expression" => {
+                    let (Some(left), Some(right)) = (node.child_by_field_name("left"), node.child_by_field_name("right")) else { return };
+                    match node.child_by_field_name("operator").map(|operator| operator.kind()) {
+                        Some("instanceof") => right,
+                        Some("==" | "===" | "!=" | "!==") if is_class_of(left, content) => right,
+                        Some("==" | "===" | "!=" | "!==") if is_class_of(right, content) => left,
+                        _ => return,
+                    }
+                }
+                "function_call_expression" => {
+                    let function = node.child_by_field_name("function").map_or("", |function| php::node_text(function, content).trim_start_matches('\\'));
+                    if !function.eq_ignore_ascii_case("is_a") && !function.eq_ignore_ascii_case("is_subclass_of") {
+                        return;
+                    }
+                    let Some(argument) = node.child_by_field_name("arguments").and_then(|arguments| arguments.named_child(1)).and_then(|argument| argument.named_child(0)) else { return };
+                    argument
+                }
+                _ => return,
+            };
+            if checked_class(checked, &names, content).is_some_and(|fqcn| php::class_matches(&fqcn, class)) {
+                print_match(path, node.start_position().row + 1, php::enclosing_function(node, content), &highlight_node(&lines, node));
+            }
+        });
+    })
+}
+
+// The functions and superglobals --env looks for, and whether they read config keys rather
+// than environment variables
+const ENV_READS: [(&str, bool); 5] = [("getenv", false), ("$_ENV", false), ("$_SERVER", false), ("env", false), ("config", true)];
+
+// Finds reads of environment variables and config keys, reporting each with the name it reads,
+// then lists the distinct names with how often and how they're read: the inventory of what an
+// app expects its environment to provide. Reads whose name is computed are counted separately
+fn env_search(dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let prefilter = |content: &str| ["getenv", "$_ENV", "$_SERVER", "env(", "config("].iter().any(|read| content.contains(read));
+    // Name to whether it's a config key, how often it's read and the ways it's read
+    let mut names: std::collections::BTreeMap<String, (bool, usize, Vec<String>)> = std::collections::BTreeMap::new();
+    let mut computed = 0;
+
+    for_each_php_tree(dir, file, exclude_dirs, prefilter, |path, content, tree| {
+        let lines: Vec<&str> = content.lines().collect();
+        php::walk(tree.root_node(), &mut |node| {
+            let (read, key) = match node.kind() {
+                "function_call_expression" => {
+                    let function = node.child_by_field_name("function").map_or("", |function| php::node_text(function, content).trim_start_matches('\\'));
+                    let Some(read) = ENV_READS.iter().find(|(read, _)| !read.starts_with('$') && read.eq_ignore_ascii_case(function)) else { return };
+                    (read, node.child_by_field_name("arguments").and_then(|arguments| arguments.named_child(0)).and_then(|argument| argument.named_child(0)))
+                }
+                "subscript_expression" => {
+                    let variable = node.named_child(0).map_or("", |variable| php::node_text(variable, content));
+                    let Some(read) = ENV_READS.iter().find(|(read, _)| *read == variable) else { return };
+                    (read, node.named_child(1))
+                }
+                _ => return,
+            };
+            let (function, is_config) = *read;
+            let label = if function.starts_with('$') { function.to_string() } else { format!("{}()", function) };
+            let name = key.and_then(|key| php::string_literal(key, content));
+            match &name {
+                Some(name) => {
+                    let (_, count, reads) = names.entry(name.clone()).or_insert((is_config, 0, Vec::new()));
+                    *count += 1;
+                    if !reads.contains(&label) {
+                        reads.push(label.clone());
+                    }
+                }
+                None => computed += 1,
+            }
+
+            let arm = format!("{} {}", label, name.as_deref().unwrap_or("?"));
+            let line_styled = highlight_node(&lines, node);
+            match php::enclosing_function(node, content) {
+                Some(func_name) => print_body_match(path, node.start_position().row + 1, func_name, Some(arm), &line_styled),
+                None => print_symbol_match(path, node.start_position().row + 1, Some(&format!("[{}]", arm)), &line_styled),
+            }
+        });
+    })?;
+
+    for (is_config, heading) in [(false, "Environment variables"), (true, "Config keys")] {
+        let read: Vec<_> = names.iter().filter(|(_, (config, _, _))| *config == is_config).collect();
+        if read.is_empty() {
+            continue;
+        }
+        report::summary(format!("{}: {}", heading.bold(), read.len()));
+        let width = read.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        for (name, (_, count, reads)) in read {
+            report::summary(format!("  {:<width$}  {:>4}  {}", name, count, reads.join(", ").dimmed(), width = width));
+        }
+    }
+    if computed > 0 {
+        report::summary(format!("{} read{} with a computed name", computed, if computed == 1 { "" } else { "s" }));
+    }
+    Ok(())
+}
+
+// A translation call whose key isn't a string literal
+struct DynamicKey {
+    path: std::path::PathBuf,
+    line: usize,
+    caller: Option<String>,
+    line_styled: String,
+}
+
+// Finds calls of the translation functions, and of methods of the same names like
+// `$translator->trans()`, reporting each with the literal key it passes, then lists the
+// distinct keys with how often they're used. Calls passing a variable, concatenation or other
+// computed key are reported separately, as those keys can't be extracted
+fn i18n_search(functions: &[String], dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let prefilter = |content: &str| functions.iter().any(|function| content.contains(&format!("{}(", function)));
+    let mut keys: std::collections::BTreeMap<String, usize> = std::collections::BTreeMap::new();
+    let mut dynamic = Vec::new();
+
+    for_each_php_tree(dir, file, exclude_dirs, prefilter, |path, content, tree| {
+        let lines: Vec<&str> = content.lines().collect();
+        php::walk(tree.root_node(), &mut |node| {
+            let function = match node.kind() {
+                "function_call_expression" => node.child_by_field_name("function"),
+                "member_call_expression" | "nullsafe_member_call_expression" | "scoped_call_expression" => node.child_by_field_name("name"),
+                _ => None,
+            };
+            let Some(function) = function.map(|function| php::short_name(php::node_text(function, content))) else { return };
+            if !functions.iter().any(|name| name.eq_ignore_ascii_case(function)) {
+                return;
+            }
+            let Some(argument) = node.child_by_field_name("arguments").and_then(|arguments| arguments.named_child(0)).and_then(|argument| argument.named_child(0)) else { return };
+            let line = node.start_position().row + 1;
+            let line_styled = highlight_node(&lines, node);
+            let Some(key) = php::string_literal(argument, content) else {
+                dynamic.push(DynamicKey { path: path.to_path_buf(), line, caller: php::enclosing_function(node, content).map(str::to_string), line_styled });
+                return;
+            };
+            *keys.entry(key.clone()).or_insert(0) += 1;
+
+            let arm = format!("{}() {}", function, key);
+            match php::enclosing_function(node, content) {
+                Some(func_name) => print_body_match(path, line, func_name, Some(arm), &line_styled),
+                None => print_symbol_match(path, line, Some(&format!("[{}]", arm)), &line_styled),
+            }
+        });
+    })?;
+
+    if !dynamic.is_empty() {
+        report::summary(format!("{}: {}", "Dynamic keys".bold().yellow(), dynamic.len()));
+        for key in &dynamic {
+            print_match(&key.path, key.line, key.caller.as_deref(), &key.line_styled);
+        }
+    }
+    if !keys.is_empty() {
+        report::summary(format!("{}: {}", "Translation keys".bold(), keys.len()));
+        let width = keys.keys().map(|key| key.len()).max().unwrap_or(0);
+        for (key, count) in &keys {
+            report::summary(format!("  {:<width$}  {:>4}", key, count, width = width));
+        }
+    }
+    Ok(())
+}
+
+// The PSR-3 log levels, most severe first, which are also the names of the logger methods
+const LOG_LEVELS: [&str; 8] = ["emergency", "alert", "critical", "error", "warning", "notice", "info", "debug"];
+
+// The level a call logs at and the argument holding its message, if it's a logger call: a
+// level method called on something named like a logger (`$this->logger`, `Log::`, `logger()`),
+// `log($level, $message)` on one, or `error_log()` and `syslog()`
+fn log_call<'a>(node: tree_sitter::Node<'a>, content: &str) -> Option<(String, Option<tree_sitter::Node<'a>>)> {
+    let arguments: Vec<tree_sitter::Node> = node.child_by_field_name("arguments")
+        .map(|arguments| {
+            let mut cursor = arguments.walk();
+            arguments.named_children(&mut cursor).filter(|argument| argument.kind() == "argument").filter_map(|argument| argument.named_child(0)).collect()
+        })
+        .unwrap_or_default();
+    if node.kind() == "function_call_expression" {
+        let function = php::node_text(node.child_by_field_name("function")?, content).trim_start_matches('\\').to_lowercase();
+        return match function.as_str() {
+            "error_log" => Some(("error".to_string(), arguments.first().copied())),
+            "syslog" => {
+                let level = arguments.first().map_or("", |level| php::node_text(*level, content)).trim_start_matches("LOG_").to_lowercase();
+                let level = match level.as_str() {
+                    "emerg" => "emergency",
+                    "crit" => "critical",
+                    "err" => "error",
+                    "warning" | "notice" | "info" | "debug" | "alert" => level.as_str(),
+                    _ => "?",
+                };
+                Some((level.to_string(), arguments.get(1).copied()))
+            }
+            _ => None,
+        };
+    }
+
+    let receiver = node.child_by_field_name("object").or_else(|| node.child_by_field_name("scope"))?;
+    let receiver = php::node_text(receiver, content).to_lowercase();
+    if !receiver.rsplit(['>', ':', '$', '\\']).next().is_some_and(|last| last.contains("log")) {
+        return None;
+    }
+    let method = php::node_text(node.child_by_field_name("name")?, content).to_lowercase();
+    if LOG_LEVELS.contains(&method.as_str()) {
+        return Some((method, arguments.first().copied()));
+    }
+    if method != "log" {
+        return None;
+    }
+    // The level of `log()` is a string or a constant like `LogLevel::ERROR`
+    let level = arguments.first().map(|level| php::string_literal(*level, content).unwrap_or_else(|| php::node_text(*level, content).rsplit("::").next().unwrap_or("").to_string()));
+    let level = level.map(|level| level.to_lowercase()).filter(|level| LOG_LEVELS.contains(&level.as_str()));
+    Some((level.unwrap_or_else(|| "?".to_string()), arguments.get(1).copied()))
+}
+
+// Finds logger calls, reporting each with its level and message template, then sums them up by
+// level and lists the distinct templates for a message catalog. With `levels` only calls at
+// those levels are reported. Messages that aren't a string literal are counted separately
+fn log_search(levels: &[String], dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let prefilter = |content: &str| content.contains("log") || content.contains("Log");
+    let mut by_level: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+    let mut templates: std::collections::BTreeMap<(String, String), usize> = std::collections::BTreeMap::new();
+    let mut computed = 0;
+
+    for_each_php_tree(dir, file, exclude_dirs, prefilter, |path, content, tree| {
+        let lines: Vec<&str> = content.lines().collect();
+        php::walk(tree.root_node(), &mut |node| {
+            if !matches!(node.kind(), "function_call_expression" | "member_call_expression" | "nullsafe_member_call_expression" | "scoped_call_expression") {
+                return;
+            }
+            let Some((level, message)) = log_call(node, content) else { return };
+            if !levels.is_empty() && !levels.iter().any(|wanted| wanted.eq_ignore_ascii_case(&level)) {
+                return;
+            }
+            let line = node.start_position().row + 1;
+            if !is_reported(path, line) {
+                return;
+            }
+            *by_level.entry(level.clone()).or_insert(0) += 1;
+            let template = message.and_then(|message| php::string_literal(message, content));
+            match &template {
+                Some(template) => *templates.entry((level.clone(), template.clone())).or_insert(0) += 1,
+                None => computed += 1,
+            }
+
+            let arm = format!("{} {}", level, template.as_deref().unwrap_or("?"));
+            let line_styled = highlight_node(&lines, node);
+            match php::enclosing_function(node, content) {
+                Some(func_name) => print_body_match(path, line, func_name, Some(arm), &line_styled),
+                None => print_symbol_match(path, line, Some(&format!("[{}]", arm)), &line_styled),
+            }
+        });
+    })?;
+
+    if by_level.is_empty() {
+        return Ok(());
+    }
+    let severity = |level: &str| LOG_LEVELS.iter().position(|known| *known == level).unwrap_or(LOG_LEVELS.len());
+    let mut counts: Vec<(&String, &usize)> = by_level.iter().collect();
+    counts.sort_by_key(|(level, _)| severity(level));
+    report::summary(format!("{}: {}", "Log calls".bold(), counts.iter().map(|(level, count)| format!("{} {}", level, count)).collect::<Vec<_>>().join(", ")));
+    if !templates.is_empty() {
+        let mut templates: Vec<(&(String, String), &usize)> = templates.iter().collect();
+        templates.sort_by_key(|((level, _), _)| severity(level));
+        report::summary(format!("{}: {}", "Message templates".bold(), templates.len()));
+        let width = templates.iter().map(|((level, _), _)| level.len()).max().unwrap_or(0);
+        for ((level, template), count) in templates {
+            report::summary(format!("  {:<width$}  {:>4}  {}", level, count, template, width = width));
+        }
+    }
+    if computed > 0 {
+        report::summary(format!("{} call{} with a computed message", computed, if computed == 1 { "" } else { "s" }));
+    }
+    Ok(())
+}
+
+// Searches only inside test methods, in the files --tests-only picks, to answer questions like
+// which tests assert on an error message
+fn assertion_search(query: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let pattern = match budget::regex(query) {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            eprintln!("Invalid regex pattern: {}", e);
+            return Err(anyhow::anyhow!("Invalid regex pattern"));
+        }
+    };
+
+    for_each_php_tree(dir, file, exclude_dirs, |content| pattern.is_match(content), |path, content, tree| {
+        let lines: Vec<&str> = content.lines().collect();
+        php::walk(tree.root_node(), &mut |node| {
+            if node.kind() != "method_declaration" || !is_test_method(node, content) {
+                return;
+            }
+            let (Some(name), Some(body)) = (node.child_by_field_name("name"), node.child_by_field_name("body")) else { return };
+            let func_name = php::node_text(name, content);
+            let start_row = body.start_position().row;
+            context::begin_function(node, content);
+            for row in start_row..=body.end_position().row {
+                let line = lines.get(row).copied().unwrap_or("");
+                if pattern.is_match(line) {
+                    print_body_match(path, row + 1, func_name, arm_at(body, row - start_row, line, content), &highlight(line, &pattern));
+                }
+            }
+            context::end_function();
+        });
+    })
+}
+
+// Whether PHPUnit runs a method as a test: its name starts with `test`, it has a #[Test]
+// attribute or its docblock has an @test tag
+fn is_test_method(method: tree_sitter::Node, content: &str) -> bool {
+    if method.child_by_field_name("name").is_some_and(|name| php::node_text(name, content).starts_with("test")) {
+        return true;
+    }
+    let mut has_attribute = false;
+    if let Some(attributes) = method.child_by_field_name("attributes") {
+        php::walk(attributes, &mut |node| {
+            if node.kind() == "attribute" && node.named_child(0).is_some_and(|name| php::short_name(php::node_text(name, content)) == "Test") {
+                has_attribute = true;
+            }
+        });
+    }
+    has_attribute || method.prev_named_sibling()
+        .filter(|comment| comment.kind() == "comment" && php::node_text(*comment, content).starts_with("/**"))
+        .is_some_and(|comment| php::node_text(comment, content).split_whitespace().any(|word| word == "@test"))
+}
+
+// A definition or call of a function found by --function
+struct FunctionSite {
+    path: std::path::PathBuf,
+    line: usize,
+    caller: Option<String>,
+    line_styled: String,
+}
+
+// Lists every global function whose name matches `functions` with its definitions followed by
+// its calls. The matches are collected over the whole search first so a function defined in
+// one file and called in others is reported in one place, and a function that's called but
+// never defined below the search directory gets flagged as most likely coming from PHP, an
+// extension or a framework
+fn function_search(functions: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let functions = match budget::regex(&format!("(?i)^(?:{})$", functions)) {
+        Ok(functions) => functions,
+        Err(e) => {
+            eprintln!("Invalid --function pattern: {}", e);
+            return Err(anyhow::anyhow!("Invalid --function pattern"));
+        }
+    };
+
+    // Function key to its name as first seen, its definitions and its calls
+    let mut found: std::collections::BTreeMap<String, (String, Vec<FunctionSite>, Vec<FunctionSite>)> = std::collections::BTreeMap::new();
+    for_each_php_tree(dir, file, exclude_dirs, |content| content.contains('('), |path, content, tree| {
+        let names = php::FileNames::new(tree.root_node(), content);
+        let lines: Vec<&str> = content.lines().collect();
+        php::walk(tree.root_node(), &mut |node| {
+            let (name, is_definition, site) = if node.kind() == "function_definition" {
+                let Some(name) = node.child_by_field_name("name") else { return };
+                let site = FunctionSite { path: path.to_path_buf(), line: node.start_position().row + 1, caller: None, line_styled: php::signature(node, content) };
+                (php::node_text(name, content), true, site)
+            } else if let Some(call) = calls::call_site(node, content, &names).filter(|call| call.kind == calls::CallKind::Function) {
+                let site = FunctionSite {
+                    path: path.to_path_buf(),
+                    line: call.name_node.start_position().row + 1,
+                    caller: php::enclosing_function(node, content).map(str::to_string),
+                    line_styled: highlight_node(&lines, call.name_node),
+                };
+                (call.name, false, site)
+            } else {
+                return;
+            };
+            if !functions.is_match(name) {
+                return;
+            }
+            let (_, definitions, calls) = found.entry(calls::function_key(name)).or_insert_with(|| (name.to_string(), Vec::new(), Vec::new()));
+            if is_definition {
+                definitions.push(site);
+            } else {
+                calls.push(site);
+            }
+        });
+    })?;
+
+    for (name, definitions, calls) in found.values() {
+        let plural = |count: usize| if count == 1 { "" } else { "s" };
+        if definitions.is_empty() {
+            report::summary(format!("{}() called {} time{}, {}", name.bold(), calls.len(), plural(calls.len()), "never defined in the search directory, likely a PHP, extension or framework function".yellow()));
+        } else {
+            report::summary(format!("{}() defined {} time{}, called {} time{}", name.bold(), definitions.len(), plural(definitions.len()), calls.len(), plural(calls.len())));
+        }
+        for site in definitions {
+            print_body_match(&site.path, site.line, name, Some("definition".to_string()), &site.line_styled);
+        }
+        for site in calls {
+            print_match(&site.path, site.line, site.caller.as_deref(), &site.line_styled);
+        }
+    }
+    Ok(())
+}
+
+// Whether an expression gives the class of an object: `get_class($x)` or `$x::class`
+fn is_class_of(node: tree_sitter::Node, content: &str) -> bool {
+    match node.kind() {
+        "function_call_expression" => node.child_by_field_name("function")
+            .is_some_and(|function| php::node_text(function, content).trim_start_matches('\\').eq_ignore_ascii_case("get_class")),
+        "class_constant_access_expression" => node.named_child(0).is_some_and(|object| object.kind() == "variable_name")
+            && node.named_child(1).is_some_and(|name| php::node_text(name, content).eq_ignore_ascii_case("class")),
+        _ => false,
+    }
+}
+
+// The fully qualified class a type check compares with, written as `Name`, `Name::class` or a
+// string holding the fully qualified name
+fn checked_class(node: tree_sitter::Node, names: &php::FileNames, content: &str) -> Option<String> {
+    match node.kind() {
+        "name" | "qualified_name" => names.resolve_node(node, content),
+        "class_constant_access_expression" => {
+            let class = node.named_child(0).filter(|class| class.kind() == "name" || class.kind() == "qualified_name")?;
+            node.named_child(1).filter(|name| php::node_text(*name, content).eq_ignore_ascii_case("class"))?;
+            names.resolve_node(class, content)
+        }
+        "string" | "encapsed_string" => {
+            let text = php::node_text(node, content);
+            let text = text.get(1..text.len().checked_sub(1)?)?;
+            Some(text.replace("\\\\", "\\").trim_start_matches('\\').to_string())
+        }
+        _ => None,
+    }
+}
+
+// Finds `Class::NAME` accesses of a class constant or enum case, resolving the class through
+// imports, aliases and self/static/parent. Strings that merely contain the text don't count
+fn const_usage_search(query: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let Some((class, constant)) = query.rsplit_once("::").filter(|(class, constant)| !class.is_empty() && !constant.is_empty()) else {
+        eprintln!("Error: Expected the constant as Class::NAME, got '{}'.", query);
+        return Err(anyhow::anyhow!("Expected the constant as Class::NAME, got '{}'", query));
+    };
+
+    for_each_php_tree(dir, file, exclude_dirs, |content| content.contains(constant), |path, content, tree| {
+        let names = php::FileNames::new(tree.root_node(), content);
+        let lines: Vec<&str> = content.lines().collect();
+        php::walk(tree.root_node(), &mut |node| {
+            if node.kind() != "class_constant_access_expression" {
+                return;
+            }
+            let mut cursor = node.walk();
+            let children: Vec<tree_sitter::Node> = node.named_children(&mut cursor).collect();
+            let [scope, name] = children.as_slice() else { return };
+            // Constant names are case-sensitive, unlike class names
+            if php::node_text(*name, content) != constant {
+                return;
+            }
+            let Some(fqcn) = names.resolve_node(*scope, content) else { return };
+            if php::class_matches(&fqcn, class) {
+                print_match(path, node.start_position().row + 1, php::enclosing_function(node, content), &highlight_node(&lines, node));
+            }
+        });
+    })
+}
+
+// Lists self::, static:: and parent:: calls of a method. A self:: call is flagged when
+// subclasses override the method, since it keeps calling the class's own version, and a static::
+// call when it dispatches to those overrides. parent:: calls name the ancestor they end up in
+fn late_static_binding_search(query: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let (class_filter, method) = match query.rsplit_once("::") {
+        Some((class, method)) => (Some(class), method),
+        None => (None, query),
+    };
+
+    let files = declarations::scan(dir, file, exclude_dirs)?;
+    let classes: Vec<&declarations::ClassDecl> = files.iter().flat_map(|(_, decls)| decls.classes.iter()).collect();
+    let parents = declarations::hierarchy(classes.iter().copied());
+    let declares = |class: &declarations::ClassDecl| class.methods.iter().any(|m| m.name.eq_ignore_ascii_case(method));
+    let find_class = |fqcn: &str| classes.iter().copied().find(|class| class.fqcn.eq_ignore_ascii_case(fqcn));
+    // Call sites in the hierarchy of the --late-static-binding class, when one was given
+    let in_hierarchy = |fqcn: &str| class_filter.is_none_or(|filter| {
+        classes.iter().any(|class| php::class_matches(&class.fqcn, filter)
+            && (class.fqcn.eq_ignore_ascii_case(fqcn) || declarations::inherits_from(fqcn, &class.fqcn, &parents) || declarations::inherits_from(&class.fqcn, fqcn, &parents)))
+    });
+
+    let method_lower = method.to_lowercase();
+    for_each_php_tree(dir, file, exclude_dirs, |content| content.to_lowercase().contains(&method_lower), |path, content, tree| {
+        let names = php::FileNames::new(tree.root_node(), content);
+        let lines: Vec<&str> = content.lines().collect();
+        php::walk(tree.root_node(), &mut |node| {
+            if node.kind() != "scoped_call_expression" {
+                return;
+            }
+            let (Some(scope), Some(name)) = (node.child_by_field_name("scope"), node.child_by_field_name("name")) else { return };
+            if !php::node_text(name, content).eq_ignore_ascii_case(method) {
+                return;
+            }
+            let scope_text = php::node_text(scope, content).to_lowercase();
+            if !matches!(scope_text.as_str(), "self" | "static" | "parent") {
+                return;
+            }
+            let Some(caller) = php::enclosing_class(node).and_then(|class| names.class_fqcn(class, content)) else { return };
+            if !in_hierarchy(&caller) {
+                return;
+            }
+
+            let overrides: Vec<&str> = classes.iter()
+                .filter(|class| declares(class) && declarations::inherits_from(&class.fqcn, &caller, &parents))
+                .map(|class| class.name.as_str())
+                .collect();
+            let note = match scope_text.as_str() {
+                "self" if !overrides.is_empty() => format!("[self:: skips the overrides in {}]", overrides.join(", ")).red().to_string(),
+                "static" if !overrides.is_empty() => format!("[static:: calls the overrides in {} when called through a subclass]", overrides.join(", ")).yellow().to_string(),
+                "parent" => {
+                    // The first ancestor up the extends chain declaring the method
+                    let mut current = find_class(&caller).and_then(|class| class.extends.first()).and_then(|parent| find_class(parent));
+                    while let Some(class) = current.filter(|class| !declares(class)) {
+                        current = class.extends.first().and_then(|parent| find_class(parent));
+                    }
+                    match current {
+                        Some(class) => format!("[calls {}::{}]", class.name, method).dimmed().to_string(),
+                        None => String::new(),
+                    }
+                }
+                _ => String::new(),
+            };
+
+            let line = format!("{} {}", highlight_node(&lines, node).trim(), note);
+            let symbol = php::enclosing_function(node, content).map(|function| format!("{}::{}", php::short_name(&caller), function));
+            print_match(path, node.start_position().row + 1, symbol.as_deref(), &line);
+        });
+    })
+}
+
+// Searches only the expressions of return statements inside functions and methods
+fn returns_expr_search(query: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let pattern = match budget::regex(query) {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            eprintln!("Invalid regex pattern: {}", e);
+            return Err(anyhow::anyhow!("Invalid regex pattern"));
+        }
+    };
+
+    for_each_php_tree(dir, file, exclude_dirs, |content| content.contains("return") && pattern.is_match(content), |path, content, tree| {
+        php::walk(tree.root_node(), &mut |node| {
+            if node.kind() != "return_statement" {
+                return;
+            }
+            let Some(func_name) = php::enclosing_function(node, content) else { return };
+            let start_row = node.start_position().row;
+            for (i, line) in php::node_text(node, content).lines().enumerate() {
+                if pattern.is_match(line) {
+                    print_match(path, start_row + i + 1, Some(func_name), &highlight(line, &pattern));
+                }
+            }
+        });
+    })
+}
+
+// Finds match arms and switch cases with a condition matching the pattern, reporting each arm once
+fn match_arm_search(query: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let pattern = match budget::regex(query) {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            eprintln!("Invalid regex pattern: {}", e);
+            return Err(anyhow::anyhow!("Invalid regex pattern"));
+        }
+    };
+
+    let prefilter = |content: &str| (content.contains("match") || content.contains("case")) && pattern.is_match(content);
+    for_each_php_tree(dir, file, exclude_dirs, prefilter, |path, content, tree| {
+        let lines: Vec<&str> = content.lines().collect();
+        php::walk(tree.root_node(), &mut |node| {
+            let conditions: Vec<tree_sitter::Node> = match node.kind() {
+                "match_conditional_expression" => {
+                    let Some(list) = node.child_by_field_name("conditional_expressions") else { return };
+                    let mut cursor = list.walk();
+                    list.named_children(&mut cursor).collect()
+                }
+                "case_statement" => node.child_by_field_name("value").into_iter().collect(),
+                _ => return,
+            };
+            let Some(condition) = conditions.into_iter().find(|condition| pattern.is_match(php::node_text(*condition, content))) else { return };
+            let row = condition.start_position().row;
+            print_match(path, row + 1, php::enclosing_function(node, content), &highlight_node(&lines, condition));
+        });
+    })
+}
+
+// Finds declared parameter, property and return types equal to the query type. Types are
+// compared after normalization, so member order, `?T` vs `T|null` and imports don't matter
+fn type_query_search(query: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let query_type = match declarations::TypeDecl::parse(query) {
+        Ok(query_type) => query_type,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return Err(e);
+        }
+    };
+
+    let check_function = |path: &std::path::Path, symbol: &str, function: &declarations::FunctionDecl| {
+        for param in &function.params {
+            if param.type_hint.as_ref().is_some_and(|type_hint| type_hint.matches(&query_type)) {
+                print_match(path, function.line, Some(symbol), &format!("parameter {}", param.to_string().bold().red()));
+            }
+        }
+        if let Some(return_type) = &function.return_type
+            && return_type.matches(&query_type) {
+            print_match(path, function.line, Some(symbol), &format!("returns {}", return_type.text.bold().red()));
+        }
+    };
+
+    for (path, decls) in declarations::scan(dir, file, exclude_dirs)? {
+        for class in &decls.classes {
+            for property in &class.properties {
+                if let Some(type_hint) = &property.type_hint
+                    && type_hint.matches(&query_type) {
+                    let symbol = format!("{}::${}", class.name, property.name);
+                    print_symbol_match(&path, property.line, Some(&symbol), &format!("property {}", type_hint.text.bold().red()));
+                }
+            }
+            for method in &class.methods {
+                check_function(&path, &format!("{}::{}", class.name, method.name), method);
+            }
+        }
+        for function in &decls.functions {
+            check_function(&path, &function.name, function);
+        }
+    }
+    Ok(())
+}
+
+// Finds tags in docblocks and reports each with its value and the symbol the docblock documents:
+// the declaration right after it, or the enclosing function for inline `/** @var */` blocks
+fn doc_tag_search(query: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let query = format!("@{}", query.trim_start_matches('@')).to_lowercase();
+    let (query, prefix) = match query.strip_suffix('*') {
+        Some(prefix) => (prefix.to_string(), true),
+        None => (query, false),
+    };
+    let tag_matches = |tag: &str| {
+        let tag = tag.to_lowercase();
+        if prefix { tag.starts_with(&query) } else { tag == query }
+    };
+
+    for_each_php_tree(dir, file, exclude_dirs, |content| content.to_lowercase().contains(&query), |path, content, tree| {
+        let names = php::FileNames::new(tree.root_node(), content);
+        php::walk(tree.root_node(), &mut |node| {
+            if node.kind() != "comment" || !php::node_text(node, content).starts_with("/**") {
+                return;
+            }
+            let symbol = doc_symbol(node, content, &names);
+            for (i, line) in php::node_text(node, content).lines().enumerate() {
+                let line = line.trim().trim_start_matches("/**").trim_end_matches("*/").trim_start_matches('*').trim();
+                let tag = line.split_whitespace().next().unwrap_or("");
+                if !tag.starts_with('@') || !tag_matches(tag) {
+                    continue;
+                }
+                let line_styled = format!("{}{}", tag.bold().red(), &line[tag.len()..]);
+                print_symbol_match(path, node.start_position().row + i + 1, symbol.as_deref(), &line_styled);
+            }
+        });
+    })
+}
+
+// Finds methods and functions by what their docblock says they do: the description above the
+// tags is matched case-insensitively and each hit is printed as the signature with the matching
+// description line, so a method is found when only its purpose is remembered
+fn doc_search(query: &str, dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let pattern = match budget::builder(query).case_insensitive(true).build() {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            eprintln!("Invalid regex pattern: {}", e);
+            return Err(anyhow::anyhow!("Invalid regex pattern"));
+        }
+    };
+
+    for_each_php_tree(dir, file, exclude_dirs, |content| content.contains("/**"), |path, content, tree| {
+        let names = php::FileNames::new(tree.root_node(), content);
+        php::walk(tree.root_node(), &mut |node| {
+            if node.kind() != "method_declaration" && node.kind() != "function_definition" {
+                return;
+            }
+            let Some(comment) = node.prev_named_sibling().filter(|comment| comment.kind() == "comment" && php::node_text(*comment, content).starts_with("/**")) else {
+                return;
+            };
+            let Some(line) = php::node_text(comment, content).lines()
+                .map(|line| line.trim().trim_start_matches("/**").trim_end_matches("*/").trim_start_matches('*').trim())
+                .take_while(|line| !line.starts_with('@'))
+                .find(|line| pattern.is_match(line)) else {
+                return;
+            };
+            let line_styled = format!("{} {}", php::signature(node, content), format!("// {}", highlight(line, &pattern)).dimmed());
+            print_symbol_match(path, node.start_position().row + 1, doc_symbol(comment, content, &names).as_deref(), &line_styled);
+        });
+    })
+}
+
+// Names the symbol a docblock belongs to, e.g. `Cart`, `Cart::add()`, `Cart::$items` or `Cart::LIMIT`
+fn doc_symbol(comment: tree_sitter::Node, content: &str, names: &php::FileNames) -> Option<String> {
+    let class_name = |node: tree_sitter::Node| {
+        php::enclosing_class(node)
+            .and_then(|class| names.class_fqcn(class, content))
+            .map(|fqcn| php::short_name(&fqcn).to_string())
+            .unwrap_or_default()
+    };
+    let Some(declaration) = comment.next_named_sibling() else {
+        return php::enclosing_function(comment, content).map(|name| format!("{}()", name));
+    };
+    let name = |field: &str| declaration.child_by_field_name(field).map(|name| php::node_text(name, content).to_string());
+    match declaration.kind() {
+        "class_declaration" | "interface_declaration" | "trait_declaration" | "enum_declaration" => name("name"),
+        "method_declaration" => Some(format!("{}::{}()", class_name(declaration), name("name")?)),
+        "function_definition" => Some(format!("{}()", name("name")?)),
+        "enum_case" => Some(format!("{}::{}", class_name(declaration), name("name")?)),
+        "property_declaration" => {
+            let mut cursor = declaration.walk();
+            let element = declaration.named_children(&mut cursor).find(|child| child.kind() == "property_element")?;
+            let property = php::node_text(element.child_by_field_name("name")?, content);
+            Some(format!("{}::{}", class_name(declaration), property))
+        }
+        "const_declaration" => {
+            let mut cursor = declaration.walk();
+            let element = declaration.named_children(&mut cursor).find(|child| child.kind() == "const_element")?;
+            let mut element_cursor = element.walk();
+            let constant = element.named_children(&mut element_cursor).find(|child| child.kind() == "name")?;
+            Some(format!("{}::{}", class_name(declaration), php::node_text(constant, content)))
+        }
+        _ => php::enclosing_function(comment, content).map(|name| format!("{}()", name)),
+    }
+}
+
+// Loads the --plugin libraries and reports whatever their matchers find in each file
+#[cfg(unix)]
+fn plugin_search(libraries: &[String], dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    let mut plugins = Vec::new();
+    for library in libraries {
+        match plugin::Plugin::load(library) {
+            Ok(plugin) => plugins.push(plugin),
+            Err(e) => {
+                eprintln!("Error: Could not load plugin '{}': {}", library, e);
+                return Err(anyhow::anyhow!("Could not load plugin '{}': {}", library, e));
+            }
+        }
+    }
+
+    for_each_php_tree(dir, file, exclude_dirs, |_| true, |path, content, tree| {
+        for plugin in &plugins {
+            match plugin.run(path, content, tree) {
+                Ok(matches) => {
+                    for found in matches {
+                        print_symbol_match(path, found.line, found.symbol.as_deref(), &found.message);
+                    }
+                }
+                Err(e) => eprintln!("Warning: Plugin '{}' failed on '{}': {}", plugin.path, path.display(), e),
+            }
+        }
+    })
+}
+
+#[cfg(not(unix))]
+fn plugin_search(_libraries: &[String], _dir: &str, _file: &str, _exclude_dirs: &str) -> Result<()> {
+    eprintln!("Error: Plugins are only supported on Unix-like systems.");
+    Err(anyhow::anyhow!("Plugins are only supported on Unix-like systems"))
+}
+
+// Finds methods and functions with a non-void return type whose body never returns a value.
+// Returns of nested closures don't count, and generators are skipped since they only yield
+fn no_return_search(dir: &str, file: &str, exclude_dirs: &str) -> Result<()> {
+    for_each_php_tree(dir, file, exclude_dirs, |_| true, |path, content, tree| {
+        let lines: Vec<&str> = content.lines().collect();
+        php::walk(tree.root_node(), &mut |node| {
+            if node.kind() != "method_declaration" && node.kind() != "function_definition" {
+                return;
+            }
+            let Some(return_type) = node.child_by_field_name("return_type") else { return };
+            if matches!(php::node_text(return_type, content).to_lowercase().as_str(), "void" | "never") {
+                return;
+            }
+            let (Some(name), Some(body)) = (node.child_by_field_name("name"), node.child_by_field_name("body")) else { return };
+
+            let mut returns = false;
+            let mut yields = false;
+            php::walk_body(body, &mut |inner| match inner.kind() {
+                "return_statement" => returns |= inner.named_child_count() > 0,
+                "yield_expression" => yields = true,
+                _ => {}
+            });
+            if returns || yields {
+                return;
+            }
+
+            let row = node.start_position().row;
+            let line = lines.get(row).copied().unwrap_or("");
+            print_match(path, row + 1, Some(php::node_text(name, content)), line);
+        });
+    })
+}
@@ -0,0 +1,49 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+// Lines longer than this don't occur in code people write, only in minified or encoded blobs
+const MAX_LINE_LENGTH: usize = 10_000;
+
+// Set when --include-minified turned the check off
+static INCLUDE: OnceLock<()> = OnceLock::new();
+
+// The files skipped so far, a set as some commands read files more than once
+static SKIPPED: Mutex<BTreeSet<PathBuf>> = Mutex::new(BTreeSet::new());
+
+pub fn include() {
+    let _ = INCLUDE.set(());
+}
+
+// Whether a file should be left out as binary, minified or encoded (ionCube, SourceGuardian),
+// noting it for the summary. Parsing such files takes long and their matches are garbage
+pub fn skip(path: &Path, content: &str) -> bool {
+    if INCLUDE.get().is_some() {
+        return false;
+    }
+    let header = &content[..content.floor_char_boundary(1024)];
+    if !content.contains('\0')
+        && !header.contains("ionCube Loader")
+        && !header.contains("SourceGuardian")
+        && !content.lines().any(|line| line.len() > MAX_LINE_LENGTH) {
+        return false;
+    }
+    skip_binary(path);
+    true
+}
+
+// Notes a file that isn't text at all
+pub fn skip_binary(path: &Path) {
+    if let Ok(mut skipped) = SKIPPED.lock() {
+        skipped.insert(path.to_path_buf());
+    }
+}
+
+pub fn is_included() -> bool {
+    INCLUDE.get().is_some()
+}
+
+// How many files were skipped
+pub fn skipped() -> usize {
+    SKIPPED.lock().map_or(0, |skipped| skipped.len())
+}
@@ -0,0 +1,321 @@
+use anyhow::Result;
+use tree_sitter::{Node, Parser as TreeSitterParser};
+
+use crate::tree_sitter_php;
+
+pub fn new_parser() -> Result<TreeSitterParser> {
+    let mut parser = TreeSitterParser::new();
+    parser.set_language(unsafe { tree_sitter_php() })?;
+    Ok(parser)
+}
+
+// Whether a name can be used for a class, function or method
+pub fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_') && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+// Returns the source text of a node, or an empty string if it isn't valid UTF-8
+pub fn node_text<'a>(node: Node, content: &'a str) -> &'a str {
+    node.utf8_text(content.as_bytes()).unwrap_or("")
+}
+
+// Visits every node below (and including) `node` in document order
+pub fn walk<'a>(node: Node<'a>, visit: &mut dyn FnMut(Node<'a>)) {
+    visit(node);
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        walk(child, visit);
+    }
+}
+
+// Visits the nodes of a function body without descending into nested functions or closures
+pub fn walk_body<'a>(node: Node<'a>, visit: &mut dyn FnMut(Node<'a>)) {
+    visit(node);
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if !matches!(child.kind(), "anonymous_function" | "arrow_function" | "function_definition" | "class_declaration" | "anonymous_class") {
+            walk_body(child, visit);
+        }
+    }
+}
+
+// The tokens of the code below `node`: its leaf nodes without comments, the opening tag and
+// nodes the parser inserted to recover from errors, so layout and comments don't matter
+pub fn tokens<'a>(node: Node<'a>, content: &str) -> Vec<Node<'a>> {
+    let mut tokens = Vec::new();
+    walk(node, &mut |node| {
+        if node.child_count() == 0 && !node.is_missing() && !matches!(node.kind(), "comment" | "php_tag") && !node_text(node, content).is_empty() {
+            tokens.push(node);
+        }
+    });
+    tokens
+}
+
+// The names in code below `node` that refer to a class: types, `extends` and `implements`,
+// `new`, static calls and accesses, `instanceof`, attributes and trait uses. `self`, `static`
+// and `parent` are left out
+pub fn class_references<'a>(node: Node<'a>, content: &str) -> Vec<Node<'a>> {
+    let mut references = Vec::new();
+    walk(node, &mut |node| {
+        if node.kind() != "name" && node.kind() != "qualified_name" {
+            return;
+        }
+        let Some(parent) = node.parent() else { return };
+        let is_first = parent.named_child(0) == Some(node);
+        let is_reference = match parent.kind() {
+            "named_type" | "base_clause" | "class_interface_clause" | "use_declaration" => true,
+            "object_creation_expression" | "class_constant_access_expression" | "attribute" => is_first,
+            "scoped_call_expression" | "scoped_property_access_expression" => parent.child_by_field_name("scope") == Some(node),
+            "binary_expression" => parent.child_by_field_name("right") == Some(node)
+                && parent.child_by_field_name("operator").is_some_and(|operator| operator.kind() == "instanceof"),
+            _ => false,
+        };
+        if is_reference && !matches!(node_text(node, content).to_lowercase().as_str(), "self" | "static" | "parent") {
+            references.push(node);
+        }
+    });
+    references
+}
+
+// Finds the closest named method or function declaration containing `node`
+pub fn enclosing_function_node<'a>(node: Node<'a>) -> Option<Node<'a>> {
+    let mut current = node.parent();
+    while let Some(parent) = current {
+        if parent.kind() == "method_declaration" || parent.kind() == "function_definition" {
+            return Some(parent);
+        }
+        current = parent.parent();
+    }
+    None
+}
+
+// The declaration of a method or function up to its body, with a parameter list written over
+// several lines joined back onto one
+pub fn signature(function: Node, content: &str) -> String {
+    let end = function.child_by_field_name("body").map_or(function.end_byte(), |body| body.start_byte());
+    content[function.start_byte()..end].split_whitespace().collect::<Vec<_>>().join(" ").replace("( ", "(").replace(", )", ")").replace(" )", ")")
+}
+
+// The value of a quoted string without interpolation, as written between the quotes
+pub fn string_literal(node: Node, content: &str) -> Option<String> {
+    if node.kind() != "string" && node.kind() != "encapsed_string" {
+        return None;
+    }
+    let mut cursor = node.walk();
+    if node.named_children(&mut cursor).any(|child| child.kind() != "string_content" && child.kind() != "escape_sequence") {
+        return None;
+    }
+    let text = node_text(node, content);
+    text.get(1..text.len().checked_sub(1)?).map(str::to_string)
+}
+
+// Finds the name of the closest named method or function containing `node`
+pub fn enclosing_function<'a>(node: Node, content: &'a str) -> Option<&'a str> {
+    enclosing_function_node(node)?.child_by_field_name("name").map(|name| node_text(name, content))
+}
+
+// Finds the closest class-like declaration (class, trait, interface or enum) containing `node`
+pub fn enclosing_class<'a>(node: Node<'a>) -> Option<Node<'a>> {
+    let mut current = node.parent();
+    while let Some(parent) = current {
+        if matches!(parent.kind(), "class_declaration" | "trait_declaration" | "interface_declaration" | "enum_declaration") {
+            return Some(parent);
+        }
+        current = parent.parent();
+    }
+    None
+}
+
+// Finds the match or switch arm containing `node` within its function, if any
+pub fn enclosing_arm<'a>(node: Node<'a>) -> Option<Node<'a>> {
+    let mut current = Some(node);
+    while let Some(candidate) = current {
+        match candidate.kind() {
+            "match_conditional_expression" | "match_default_expression" | "case_statement" | "default_statement" => return Some(candidate),
+            "method_declaration" | "function_definition" | "anonymous_function" | "arrow_function" => return None,
+            _ => current = candidate.parent(),
+        }
+    }
+    None
+}
+
+// Describes an arm for output, e.g. `case Status::Paid` or `match 'a', 'b'`
+pub fn arm_label(arm: Node, content: &str) -> String {
+    match arm.kind() {
+        "match_conditional_expression" => {
+            let conditions = arm.child_by_field_name("conditional_expressions").map(|list| node_text(list, content)).unwrap_or("");
+            format!("match {}", conditions)
+        }
+        "match_default_expression" => "match default".to_string(),
+        "case_statement" => format!("case {}", arm.child_by_field_name("value").map(|value| node_text(value, content)).unwrap_or("")),
+        _ => "default".to_string(),
+    }
+}
+
+// Returns the last segment of a (possibly qualified) class name
+pub fn short_name(name: &str) -> &str {
+    name.rsplit('\\').next().unwrap_or(name)
+}
+
+// Checks a resolved class name against a user supplied one. A qualified query has to match the
+// full name, a bare one only the short name, both case-insensitively like PHP itself
+pub fn class_matches(fqcn: &str, query: &str) -> bool {
+    let query = query.trim_start_matches('\\');
+    if query.contains('\\') {
+        fqcn.eq_ignore_ascii_case(query)
+    } else {
+        short_name(fqcn).eq_ignore_ascii_case(query)
+    }
+}
+
+// A namespace in effect from `start_byte` onwards along with the class imports declared in it
+struct NameScope {
+    start_byte: usize,
+    namespace: String,
+    aliases: Vec<(String, String)>,
+}
+
+// Per-file namespace and `use` information used to turn class names into fully qualified ones
+pub struct FileNames {
+    scopes: Vec<NameScope>,
+}
+
+impl FileNames {
+    pub fn new(root: Node, content: &str) -> Self {
+        let mut scopes = vec![NameScope { start_byte: 0, namespace: String::new(), aliases: Vec::new() }];
+        walk(root, &mut |node| match node.kind() {
+            "namespace_definition" => {
+                let namespace = node.child_by_field_name("name").map(|name| node_text(name, content)).unwrap_or("");
+                scopes.push(NameScope { start_byte: node.start_byte(), namespace: namespace.to_string(), aliases: Vec::new() });
+            }
+            "namespace_use_declaration" => {
+                if let Some(scope) = scopes.last_mut() {
+                    scope.aliases.extend(use_declaration_aliases(node, content));
+                }
+            }
+            _ => {}
+        });
+        FileNames { scopes }
+    }
+
+    fn scope_at(&self, byte: usize) -> &NameScope {
+        self.scopes.iter().rev().find(|scope| scope.start_byte <= byte).unwrap_or(&self.scopes[0])
+    }
+
+    // Namespace in effect at the given byte offset
+    pub fn namespace_at(&self, byte: usize) -> &str {
+        &self.scope_at(byte).namespace
+    }
+
+    // Whether the first segment of a name written at `byte` is a class imported with `use`
+    pub fn is_imported(&self, name: &str, byte: usize) -> bool {
+        let first = name.split('\\').next().unwrap_or(name);
+        self.scope_at(byte).aliases.iter().any(|(alias, _)| alias.eq_ignore_ascii_case(first))
+    }
+
+    // Resolves a class name as written at `byte` into its fully qualified form (without leading \)
+    pub fn resolve(&self, name: &str, byte: usize) -> String {
+        if let Some(absolute) = name.strip_prefix('\\') {
+            return absolute.to_string();
+        }
+        let scope = self.scope_at(byte);
+        let (first, rest) = match name.split_once('\\') {
+            Some((first, rest)) => (first, Some(rest)),
+            None => (name, None),
+        };
+        if first.eq_ignore_ascii_case("namespace") {
+            return join_name(&scope.namespace, rest.unwrap_or(""));
+        }
+        if let Some((_, fqcn)) = scope.aliases.iter().find(|(alias, _)| alias.eq_ignore_ascii_case(first)) {
+            return match rest {
+                Some(rest) => format!("{}\\{}", fqcn, rest),
+                None => fqcn.clone(),
+            };
+        }
+        join_name(&scope.namespace, name)
+    }
+
+    // Resolves a name or qualified_name node, treating self/static as the enclosing class
+    // and parent as the class it extends
+    pub fn resolve_node(&self, node: Node, content: &str) -> Option<String> {
+        let text = node_text(node, content);
+        if text.eq_ignore_ascii_case("self") || text.eq_ignore_ascii_case("static") {
+            return self.class_fqcn(enclosing_class(node)?, content);
+        }
+        if text.eq_ignore_ascii_case("parent") {
+            return self.parent_class(enclosing_class(node)?, content);
+        }
+        Some(self.resolve(text, node.start_byte()))
+    }
+
+    // Fully qualified name of a class-like declaration node
+    pub fn class_fqcn(&self, class: Node, content: &str) -> Option<String> {
+        let class_name = class.child_by_field_name("name")?;
+        Some(join_name(self.namespace_at(class.start_byte()), node_text(class_name, content)))
+    }
+
+    // Fully qualified name of the class a class declaration extends
+    pub fn parent_class(&self, class: Node, content: &str) -> Option<String> {
+        let mut cursor = class.walk();
+        let base_clause = class.children(&mut cursor).find(|child| child.kind() == "base_clause")?;
+        let mut base_cursor = base_clause.walk();
+        let parent = base_clause.named_children(&mut base_cursor).find(|name| name.kind() == "name" || name.kind() == "qualified_name")?;
+        Some(self.resolve(node_text(parent, content), parent.start_byte()))
+    }
+}
+
+fn join_name(namespace: &str, name: &str) -> String {
+    if namespace.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}\\{}", namespace, name)
+    }
+}
+
+// Collects (alias, fully qualified name) pairs from a class `use` statement, including group uses
+pub fn use_declaration_aliases(node: Node, content: &str) -> Vec<(String, String)> {
+    use_clauses(node, content).into_iter()
+        .map(|(clause, fqcn)| {
+            let alias = clause.child_by_field_name("alias")
+                .map(|alias| node_text(alias, content).to_string())
+                .unwrap_or_else(|| short_name(&fqcn).to_string());
+            (alias, fqcn)
+        })
+        .collect()
+}
+
+// The clauses of a class `use` statement, including those of a group use, each with the fully
+// qualified name it imports
+pub fn use_clauses<'a>(node: Node<'a>, content: &str) -> Vec<(Node<'a>, String)> {
+    let is_class_import = |node: Node| node.child_by_field_name("type").is_none();
+    if !is_class_import(node) {
+        return Vec::new();
+    }
+
+    let mut prefix = "";
+    let mut clauses = Vec::new();
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        match child.kind() {
+            "namespace_name" => prefix = node_text(child, content),
+            "namespace_use_clause" => clauses.push(child),
+            "namespace_use_group" => {
+                let mut group_cursor = child.walk();
+                clauses.extend(child.named_children(&mut group_cursor).filter(|c| c.kind() == "namespace_use_clause"));
+            }
+            _ => {}
+        }
+    }
+
+    clauses.into_iter()
+        .filter(|clause| is_class_import(*clause))
+        .filter_map(|clause| {
+            let mut clause_cursor = clause.walk();
+            let name = clause.named_children(&mut clause_cursor)
+                .find(|c| c.kind() == "name" || c.kind() == "qualified_name")
+                .map(|c| node_text(c, content))?;
+            Some((clause, join_name(prefix, name).trim_start_matches('\\').to_string()))
+        })
+        .collect()
+}
@@ -0,0 +1,136 @@
+use std::ffi::{CStr, CString, c_char, c_void};
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::json::{self, Value};
+
+// A matcher loaded from a shared library with --plugin. The library exports
+//
+//     char *phrep_plugin_match(const char *file);
+//     void phrep_plugin_free(char *matches);    (optional)
+//
+// `file` is a JSON object with the path, the source and every node of the syntax tree in
+// document order: {"path", "source", "nodes": [{"kind", "field", "named", "depth",
+// "start_byte", "end_byte", "start_line", "start_column", "end_line", "end_column"}]}, lines
+// counting from 1. The plugin answers with a JSON array of {"line", "symbol", "message"}
+// objects ("symbol" may be left out) or NULL for no matches, which phrep releases with
+// phrep_plugin_free when the plugin has one
+pub struct Plugin {
+    pub path: String,
+    handle: *mut c_void,
+    match_file: unsafe extern "C" fn(*const c_char) -> *mut c_char,
+    free: Option<unsafe extern "C" fn(*mut c_char)>,
+}
+
+pub struct PluginMatch {
+    pub line: usize,
+    pub symbol: Option<String>,
+    pub message: String,
+}
+
+impl Plugin {
+    pub fn load(path: &str) -> Result<Self> {
+        let c_path = CString::new(path)?;
+        let handle = unsafe { libc::dlopen(c_path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+        if handle.is_null() {
+            return Err(anyhow::anyhow!("{}", dl_error()));
+        }
+        let match_file = unsafe { libc::dlsym(handle, c"phrep_plugin_match".as_ptr()) };
+        if match_file.is_null() {
+            unsafe { libc::dlclose(handle) };
+            return Err(anyhow::anyhow!("The library doesn't export phrep_plugin_match"));
+        }
+        let free = unsafe { libc::dlsym(handle, c"phrep_plugin_free".as_ptr()) };
+        Ok(Plugin {
+            path: path.to_string(),
+            handle,
+            match_file: unsafe { std::mem::transmute::<*mut c_void, unsafe extern "C" fn(*const c_char) -> *mut c_char>(match_file) },
+            free: (!free.is_null()).then(|| unsafe { std::mem::transmute::<*mut c_void, unsafe extern "C" fn(*mut c_char)>(free) }),
+        })
+    }
+
+    // Hands one parsed file to the plugin and returns the matches it reports
+    pub fn run(&self, path: &Path, content: &str, tree: &tree_sitter::Tree) -> Result<Vec<PluginMatch>> {
+        let file = Value::Object(vec![
+            ("path".to_string(), Value::String(path.display().to_string())),
+            ("source".to_string(), Value::String(content.to_string())),
+            ("nodes".to_string(), Value::Array(node_stream(tree))),
+        ]);
+        let input = CString::new(file.to_string())?;
+
+        let output = unsafe { (self.match_file)(input.as_ptr()) };
+        if output.is_null() {
+            return Ok(Vec::new());
+        }
+        let text = unsafe { CStr::from_ptr(output) }.to_string_lossy().into_owned();
+        if let Some(free) = self.free {
+            unsafe { free(output) };
+        }
+
+        let matches = json::parse(&text)?;
+        let Some(matches) = matches.as_array() else {
+            return Err(anyhow::anyhow!("Expected a JSON array of matches"));
+        };
+        matches.iter()
+            .map(|found| {
+                let line = match found.get("line") {
+                    Some(Value::Number(line)) if *line >= 1.0 => *line as usize,
+                    _ => return Err(anyhow::anyhow!("Match without a valid \"line\"")),
+                };
+                Ok(PluginMatch {
+                    line,
+                    symbol: found.get("symbol").and_then(Value::as_str).map(str::to_string),
+                    message: found.get("message").and_then(Value::as_str).unwrap_or("").to_string(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl Drop for Plugin {
+    fn drop(&mut self) {
+        unsafe { libc::dlclose(self.handle) };
+    }
+}
+
+// Flattens the syntax tree into the node list sent to plugins
+fn node_stream(tree: &tree_sitter::Tree) -> Vec<Value> {
+    let mut nodes = Vec::new();
+    let mut cursor = tree.walk();
+    let mut depth = 0;
+    loop {
+        let node = cursor.node();
+        nodes.push(Value::Object(vec![
+            ("kind".to_string(), Value::String(node.kind().to_string())),
+            ("field".to_string(), cursor.field_name().map_or(Value::Null, |field| Value::String(field.to_string()))),
+            ("named".to_string(), Value::Bool(node.is_named())),
+            ("depth".to_string(), Value::Number(depth as f64)),
+            ("start_byte".to_string(), Value::Number(node.start_byte() as f64)),
+            ("end_byte".to_string(), Value::Number(node.end_byte() as f64)),
+            ("start_line".to_string(), Value::Number((node.start_position().row + 1) as f64)),
+            ("start_column".to_string(), Value::Number(node.start_position().column as f64)),
+            ("end_line".to_string(), Value::Number((node.end_position().row + 1) as f64)),
+            ("end_column".to_string(), Value::Number(node.end_position().column as f64)),
+        ]));
+
+        if cursor.goto_first_child() {
+            depth += 1;
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                return nodes;
+            }
+            depth -= 1;
+        }
+    }
+}
+
+fn dl_error() -> String {
+    let error = unsafe { libc::dlerror() };
+    if error.is_null() {
+        return "Unknown error".to_string();
+    }
+    unsafe { CStr::from_ptr(error) }.to_string_lossy().into_owned()
+}
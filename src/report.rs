@@ -0,0 +1,448 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use colored::*;
+
+use crate::json::Value;
+
+// Accepted values of --format
+pub const FORMATS: [&str; 6] = ["text", "table", "tree", "json", "phpstan", "checkstyle"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Text,
+    Table,
+    Tree,
+    Json,
+    Phpstan,
+    Checkstyle,
+}
+
+// A match kept back until the run is over so it can be written as one report
+struct Finding {
+    path: PathBuf,
+    line: usize,
+    symbol: Option<String>,
+    text: String,
+}
+
+impl Finding {
+    fn message(&self) -> String {
+        match &self.symbol {
+            Some(symbol) => format!("{}: {}", symbol, self.text),
+            None => self.text.clone(),
+        }
+    }
+}
+
+static FORMAT: OnceLock<Format> = OnceLock::new();
+static MAX_WIDTH: OnceLock<usize> = OnceLock::new();
+static HEATMAP: OnceLock<bool> = OnceLock::new();
+static FINDINGS: Mutex<Vec<Finding>> = Mutex::new(Vec::new());
+static SAMPLE: OnceLock<usize> = OnceLock::new();
+// The matches --sample has seen so far, the ones it keeps are in `FINDINGS`
+static SAMPLED: Mutex<Sample> = Mutex::new(Sample { seen: 0, order: Vec::new(), seed: 0 });
+
+struct Sample {
+    seen: usize,
+    // The position among all matches of each finding kept, to print them in the order found
+    order: Vec<usize>,
+    seed: u64,
+}
+
+pub fn set_format(name: &str) {
+    let format = match name {
+        "table" => Format::Table,
+        "tree" => Format::Tree,
+        "json" => Format::Json,
+        "phpstan" => Format::Phpstan,
+        "checkstyle" => Format::Checkstyle,
+        _ => Format::Text,
+    };
+    if matches!(format, Format::Json | Format::Phpstan | Format::Checkstyle) {
+        // Messages end up in JSON or XML, escape codes would only get in the way
+        colored::control::set_override(false);
+    }
+    let _ = FORMAT.set(format);
+}
+
+pub fn format() -> Format {
+    FORMAT.get().copied().unwrap_or(Format::Text)
+}
+
+// Set by --max-width to override the detected terminal width for --format table
+pub fn set_max_width(width: usize) {
+    let _ = MAX_WIDTH.set(width);
+}
+
+// Set by --heatmap: sum the matches up per file and class instead of listing them
+pub fn set_heatmap() {
+    let _ = HEATMAP.set(true);
+}
+
+// Set by --sample: keep that many matches picked at random out of all of them
+pub fn set_sample(count: usize) {
+    let seed = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |time| time.as_nanos() as u64);
+    if let Ok(mut sample) = SAMPLED.lock() {
+        sample.seed = seed | 1;
+    }
+    let _ = SAMPLE.set(count);
+}
+
+// Whether matches are collected into a report instead of printed as they're found
+pub fn collects() -> bool {
+    format() != Format::Text || HEATMAP.get().is_some() || SAMPLE.get().is_some()
+}
+
+// Whether the report is meant for tools rather than people
+pub fn is_structured() -> bool {
+    matches!(format(), Format::Json | Format::Phpstan | Format::Checkstyle)
+}
+
+// Keeps a match for the report printed by `finish`
+pub fn add(path: &Path, line: usize, symbol: Option<&str>, text: &str) {
+    let finding = Finding { path: path.to_path_buf(), line, symbol: symbol.map(str::to_string), text: text.trim().to_string() };
+    let (Ok(mut findings), Ok(mut sample)) = (FINDINGS.lock(), SAMPLED.lock()) else { return };
+    let Some(&count) = SAMPLE.get() else {
+        findings.push(finding);
+        return;
+    };
+    // Reservoir sampling: the n-th match replaces a kept one with a chance of count / n, so
+    // every match is equally likely to end up in the sample without holding on to all of them
+    let position = sample.seen;
+    sample.seen += 1;
+    if findings.len() < count {
+        findings.push(finding);
+        sample.order.push(position);
+        return;
+    }
+    // xorshift, good enough to pick matches and free of dependencies
+    let mut seed = sample.seed;
+    seed ^= seed << 13;
+    seed ^= seed >> 7;
+    seed ^= seed << 17;
+    sample.seed = seed;
+    let slot = (seed % sample.seen as u64) as usize;
+    if slot < count {
+        findings[slot] = finding;
+        sample.order[slot] = position;
+    }
+}
+
+// Prints a summary or heading line. Structured reports own stdout, so it goes to stderr there
+pub fn summary(line: impl std::fmt::Display) {
+    if is_structured() {
+        eprintln!("{}", line);
+    } else {
+        println!("{}", line);
+    }
+}
+
+// Prints the collected matches in the chosen report format
+pub fn finish() {
+    let mut findings = match FINDINGS.lock() {
+        Ok(mut findings) => std::mem::take(&mut *findings),
+        Err(_) => return,
+    };
+    if SAMPLE.get().is_some()
+        && let Ok(sample) = SAMPLED.lock() {
+        let mut sampled: Vec<(usize, Finding)> = sample.order.iter().copied().zip(findings).collect();
+        sampled.sort_by_key(|(position, _)| *position);
+        findings = sampled.into_iter().map(|(_, finding)| finding).collect();
+        summary(format!("Showing {} of {} match{}, picked at random", findings.len(), sample.seen, if sample.seen == 1 { "" } else { "es" }));
+    }
+    if HEATMAP.get().is_some() {
+        let matches: Vec<(PathBuf, usize)> = findings.into_iter().map(|finding| (finding.path, finding.line)).collect();
+        crate::heatmap::print(&matches, format() == Format::Json);
+        return;
+    }
+    match format() {
+        Format::Text => {
+            for finding in &findings {
+                println!("{}", crate::text_match(&finding.path, finding.line, finding.symbol.as_deref(), &finding.text));
+            }
+        }
+        Format::Table => table(&findings),
+        Format::Tree => {
+            let mut root = Dir::default();
+            for finding in &findings {
+                let path = crate::format_filename(&finding.path);
+                let parts: Vec<&str> = path.split(['/', '\\']).collect();
+                root.add(&parts);
+            }
+            root.print("");
+        }
+        Format::Json => {
+            let mut declarations = HashMap::new();
+            for finding in &findings {
+                println!("{}", json_line(finding, &mut declarations));
+            }
+        }
+        Format::Phpstan => println!("{}", phpstan(&by_file(&findings), findings.len())),
+        Format::Checkstyle => print!("{}", checkstyle(&by_file(&findings))),
+    }
+}
+
+// Groups findings per file, keeping files and matches in the order they were found
+fn by_file(findings: &[Finding]) -> Vec<(String, Vec<&Finding>)> {
+    let mut files: Vec<(&Path, Vec<&Finding>)> = Vec::new();
+    for finding in findings {
+        match files.iter_mut().find(|(path, _)| *path == finding.path) {
+            Some((_, file_findings)) => file_findings.push(finding),
+            None => files.push((&finding.path, vec![finding])),
+        }
+    }
+    files.into_iter()
+        .map(|(path, findings)| (crate::format_filename(path), findings))
+        .collect()
+}
+
+// Prints the matches in aligned file, line, symbol and text columns, cutting the text off at
+// the terminal width (or --max-width)
+fn table(findings: &[Finding]) {
+    let rows: Vec<(String, String, String, String)> = findings.iter()
+        .map(|finding| (
+            crate::format_filename(&finding.path),
+            finding.line.to_string(),
+            finding.symbol.clone().unwrap_or_default(),
+            strip_colors(&finding.text),
+        ))
+        .collect();
+    let width = |column: fn(&(String, String, String, String)) -> &String, header: &str| {
+        rows.iter().map(|row| column(row).chars().count()).chain([header.len()]).max().unwrap_or(0)
+    };
+    let file_width = width(|row| &row.0, "File");
+    let line_width = width(|row| &row.1, "Line");
+    let symbol_width = width(|row| &row.2, "Symbol");
+    let text_width = MAX_WIDTH.get().copied().or_else(terminal_width)
+        .map(|max_width| max_width.saturating_sub(file_width + line_width + symbol_width + 6).max(10));
+
+    let pad = |text: &str, width: usize| " ".repeat(width.saturating_sub(text.chars().count()));
+    println!("{}{}  {}{}  {}{}  {}",
+        "File".bold(), pad("File", file_width), pad("Line", line_width), "Line".bold(), "Symbol".bold(), pad("Symbol", symbol_width), "Text".bold());
+    for (file, line, symbol, text) in &rows {
+        let text = match text_width {
+            Some(text_width) => truncate(text, text_width),
+            None => text.clone(),
+        };
+        println!("{}{}  {}{}  {}{}  {}", file.bold().blue(), pad(file, file_width), pad(line, line_width), line, symbol.yellow(), pad(symbol, symbol_width), text);
+    }
+}
+
+// A directory of --format tree with the matches below it
+#[derive(Default)]
+struct Dir {
+    matches: usize,
+    files: BTreeMap<String, usize>,
+    dirs: BTreeMap<String, Dir>,
+}
+
+impl Dir {
+    // Counts a match in the file at the end of `parts`, below this directory
+    fn add(&mut self, parts: &[&str]) {
+        self.matches += 1;
+        match parts {
+            [] => {}
+            [file] => *self.files.entry(file.to_string()).or_insert(0) += 1,
+            [dir, rest @ ..] => self.dirs.entry(dir.to_string()).or_default().add(rest),
+        }
+    }
+
+    fn file_count(&self) -> usize {
+        self.files.len() + self.dirs.values().map(Dir::file_count).sum::<usize>()
+    }
+
+    // Prints the directories and files below this one, folding directories that only hold one
+    // directory into it, like `src/App/Controller/`
+    fn print(&self, indent: &str) {
+        for (name, dir) in &self.dirs {
+            let mut name = name.clone();
+            let mut dir = dir;
+            while dir.files.is_empty() && dir.dirs.len() == 1
+                && let Some((child_name, child)) = dir.dirs.iter().next() {
+                name = format!("{}/{}", name, child_name);
+                dir = child;
+            }
+            let files = dir.file_count();
+            println!("{}{} {}", indent, format!("{}/", name).bold().blue(), format!("{} match{} in {} file{}", dir.matches, if dir.matches == 1 { "" } else { "es" }, files, if files == 1 { "" } else { "s" }).dimmed());
+            dir.print(&format!("{}  ", indent));
+        }
+        for (name, matches) in &self.files {
+            println!("{}{} {}", indent, name, format!("{}", matches).dimmed());
+        }
+    }
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(width.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+// Width of the terminal stdout is attached to, if it is one
+#[cfg(unix)]
+fn terminal_width() -> Option<usize> {
+    let mut size = libc::winsize { ws_row: 0, ws_col: 0, ws_xpixel: 0, ws_ypixel: 0 };
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    if result == 0 && size.ws_col > 0 {
+        return Some(size.ws_col as usize);
+    }
+    std::env::var("COLUMNS").ok()?.parse().ok()
+}
+
+#[cfg(not(unix))]
+fn terminal_width() -> Option<usize> {
+    std::env::var("COLUMNS").ok()?.parse().ok()
+}
+
+// Removes the terminal highlighting from a line of output
+pub fn strip_colors(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
+// Version of the JSON output, recorded in every value phrep prints as JSON. Bumped when a field
+// is renamed or removed, see schema/output-v1.json
+pub const SCHEMA_VERSION: u32 = 1;
+
+// The JSON schema of everything phrep prints as JSON, printed by --schema
+pub const SCHEMA: &str = include_str!("../schema/output-v1.json");
+
+// The `schema_version` field that leads every JSON record and document
+pub fn schema_version() -> (String, Value) {
+    ("schema_version".to_string(), Value::Number(SCHEMA_VERSION as f64))
+}
+
+// One match of --format json: {"schema_version", "path", "line", "symbol", "text", "enclosing"}
+fn json_line(finding: &Finding, declarations: &mut HashMap<PathBuf, Vec<Value>>) -> Value {
+    let declarations = declarations.entry(finding.path.clone()).or_insert_with(|| enclosing_declarations(&finding.path));
+    // The innermost declaration around the line is the one with the fewest lines
+    let enclosing = declarations.iter()
+        .filter(|declaration| number(declaration, "start_line") <= finding.line && finding.line <= number(declaration, "end_line"))
+        .min_by_key(|declaration| number(declaration, "end_line") - number(declaration, "start_line"))
+        .cloned();
+    Value::Object(vec![
+        schema_version(),
+        ("path".to_string(), Value::String(crate::format_filename(&finding.path))),
+        ("line".to_string(), Value::Number(finding.line as f64)),
+        ("symbol".to_string(), finding.symbol.clone().map_or(Value::Null, Value::String)),
+        ("text".to_string(), Value::String(finding.text.clone())),
+        ("enclosing".to_string(), enclosing.unwrap_or(Value::Null)),
+    ])
+}
+
+fn number(value: &Value, key: &str) -> usize {
+    match value.get(key) {
+        Some(Value::Number(number)) => *number as usize,
+        _ => 0,
+    }
+}
+
+// The methods, functions and classes of a file with their span and signature, so consumers
+// of --format json can show the whole declaration around a match without parsing the file
+fn enclosing_declarations(path: &Path) -> Vec<Value> {
+    let Some(content) = crate::read_file(path) else { return Vec::new() };
+    let Some(tree) = crate::php::new_parser().ok().and_then(|mut parser| parser.parse(&content, None)) else { return Vec::new() };
+    let names = crate::php::FileNames::new(tree.root_node(), &content);
+    let mut declarations = Vec::new();
+    crate::php::walk(tree.root_node(), &mut |node| {
+        let Some(name) = node.child_by_field_name("name").map(|name| crate::php::node_text(name, &content)) else { return };
+        let (kind, name, id) = match node.kind() {
+            "method_declaration" => {
+                let Some(class) = crate::php::enclosing_class(node).and_then(|class| names.class_fqcn(class, &content)) else { return };
+                let Some(method) = crate::declarations::function_decl(node, &content, &names, Some(&class)) else { return };
+                ("method", format!("{}::{}", class, name), method.id())
+            }
+            "function_definition" => {
+                let Some(function) = crate::declarations::function_decl(node, &content, &names, None) else { return };
+                ("function", name.to_string(), function.id())
+            }
+            "class_declaration" | "interface_declaration" | "trait_declaration" | "enum_declaration" => {
+                let Some(fqcn) = names.class_fqcn(node, &content) else { return };
+                let kind = crate::declarations::class_kind(node);
+                (kind, fqcn.clone(), crate::declarations::symbol_id(&fqcn, kind))
+            }
+            _ => return,
+        };
+        declarations.push(Value::Object(vec![
+            ("kind".to_string(), Value::String(kind.to_string())),
+            ("name".to_string(), Value::String(name)),
+            ("id".to_string(), Value::String(id)),
+            ("signature".to_string(), Value::String(crate::php::signature(node, &content))),
+            ("start_line".to_string(), Value::Number((node.start_position().row + 1) as f64)),
+            ("end_line".to_string(), Value::Number((node.end_position().row + 1) as f64)),
+            ("start_byte".to_string(), Value::Number(node.start_byte() as f64)),
+            ("end_byte".to_string(), Value::Number(node.end_byte() as f64)),
+        ]));
+    });
+    declarations
+}
+
+// The shape of `phpstan analyse --error-format=json`
+fn phpstan(files: &[(String, Vec<&Finding>)], total: usize) -> Value {
+    let files = files.iter()
+        .map(|(path, findings)| {
+            let messages = findings.iter()
+                .map(|finding| Value::Object(vec![
+                    ("message".to_string(), Value::String(finding.message())),
+                    ("line".to_string(), Value::Number(finding.line as f64)),
+                    ("ignorable".to_string(), Value::Bool(true)),
+                ]))
+                .collect();
+            (path.to_string(), Value::Object(vec![
+                ("errors".to_string(), Value::Number(findings.len() as f64)),
+                ("messages".to_string(), Value::Array(messages)),
+            ]))
+        })
+        .collect();
+    Value::Object(vec![
+        ("totals".to_string(), Value::Object(vec![
+            ("errors".to_string(), Value::Number(0.0)),
+            ("file_errors".to_string(), Value::Number(total as f64)),
+        ])),
+        ("files".to_string(), Value::Object(files)),
+        ("errors".to_string(), Value::Array(Vec::new())),
+    ])
+}
+
+fn checkstyle(files: &[(String, Vec<&Finding>)]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<checkstyle>\n");
+    for (path, findings) in files {
+        xml.push_str(&format!("<file name=\"{}\">\n", xml_escape(path)));
+        for finding in findings {
+            xml.push_str(&format!("  <error line=\"{}\" severity=\"error\" message=\"{}\" source=\"phrep\"/>\n", finding.line, xml_escape(&finding.message())));
+        }
+        xml.push_str("</file>\n");
+    }
+    xml.push_str("</checkstyle>\n");
+    xml
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#10;"),
+            c if (c as u32) < 0x20 && c != '\t' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
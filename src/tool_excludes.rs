@@ -0,0 +1,330 @@
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::Result;
+use regex::Regex;
+use tree_sitter::Node;
+
+use crate::php;
+
+// The tools whose path settings --import-excludes reads, and their config files in the order
+// the tools themselves look for them
+pub const TOOLS: [&str; 3] = ["php-cs-fixer", "phpstan", "psalm"];
+const CONFIG_FILES: [(&str, &[&str]); 3] = [
+    ("php-cs-fixer", &[".php-cs-fixer.php", ".php-cs-fixer.dist.php", ".php_cs", ".php_cs.dist"]),
+    ("phpstan", &["phpstan.neon", "phpstan.neon.dist", "phpstan.dist.neon"]),
+    ("psalm", &["psalm.xml", "psalm.xml.dist"]),
+];
+
+// What a tool's config counts as the project's code, with paths relative to the config file
+pub struct ToolPaths {
+    // The search directory relative to the config file's directory
+    prefix: PathBuf,
+    // The files and directories the tool looks at, all of them when empty
+    paths: Vec<PathBuf>,
+    rules: Vec<Rule>,
+}
+
+// One exclude: a matcher applied to paths below a directory
+struct Rule {
+    under: PathBuf,
+    matcher: Matcher,
+}
+
+#[derive(Clone)]
+enum Matcher {
+    // A file or directory and everything below it
+    Path(PathBuf),
+    // A directory of this name at any depth, as php-cs-fixer's exclude('name') has it
+    DirName(String),
+    // A pattern for the path, with `/` separators
+    Pattern(Regex),
+    // A pattern for the file name
+    FileName(Regex),
+}
+
+static IMPORTED: OnceLock<Vec<ToolPaths>> = OnceLock::new();
+
+pub fn set(imported: Vec<ToolPaths>) {
+    let _ = IMPORTED.set(imported);
+}
+
+// Reads a tool's config from the search directory, or the closest directory above it that has
+// one, as the tools are usually configured at the project root
+pub fn load(tool: &str, dir: &str) -> Result<ToolPaths> {
+    let names = CONFIG_FILES.iter().find(|(name, _)| *name == tool).map(|(_, files)| *files).unwrap_or_default();
+    let search_dir = std::fs::canonicalize(dir).map_err(|e| anyhow::anyhow!("Could not read '{}': {}", dir, e))?;
+    let config = search_dir.ancestors()
+        .find_map(|ancestor| names.iter().map(|name| ancestor.join(name)).find(|path| path.is_file()))
+        .ok_or_else(|| anyhow::anyhow!("No {} config ({}) found in '{}' or above", tool, names.join(", "), dir))?;
+    let content = std::fs::read_to_string(&config).map_err(|e| anyhow::anyhow!("Could not read '{}': {}", config.display(), e))?;
+    let base = config.parent().unwrap_or(Path::new(""));
+    let (paths, rules) = match tool {
+        "php-cs-fixer" => php_cs_fixer(&config, &content)?,
+        "phpstan" => phpstan(&config, &content),
+        _ => psalm(&content),
+    };
+    Ok(ToolPaths {
+        prefix: search_dir.strip_prefix(base).map(Path::to_path_buf).unwrap_or_default(),
+        paths,
+        rules,
+    })
+}
+
+// Whether a file or directory is excluded by an imported config, so the walk can leave out
+// whole directories
+pub fn excluded(path: &Path, dir: &str) -> bool {
+    IMPORTED.get().is_some_and(|imported| imported.iter().any(|tool| tool.excludes(&tool.relative(path, dir))))
+}
+
+// Whether a file is among the paths of every imported config and excluded by none
+pub fn selects(path: &Path, dir: &str) -> bool {
+    IMPORTED.get().is_none_or(|imported| imported.iter().all(|tool| {
+        let relative_path = tool.relative(path, dir);
+        (tool.paths.is_empty() || tool.paths.iter().any(|included| relative_path.starts_with(included))) && !tool.excludes(&relative_path)
+    }))
+}
+
+impl ToolPaths {
+    fn relative(&self, path: &Path, dir: &str) -> PathBuf {
+        self.prefix.join(path.strip_prefix(dir).unwrap_or(path))
+    }
+
+    fn excludes(&self, relative_path: &Path) -> bool {
+        self.rules.iter().any(|rule| {
+            let Ok(inner) = relative_path.strip_prefix(&rule.under) else { return false };
+            match &rule.matcher {
+                Matcher::Path(excluded) => inner.starts_with(excluded),
+                Matcher::DirName(name) => inner.components().any(|component| component.as_os_str() == name.as_str()),
+                Matcher::Pattern(pattern) => pattern.is_match(&inner.to_string_lossy().replace('\\', "/")),
+                Matcher::FileName(pattern) => inner.file_name().is_some_and(|name| pattern.is_match(&name.to_string_lossy())),
+            }
+        })
+    }
+}
+
+// Reads the Finder calls of a php-cs-fixer config: in() gives the directories, exclude() names
+// directories below them, notPath() and notName() leave out files
+fn php_cs_fixer(config: &Path, content: &str) -> Result<(Vec<PathBuf>, Vec<Rule>)> {
+    let mut parser = php::new_parser()?;
+    let tree = parser.parse(content, None).ok_or_else(|| anyhow::anyhow!("Could not parse '{}' as PHP", config.display()))?;
+    let mut calls: Vec<(&str, Vec<String>)> = Vec::new();
+    php::walk(tree.root_node(), &mut |node| {
+        if node.kind() != "member_call_expression" {
+            return;
+        }
+        let name = node.child_by_field_name("name").map_or("", |name| php::node_text(name, content));
+        if !["in", "exclude", "notPath", "notName"].contains(&name) {
+            return;
+        }
+        let Some(arguments) = node.child_by_field_name("arguments") else { return };
+        let mut cursor = arguments.walk();
+        for argument in arguments.named_children(&mut cursor) {
+            match argument.named_child(0).and_then(|value| strings(value, content)) {
+                Some(values) => calls.push((name, values)),
+                None => eprintln!("Warning: Ignoring the {}() argument on line {} of '{}', it isn't a plain string", name, argument.start_position().row + 1, config.display()),
+            }
+        }
+    });
+
+    let dirs: Vec<PathBuf> = calls.iter().filter(|(name, _)| *name == "in").flat_map(|(_, values)| values.iter().map(|value| relative(value))).collect();
+    let roots = if dirs.is_empty() { vec![PathBuf::new()] } else { dirs.clone() };
+    let mut rules = Vec::new();
+    for (name, values) in &calls {
+        for value in values {
+            let matcher = match *name {
+                "exclude" if value.trim_end_matches('/').contains('/') => Matcher::Path(relative(value)),
+                "exclude" => Matcher::DirName(value.trim_end_matches('/').to_string()),
+                "notPath" => Matcher::Pattern(finder_pattern(value, false)),
+                "notName" => Matcher::FileName(finder_pattern(value, true)),
+                _ => continue,
+            };
+            rules.extend(roots.iter().map(|root| Rule { under: root.clone(), matcher: matcher.clone() }));
+        }
+    }
+    Ok((dirs, rules))
+}
+
+// The strings an argument evaluates to: a literal, an array of them, or a concatenation with
+// __DIR__, which stands for the config file's directory
+fn strings(node: Node, content: &str) -> Option<Vec<String>> {
+    match node.kind() {
+        "array_creation_expression" => {
+            let mut cursor = node.walk();
+            let elements: Vec<Node> = node.named_children(&mut cursor).collect();
+            elements.into_iter().map(|element| element.named_child(0).and_then(|value| strings(value, content))).collect::<Option<Vec<_>>>().map(|values| values.concat())
+        }
+        "binary_expression" if node.child_by_field_name("operator").is_some_and(|operator| php::node_text(operator, content) == ".") => {
+            let left = strings(node.child_by_field_name("left")?, content)?;
+            let right = strings(node.child_by_field_name("right")?, content)?;
+            match (left.as_slice(), right.as_slice()) {
+                ([left], [right]) => Some(vec![format!("{}{}", left, right)]),
+                _ => None,
+            }
+        }
+        "name" if php::node_text(node, content) == "__DIR__" => Some(vec![String::new()]),
+        "parenthesized_expression" => strings(node.named_child(0)?, content),
+        _ => php::string_literal(node, content).map(|value| vec![value]),
+    }
+}
+
+// A Finder pattern: a delimited regex is used as is, a glob matches the whole name or path, and
+// other paths match anywhere in the path while names have to match exactly, as Finder does it
+fn finder_pattern(value: &str, name: bool) -> Regex {
+    if let Some(pattern) = delimited_regex(value) {
+        return pattern;
+    }
+    let source = if value.contains(['*', '?']) {
+        format!("^{}$", glob(value, false))
+    } else if name {
+        format!("^{}$", regex::escape(value))
+    } else {
+        regex::escape(value)
+    };
+    Regex::new(&source).unwrap_or_else(|_| Regex::new("$^").unwrap())
+}
+
+// A PHP regex like `#^tests/.*Fixture#i`, with an `i` flag kept and other flags dropped
+fn delimited_regex(value: &str) -> Option<Regex> {
+    let delimiter = value.chars().next().filter(|c| "/#~!@".contains(*c))?;
+    let end = value.rfind(delimiter).filter(|end| *end > 0)?;
+    let flags = &value[end + 1..];
+    if !flags.chars().all(|flag| flag.is_ascii_alphabetic()) {
+        return None;
+    }
+    let pattern = &value[1..end];
+    let pattern = if flags.contains('i') { format!("(?i){}", pattern) } else { pattern.to_string() };
+    crate::budget::regex(&pattern).ok()
+}
+
+// Reads `parameters.paths` and `parameters.excludePaths` (a list, or the analyse and
+// analyseAndScan lists, or the older excludes_analyse) of a phpstan.neon
+fn phpstan(config: &Path, content: &str) -> (Vec<PathBuf>, Vec<Rule>) {
+    let mut paths = Vec::new();
+    let mut rules = Vec::new();
+    for (key, value) in neon_lists(content) {
+        let excluded = match key.as_str() {
+            "parameters.paths" => false,
+            "parameters.excludePaths" | "parameters.excludePaths.analyse" | "parameters.excludePaths.analyseAndScan" | "parameters.excludes_analyse" => true,
+            _ => continue,
+        };
+        // A trailing (?) marks a path that may not exist
+        let value = value.trim_end_matches("(?)").trim_end();
+        let value = value.strip_prefix("%currentWorkingDirectory%").unwrap_or(value);
+        if value.contains('%') {
+            eprintln!("Warning: Ignoring '{}' in '{}', phrep doesn't know the parameters it uses", value, config.display());
+            continue;
+        }
+        if !excluded {
+            paths.push(relative(value));
+        } else if value.contains(['*', '?']) {
+            // Patterns are matched with fnmatch against the whole path, so `*` also covers `/`
+            let value = value.trim_start_matches("./").trim_start_matches('/');
+            let source = match value.strip_prefix("*/") {
+                Some(rest) => format!("^(?:.*/)?{}(?:/.*)?$", glob(rest, true)),
+                None => format!("^{}(?:/.*)?$", glob(value, true)),
+            };
+            if let Ok(pattern) = Regex::new(&source) {
+                rules.push(Rule { under: PathBuf::new(), matcher: Matcher::Pattern(pattern) });
+            }
+        } else {
+            rules.push(Rule { under: PathBuf::new(), matcher: Matcher::Path(relative(value)) });
+        }
+    }
+    (paths, rules)
+}
+
+// The list items of a NEON file with the dotted keys they're under, e.g.
+// ("parameters.paths", "src"), for both `- item` lines and inline `[a, b]` lists
+fn neon_lists(content: &str) -> Vec<(String, String)> {
+    let mut items = Vec::new();
+    let mut keys: Vec<(usize, String)> = Vec::new();
+    for line in content.lines() {
+        let line = line.split(" #").next().unwrap_or(line).trim_end();
+        let item = line.trim_start();
+        if item.is_empty() || item.starts_with('#') {
+            continue;
+        }
+        let indent = line.len() - item.len();
+        if let Some(value) = item.strip_prefix('-') {
+            let key = keys.iter().map(|(_, key)| key.as_str()).collect::<Vec<_>>().join(".");
+            items.push((key, unquote(value.trim())));
+            continue;
+        }
+        keys.retain(|(key_indent, _)| *key_indent < indent);
+        let Some((key, value)) = item.split_once(':') else { continue };
+        let value = value.trim();
+        if value.is_empty() {
+            keys.push((indent, key.trim().to_string()));
+        } else if let Some(list) = value.strip_prefix('[').and_then(|list| list.strip_suffix(']')) {
+            let key = keys.iter().map(|(_, key)| key.as_str()).chain([key.trim()]).collect::<Vec<_>>().join(".");
+            items.extend(list.split(',').map(|value| unquote(value.trim())).filter(|value| !value.is_empty()).map(|value| (key.clone(), value)));
+        }
+    }
+    items
+}
+
+fn unquote(value: &str) -> String {
+    value.trim_matches(|c| c == '"' || c == '\'').to_string()
+}
+
+// Reads the <projectFiles> of a psalm.xml: its <directory> and <file> entries are the paths and
+// the ones inside <ignoreFiles> are left out
+fn psalm(content: &str) -> (Vec<PathBuf>, Vec<Rule>) {
+    let comment = Regex::new(r"(?s)<!--.*?-->").unwrap();
+    let content = comment.replace_all(content, "");
+    let mut paths = Vec::new();
+    let mut rules = Vec::new();
+    let Some(start) = content.find("<projectFiles") else { return (paths, rules) };
+    let end = content[start..].find("</projectFiles>").map_or(content.len(), |end| start + end);
+    let project_files = &content[start..end];
+    let tag = Regex::new(r"<(/?\w+)([^>]*)>").unwrap();
+    let name = Regex::new(r#"\bname\s*=\s*"([^"]*)""#).unwrap();
+    let mut ignored = false;
+    for tag in tag.captures_iter(project_files) {
+        match &tag[1] {
+            "ignoreFiles" => ignored = !tag[2].ends_with('/'),
+            "/ignoreFiles" => ignored = false,
+            "directory" | "file" => {
+                let Some(name) = name.captures(&tag[2]).map(|name| name.get(1).map_or("", |name| name.as_str())) else { continue };
+                if !ignored {
+                    paths.push(relative(name));
+                } else if name.contains(['*', '?']) {
+                    let source = format!("^{}(?:/.*)?$", glob(name.trim_start_matches("./"), false));
+                    if let Ok(pattern) = Regex::new(&source) {
+                        rules.push(Rule { under: PathBuf::new(), matcher: Matcher::Pattern(pattern) });
+                    }
+                } else {
+                    rules.push(Rule { under: PathBuf::new(), matcher: Matcher::Path(relative(name)) });
+                }
+            }
+            _ => {}
+        }
+    }
+    (paths, rules)
+}
+
+// A path from a config relative to the config's directory, without `./` and leading or trailing
+// slashes, so `__DIR__.'/src/'` and `./src` both become `src`
+fn relative(value: &str) -> PathBuf {
+    Path::new(value.trim_matches('/')).components().filter(|component| !matches!(component, Component::CurDir)).collect()
+}
+
+// The regex source of a glob. `**` always spans directories, `*` and `?` only do when
+// `spans_dirs` is set
+pub fn glob(glob: &str, spans_dirs: bool) -> String {
+    let mut source = String::new();
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                source.push_str(".*");
+            }
+            '*' => source.push_str(if spans_dirs { ".*" } else { "[^/]*" }),
+            '?' => source.push_str(if spans_dirs { "." } else { "[^/]" }),
+            c => source.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    source
+}
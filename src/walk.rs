@@ -0,0 +1,104 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex};
+
+// Directories waiting to be read and how many threads are reading one right now
+struct Queue {
+    dirs: Vec<PathBuf>,
+    busy: usize,
+}
+
+// Lists every file below `root`, reading directories on several threads so enumeration keeps
+// up on network filesystems and very deep trees. Anything `skip` returns true for is left out
+// along with its contents. Symlinks to files are listed, symlinks to directories aren't followed
+// and unreadable directories are skipped.
+// The paths come back sorted so the output order doesn't depend on thread timing
+pub fn files(root: &Path, skip: &(dyn Fn(&Path) -> bool + Sync)) -> Vec<PathBuf> {
+    if skip(root) {
+        return Vec::new();
+    }
+    if !root.is_dir() {
+        return if root.is_file() { vec![root.to_path_buf()] } else { Vec::new() };
+    }
+
+    let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let queue = Mutex::new(Queue { dirs: vec![root.to_path_buf()], busy: 0 });
+    let ready = Condvar::new();
+    let found = Mutex::new(Vec::new());
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                loop {
+                    let dir = {
+                        let mut queue = queue.lock().unwrap();
+                        loop {
+                            if let Some(dir) = queue.dirs.pop() {
+                                queue.busy += 1;
+                                break Some(dir);
+                            }
+                            if queue.busy == 0 {
+                                break None;
+                            }
+                            queue = ready.wait(queue).unwrap();
+                        }
+                    };
+                    let Some(dir) = dir else {
+                        ready.notify_all();
+                        return;
+                    };
+
+                    let (subdirs, files) = read_dir(&dir, skip);
+                    found.lock().unwrap().extend(files);
+                    let mut queue = queue.lock().unwrap();
+                    queue.dirs.extend(subdirs);
+                    queue.busy -= 1;
+                    ready.notify_all();
+                }
+            });
+        }
+    });
+
+    let mut files = found.into_inner().unwrap();
+    files.sort();
+    files
+}
+
+// Splits the entries of one directory into subdirectories and files
+fn read_dir(dir: &Path, skip: &(dyn Fn(&Path) -> bool + Sync)) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let (mut subdirs, mut files) = (Vec::new(), Vec::new());
+    let Ok(entries) = std::fs::read_dir(dir) else { return (subdirs, files) };
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        if skip(&path) {
+            continue;
+        }
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => subdirs.push(path),
+            Ok(file_type) if file_type.is_file() => files.push(path),
+            // A symlink is kept when it points to a file, which metadata follows the link to find
+            Ok(file_type) if file_type.is_symlink() && std::fs::metadata(&path).is_ok_and(|metadata| metadata.is_file()) => files.push(path),
+            _ => {}
+        }
+    }
+    (subdirs, files)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::path::Path;
+
+    #[test]
+    fn symlinked_files_are_listed_and_symlinked_dirs_not_followed() {
+        let root = std::env::temp_dir().join(format!("phrep-walk-symlinks-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/Real.php"), "<?php").unwrap();
+        std::os::unix::fs::symlink(root.join("src/Real.php"), root.join("Linked.php")).unwrap();
+        std::os::unix::fs::symlink(root.join("src"), root.join("linked_dir")).unwrap();
+        std::os::unix::fs::symlink(root.join("missing.php"), root.join("Dangling.php")).unwrap();
+
+        let files = super::files(&root, &|_: &Path| false);
+        let _ = std::fs::remove_dir_all(&root);
+        assert_eq!(files, vec![root.join("Linked.php"), root.join("src/Real.php")]);
+    }
+}
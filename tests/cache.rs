@@ -0,0 +1,83 @@
+// Runs the same search twice with --cache and different flags: the second run has to print
+// what the flags ask for, not the output cached by the first
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+// A project and a cache directory of its own, removed when the test is done
+struct Project(PathBuf);
+
+impl Project {
+    fn new(name: &str, files: &[(&str, &str)]) -> Project {
+        let dir = std::env::temp_dir().join(format!("phrep-cache-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        for (name, content) in files {
+            std::fs::write(dir.join("src").join(name), content).unwrap();
+        }
+        Project(dir)
+    }
+
+    fn phrep(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_phrep"))
+            .args(["--dir", self.src().to_str().unwrap()])
+            .args(args)
+            .env("NO_COLOR", "1")
+            .env("XDG_CACHE_HOME", self.0.join("cache"))
+            .output()
+            .unwrap()
+    }
+
+    fn src(&self) -> PathBuf {
+        self.0.join("src")
+    }
+}
+
+impl Drop for Project {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(output.status.success(), "phrep failed: {}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+const ORDER: &str = "<?php
+class Order {
+    /**
+     * Charges the customer
+     */
+    public function pay(int $amount): bool {
+        return $this->gateway->charge($amount);
+    }
+}
+";
+
+// Runs `args` with --cache before and after `flag`, in both orders, and hands each output to `check`
+fn each_order(project: &Project, args: &[&str], flag: &str, check: impl Fn(&str, bool)) {
+    let with_flag: Vec<&str> = args.iter().copied().chain([flag, "--cache"]).collect();
+    let without_flag: Vec<&str> = args.iter().copied().chain(["--cache"]).collect();
+    for (first, second) in [(&without_flag, &with_flag), (&with_flag, &without_flag)] {
+        let _ = std::fs::remove_dir_all(project.0.join("cache"));
+        check(&stdout(&project.phrep(first)), first.contains(&flag));
+        check(&stdout(&project.phrep(second)), second.contains(&flag));
+    }
+}
+
+#[test]
+fn with_signature_isnt_replayed_from_the_cache() {
+    let project = Project::new("signature", &[("Order.php", ORDER)]);
+    each_order(&project, &["gateway"], "--with-signature", |output, signature| {
+        assert_eq!(output.contains("public function pay(int $amount): bool"), signature, "{}", output);
+    });
+}
+
+#[test]
+fn with_doc_isnt_replayed_from_the_cache() {
+    let project = Project::new("doc", &[("Order.php", ORDER)]);
+    each_order(&project, &["gateway"], "--with-doc", |output, doc| {
+        assert_eq!(output.contains("/** Charges the customer */"), doc, "{}", output);
+    });
+}
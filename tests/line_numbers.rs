@@ -0,0 +1,123 @@
+// Checks which lines a search of method and function bodies looks at and the line numbers it
+// reports for them, which --diff-filter and --relative-lines rely on
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+// A directory with one PHP file, removed when the test is done
+struct Project(PathBuf);
+
+impl Project {
+    fn new(name: &str, content: &str) -> Project {
+        let dir = std::env::temp_dir().join(format!("phrep-lines-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Order.php"), content).unwrap();
+        Project(dir)
+    }
+
+    fn phrep(&self, args: &[&str]) -> String {
+        let output: Output = Command::new(env!("CARGO_BIN_EXE_phrep"))
+            .args(["--dir", self.0.to_str().unwrap()])
+            .args(args)
+            .env("NO_COLOR", "1")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "phrep failed: {}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+}
+
+impl Drop for Project {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+// The `file:line: symbol` of every match, in the order printed
+fn matches(output: &str) -> Vec<String> {
+    output.lines().filter_map(|line| line.split_once(" → ")).map(|(location, _)| location.rsplit('/').next().unwrap_or(location).to_string()).collect()
+}
+
+const ORDER: &str = "<?php
+class Order {
+    public function pay() { $total = $this->total;
+        return $this->gateway->charge($total);
+    }
+    public function refund() {
+        $this->gateway->refund($this->total);
+    }
+}
+";
+
+#[test]
+fn a_method_body_is_searched_from_its_opening_brace_line() {
+    let project = Project::new("brace-line", ORDER);
+    assert_eq!(matches(&project.phrep(&["\\$total ="])), ["Order.php:3: pay()"]);
+}
+
+#[test]
+fn the_line_after_a_method_body_isnt_part_of_it() {
+    let project = Project::new("after-body", ORDER);
+    // Line 6 declares refund() right after the body of pay() and is in neither body
+    assert_eq!(matches(&project.phrep(&["refund"])), ["Order.php:7: refund()"]);
+}
+
+const INVOICE: &str = "<?php
+namespace App;
+
+use App\\Gateway;
+
+class Invoice {
+    public function send() {
+        $this->mailer->send($this->pdf());
+        $this->mailer->log();
+    }
+}
+
+function resend($invoice) {
+    $invoice->mailer->send();
+}
+";
+
+#[test]
+fn matches_report_the_line_they_are_on_in_the_file() {
+    let project = Project::new("real-lines", INVOICE);
+    assert_eq!(matches(&project.phrep(&["mailer"])), ["Order.php:8: send()", "Order.php:9: send()", "Order.php:14: resend()"]);
+}
+
+#[test]
+fn diff_filter_reports_the_matches_on_added_lines() {
+    let project = Project::new("diff-filter", INVOICE);
+    let patch = project.0.join("added.patch");
+    std::fs::write(&patch, "--- a/Order.php\n+++ b/Order.php\n@@ -8,0 +9,1 @@\n+        $this->mailer->log();\n@@ -13,0 +14,1 @@\n+    $invoice->mailer->send();\n").unwrap();
+    assert_eq!(matches(&project.phrep(&["mailer", "--diff-filter", patch.to_str().unwrap()])), ["Order.php:9: send()", "Order.php:14: resend()"]);
+}
+
+const PAY: &str = "<?php
+class Order {
+    /**
+     * Charges the customer
+     */
+    #[Route('/pay')]
+    public function pay(
+        int $amount,
+    ): bool {
+        $this->log();
+
+        return $this->gateway->charge($amount);
+    }
+}
+";
+
+#[test]
+fn a_method_body_match_reports_its_line_in_the_file() {
+    let project = Project::new("method-body", PAY);
+    assert_eq!(matches(&project.phrep(&["gateway"])), ["Order.php:12: pay()"]);
+}
+
+#[test]
+fn relative_lines_count_from_the_signature_below_the_docblock_and_attributes() {
+    let project = Project::new("relative-lines", PAY);
+    assert_eq!(matches(&project.phrep(&["gateway|log", "--relative-lines"])), ["Order.php:10 (+3 in pay): pay()", "Order.php:12 (+5 in pay): pay()"]);
+}
@@ -0,0 +1,107 @@
+// Runs phrep over a directory of broken PHP files next to a good one: every search has to get
+// through the whole run, say what it skipped and still report the good file
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+// A fresh directory with the fixtures, removed when the test is done
+struct Fixtures(PathBuf);
+
+impl Fixtures {
+    fn new(name: &str) -> Fixtures {
+        let dir = std::env::temp_dir().join(format!("phrep-malformed-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let files: [(&str, &[u8]); 5] = [
+            ("good.php", b"<?php\nclass Good {\n    public function load() { return $this->db->find(1); }\n}\n"),
+            // Cut off in the middle of a call
+            ("truncated.php", b"<?php\nclass Cut {\n    public function half() { $this->db->query("),
+            // NUL bytes, like a compiled or encoded file with a .php name
+            ("binary.php", b"<?php\n$this->db->x();\0\0\0\x01\x02"),
+            // Latin-1 bytes that aren't valid UTF-8
+            ("latin1.php", b"<?php\n// caf\xe9 \xff\xfe\nfunction f() { $this->db->y(); }\n"),
+            // Nothing a parser can make sense of
+            ("garbage.php", b"<?php }}}} ))) class { function ( { $this->db"),
+        ];
+        for (name, content) in files {
+            std::fs::write(dir.join(name), content).unwrap();
+        }
+        Fixtures(dir)
+    }
+
+    fn phrep(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_phrep"))
+            .args(["--dir", self.0.to_str().unwrap()])
+            .args(args)
+            .env("NO_COLOR", "1")
+            .output()
+            .unwrap()
+    }
+}
+
+impl Drop for Fixtures {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+// The run succeeded, reported the good file and counted the binary and invalid UTF-8 ones
+fn assert_survived(output: &Output) {
+    assert!(output.status.success(), "phrep failed: {}", stderr(output));
+    assert!(!stderr(output).contains("panicked"), "phrep panicked: {}", stderr(output));
+    assert!(stdout(output).contains("good.php:3"), "good.php wasn't reported: {}", stdout(output));
+    assert!(stdout(output).contains("Skipped 2 binary or minified files"), "the skipped files weren't counted: {}", stdout(output));
+    assert!(stdout(output).contains("Search completed successfully."), "the run didn't finish: {}", stdout(output));
+}
+
+#[test]
+fn basic_search_gets_past_broken_files() {
+    let fixtures = Fixtures::new("basic");
+    assert_survived(&fixtures.phrep(&["\\$this->db"]));
+}
+
+#[test]
+fn grep_search_reports_the_lines_of_unparsable_files() {
+    let fixtures = Fixtures::new("grep");
+    let output = fixtures.phrep(&["\\$this->db", "--grep"]);
+    assert_survived(&output);
+    // Grep doesn't need a tree, so broken but readable files still match
+    assert!(stdout(&output).contains("truncated.php:3"), "{}", stdout(&output));
+    assert!(stdout(&output).contains("garbage.php:1"), "{}", stdout(&output));
+}
+
+#[test]
+fn method_search_gets_past_broken_files() {
+    let fixtures = Fixtures::new("method-search");
+    let output = fixtures.phrep(&["load", "--method-search"]);
+    assert!(output.status.success(), "phrep failed: {}", stderr(&output));
+    assert!(stdout(&output).contains("good.php:3"), "{}", stdout(&output));
+}
+
+#[test]
+fn included_binary_files_are_warned_about() {
+    let fixtures = Fixtures::new("include-minified");
+    let output = fixtures.phrep(&["\\$this->db", "--include-minified"]);
+    assert!(output.status.success(), "phrep failed: {}", stderr(&output));
+    assert!(stdout(&output).contains("good.php:3"), "{}", stdout(&output));
+    assert!(stderr(&output).contains("Warning: Could not read file") && stderr(&output).contains("latin1.php"), "{}", stderr(&output));
+}
+
+#[test]
+fn commands_get_past_broken_files() {
+    let fixtures = Fixtures::new("commands");
+    for command in [&["symbols"][..], &["untyped"], &["unused-params"], &["uninitialized-props"]] {
+        let output = fixtures.phrep(command);
+        assert!(output.status.success(), "{:?} failed: {}", command, stderr(&output));
+        assert!(!stderr(&output).contains("panicked"), "{:?} panicked: {}", command, stderr(&output));
+    }
+    assert!(stdout(&fixtures.phrep(&["symbols"])).contains("Good::load"));
+}
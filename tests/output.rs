@@ -0,0 +1,75 @@
+// Checks how matches are printed: what of a line is shown and how its file is named
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+// A directory with one PHP file, removed when the test is done
+struct Project(PathBuf);
+
+impl Project {
+    fn new(name: &str, content: &str) -> Project {
+        let dir = std::env::temp_dir().join(format!("phrep-output-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Repository.php"), content).unwrap();
+        Project(dir)
+    }
+
+    fn phrep(&self, args: &[&str]) -> String {
+        let dir = ["--dir", self.0.to_str().unwrap()];
+        self.phrep_inside(&[&dir[..], args].concat())
+    }
+
+    // Runs phrep from inside the directory, so it searches `.` unless told otherwise
+    fn phrep_inside(&self, args: &[&str]) -> String {
+        let output: Output = Command::new(env!("CARGO_BIN_EXE_phrep"))
+            .current_dir(&self.0)
+            .args(args)
+            .env("NO_COLOR", "1")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "phrep failed: {}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+}
+
+impl Drop for Project {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+const INDENTED: &str = "<?php
+class Repository {
+    public function load($customer) {
+        $result = $this->repository->findEverythingFor($customer);
+    }
+}
+";
+
+#[test]
+fn max_columns_doesnt_count_indentation() {
+    let project = Project::new("max-columns", INDENTED);
+    for mode in [&[][..], &["--grep"]] {
+        let args: Vec<&str> = ["repository", "--max-columns", "30"].into_iter().chain(mode.iter().copied()).collect();
+        let output = project.phrep(&args);
+        assert!(output.contains("→ $result = $this->repository->f [... omitted]"), "{:?}: {}", mode, output);
+    }
+}
+
+#[test]
+fn max_columns_leaves_short_indented_lines_alone() {
+    let project = Project::new("max-columns-short", INDENTED);
+    let output = project.phrep(&["repository", "--max-columns", "60"]);
+    assert!(output.contains("→ $result = $this->repository->findEverythingFor($customer);\n"), "{}", output);
+}
+
+#[test]
+fn reports_name_files_the_way_text_output_does() {
+    let project = Project::new("report-paths", INDENTED);
+    assert!(project.phrep_inside(&["repository"]).contains("Repository.php:4: load() →"));
+    for (format, path) in [("json", "\"path\":\"Repository.php\""), ("phpstan", "\"files\":{\"Repository.php\""), ("checkstyle", "<file name=\"Repository.php\">")] {
+        let output = project.phrep_inside(&["repository", "--format", format]);
+        assert!(output.contains(path), "{}: {}", format, output);
+    }
+}
@@ -0,0 +1,60 @@
+// Checks that phrep refuses to run two search modes at once instead of silently picking one
+
+use std::process::Command;
+
+// Every search mode flag with a value it takes, if it takes one
+const MODES: [(&str, Option<&str>); 30] = [
+    ("--print-method", None),
+    ("--grep", None),
+    ("--grep-compat", None),
+    ("--method-search", None),
+    ("--superglobals", None),
+    ("--new", Some("Order")),
+    ("--throws", Some("Exception")),
+    ("--catches", Some("Exception")),
+    ("--returns-expr", Some("null")),
+    ("--no-return", None),
+    ("--match-arm", Some("default")),
+    ("--type-query", Some("int")),
+    ("--doc-tag", Some("deprecated")),
+    ("--plugin", Some("laravel")),
+    ("--const-usage", Some("Order::PAID")),
+    ("--late-static-binding", Some("create")),
+    ("--tokens", Some("$a + $b")),
+    ("--heredoc", Some("SELECT")),
+    ("--default-value", Some("null")),
+    ("--callback-of", Some("array_map")),
+    ("--doc-search", Some("TODO")),
+    ("--class-ref", Some("App\\Order")),
+    ("--instanceof", Some("Order")),
+    ("--function", Some("strlen")),
+    ("--assertions", Some("assertTrue")),
+    ("--env", None),
+    ("--i18n", None),
+    ("--logs", None),
+    ("--arg-of", Some("strlen")),
+    ("--inject", Some("sql")),
+];
+
+fn mode_args((flag, value): (&'static str, Option<&'static str>)) -> Vec<&'static str> {
+    std::iter::once(flag).chain(value).collect()
+}
+
+#[test]
+fn two_search_modes_are_refused() {
+    let dir = std::env::temp_dir().join(format!("phrep-modes-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for (i, &first) in MODES.iter().enumerate() {
+        for &second in &MODES[i + 1..] {
+            let output = Command::new(env!("CARGO_BIN_EXE_phrep"))
+                .args(["--dir", dir.to_str().unwrap(), "query"])
+                .args(mode_args(first))
+                .args(mode_args(second))
+                .output()
+                .unwrap();
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            assert!(!output.status.success() && stderr.contains("cannot be used with"), "{} and {} were accepted together: {}", first.0, second.0, stderr);
+        }
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}